@@ -0,0 +1,660 @@
+//! Bitcoin amounts with checked arithmetic and denominations.
+
+use codec::{Decode, Encode};
+use core::{fmt, str};
+
+use crate::constants::{MAX_MONEY, SATOSHIS_IN_COIN};
+
+/// Unit in which an amount is parsed or formatted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Denomination {
+    /// BTC, 100 000 000 satoshis
+    Bitcoin,
+    /// mBTC, 100 000 satoshis
+    MilliBitcoin,
+    /// bits or µBTC, 100 satoshis
+    Bit,
+    /// sat
+    Satoshi,
+}
+
+impl Denomination {
+    /// Number of decimal places of the denomination in satoshis
+    fn precision(self) -> u32 {
+        match self {
+            Denomination::Bitcoin => 8,
+            Denomination::MilliBitcoin => 5,
+            Denomination::Bit => 2,
+            Denomination::Satoshi => 0,
+        }
+    }
+}
+
+impl fmt::Display for Denomination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match *self {
+            Denomination::Bitcoin => "BTC",
+            Denomination::MilliBitcoin => "mBTC",
+            Denomination::Bit => "bits",
+            Denomination::Satoshi => "sat",
+        };
+        name.fmt(f)
+    }
+}
+
+impl str::FromStr for Denomination {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "BTC" | "btc" => Ok(Denomination::Bitcoin),
+            "mBTC" | "mbtc" => Ok(Denomination::MilliBitcoin),
+            "bits" | "bit" | "uBTC" | "µBTC" => Ok(Denomination::Bit),
+            "sat" | "sats" | "satoshi" | "satoshis" => Ok(Denomination::Satoshi),
+            _ => Err(ParseAmountError::UnknownDenomination),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseAmountError {
+    /// The amount is negative
+    Negative,
+    /// The amount overflows or is above `MAX_MONEY`
+    TooBig,
+    /// The amount has more decimal places than its denomination allows
+    TooPrecise,
+    /// The amount is not a decimal number
+    InvalidFormat,
+    /// The denomination is missing or unknown
+    UnknownDenomination,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseAmountError {}
+
+impl fmt::Display for ParseAmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match *self {
+            ParseAmountError::Negative => "Amount is negative",
+            ParseAmountError::TooBig => "Amount is too big",
+            ParseAmountError::TooPrecise => "Amount has too many decimal places",
+            ParseAmountError::InvalidFormat => "Invalid amount format",
+            ParseAmountError::UnknownDenomination => "Unknown denomination",
+        };
+        msg.fmt(f)
+    }
+}
+
+/// Parses a decimal number in `denomination` into satoshis and whether it is negative
+fn parse_sat(s: &str, denomination: Denomination) -> Result<(bool, u64), ParseAmountError> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (integer, fraction) = match s.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (s, ""),
+    };
+    if integer.is_empty() && fraction.is_empty() {
+        return Err(ParseAmountError::InvalidFormat);
+    }
+
+    let precision = denomination.precision() as usize;
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > precision {
+        return Err(ParseAmountError::TooPrecise);
+    }
+
+    let mut sat = 0u64;
+    let digits = integer
+        .chars()
+        .chain(fraction.chars())
+        .chain(core::iter::repeat_n('0', precision - fraction.len()));
+    for c in digits {
+        let digit = c.to_digit(10).ok_or(ParseAmountError::InvalidFormat)?;
+        sat = sat
+            .checked_mul(10)
+            .and_then(|sat| sat.checked_add(u64::from(digit)))
+            .ok_or(ParseAmountError::TooBig)?;
+    }
+    Ok((negative && sat != 0, sat))
+}
+
+/// Formats `sat` satoshis in `denomination` without trailing zeros
+fn fmt_sat(
+    f: &mut dyn fmt::Write,
+    negative: bool,
+    sat: u64,
+    denomination: Denomination,
+) -> fmt::Result {
+    let precision = denomination.precision();
+    let unit = 10u64.pow(precision);
+    if negative {
+        f.write_str("-")?;
+    }
+    write!(f, "{}", sat / unit)?;
+
+    let mut fraction = sat % unit;
+    if fraction == 0 {
+        return Ok(());
+    }
+    let mut width = precision as usize;
+    while fraction.is_multiple_of(10) {
+        fraction /= 10;
+        width -= 1;
+    }
+    write!(f, ".{:0width$}", fraction, width = width)
+}
+
+/// Splits `"<value> <denomination>"`
+fn split_denomination(s: &str) -> Result<(&str, Denomination), ParseAmountError> {
+    let (value, denomination) = s
+        .trim()
+        .split_once(' ')
+        .ok_or(ParseAmountError::UnknownDenomination)?;
+    Ok((value, denomination.trim().parse()?))
+}
+
+/// An amount of satoshis, never negative
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Encode,
+    Decode,
+    scale_info::TypeInfo
+)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const ONE_SAT: Amount = Amount(1);
+    pub const ONE_BTC: Amount = Amount(SATOSHIS_IN_COIN);
+    pub const MAX_MONEY: Amount = Amount(MAX_MONEY);
+
+    pub const fn from_sat(sat: u64) -> Amount {
+        Amount(sat)
+    }
+
+    pub const fn to_sat(self) -> u64 {
+        self.0
+    }
+
+    /// Whether the amount is at most `MAX_MONEY`
+    pub fn is_valid_money(self) -> bool {
+        self.0 <= MAX_MONEY
+    }
+
+    /// Parses a decimal number in `denomination`, such as `"0.001"` in BTC.
+    ///
+    /// Amounts above `MAX_MONEY` are rejected.
+    pub fn from_str_in(s: &str, denomination: Denomination) -> Result<Amount, ParseAmountError> {
+        let (negative, sat) = parse_sat(s, denomination)?;
+        if negative {
+            return Err(ParseAmountError::Negative);
+        }
+        if sat > MAX_MONEY {
+            return Err(ParseAmountError::TooBig);
+        }
+        Ok(Amount(sat))
+    }
+
+    /// Formats the amount in `denomination`, without the denomination
+    pub fn fmt_value_in(self, f: &mut dyn fmt::Write, denomination: Denomination) -> fmt::Result {
+        fmt_sat(f, false, self.0, denomination)
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    pub fn checked_mul(self, other: u64) -> Option<Amount> {
+        self.0.checked_mul(other).map(Amount)
+    }
+
+    pub fn checked_div(self, other: u64) -> Option<Amount> {
+        self.0.checked_div(other).map(Amount)
+    }
+
+    /// Sum of `amounts`, `None` on overflow
+    pub fn checked_sum<I: IntoIterator<Item = Amount>>(amounts: I) -> Option<Amount> {
+        amounts
+            .into_iter()
+            .try_fold(Amount::ZERO, |sum, amount| sum.checked_add(amount))
+    }
+
+    /// Converts to a signed amount, `None` if it does not fit in an `i64`
+    pub fn to_signed(self) -> Option<SignedAmount> {
+        i64::try_from(self.0).ok().map(SignedAmount)
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> u64 {
+        amount.0
+    }
+}
+
+/// Formats as `"<value> BTC"`
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_value_in(f, Denomination::Bitcoin)?;
+        write!(f, " {}", Denomination::Bitcoin)
+    }
+}
+
+/// Parses `"<value> <denomination>"`, such as `"0.001 BTC"` or `"1500 sat"`
+impl str::FromStr for Amount {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, denomination) = split_denomination(s)?;
+        Amount::from_str_in(value, denomination)
+    }
+}
+
+/// A signed amount of satoshis, such as a balance change or a fee difference
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Encode,
+    Decode,
+    scale_info::TypeInfo
+)]
+pub struct SignedAmount(i64);
+
+impl SignedAmount {
+    pub const ZERO: SignedAmount = SignedAmount(0);
+    pub const ONE_SAT: SignedAmount = SignedAmount(1);
+    pub const ONE_BTC: SignedAmount = SignedAmount(SATOSHIS_IN_COIN as i64);
+    pub const MAX_MONEY: SignedAmount = SignedAmount(MAX_MONEY as i64);
+
+    pub const fn from_sat(sat: i64) -> SignedAmount {
+        SignedAmount(sat)
+    }
+
+    pub const fn to_sat(self) -> i64 {
+        self.0
+    }
+
+    /// Whether the absolute value of the amount is at most `MAX_MONEY`
+    pub fn is_valid_money(self) -> bool {
+        self.0.unsigned_abs() <= MAX_MONEY
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    /// Absolute value, `None` on overflow
+    pub fn checked_abs(self) -> Option<SignedAmount> {
+        self.0.checked_abs().map(SignedAmount)
+    }
+
+    /// Parses a decimal number in `denomination`, such as `"-0.001"` in BTC.
+    ///
+    /// Amounts whose absolute value is above `MAX_MONEY` are rejected.
+    pub fn from_str_in(
+        s: &str,
+        denomination: Denomination,
+    ) -> Result<SignedAmount, ParseAmountError> {
+        let (negative, sat) = parse_sat(s, denomination)?;
+        if sat > MAX_MONEY {
+            return Err(ParseAmountError::TooBig);
+        }
+        let sat = sat as i64;
+        Ok(SignedAmount(if negative { -sat } else { sat }))
+    }
+
+    /// Formats the amount in `denomination`, without the denomination
+    pub fn fmt_value_in(self, f: &mut dyn fmt::Write, denomination: Denomination) -> fmt::Result {
+        fmt_sat(f, self.is_negative(), self.0.unsigned_abs(), denomination)
+    }
+
+    pub fn checked_add(self, other: SignedAmount) -> Option<SignedAmount> {
+        self.0.checked_add(other.0).map(SignedAmount)
+    }
+
+    pub fn checked_sub(self, other: SignedAmount) -> Option<SignedAmount> {
+        self.0.checked_sub(other.0).map(SignedAmount)
+    }
+
+    pub fn checked_mul(self, other: i64) -> Option<SignedAmount> {
+        self.0.checked_mul(other).map(SignedAmount)
+    }
+
+    pub fn checked_div(self, other: i64) -> Option<SignedAmount> {
+        self.0.checked_div(other).map(SignedAmount)
+    }
+
+    /// Converts to an unsigned amount
+    pub fn to_unsigned(self) -> Result<Amount, ParseAmountError> {
+        u64::try_from(self.0)
+            .map(Amount)
+            .map_err(|_| ParseAmountError::Negative)
+    }
+}
+
+impl From<SignedAmount> for i64 {
+    fn from(amount: SignedAmount) -> i64 {
+        amount.0
+    }
+}
+
+/// Formats as `"<value> BTC"`
+impl fmt::Display for SignedAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_value_in(f, Denomination::Bitcoin)?;
+        write!(f, " {}", Denomination::Bitcoin)
+    }
+}
+
+/// Parses `"<value> <denomination>"`, such as `"-0.001 BTC"`
+impl str::FromStr for SignedAmount {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, denomination) = split_denomination(s)?;
+        SignedAmount::from_str_in(value, denomination)
+    }
+}
+
+/// Serde support for amounts.
+///
+/// Amounts serialize as a number of satoshis. Human-readable formats deserialize them
+/// from either a number of satoshis or a string with a denomination such as
+/// `"0.001 BTC"`, other formats from a number of satoshis only, within `MAX_MONEY`
+/// like when parsed. Use
+/// `#[serde(with = "amount::serde_str")]` to serialize as such a string instead.
+mod serde_impl {
+    use super::{Amount, ParseAmountError, SignedAmount, MAX_MONEY};
+    use core::fmt;
+    use serde::de::{self, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    impl Serialize for Amount {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u64(self.0)
+        }
+    }
+
+    impl Serialize for SignedAmount {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_i64(self.0)
+        }
+    }
+
+    struct AmountVisitor;
+
+    impl<'de> Visitor<'de> for AmountVisitor {
+        type Value = Amount;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a number of satoshis or an amount with a denomination")
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<Amount, E> {
+            if value > MAX_MONEY {
+                return Err(E::custom(ParseAmountError::TooBig));
+            }
+            Ok(Amount(value))
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<Amount, E> {
+            let value = u64::try_from(value).map_err(|_| E::custom(ParseAmountError::Negative))?;
+            self.visit_u64(value)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Amount, E> {
+            value.parse().map_err(E::custom)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Amount {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_any(AmountVisitor)
+            } else {
+                AmountVisitor.visit_u64(u64::deserialize(deserializer)?)
+            }
+        }
+    }
+
+    struct SignedAmountVisitor;
+
+    impl<'de> Visitor<'de> for SignedAmountVisitor {
+        type Value = SignedAmount;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a number of satoshis or an amount with a denomination")
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<SignedAmount, E> {
+            if value > MAX_MONEY {
+                return Err(E::custom(ParseAmountError::TooBig));
+            }
+            Ok(SignedAmount(value as i64))
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<SignedAmount, E> {
+            if value.unsigned_abs() > MAX_MONEY {
+                return Err(E::custom(ParseAmountError::TooBig));
+            }
+            Ok(SignedAmount(value))
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<SignedAmount, E> {
+            value.parse().map_err(E::custom)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for SignedAmount {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_any(SignedAmountVisitor)
+            } else {
+                SignedAmountVisitor.visit_i64(i64::deserialize(deserializer)?)
+            }
+        }
+    }
+}
+
+/// Serializes an [`Amount`] or [`SignedAmount`] as a string with a denomination, for
+/// use with `#[serde(with = "amount::serde_str")]`
+pub mod serde_str {
+    #[cfg(not(feature = "std"))]
+    use alloc::string::String;
+    use core::fmt::Display;
+    use core::str::FromStr;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T: Display, S: Serializer>(
+        amount: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(amount)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de> + FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            T::deserialize(deserializer)
+        } else {
+            let s = String::deserialize(deserializer)?;
+            s.parse().map_err(de::Error::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!("0.001 BTC".parse(), Ok(Amount::from_sat(100_000)));
+        assert_eq!("1 mBTC".parse(), Ok(Amount::from_sat(100_000)));
+        assert_eq!("2.5 bits".parse(), Ok(Amount::from_sat(250)));
+        assert_eq!("1500 sat".parse(), Ok(Amount::from_sat(1_500)));
+        assert_eq!(".5 btc".parse(), Ok(Amount::from_sat(50_000_000)));
+        assert_eq!(
+            Amount::from_str_in("21000000", Denomination::Bitcoin),
+            Ok(Amount::MAX_MONEY)
+        );
+        assert_eq!(
+            Amount::from_str_in("1.50000000000", Denomination::Bitcoin),
+            Ok(Amount::from_sat(150_000_000))
+        );
+
+        assert_eq!(
+            "0.000000001 BTC".parse::<Amount>(),
+            Err(ParseAmountError::TooPrecise)
+        );
+        assert_eq!(
+            "1.5 sat".parse::<Amount>(),
+            Err(ParseAmountError::TooPrecise)
+        );
+        assert_eq!(
+            "21000000.00000001 BTC".parse::<Amount>(),
+            Err(ParseAmountError::TooBig)
+        );
+        assert_eq!(
+            "99999999999999999999 sat".parse::<Amount>(),
+            Err(ParseAmountError::TooBig)
+        );
+        assert_eq!("-1 sat".parse::<Amount>(), Err(ParseAmountError::Negative));
+        assert_eq!(
+            "1,5 BTC".parse::<Amount>(),
+            Err(ParseAmountError::InvalidFormat)
+        );
+        assert_eq!(
+            ". BTC".parse::<Amount>(),
+            Err(ParseAmountError::InvalidFormat)
+        );
+        assert_eq!(
+            "1 MBTC".parse::<Amount>(),
+            Err(ParseAmountError::UnknownDenomination)
+        );
+        assert_eq!(
+            "1".parse::<Amount>(),
+            Err(ParseAmountError::UnknownDenomination)
+        );
+    }
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(Amount::from_sat(100_000).to_string(), "0.001 BTC");
+        assert_eq!(Amount::ONE_BTC.to_string(), "1 BTC");
+        assert_eq!(Amount::from_sat(123_456_789).to_string(), "1.23456789 BTC");
+        assert_eq!(SignedAmount::from_sat(-50).to_string(), "-0.0000005 BTC");
+
+        let mut s = String::new();
+        Amount::from_sat(250)
+            .fmt_value_in(&mut s, Denomination::Bit)
+            .unwrap();
+        assert_eq!(s, "2.5");
+
+        for sat in [0, 1, 99, 100_000, 2_100_000_000_000_000] {
+            let amount = Amount::from_sat(sat);
+            assert_eq!(amount.to_string().parse(), Ok(amount));
+        }
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let max = Amount::from_sat(u64::MAX);
+        assert_eq!(max.checked_add(Amount::ONE_SAT), None);
+        assert_eq!(Amount::ZERO.checked_sub(Amount::ONE_SAT), None);
+        assert_eq!(
+            Amount::ONE_BTC.checked_mul(3),
+            Some(Amount::from_sat(300_000_000))
+        );
+        assert_eq!(Amount::ONE_BTC.checked_div(0), None);
+        assert_eq!(
+            Amount::checked_sum([Amount::ONE_BTC, Amount::ONE_SAT]),
+            Some(Amount::from_sat(100_000_001))
+        );
+        assert_eq!(Amount::checked_sum([max, Amount::ONE_SAT]), None);
+        assert!(Amount::MAX_MONEY.is_valid_money());
+        assert!(!Amount::MAX_MONEY
+            .checked_add(Amount::ONE_SAT)
+            .unwrap()
+            .is_valid_money());
+
+        let signed = SignedAmount::from_sat(-5);
+        assert_eq!(signed.to_unsigned(), Err(ParseAmountError::Negative));
+        assert_eq!(
+            signed.checked_abs().unwrap().to_unsigned(),
+            Ok(Amount::from_sat(5))
+        );
+        assert_eq!(max.to_signed(), None);
+        assert_eq!("-0.5 BTC".parse(), Ok(SignedAmount::from_sat(-50_000_000)));
+    }
+
+    #[test]
+    fn test_amount_serde() {
+        #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+        struct Payment {
+            value: Amount,
+            #[serde(with = "serde_str")]
+            fee: SignedAmount,
+        }
+
+        let payment = Payment {
+            value: Amount::from_sat(100_000),
+            fee: SignedAmount::from_sat(-1_000),
+        };
+        let json = serde_json::to_string(&payment).unwrap();
+        assert_eq!(json, r#"{"value":100000,"fee":"-0.00001 BTC"}"#);
+        assert_eq!(serde_json::from_str::<Payment>(&json).unwrap(), payment);
+
+        let value: Amount = serde_json::from_str(r#""0.001 BTC""#).unwrap();
+        assert_eq!(value, Amount::from_sat(100_000));
+        assert!(serde_json::from_str::<Amount>("-1").is_err());
+
+        // the same range as when parsed
+        assert_eq!(
+            serde_json::from_str::<Amount>("2100000000000000").unwrap(),
+            Amount::MAX_MONEY
+        );
+        assert!(serde_json::from_str::<Amount>("2100000000000001").is_err());
+        assert!(serde_json::from_str::<Amount>(&u64::MAX.to_string()).is_err());
+        assert_eq!(
+            serde_json::from_str::<SignedAmount>("-2100000000000000").unwrap(),
+            SignedAmount::from_sat(-SignedAmount::MAX_MONEY.to_sat())
+        );
+        assert!(serde_json::from_str::<SignedAmount>("2100000000000001").is_err());
+        assert!(serde_json::from_str::<SignedAmount>("-2100000000000001").is_err());
+
+        // binary formats only take a number of satoshis
+        let cbor = serde_cbor::to_vec(&payment).unwrap();
+        assert_eq!(serde_cbor::from_slice::<Payment>(&cbor).unwrap(), payment);
+        let cbor = serde_cbor::to_vec(&"0.001 BTC").unwrap();
+        assert!(serde_cbor::from_slice::<Amount>(&cbor).is_err());
+        let cbor = serde_cbor::to_vec(&(MAX_MONEY + 1)).unwrap();
+        assert!(serde_cbor::from_slice::<Amount>(&cbor).is_err());
+        assert!(serde_cbor::from_slice::<SignedAmount>(&cbor).is_err());
+    }
+}
@@ -0,0 +1,688 @@
+//! Compact blocks of BIP 152.
+//!
+//! A block is announced with its header and the short ids of its transactions,
+//! and rebuilt from the transactions of the mempool, requesting only the missing
+//! ones to the peer.
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use core::fmt;
+use light_bitcoin_crypto::{sha256, siphash24};
+use light_bitcoin_primitives::{io, H256};
+use light_bitcoin_serialization::{CompactInteger, Deserializable, Reader, Serializable, Stream};
+
+use crate::block_header::BlockHeader;
+use crate::indexed_block::IndexedBlock;
+use crate::indexed_header::IndexedBlockHeader;
+use crate::indexed_transaction::IndexedTransaction;
+use crate::transaction::Transaction;
+
+/// Size of a short transaction id in bytes
+pub const SHORT_ID_SIZE: usize = 6;
+/// Maximum number of transactions of a block, with the smallest serializable transaction
+const MAX_BLOCK_TRANSACTIONS: usize = 4_000_000 / 40;
+
+/// Compact block errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The compact block has no transactions, too many, or invalid prefilled indexes
+    InvalidCompactBlock,
+    /// Two transactions of the compact block have the same short id, the full block
+    /// should be requested instead
+    ShortIdCollision,
+    /// The transactions do not match the missing transactions of the block
+    UnexpectedTransactions,
+    /// The rebuilt block does not match the header merkle root, which may be a short id
+    /// collision with a mempool transaction
+    MerkleRootMismatch,
+    /// Transaction indexes are not strictly increasing
+    InvalidIndexes,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match *self {
+            Error::InvalidCompactBlock => "Invalid compact block",
+            Error::ShortIdCollision => "Duplicate short ids in compact block",
+            Error::UnexpectedTransactions => "Unexpected block transactions",
+            Error::MerkleRootMismatch => "Merkle root mismatch of the rebuilt block",
+            Error::InvalidIndexes => "Transaction indexes not strictly increasing",
+        };
+        msg.fmt(f)
+    }
+}
+
+/// A transaction sent in full in a compact block, with its index in the block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefilledTransaction {
+    pub index: u16,
+    pub transaction: Transaction,
+}
+
+/// The `cmpctblock` message.
+///
+/// Transactions are serialized with their witness when the stream includes them, as
+/// in version 2 compact blocks.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HeaderAndShortIds {
+    pub header: BlockHeader,
+    pub nonce: u64,
+    /// Short ids of the transactions not prefilled, in block order
+    pub short_ids: Vec<u64>,
+    /// Prefilled transactions, with strictly increasing indexes
+    prefilled_transactions: Vec<PrefilledTransaction>,
+}
+
+impl HeaderAndShortIds {
+    /// Checks the indexes of the prefilled transactions are strictly increasing, so
+    /// that they can be encoded as differences.
+    pub fn new(
+        header: BlockHeader,
+        nonce: u64,
+        short_ids: Vec<u64>,
+        prefilled_transactions: Vec<PrefilledTransaction>,
+    ) -> Result<Self, Error> {
+        check_indexes(prefilled_transactions.iter().map(|tx| tx.index))?;
+        Ok(HeaderAndShortIds {
+            header,
+            nonce,
+            short_ids,
+            prefilled_transactions,
+        })
+    }
+
+    /// Creates the compact block of a block, prefilling the coinbase.
+    ///
+    /// Short ids are computed from the wtxids in version 2 compact blocks, and from the
+    /// txids in version 1.
+    pub fn from_block(block: &IndexedBlock, nonce: u64, use_wtxid: bool) -> Self {
+        let mut compact = HeaderAndShortIds {
+            header: block.header.raw,
+            nonce,
+            short_ids: Vec::with_capacity(block.transactions.len().saturating_sub(1)),
+            prefilled_transactions: vec![],
+        };
+        if let Some((coinbase, transactions)) = block.transactions.split_first() {
+            compact.prefilled_transactions.push(PrefilledTransaction {
+                index: 0,
+                transaction: coinbase.raw.clone(),
+            });
+            let key = compact.short_id_key();
+            compact.short_ids = transactions
+                .iter()
+                .map(|tx| short_id(key, &transaction_id(tx, use_wtxid)))
+                .collect();
+        }
+        compact
+    }
+
+    /// Siphash keys from the single sha256 of the header and the nonce
+    pub fn short_id_key(&self) -> (u64, u64) {
+        let mut stream = Stream::default();
+        stream.append(&self.header).append(&self.nonce);
+        let hash = sha256(&stream.out());
+        let mut k0 = [0u8; 8];
+        let mut k1 = [0u8; 8];
+        k0.copy_from_slice(&hash.as_bytes()[..8]);
+        k1.copy_from_slice(&hash.as_bytes()[8..16]);
+        (u64::from_le_bytes(k0), u64::from_le_bytes(k1))
+    }
+
+    /// Short id of a txid or a wtxid in this compact block
+    pub fn short_id(&self, hash: &H256) -> u64 {
+        short_id(self.short_id_key(), hash)
+    }
+
+    /// Prefilled transactions, in block order
+    pub fn prefilled_transactions(&self) -> &[PrefilledTransaction] {
+        &self.prefilled_transactions
+    }
+
+    /// Number of transactions of the block
+    pub fn transaction_count(&self) -> usize {
+        self.short_ids.len() + self.prefilled_transactions.len()
+    }
+}
+
+fn short_id(key: (u64, u64), hash: &H256) -> u64 {
+    siphash24(key.0, key.1, hash.as_bytes()) & 0xffff_ffff_ffff
+}
+
+fn transaction_id(tx: &IndexedTransaction, use_wtxid: bool) -> H256 {
+    if use_wtxid {
+        tx.raw.witness_hash()
+    } else {
+        tx.hash
+    }
+}
+
+impl Serializable for HeaderAndShortIds {
+    fn serialize(&self, stream: &mut Stream) {
+        stream
+            .append(&self.header)
+            .append(&self.nonce)
+            .append(&CompactInteger::from(self.short_ids.len()));
+        for short_id in &self.short_ids {
+            stream.append_slice(&short_id.to_le_bytes()[..SHORT_ID_SIZE]);
+        }
+        stream.append(&CompactInteger::from(self.prefilled_transactions.len()));
+        let mut next = 0u32;
+        for prefilled in &self.prefilled_transactions {
+            // strictly increasing, checked by `new`
+            let index = u32::from(prefilled.index);
+            stream
+                .append(&CompactInteger::from(index - next))
+                .append(&prefilled.transaction);
+            next = index + 1;
+        }
+    }
+}
+
+impl Deserializable for HeaderAndShortIds {
+    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
+    where
+        T: io::Read,
+    {
+        let header = reader.read()?;
+        let nonce = reader.read()?;
+
+        let count: usize = reader.read::<CompactInteger>()?.into();
+        if count > MAX_BLOCK_TRANSACTIONS {
+            return Err(io::Error::ReadMalformedData);
+        }
+        let mut short_ids = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut bytes = [0u8; 8];
+            reader.read_slice(&mut bytes[..SHORT_ID_SIZE])?;
+            short_ids.push(u64::from_le_bytes(bytes));
+        }
+
+        let count: usize = reader.read::<CompactInteger>()?.into();
+        if count > MAX_BLOCK_TRANSACTIONS {
+            return Err(io::Error::ReadMalformedData);
+        }
+        let mut prefilled_transactions = Vec::with_capacity(count);
+        let mut next = 0;
+        for _ in 0..count {
+            let index = read_differential_index(reader, next)?;
+            prefilled_transactions.push(PrefilledTransaction {
+                index,
+                transaction: reader.read()?,
+            });
+            next = u32::from(index) + 1;
+        }
+
+        Ok(HeaderAndShortIds {
+            header,
+            nonce,
+            short_ids,
+            prefilled_transactions,
+        })
+    }
+}
+
+/// Reads an index encoded as the difference with the index following the previous one.
+/// Indexes above 16 bits are rejected.
+fn read_differential_index<T: io::Read>(
+    reader: &mut Reader<T>,
+    next: u32,
+) -> Result<u16, io::Error> {
+    let difference = u64::from(reader.read::<CompactInteger>()?);
+    u16::try_from(u64::from(next) + difference).map_err(|_| io::Error::ReadMalformedData)
+}
+
+/// Checks the indexes are strictly increasing, so that they can be encoded as the
+/// difference with the index following the previous one.
+fn check_indexes<I>(indexes: I) -> Result<(), Error>
+where
+    I: IntoIterator<Item = u16>,
+{
+    let mut next = 0u32;
+    for index in indexes {
+        let index = u32::from(index);
+        if index < next {
+            return Err(Error::InvalidIndexes);
+        }
+        next = index + 1;
+    }
+    Ok(())
+}
+
+/// Writes strictly increasing indexes, see [`check_indexes`].
+fn write_differential_indexes(stream: &mut Stream, indexes: &[u16]) {
+    stream.append(&CompactInteger::from(indexes.len()));
+    let mut next = 0u32;
+    for index in indexes {
+        let index = u32::from(*index);
+        stream.append(&CompactInteger::from(index - next));
+        next = index + 1;
+    }
+}
+
+/// The `getblocktxn` message, requesting the transactions of a block by index
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BlockTransactionsRequest {
+    pub block_hash: H256,
+    /// Strictly increasing indexes of the requested transactions
+    indexes: Vec<u16>,
+}
+
+impl BlockTransactionsRequest {
+    /// Checks the requested indexes are strictly increasing, so that they can be
+    /// encoded as differences.
+    pub fn new(block_hash: H256, indexes: Vec<u16>) -> Result<Self, Error> {
+        check_indexes(indexes.iter().copied())?;
+        Ok(BlockTransactionsRequest {
+            block_hash,
+            indexes,
+        })
+    }
+
+    /// Indexes of the requested transactions, in block order
+    pub fn indexes(&self) -> &[u16] {
+        &self.indexes
+    }
+}
+
+impl Serializable for BlockTransactionsRequest {
+    fn serialize(&self, stream: &mut Stream) {
+        stream.append(&self.block_hash);
+        write_differential_indexes(stream, &self.indexes);
+    }
+}
+
+impl Deserializable for BlockTransactionsRequest {
+    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
+    where
+        T: io::Read,
+    {
+        let block_hash = reader.read()?;
+        let count: usize = reader.read::<CompactInteger>()?.into();
+        if count > MAX_BLOCK_TRANSACTIONS {
+            return Err(io::Error::ReadMalformedData);
+        }
+        let mut indexes = Vec::with_capacity(count);
+        let mut next = 0;
+        for _ in 0..count {
+            let index = read_differential_index(reader, next)?;
+            indexes.push(index);
+            next = u32::from(index) + 1;
+        }
+        Ok(BlockTransactionsRequest {
+            block_hash,
+            indexes,
+        })
+    }
+}
+
+/// The `blocktxn` message, with the transactions requested by a `getblocktxn`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serializable, Deserializable)]
+pub struct BlockTransactions {
+    pub block_hash: H256,
+    pub transactions: Vec<Transaction>,
+}
+
+/// A block rebuilt from a compact block and the mempool, waiting for its missing
+/// transactions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartiallyDownloadedBlock {
+    header: IndexedBlockHeader,
+    transactions: Vec<Option<Transaction>>,
+}
+
+impl PartiallyDownloadedBlock {
+    /// Fills the block with the prefilled transactions and the matching transactions of
+    /// the mempool. Mempool transactions sharing a short id are left missing.
+    pub fn new<'a, I>(
+        compact: &HeaderAndShortIds,
+        mempool: I,
+        use_wtxid: bool,
+    ) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = &'a IndexedTransaction>,
+    {
+        let count = compact.transaction_count();
+        if count == 0 || count > MAX_BLOCK_TRANSACTIONS {
+            return Err(Error::InvalidCompactBlock);
+        }
+
+        let mut transactions = vec![None; count];
+        for prefilled in &compact.prefilled_transactions {
+            let slot = transactions
+                .get_mut(usize::from(prefilled.index))
+                .ok_or(Error::InvalidCompactBlock)?;
+            if slot.is_some() {
+                return Err(Error::InvalidCompactBlock);
+            }
+            *slot = Some(prefilled.transaction.clone());
+        }
+
+        // short ids are assigned to the remaining slots in order
+        let mut slots = BTreeMap::new();
+        let empty_slots = transactions
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| tx.is_none())
+            .map(|(index, _)| index);
+        for (short_id, index) in compact.short_ids.iter().zip(empty_slots) {
+            if slots.insert(*short_id, (index, 0usize)).is_some() {
+                return Err(Error::ShortIdCollision);
+            }
+        }
+
+        let key = compact.short_id_key();
+        for tx in mempool {
+            if let Some((index, found)) =
+                slots.get_mut(&short_id(key, &transaction_id(tx, use_wtxid)))
+            {
+                *found += 1;
+                transactions[*index] = if *found == 1 {
+                    Some(tx.raw.clone())
+                } else {
+                    None
+                };
+            }
+        }
+
+        Ok(PartiallyDownloadedBlock {
+            header: compact.header.into(),
+            transactions,
+        })
+    }
+
+    /// Indexes of the transactions not found in the mempool
+    pub fn missing(&self) -> Vec<u16> {
+        self.transactions
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| tx.is_none())
+            .map(|(index, _)| index as u16)
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.transactions.iter().all(Option::is_some)
+    }
+
+    /// The `getblocktxn` request of the missing transactions
+    pub fn request(&self) -> BlockTransactionsRequest {
+        // the missing indexes are in block order
+        BlockTransactionsRequest {
+            block_hash: self.header.hash,
+            indexes: self.missing(),
+        }
+    }
+
+    /// Completes the block with the `blocktxn` response, and checks its merkle root.
+    pub fn fill(self, response: &BlockTransactions) -> Result<IndexedBlock, Error> {
+        if response.block_hash != self.header.hash {
+            return Err(Error::UnexpectedTransactions);
+        }
+        let missing = self.transactions.iter().filter(|tx| tx.is_none()).count();
+        if response.transactions.len() != missing {
+            return Err(Error::UnexpectedTransactions);
+        }
+
+        let mut received = response.transactions.iter();
+        let transactions = self
+            .transactions
+            .into_iter()
+            .map(|tx| match tx {
+                Some(tx) => tx.into(),
+                None => received.next().expect("counted above; qed").clone().into(),
+            })
+            .collect();
+        let block = IndexedBlock::new(self.header, transactions);
+        if block.merkle_root() != block.header.raw.merkle_root_hash {
+            return Err(Error::MerkleRootMismatch);
+        }
+        Ok(block)
+    }
+
+    /// The block, when no transaction is missing
+    pub fn into_block(self) -> Result<IndexedBlock, Error> {
+        let response = BlockTransactions {
+            block_hash: self.header.hash,
+            transactions: vec![],
+        };
+        self.fill(&response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_root::merkle_root;
+    use crate::transaction::{OutPoint, TransactionInput, TransactionOutput};
+    use light_bitcoin_serialization::{
+        deserialize, serialize, serialize_with_flags, SERIALIZE_TRANSACTION_WITNESS,
+    };
+
+    fn transaction(index: u32, witness: bool) -> Transaction {
+        Transaction {
+            version: 2,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    txid: H256::repeat_byte(index as u8 + 1),
+                    index,
+                },
+                script_sig: Default::default(),
+                sequence: 0xffff_ffff,
+                script_witness: if witness {
+                    vec![vec![index as u8; 72].into()]
+                } else {
+                    vec![]
+                },
+            }],
+            outputs: vec![TransactionOutput {
+                value: 1000,
+                script_pubkey: vec![0x51].into(),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    fn block() -> IndexedBlock {
+        let coinbase = TransactionInput::coinbase(vec![1, 2].into());
+        let mut transactions = vec![Transaction {
+            inputs: vec![coinbase],
+            ..transaction(0, false)
+        }];
+        transactions.extend((1..6).map(|index| transaction(index, index % 2 == 0)));
+        let hashes = transactions
+            .iter()
+            .map(Transaction::hash)
+            .collect::<Vec<_>>();
+        let header = BlockHeader {
+            version: 4,
+            merkle_root_hash: merkle_root(&hashes),
+            time: 1_600_000_000,
+            ..Default::default()
+        };
+        IndexedBlock::new(
+            header.into(),
+            transactions.into_iter().map(Into::into).collect(),
+        )
+    }
+
+    #[test]
+    fn test_compact_block_serialization() {
+        let block = block();
+        let compact = HeaderAndShortIds::from_block(&block, 42, true);
+        assert_eq!(compact.short_ids.len(), 5);
+        assert!(compact.short_ids.iter().all(|id| *id < 1 << 48));
+        assert_eq!(compact.prefilled_transactions()[0].index, 0);
+
+        for flags in [0, SERIALIZE_TRANSACTION_WITNESS] {
+            let serialized = serialize_with_flags(&compact, flags);
+            let header_size = 80 + 8 + 1 + 5 * SHORT_ID_SIZE + 1 + 1;
+            assert_eq!(
+                serialized.len(),
+                header_size + block.transactions[0].raw.serialized_size()
+            );
+            let deserialized: HeaderAndShortIds = deserialize(serialized.as_ref()).unwrap();
+            assert_eq!(deserialized, compact);
+        }
+
+        // several prefilled transactions, interleaved with their indexes
+        let mut prefilled = compact.prefilled_transactions().to_vec();
+        prefilled.push(PrefilledTransaction {
+            index: 3,
+            transaction: block.transactions[3].raw.clone(),
+        });
+        let mut short_ids = compact.short_ids.clone();
+        short_ids.remove(2);
+        let compact = HeaderAndShortIds::new(compact.header, 42, short_ids, prefilled).unwrap();
+        let serialized = serialize_with_flags(&compact, SERIALIZE_TRANSACTION_WITNESS);
+        let deserialized: HeaderAndShortIds = deserialize(serialized.as_ref()).unwrap();
+        assert_eq!(deserialized, compact);
+        let partial =
+            PartiallyDownloadedBlock::new(&deserialized, &block.transactions[1..], true).unwrap();
+        assert_eq!(partial.into_block(), Ok(block.clone()));
+
+        // indexes are encoded as differences
+        let request =
+            BlockTransactionsRequest::new(H256::repeat_byte(1), vec![0, 1, 5, 6]).unwrap();
+        let serialized = serialize(&request);
+        assert_eq!(&serialized[32..], &[4, 0, 0, 3, 0]);
+        assert_eq!(
+            deserialize::<_, BlockTransactionsRequest>(serialized.as_ref()).unwrap(),
+            request
+        );
+
+        // indexes above 16 bits are rejected
+        let mut overflow = serialize(&H256::zero()).as_ref().to_vec();
+        overflow.extend_from_slice(&[2, 0xfd, 0xff, 0xff, 0]);
+        assert!(deserialize::<_, BlockTransactionsRequest>(&overflow[..]).is_err());
+
+        // the last index re-serializes
+        let mut last = serialize(&H256::zero()).as_ref().to_vec();
+        last.extend_from_slice(&[1, 0xfd, 0xff, 0xff]);
+        let request = deserialize::<_, BlockTransactionsRequest>(&last[..]).unwrap();
+        assert_eq!(request.indexes(), [u16::MAX]);
+        assert_eq!(serialize(&request).as_ref(), &last[..]);
+        let last = PrefilledTransaction {
+            index: u16::MAX,
+            transaction: transaction(1, false),
+        };
+        let compact =
+            HeaderAndShortIds::new(Default::default(), 0, vec![], vec![last.clone()]).unwrap();
+        let serialized = serialize(&compact);
+        assert_eq!(
+            deserialize::<_, HeaderAndShortIds>(serialized.as_ref()).unwrap(),
+            compact
+        );
+
+        // indexes which are not strictly increasing are rejected
+        for indexes in [vec![1, 1], vec![5, 2], vec![u16::MAX, 0]] {
+            assert_eq!(
+                BlockTransactionsRequest::new(H256::zero(), indexes),
+                Err(Error::InvalidIndexes)
+            );
+        }
+        let first = PrefilledTransaction {
+            index: 0,
+            transaction: transaction(2, false),
+        };
+        assert_eq!(
+            HeaderAndShortIds::new(Default::default(), 0, vec![], vec![last, first]),
+            Err(Error::InvalidIndexes)
+        );
+
+        let response = BlockTransactions {
+            block_hash: H256::repeat_byte(1),
+            transactions: vec![transaction(1, true)],
+        };
+        let serialized = serialize_with_flags(&response, SERIALIZE_TRANSACTION_WITNESS);
+        assert_eq!(
+            deserialize::<_, BlockTransactions>(serialized.as_ref()).unwrap(),
+            response
+        );
+    }
+
+    #[test]
+    fn test_reconstruct_from_mempool() {
+        let block = block();
+        for use_wtxid in [false, true] {
+            let compact = HeaderAndShortIds::from_block(&block, 7, use_wtxid);
+            let partial =
+                PartiallyDownloadedBlock::new(&compact, &block.transactions[1..], use_wtxid)
+                    .unwrap();
+            assert!(partial.is_complete());
+            assert_eq!(partial.into_block(), Ok(block.clone()));
+        }
+    }
+
+    #[test]
+    fn test_reconstruct_missing_transactions() {
+        let block = block();
+        let compact = HeaderAndShortIds::from_block(&block, 7, true);
+        let mempool = vec![
+            block.transactions[1].clone(),
+            block.transactions[3].clone(),
+            block.transactions[5].clone(),
+            transaction(10, false).into(),
+        ];
+        let partial = PartiallyDownloadedBlock::new(&compact, &mempool, true).unwrap();
+        assert!(!partial.is_complete());
+        assert_eq!(partial.missing(), vec![2, 4]);
+        let request = partial.request();
+        assert_eq!(request.block_hash, block.header.hash);
+        assert_eq!(
+            partial.clone().into_block(),
+            Err(Error::UnexpectedTransactions)
+        );
+
+        let response = |indexes: &[usize]| BlockTransactions {
+            block_hash: block.header.hash,
+            transactions: indexes
+                .iter()
+                .map(|index| block.transactions[*index].raw.clone())
+                .collect(),
+        };
+        assert_eq!(
+            partial.clone().fill(&response(&[2])),
+            Err(Error::UnexpectedTransactions)
+        );
+        assert_eq!(
+            partial.clone().fill(&response(&[4, 2])),
+            Err(Error::MerkleRootMismatch)
+        );
+        assert_eq!(partial.fill(&response(&[2, 4])), Ok(block));
+    }
+
+    #[test]
+    fn test_invalid_compact_block() {
+        let block = block();
+        let compact = HeaderAndShortIds::from_block(&block, 7, true);
+        let prefilled = PrefilledTransaction {
+            index: 6,
+            ..compact.prefilled_transactions()[0].clone()
+        };
+        let compact =
+            HeaderAndShortIds::new(compact.header, 7, compact.short_ids, vec![prefilled]).unwrap();
+        assert_eq!(
+            PartiallyDownloadedBlock::new(&compact, &[], true),
+            Err(Error::InvalidCompactBlock)
+        );
+
+        let mut compact = HeaderAndShortIds::from_block(&block, 7, true);
+        compact.short_ids[1] = compact.short_ids[0];
+        assert_eq!(
+            PartiallyDownloadedBlock::new(&compact, &[], true),
+            Err(Error::ShortIdCollision)
+        );
+
+        assert_eq!(
+            PartiallyDownloadedBlock::new(&HeaderAndShortIds::default(), &[], true),
+            Err(Error::InvalidCompactBlock)
+        );
+    }
+}
@@ -0,0 +1,402 @@
+//! Compact block filters of BIP 158, and the filter headers of BIP 157.
+//!
+//! A basic filter is a Golomb-coded set of the output scripts of a block and of the
+//! scripts spent by its inputs, allowing light clients to match their own scripts
+//! without downloading the block or revealing the scripts to peers.
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeSet, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
+
+use core::fmt;
+use light_bitcoin_crypto::{dhash256, siphash24};
+use light_bitcoin_primitives::{Bytes, H256};
+use light_bitcoin_serialization::{CompactInteger, Deserializable, Reader, Serializable, Stream};
+
+use crate::indexed_block::IndexedBlock;
+use crate::transaction::OutPoint;
+
+/// Golomb-Rice parameter of basic filters
+pub const BASIC_FILTER_P: u8 = 19;
+/// Inverse false positive rate of basic filters
+pub const BASIC_FILTER_M: u64 = 784_931;
+
+/// `OP_RETURN` outputs are not included in basic filters
+const OP_RETURN: u8 = 0x6a;
+
+/// Block filter errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The script of the spent output is not provided
+    MissingPrevout(OutPoint),
+    /// The filter content is truncated or malformed
+    InvalidFilter,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match *self {
+            Error::MissingPrevout(_) => "Missing script of a spent output",
+            Error::InvalidFilter => "Invalid block filter",
+        };
+        msg.fmt(f)
+    }
+}
+
+/// Basic block filter, serialized as in the `cfilter` message
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serializable, Deserializable)]
+pub struct BlockFilter {
+    /// Number of elements followed by the Golomb-Rice coded set
+    pub content: Bytes,
+}
+
+impl BlockFilter {
+    pub fn new(content: Bytes) -> Self {
+        BlockFilter { content }
+    }
+
+    /// Builds the basic filter of a block.
+    ///
+    /// `prevout_script` provides the script of each output spent by the block, as found
+    /// in the undo data of Bitcoin Core.
+    pub fn new_basic<F>(block: &IndexedBlock, mut prevout_script: F) -> Result<Self, Error>
+    where
+        F: FnMut(&OutPoint) -> Option<Bytes>,
+    {
+        let mut elements = BTreeSet::new();
+        for tx in &block.transactions {
+            for output in &tx.raw.outputs {
+                let script: &[u8] = &output.script_pubkey;
+                if !script.is_empty() && script[0] != OP_RETURN {
+                    elements.insert(script.to_vec());
+                }
+            }
+            if tx.raw.is_coinbase() {
+                continue;
+            }
+            for input in &tx.raw.inputs {
+                let script = prevout_script(&input.previous_output)
+                    .ok_or(Error::MissingPrevout(input.previous_output))?;
+                if !script.is_empty() {
+                    elements.insert(script.into());
+                }
+            }
+        }
+
+        let key = filter_key(&block.header.hash);
+        let values = hashed_set(&key, elements.len() as u64, elements.iter());
+
+        let mut stream = Stream::default();
+        stream.append(&CompactInteger::from(elements.len()));
+        let mut writer = BitWriter::default();
+        let mut last = 0;
+        for value in values {
+            writer.write_golomb(value - last);
+            last = value;
+        }
+        stream.append_slice(&writer.finish());
+        Ok(BlockFilter::new(stream.out()))
+    }
+
+    /// Double sha256 of the filter content
+    pub fn filter_hash(&self) -> H256 {
+        dhash256(&self.content)
+    }
+
+    /// Filter header of this filter, chained to the filter header of the previous block
+    pub fn filter_header(&self, previous_filter_header: &H256) -> H256 {
+        filter_header(&self.filter_hash(), previous_filter_header)
+    }
+
+    /// Returns true if any of the scripts is in the filter of the block.
+    pub fn match_any<'a, I>(&self, block_hash: &H256, scripts: I) -> Result<bool, Error>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let (_, matched) = self.query(block_hash, scripts)?;
+        Ok(matched > 0)
+    }
+
+    /// Returns true if all the scripts are in the filter of the block.
+    pub fn match_all<'a, I>(&self, block_hash: &H256, scripts: I) -> Result<bool, Error>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let (queries, matched) = self.query(block_hash, scripts)?;
+        Ok(matched == queries)
+    }
+
+    /// Number of elements in the filter
+    pub fn element_count(&self) -> Result<u64, Error> {
+        let mut reader = Reader::new(&self.content);
+        let n: CompactInteger = reader.read().map_err(|_| Error::InvalidFilter)?;
+        Ok(n.into())
+    }
+
+    /// Returns the number of queried scripts and how many of them are in the filter
+    fn query<'a, I>(&self, block_hash: &H256, scripts: I) -> Result<(usize, usize), Error>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let n = self.element_count()?;
+        // each element takes at least P + 1 bits
+        if n.saturating_mul(u64::from(BASIC_FILTER_P) + 1) > self.content.len() as u64 * 8 {
+            return Err(Error::InvalidFilter);
+        }
+        let queries = hashed_set(&filter_key(block_hash), n, scripts);
+        let mut matched = 0;
+        if n == 0 {
+            return Ok((queries.len(), matched));
+        }
+
+        let header_size = CompactInteger::from(n).serialized_size();
+        let mut reader = BitReader::new(&self.content[header_size..]);
+        let mut queries_left = queries.iter().peekable();
+        let mut value = 0u64;
+        for _ in 0..n {
+            if queries_left.peek().is_none() {
+                break;
+            }
+            value += reader.read_golomb()?;
+            while let Some(query) = queries_left.next_if(|query| **query <= value) {
+                if *query == value {
+                    matched += 1;
+                }
+            }
+        }
+        Ok((queries.len(), matched))
+    }
+}
+
+/// Filter header of BIP 157, committing to the filter hash and the previous filter
+/// header. The previous filter header of the genesis block is zero.
+pub fn filter_header(filter_hash: &H256, previous_filter_header: &H256) -> H256 {
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(filter_hash.as_bytes());
+    data[32..].copy_from_slice(previous_filter_header.as_bytes());
+    dhash256(&data)
+}
+
+/// Siphash keys from the first 16 bytes of the block hash
+fn filter_key(block_hash: &H256) -> (u64, u64) {
+    let bytes = block_hash.as_bytes();
+    let mut k0 = [0u8; 8];
+    let mut k1 = [0u8; 8];
+    k0.copy_from_slice(&bytes[..8]);
+    k1.copy_from_slice(&bytes[8..16]);
+    (u64::from_le_bytes(k0), u64::from_le_bytes(k1))
+}
+
+/// Sorted hashes of the elements, mapped to the range `[0, n * M)`
+fn hashed_set<I, T>(key: &(u64, u64), n: u64, elements: I) -> Vec<u64>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let range = n * BASIC_FILTER_M;
+    let mut values = elements
+        .into_iter()
+        .map(|element| {
+            let hash = siphash24(key.0, key.1, element.as_ref());
+            ((u128::from(hash) * u128::from(range)) >> 64) as u64
+        })
+        .collect::<Vec<_>>();
+    values.sort_unstable();
+    values
+}
+
+/// Writes bits from the most significant bit of each byte
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    used: u8,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.used == 0 {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().expect("pushed above; qed") |= 0x80 >> self.used;
+        }
+        self.used = (self.used + 1) % 8;
+    }
+
+    /// Unary coded quotient followed by the `P` low bits
+    fn write_golomb(&mut self, value: u64) {
+        for _ in 0..value >> BASIC_FILTER_P {
+            self.write_bit(true);
+        }
+        self.write_bit(false);
+        for i in (0..BASIC_FILTER_P).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, position: 0 }
+    }
+
+    fn read_bit(&mut self) -> Result<bool, Error> {
+        let byte = self
+            .bytes
+            .get(self.position / 8)
+            .ok_or(Error::InvalidFilter)?;
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+        Ok(bit)
+    }
+
+    fn read_golomb(&mut self) -> Result<u64, Error> {
+        let mut quotient = 0u64;
+        while self.read_bit()? {
+            quotient += 1;
+        }
+        let mut value = quotient;
+        for _ in 0..BASIC_FILTER_P {
+            value = (value << 1) | u64::from(self.read_bit()?);
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use light_bitcoin_primitives::h256_rev;
+    use light_bitcoin_serialization::{deserialize, serialize};
+
+    // testnet genesis block
+    const GENESIS: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff001d1aa4ae180101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+    fn genesis() -> IndexedBlock {
+        let block: Block = deserialize(hex::decode(GENESIS).unwrap().as_slice()).unwrap();
+        block.into()
+    }
+
+    // test vector from bip-0158/testnet-19.json
+    #[test]
+    fn test_basic_filter_genesis() {
+        let block = genesis();
+        assert_eq!(
+            block.header.hash,
+            h256_rev("000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943")
+        );
+
+        let filter = BlockFilter::new_basic(&block, |_| None).unwrap();
+        assert_eq!(filter.content, hex::decode("019dfca8").unwrap().into());
+        assert_eq!(
+            filter.filter_header(&H256::zero()),
+            h256_rev("21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750")
+        );
+
+        let script: &[u8] = &block.transactions[0].raw.outputs[0].script_pubkey;
+        assert_eq!(filter.match_any(&block.header.hash, vec![script]), Ok(true));
+        assert_eq!(
+            filter.match_any(&block.header.hash, vec![&[0x51u8][..]]),
+            Ok(false)
+        );
+
+        // serialized with its length in the cfilter message
+        assert_eq!(
+            serialize(&filter),
+            hex::decode("04019dfca8").unwrap().into()
+        );
+        assert_eq!(
+            deserialize::<_, BlockFilter>(&[4u8, 1, 0x9d, 0xfc, 0xa8][..]).unwrap(),
+            filter
+        );
+    }
+
+    #[test]
+    fn test_basic_filter_match() {
+        let mut block = genesis();
+        let coinbase = block.transactions[0].raw.clone();
+        let mut tx = coinbase.clone();
+        tx.inputs[0].previous_output = OutPoint {
+            txid: block.transactions[0].hash,
+            index: 0,
+        };
+        tx.outputs = (0..100u8)
+            .map(|i| crate::TransactionOutput {
+                value: 1,
+                script_pubkey: vec![0x00, 0x14, i].into(),
+            })
+            .collect();
+        // OP_RETURN and empty scripts are not included
+        tx.outputs[0].script_pubkey = vec![OP_RETURN, 0x01, 0x00].into();
+        tx.outputs[1].script_pubkey = Default::default();
+        block.transactions.push(tx.into());
+
+        assert_eq!(
+            BlockFilter::new_basic(&block, |_| None),
+            Err(Error::MissingPrevout(OutPoint {
+                txid: block.transactions[0].hash,
+                index: 0,
+            }))
+        );
+        let spent: Bytes = vec![0x51, 0x52].into();
+        let filter = BlockFilter::new_basic(&block, |_| Some(spent.clone())).unwrap();
+        let hash = block.header.hash;
+
+        // 98 outputs, the coinbase output and the spent script
+        assert_eq!(filter.element_count(), Ok(100));
+        let included = vec![&[0x00, 0x14, 50][..], &[0x51, 0x52][..]];
+        assert_eq!(filter.match_all(&hash, included.clone()), Ok(true));
+        assert_eq!(filter.match_any(&hash, included), Ok(true));
+        assert_eq!(
+            filter.match_any(&hash, vec![&[OP_RETURN, 0x01, 0x00][..]]),
+            Ok(false)
+        );
+        let partial = vec![&[0x00, 0x14, 50][..], &[0x00, 0x15, 50][..]];
+        assert_eq!(filter.match_any(&hash, partial.clone()), Ok(true));
+        assert_eq!(filter.match_all(&hash, partial), Ok(false));
+
+        // the filter is keyed by the block hash
+        assert_eq!(
+            filter.match_all(&H256::repeat_byte(1), vec![&[0x51, 0x52][..]]),
+            Ok(false)
+        );
+
+        let scripts = block
+            .transactions
+            .iter()
+            .flat_map(|tx| tx.raw.outputs.iter().skip(2))
+            .map(|output| &output.script_pubkey[..])
+            .collect::<Vec<_>>();
+        assert_eq!(filter.match_all(&hash, scripts.clone()), Ok(true));
+        let truncated = BlockFilter::new(filter.content[..10].to_vec().into());
+        assert_eq!(
+            truncated.match_all(&hash, scripts),
+            Err(Error::InvalidFilter)
+        );
+    }
+
+    #[test]
+    fn test_empty_filter() {
+        let filter = BlockFilter::new(vec![0].into());
+        assert_eq!(
+            filter.match_any(&H256::zero(), vec![&[0x51u8][..]]),
+            Ok(false)
+        );
+        assert_eq!(filter.match_all(&H256::zero(), vec![]), Ok(true));
+    }
+}
@@ -0,0 +1,266 @@
+//! Reader of the block files of Bitcoin Core, `blocks/blk*.dat`.
+//!
+//! A block file is a sequence of blocks, each one prefixed with the magic of its
+//! network and its size. Since Core 28, the bytes of the files are xored with the
+//! 8 bytes key stored in `blocks/xor.dat`.
+
+use std::{error, fmt, io};
+
+use light_bitcoin_primitives::io as ser_io;
+use light_bitcoin_serialization::Reader;
+
+use crate::indexed_block::IndexedBlock;
+
+/// Size of the obfuscation key of the block files
+pub const XOR_KEY_SIZE: usize = 8;
+/// Largest serialized size of a block, witnesses included
+pub const MAX_BLOCK_SERIALIZED_SIZE: u32 = 4_000_000;
+/// Smallest size of a block, its header
+const MIN_BLOCK_SIZE: u32 = 80;
+
+/// Block file errors
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the file failed, or it ends in the middle of a block
+    Io(io::Error),
+    /// The framed block can not be parsed
+    InvalidBlock(ser_io::Error),
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => err.fmt(f),
+            Error::InvalidBlock(err) => write!(f, "Invalid block: {}", err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Iterator over the blocks of a block file, reading one block at a time.
+///
+/// The bytes between the blocks, like the zeros preallocated at the end of the
+/// file, are skipped up to the next magic. Reading byte by byte from a file is
+/// slow, so the file should be wrapped in a `BufReader`.
+pub struct BlockFileReader<R> {
+    inner: R,
+    magic: [u8; 4],
+    xor_key: [u8; XOR_KEY_SIZE],
+    position: u64,
+}
+
+impl<R: io::Read> BlockFileReader<R> {
+    /// Reads the blocks of the network with the magic from a file which is not
+    /// obfuscated.
+    pub fn new(inner: R, magic: u32) -> Self {
+        Self::with_xor_key(inner, magic, [0; XOR_KEY_SIZE])
+    }
+
+    /// Reads the blocks from a file obfuscated with the key of `xor.dat`.
+    ///
+    /// The key applies from the start of the file, where `inner` must be.
+    pub fn with_xor_key(inner: R, magic: u32, xor_key: [u8; XOR_KEY_SIZE]) -> Self {
+        BlockFileReader {
+            inner,
+            magic: magic.to_le_bytes(),
+            xor_key,
+            position: 0,
+        }
+    }
+
+    /// Offset of the next byte read in the file
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the next block, or `None` at the end of the file.
+    ///
+    /// The header and the transactions are hashed while they are parsed.
+    pub fn next_block(&mut self) -> Result<Option<IndexedBlock>, Error> {
+        loop {
+            if !self.find_magic()? {
+                return Ok(None);
+            }
+            let mut size = [0u8; 4];
+            self.read_exact(&mut size)?;
+            let size = u32::from_le_bytes(size);
+            // not the start of a block, keep looking after the magic
+            if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SERIALIZED_SIZE).contains(&size) {
+                continue;
+            }
+
+            let mut data = vec![0u8; size as usize];
+            self.read_exact(&mut data)?;
+            let mut reader = Reader::new(&data);
+            let block: IndexedBlock = reader.read().map_err(Error::InvalidBlock)?;
+            if !reader.is_finished() {
+                return Err(Error::InvalidBlock(ser_io::Error::UnreadData));
+            }
+            return Ok(Some(block));
+        }
+    }
+
+    /// Skips the bytes up to the magic, returns `false` at the end of the file.
+    fn find_magic(&mut self) -> Result<bool, Error> {
+        let mut window = [0u8; 4];
+        let mut filled = 0;
+        loop {
+            let mut byte = [0u8];
+            if self.inner.read(&mut byte)? == 0 {
+                return Ok(false);
+            }
+            self.deobfuscate(&mut byte);
+            if filled < window.len() {
+                window[filled] = byte[0];
+                filled += 1;
+            } else {
+                window.rotate_left(1);
+                window[3] = byte[0];
+            }
+            if filled == window.len() && window == self.magic {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.inner.read_exact(buf)?;
+        self.deobfuscate(buf);
+        Ok(())
+    }
+
+    fn deobfuscate(&mut self, buf: &mut [u8]) {
+        for byte in buf.iter_mut() {
+            *byte ^= self.xor_key[(self.position % XOR_KEY_SIZE as u64) as usize];
+            self.position += 1;
+        }
+    }
+}
+
+impl<R: io::Read> Iterator for BlockFileReader<R> {
+    type Item = Result<IndexedBlock, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_block().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_header::BlockHeader;
+    use crate::merkle_root::merkle_root;
+    use crate::transaction::{OutPoint, Transaction, TransactionInput, TransactionOutput};
+    use light_bitcoin_primitives::H256;
+    use light_bitcoin_serialization::{serialize_with_flags, SERIALIZE_TRANSACTION_WITNESS};
+
+    const MAGIC: u32 = 0xd9b4_bef9;
+
+    fn block(time: u32) -> IndexedBlock {
+        let coinbase = Transaction {
+            version: 2,
+            inputs: vec![TransactionInput::coinbase(vec![1, 2].into())],
+            outputs: vec![TransactionOutput {
+                value: 50,
+                script_pubkey: vec![0x51].into(),
+            }],
+            lock_time: 0,
+        };
+        let spend = Transaction {
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    txid: H256::repeat_byte(time as u8),
+                    index: 0,
+                },
+                script_sig: Default::default(),
+                sequence: 0xffff_ffff,
+                script_witness: vec![vec![7; 72].into(), vec![2; 33].into()],
+            }],
+            ..coinbase.clone()
+        };
+        let transactions = vec![coinbase, spend];
+        let hashes = transactions
+            .iter()
+            .map(Transaction::hash)
+            .collect::<Vec<_>>();
+        let header = BlockHeader {
+            version: 4,
+            merkle_root_hash: merkle_root(&hashes),
+            time,
+            ..Default::default()
+        };
+        IndexedBlock::new(
+            header.into(),
+            transactions.into_iter().map(Into::into).collect(),
+        )
+    }
+
+    fn frame(block: &IndexedBlock) -> Vec<u8> {
+        let raw = serialize_with_flags(&block.clone().raw_block(), SERIALIZE_TRANSACTION_WITNESS);
+        let mut data = MAGIC.to_le_bytes().to_vec();
+        data.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+        data.extend_from_slice(&raw);
+        data
+    }
+
+    #[test]
+    fn test_read_blocks() {
+        let blocks = vec![block(1), block(2), block(3)];
+        let mut file: Vec<u8> = blocks.iter().flat_map(frame).collect();
+        // a magic followed by an invalid size, and the preallocated space
+        file.extend_from_slice(&MAGIC.to_le_bytes());
+        file.extend_from_slice(&[0xff; 4]);
+        file.extend_from_slice(&[0; 1000]);
+
+        let read = BlockFileReader::new(&file[..], MAGIC)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, blocks);
+        for (read, block) in read.iter().zip(&blocks) {
+            for (read, tx) in read.transactions.iter().zip(&block.transactions) {
+                assert_eq!(read.hash, tx.raw.hash());
+                assert_eq!(read.raw, tx.raw);
+            }
+        }
+
+        let xor_key = [0x5a, 0x01, 0xc3, 0x00, 0x77, 0x10, 0xfe, 0x42];
+        let obfuscated: Vec<u8> = file
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ xor_key[i % XOR_KEY_SIZE])
+            .collect();
+        let mut reader = BlockFileReader::with_xor_key(&obfuscated[..], MAGIC, xor_key);
+        assert_eq!(reader.next_block().unwrap(), Some(blocks[0].clone()));
+        assert_eq!(reader.position(), frame(&blocks[0]).len() as u64);
+        assert_eq!(reader.count(), 2);
+
+        // without the key, the magic is never found
+        assert_eq!(BlockFileReader::new(&obfuscated[..], MAGIC).count(), 0);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let mut file = frame(&block(1));
+        file.truncate(file.len() - 1);
+        let mut reader = BlockFileReader::new(&file[..], MAGIC);
+        assert!(matches!(reader.next_block(), Err(Error::Io(_))));
+
+        let mut file = frame(&block(1));
+        // one more byte in the frame than in the block
+        let size = u32::from_le_bytes(file[4..8].try_into().unwrap()) + 1;
+        file[4..8].copy_from_slice(&size.to_le_bytes());
+        file.push(0);
+        let mut reader = BlockFileReader::new(&file[..], MAGIC);
+        assert!(matches!(
+            reader.next_block(),
+            Err(Error::InvalidBlock(ser_io::Error::UnreadData))
+        ));
+    }
+}
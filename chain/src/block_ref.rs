@@ -0,0 +1,200 @@
+//! Borrowed view of a serialized block.
+
+use light_bitcoin_crypto::dhash256;
+use light_bitcoin_primitives::{io, H256};
+use light_bitcoin_serialization::deserialize;
+
+use crate::block::Block;
+use crate::block_header::BlockHeader;
+use crate::transaction_ref::{Cursor, TransactionRef};
+
+/// Size of a serialized block header
+const HEADER_SIZE: usize = 80;
+
+/// A serialized block, read in place.
+///
+/// Every transaction is checked when the view is created, the transactions are
+/// then found again by walking the bytes, without keeping their offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRef<'a> {
+    data: &'a [u8],
+    /// Offset of the first transaction
+    transactions: usize,
+    transaction_count: usize,
+}
+
+impl<'a> BlockRef<'a> {
+    /// Checks the block, which must be the whole data.
+    pub fn new(data: &'a [u8]) -> Result<Self, io::Error> {
+        let mut cursor = Cursor::new(data);
+        cursor.read_slice(HEADER_SIZE)?;
+        let transaction_count = cursor.read_compact()?;
+        let transactions = cursor.position();
+
+        let mut rest = &data[transactions..];
+        for _ in 0..transaction_count {
+            rest = TransactionRef::parse(rest)?.1;
+        }
+        if !rest.is_empty() {
+            return Err(io::Error::UnreadData);
+        }
+
+        Ok(BlockRef {
+            data,
+            transactions,
+            transaction_count,
+        })
+    }
+
+    /// The serialized block, with the witnesses of its transactions
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn header_bytes(&self) -> &'a [u8] {
+        &self.data[..HEADER_SIZE]
+    }
+
+    pub fn header(&self) -> BlockHeader {
+        deserialize(self.header_bytes()).expect("checked when parsed; qed")
+    }
+
+    pub fn hash(&self) -> H256 {
+        dhash256(self.header_bytes())
+    }
+
+    pub fn transaction_count(&self) -> usize {
+        self.transaction_count
+    }
+
+    pub fn transactions(&self) -> Transactions<'a> {
+        Transactions {
+            data: &self.data[self.transactions..],
+            remaining: self.transaction_count,
+        }
+    }
+
+    /// Copies the block into an owned one.
+    pub fn to_block(&self) -> Result<Block, io::Error> {
+        deserialize(self.data)
+    }
+}
+
+/// Iterator over the transactions of a borrowed block
+#[derive(Debug, Clone)]
+pub struct Transactions<'a> {
+    data: &'a [u8],
+    remaining: usize,
+}
+
+impl<'a> Iterator for Transactions<'a> {
+    type Item = TransactionRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let (transaction, rest) =
+            TransactionRef::parse(self.data).expect("checked when parsed; qed");
+        self.data = rest;
+        Some(transaction)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Transactions<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_root::merkle_root;
+    use crate::transaction::{OutPoint, Transaction, TransactionInput, TransactionOutput};
+    use light_bitcoin_serialization::{
+        serialize, serialize_with_flags, SERIALIZE_TRANSACTION_WITNESS,
+    };
+
+    fn block() -> Block {
+        let transactions: Vec<Transaction> = (0..5u8)
+            .map(|index| Transaction {
+                version: 1,
+                inputs: vec![TransactionInput {
+                    previous_output: OutPoint {
+                        txid: H256::repeat_byte(index),
+                        index: 0,
+                    },
+                    script_sig: vec![index; 10].into(),
+                    sequence: 0xffff_ffff,
+                    script_witness: if index % 2 == 1 {
+                        vec![vec![index; 64].into()]
+                    } else {
+                        vec![]
+                    },
+                }],
+                outputs: vec![TransactionOutput {
+                    value: u64::from(index) * 100,
+                    script_pubkey: vec![0x00, 0x14, index].into(),
+                }],
+                lock_time: 0,
+            })
+            .collect();
+        let hashes = transactions
+            .iter()
+            .map(Transaction::hash)
+            .collect::<Vec<_>>();
+        let header = BlockHeader {
+            version: 4,
+            merkle_root_hash: merkle_root(&hashes),
+            time: 1_700_000_000,
+            ..Default::default()
+        };
+        Block::new(header, transactions)
+    }
+
+    #[test]
+    fn test_block_ref() {
+        let block = block();
+        let data = serialize_with_flags(&block, SERIALIZE_TRANSACTION_WITNESS);
+        let view = BlockRef::new(&data).unwrap();
+
+        assert_eq!(view.header(), block.header);
+        assert_eq!(view.hash(), block.header.hash());
+        assert_eq!(view.transaction_count(), 5);
+        assert_eq!(view.to_block(), Ok(block.clone()));
+        for (view, owned) in view.transactions().zip(&block.transactions) {
+            assert_eq!(view.txid(), owned.hash());
+            assert_eq!(view.wtxid(), owned.witness_hash());
+        }
+        let txids = view.transactions().map(|tx| tx.txid()).collect::<Vec<_>>();
+        assert_eq!(merkle_root(&txids), block.header.merkle_root_hash);
+
+        let value: u64 = view
+            .transactions()
+            .flat_map(|tx| tx.outputs())
+            .filter(|output| output.script_pubkey.starts_with(&[0x00, 0x14]))
+            .map(|output| output.value)
+            .sum();
+        assert_eq!(value, 1000);
+
+        assert_eq!(
+            BlockRef::new(&data[..data.len() - 1]),
+            Err(io::Error::UnexpectedEof)
+        );
+        let mut trailing = data.to_vec();
+        trailing.push(0);
+        assert_eq!(BlockRef::new(&trailing), Err(io::Error::UnreadData));
+    }
+
+    #[test]
+    fn test_block_ref_non_canonical() {
+        // an empty transaction whose input count is a non-canonical zero
+        let mut data = serialize(&BlockHeader::default()).take();
+        data.extend_from_slice(&[0x01, 0x02, 0x00, 0x00, 0x00, 0xfd, 0x00, 0x00, 0x00]);
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(BlockRef::new(&data), Err(io::Error::ReadMalformedData));
+        assert!(deserialize::<_, Block>(&data[..]).is_err());
+    }
+}
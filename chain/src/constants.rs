@@ -16,9 +16,21 @@ pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
 // applied to extract that lock-time from the sequence field.
 pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_ffff;
 
+// In order to use the same number of bits to encode roughly the
+// same wall-clock duration, and because blocks are naturally
+// limited to occur every 600s on average, the minimum granularity
+// for time-based relative lock-time is fixed at 512 seconds.
+// Converting from CTxIn::nSequence to seconds is performed by
+// multiplying by 512 = 2^9, or equivalently shifting up by
+// 9 bits.
+pub const SEQUENCE_LOCKTIME_GRANULARITY: u32 = 9;
+
 /// Threshold for `nLockTime`: below this value it is interpreted as block number,
 /// otherwise as UNIX timestamp.
 pub const LOCKTIME_THRESHOLD: u32 = 500_000_000; // Tue Nov  5 00:53:20 1985 UTC
 
 /// Number of Satoshis in single coin
 pub const SATOSHIS_IN_COIN: u64 = 100_000_000;
+
+/// Maximum amount of satoshis that can exist
+pub const MAX_MONEY: u64 = 21_000_000 * SATOSHIS_IN_COIN;
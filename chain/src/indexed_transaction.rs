@@ -72,9 +72,15 @@ impl Deserializable for IndexedTransaction {
     {
         let data = reader.read_and_hash::<Transaction>()?;
         // TODO: use len
+        // the bytes read include the witnesses, which are not part of the txid
+        let hash = if data.data.has_witness() {
+            data.data.hash()
+        } else {
+            data.hash
+        };
         let tx = IndexedTransaction {
             raw: data.data,
-            hash: data.hash,
+            hash,
         };
 
         Ok(tx)
@@ -3,12 +3,20 @@
 #[cfg(not(feature = "std"))]
 extern crate alloc;
 
+pub mod amount;
+pub mod bip152;
+pub mod bip158;
+#[cfg(feature = "std")]
+pub mod block_file;
 pub mod constants;
+pub mod lock_time;
 
 mod block;
 mod block_header;
+mod block_ref;
 mod merkle_root;
 mod transaction;
+mod transaction_ref;
 
 mod indexed_block;
 mod indexed_header;
@@ -19,12 +27,20 @@ mod read_and_hash;
 
 pub use light_bitcoin_primitives::*;
 
+pub use self::amount::{Amount, Denomination, ParseAmountError, SignedAmount};
 pub use self::block::Block;
 pub use self::block_header::BlockHeader;
+pub use self::block_ref::{BlockRef, Transactions};
+pub use self::lock_time::{
+    calculate_sequence_locks, AbsoluteLockTime, RelativeLockTime, SequenceLocks,
+};
 pub use self::merkle_root::{merkle_node_hash, merkle_root};
 pub use self::transaction::{
-    ConstructTransaction, OutPoint, Transaction, TransactionInput, TransactionOutput,
-    TransactionOutputArray,
+    serde_outpoint, ConstructTransaction, OutPoint, Transaction, TransactionInput,
+    TransactionOutput, TransactionOutputArray, WITNESS_SCALE_FACTOR,
+};
+pub use self::transaction_ref::{
+    InputRef, Inputs, OutputRef, Outputs, TransactionRef, WitnessRef, Witnesses,
 };
 
 pub use self::indexed_block::IndexedBlock;
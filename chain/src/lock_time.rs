@@ -0,0 +1,262 @@
+//! Absolute (`nLockTime`) and relative (BIP68 `nSequence`) lock-times.
+//!
+//! https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki
+
+use crate::constants::{
+    LOCKTIME_THRESHOLD, SEQUENCE_LOCKTIME_DISABLE_FLAG, SEQUENCE_LOCKTIME_GRANULARITY,
+    SEQUENCE_LOCKTIME_MASK, SEQUENCE_LOCKTIME_TYPE_FLAG,
+};
+use crate::transaction::Transaction;
+
+/// An `nLockTime`, or a `OP_CHECKLOCKTIMEVERIFY` operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AbsoluteLockTime {
+    /// A block height, below `LOCKTIME_THRESHOLD`
+    Height(u32),
+    /// A UNIX timestamp, compared to the median time past since BIP113
+    Time(u32),
+}
+
+impl AbsoluteLockTime {
+    /// Interprets an `nLockTime`.
+    pub fn from_consensus(lock_time: u32) -> Self {
+        if lock_time < LOCKTIME_THRESHOLD {
+            AbsoluteLockTime::Height(lock_time)
+        } else {
+            AbsoluteLockTime::Time(lock_time)
+        }
+    }
+
+    /// The `nLockTime` of this lock-time.
+    pub fn to_consensus(self) -> u32 {
+        match self {
+            AbsoluteLockTime::Height(height) => height,
+            AbsoluteLockTime::Time(time) => time,
+        }
+    }
+
+    pub fn is_same_unit(self, other: AbsoluteLockTime) -> bool {
+        matches!(
+            (self, other),
+            (AbsoluteLockTime::Height(_), AbsoluteLockTime::Height(_))
+                | (AbsoluteLockTime::Time(_), AbsoluteLockTime::Time(_))
+        )
+    }
+
+    /// Whether a transaction with this lock-time can be included in a block
+    /// at `height` whose (median) time is `time`.
+    pub fn is_satisfied_by(self, height: u32, time: u32) -> bool {
+        match self {
+            AbsoluteLockTime::Height(lock) => lock < height,
+            AbsoluteLockTime::Time(lock) => lock < time,
+        }
+    }
+
+    /// Whether `lock_time`, the one of a transaction, satisfies this lock-time,
+    /// as checked by `OP_CHECKLOCKTIMEVERIFY`: both must have the same unit.
+    pub fn is_implied_by(self, lock_time: AbsoluteLockTime) -> bool {
+        self.is_same_unit(lock_time) && self.to_consensus() <= lock_time.to_consensus()
+    }
+}
+
+/// A relative lock-time of an `nSequence`, or a `OP_CHECKSEQUENCEVERIFY` operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RelativeLockTime {
+    /// A number of blocks
+    Blocks(u16),
+    /// A number of 512 seconds intervals
+    Time(u16),
+}
+
+impl RelativeLockTime {
+    /// Interprets an `nSequence`, `None` when the disable flag is set.
+    ///
+    /// The bits which have no consensus meaning are ignored.
+    pub fn from_sequence(sequence: u32) -> Option<Self> {
+        if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return None;
+        }
+        let value = (sequence & SEQUENCE_LOCKTIME_MASK) as u16;
+        if sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            Some(RelativeLockTime::Time(value))
+        } else {
+            Some(RelativeLockTime::Blocks(value))
+        }
+    }
+
+    /// The `nSequence` encoding this lock-time.
+    pub fn to_sequence(self) -> u32 {
+        match self {
+            RelativeLockTime::Blocks(blocks) => u32::from(blocks),
+            RelativeLockTime::Time(intervals) => SEQUENCE_LOCKTIME_TYPE_FLAG | u32::from(intervals),
+        }
+    }
+
+    pub fn is_same_unit(self, other: RelativeLockTime) -> bool {
+        matches!(
+            (self, other),
+            (RelativeLockTime::Blocks(_), RelativeLockTime::Blocks(_))
+                | (RelativeLockTime::Time(_), RelativeLockTime::Time(_))
+        )
+    }
+
+    /// Whether `lock_time`, the one of an input, satisfies this lock-time, as
+    /// checked by `OP_CHECKSEQUENCEVERIFY`: both must have the same unit.
+    pub fn is_implied_by(self, lock_time: RelativeLockTime) -> bool {
+        match (self, lock_time) {
+            (RelativeLockTime::Blocks(this), RelativeLockTime::Blocks(other)) => this <= other,
+            (RelativeLockTime::Time(this), RelativeLockTime::Time(other)) => this <= other,
+            _ => false,
+        }
+    }
+}
+
+/// The last block height and median time past at which a transaction is still
+/// locked by the relative lock-times of its inputs, `-1` when not locked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SequenceLocks {
+    pub min_height: i64,
+    pub min_time: i64,
+}
+
+impl Default for SequenceLocks {
+    fn default() -> Self {
+        SequenceLocks {
+            min_height: -1,
+            min_time: -1,
+        }
+    }
+}
+
+impl SequenceLocks {
+    /// Whether the transaction can be included in a block at `block_height`,
+    /// whose previous block has the median time past `block_mtp`.
+    pub fn evaluate_sequence_locks(&self, block_height: u32, block_mtp: u32) -> bool {
+        self.min_height < i64::from(block_height) && self.min_time < i64::from(block_mtp)
+    }
+}
+
+/// Calculates the BIP68 sequence locks of `tx`.
+///
+/// `prev_heights` are the heights of the blocks including the outputs spent by
+/// the inputs, and `prev_mtps` the median time past of the blocks before those.
+/// Transactions with a version below 2 are not locked.
+///
+/// # Panics
+///
+/// If there is not one height and one time per input.
+pub fn calculate_sequence_locks(
+    tx: &Transaction,
+    prev_heights: &[u32],
+    prev_mtps: &[u32],
+) -> SequenceLocks {
+    assert_eq!(prev_heights.len(), tx.inputs.len());
+    assert_eq!(prev_mtps.len(), tx.inputs.len());
+
+    let mut locks = SequenceLocks::default();
+    if (tx.version as u32) < 2 {
+        return locks;
+    }
+
+    for ((input, &height), &mtp) in tx.inputs.iter().zip(prev_heights).zip(prev_mtps) {
+        match RelativeLockTime::from_sequence(input.sequence) {
+            None => {}
+            Some(RelativeLockTime::Blocks(blocks)) => {
+                let min_height = i64::from(height) + i64::from(blocks) - 1;
+                locks.min_height = locks.min_height.max(min_height);
+            }
+            Some(RelativeLockTime::Time(intervals)) => {
+                let seconds = i64::from(intervals) << SEQUENCE_LOCKTIME_GRANULARITY;
+                let min_time = i64::from(mtp) + seconds - 1;
+                locks.min_time = locks.min_time.max(min_time);
+            }
+        }
+    }
+    locks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::SEQUENCE_FINAL;
+    use crate::transaction::TransactionInput;
+
+    fn transaction(version: i32, sequences: &[u32]) -> Transaction {
+        Transaction {
+            version,
+            inputs: sequences
+                .iter()
+                .map(|&sequence| TransactionInput {
+                    sequence,
+                    ..Default::default()
+                })
+                .collect(),
+            outputs: vec![],
+            lock_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_lock_time_units() {
+        assert_eq!(
+            AbsoluteLockTime::from_consensus(499_999_999),
+            AbsoluteLockTime::Height(499_999_999)
+        );
+        assert_eq!(
+            AbsoluteLockTime::from_consensus(LOCKTIME_THRESHOLD),
+            AbsoluteLockTime::Time(LOCKTIME_THRESHOLD)
+        );
+        let height = AbsoluteLockTime::Height(100);
+        assert!(height.is_implied_by(AbsoluteLockTime::Height(100)));
+        assert!(!height.is_implied_by(AbsoluteLockTime::Height(99)));
+        assert!(!height.is_implied_by(AbsoluteLockTime::Time(LOCKTIME_THRESHOLD)));
+        assert!(height.is_satisfied_by(101, 0));
+        assert!(!height.is_satisfied_by(100, u32::MAX));
+
+        assert_eq!(RelativeLockTime::from_sequence(SEQUENCE_FINAL), None);
+        assert_eq!(
+            RelativeLockTime::from_sequence(0x0040_0010),
+            Some(RelativeLockTime::Time(16))
+        );
+        // bits without consensus meaning are ignored
+        assert_eq!(
+            RelativeLockTime::from_sequence(0x0001_0010),
+            Some(RelativeLockTime::Blocks(16))
+        );
+        assert_eq!(RelativeLockTime::Time(16).to_sequence(), 0x0040_0010);
+        let blocks = RelativeLockTime::Blocks(10);
+        assert!(blocks.is_implied_by(RelativeLockTime::Blocks(10)));
+        assert!(!blocks.is_implied_by(RelativeLockTime::Blocks(9)));
+        assert!(!blocks.is_implied_by(RelativeLockTime::Time(10)));
+    }
+
+    #[test]
+    fn test_sequence_locks() {
+        let blocks = RelativeLockTime::Blocks(10).to_sequence();
+        let time = RelativeLockTime::Time(2).to_sequence();
+        let tx = transaction(2, &[blocks, time, SEQUENCE_FINAL]);
+
+        let locks = calculate_sequence_locks(&tx, &[100, 100, 200], &[1_000, 5_000, 9_000]);
+        assert_eq!(
+            locks,
+            SequenceLocks {
+                min_height: 109,
+                min_time: 5_000 + 1024 - 1,
+            }
+        );
+        assert!(!locks.evaluate_sequence_locks(109, 7_000));
+        assert!(!locks.evaluate_sequence_locks(110, 6_023));
+        assert!(locks.evaluate_sequence_locks(110, 6_024));
+
+        // not enforced before version 2
+        let tx = transaction(1, &[blocks]);
+        let locks = calculate_sequence_locks(&tx, &[100], &[0]);
+        assert_eq!(locks, SequenceLocks::default());
+        assert!(locks.evaluate_sequence_locks(0, 0));
+
+        // a zero lock-time can be in the block of the spent outputs
+        let tx = transaction(2, &[0]);
+        let locks = calculate_sequence_locks(&tx, &[100], &[0]);
+        assert!(locks.evaluate_sequence_locks(100, 0));
+    }
+}
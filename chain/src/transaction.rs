@@ -12,7 +12,9 @@ use light_bitcoin_serialization::{
     Deserializable, Reader, Serializable, Stream, SERIALIZE_TRANSACTION_WITNESS,
 };
 
-use crate::constants::{LOCKTIME_THRESHOLD, SEQUENCE_FINAL};
+use crate::amount::Amount;
+use crate::constants::SEQUENCE_FINAL;
+use crate::lock_time::{AbsoluteLockTime, RelativeLockTime};
 
 /// Must be zero.
 pub const WITNESS_MARKER: u8 = 0;
@@ -52,6 +54,25 @@ impl fmt::Debug for OutPoint {
     }
 }
 
+/// Formats as `txid:vout`, with the txid reversed like block explorers show it.
+impl fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", hex::encode(hash_rev(self.txid)), self.index)
+    }
+}
+
+impl str::FromStr for OutPoint {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (txid, index) = s.split_once(':').ok_or("missing `:` in outpoint")?;
+        let mut hash = H256::default();
+        hex::decode_to_slice(txid, hash.as_bytes_mut()).map_err(|_| "invalid outpoint txid")?;
+        let index = index.parse().map_err(|_| "invalid outpoint vout")?;
+        Ok(OutPoint::new(hash_rev(hash), index))
+    }
+}
+
 impl Default for OutPoint {
     fn default() -> Self {
         Self::null()
@@ -94,6 +115,7 @@ impl OutPoint {
     serde::Serialize,
     serde::Deserialize
 )]
+#[derive(Serializable, Deserializable)]
 pub struct TransactionInput {
     /// The reference to the previous output that is being used an an input
     pub previous_output: OutPoint,
@@ -110,6 +132,7 @@ pub struct TransactionInput {
     /// Encodable/Decodable, as it is (de)serialized at the end of the full
     /// Transaction. It *is* (de)serialized with the rest of the TxIn in other
     /// (de)serialization routines.
+    #[serialize(skip)]
     pub script_witness: Vec<Bytes>,
 }
 
@@ -127,32 +150,15 @@ impl TransactionInput {
         self.sequence == SEQUENCE_FINAL
     }
 
-    pub fn has_witness(&self) -> bool {
-        !self.script_witness.is_empty()
-    }
-}
-
-impl Serializable for TransactionInput {
-    fn serialize(&self, stream: &mut Stream) {
-        stream
-            .append(&self.previous_output)
-            .append(&self.script_sig)
-            .append(&self.sequence);
+    /// The BIP68 relative lock-time of the input, `None` when disabled.
+    ///
+    /// It is only enforced for transactions with a version of 2 or more.
+    pub fn relative_lock_time(&self) -> Option<RelativeLockTime> {
+        RelativeLockTime::from_sequence(self.sequence)
     }
-}
 
-impl Deserializable for TransactionInput {
-    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
-    where
-        Self: Sized,
-        T: io::Read,
-    {
-        Ok(TransactionInput {
-            previous_output: reader.read()?,
-            script_sig: reader.read()?,
-            sequence: reader.read()?,
-            script_witness: vec![],
-        })
+    pub fn has_witness(&self) -> bool {
+        !self.script_witness.is_empty()
     }
 }
 
@@ -317,18 +323,19 @@ impl Transaction {
         self.inputs.iter().all(TransactionInput::is_final)
     }
 
+    pub fn absolute_lock_time(&self) -> AbsoluteLockTime {
+        AbsoluteLockTime::from_consensus(self.lock_time)
+    }
+
     pub fn is_final_in_block(&self, block_height: u32, block_time: u32) -> bool {
         if self.lock_time == 0 {
             return true;
         }
 
-        let max_lock_time = if self.lock_time < LOCKTIME_THRESHOLD {
-            block_height
-        } else {
-            block_time
-        };
-
-        if self.lock_time < max_lock_time {
+        if self
+            .absolute_lock_time()
+            .is_satisfied_by(block_height, block_time)
+        {
             return true;
         }
 
@@ -350,6 +357,16 @@ impl Transaction {
         result
     }
 
+    /// Sum of the output values, `None` if it overflows or is above `MAX_MONEY`
+    pub fn checked_total_spends(&self) -> Option<Amount> {
+        Amount::checked_sum(
+            self.outputs
+                .iter()
+                .map(|output| Amount::from_sat(output.value)),
+        )
+        .filter(|total| total.is_valid_money())
+    }
+
     /// utility function for size/weight functions.
     pub fn scaled_size(&self, scale_factor: usize) -> usize {
         let mut input_weight = 0;
@@ -510,6 +527,33 @@ impl codec::Decode for TransactionInput {
     }
 }
 
+/// Serializes an [`OutPoint`] as a `"txid:vout"` string in human-readable formats,
+/// for use with `#[serde(with = "serde_outpoint")]`
+pub mod serde_outpoint {
+    #[cfg(not(feature = "std"))]
+    use alloc::string::String;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::OutPoint;
+
+    pub fn serialize<S: Serializer>(outpoint: &OutPoint, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(outpoint)
+        } else {
+            outpoint.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OutPoint, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            s.parse().map_err(de::Error::custom)
+        } else {
+            OutPoint::deserialize(deserializer)
+        }
+    }
+}
+
 impl codec::Encode for OutPoint {
     fn encode(&self) -> Vec<u8> {
         let value = serialize::<OutPoint>(self);
@@ -671,4 +715,30 @@ mod tests {
             transaction_with_witness.witness_hash()
         );
     }
+
+    #[test]
+    fn test_outpoint_string() {
+        let s = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16:1";
+        let outpoint: OutPoint = s.parse().unwrap();
+        assert_eq!(
+            outpoint,
+            OutPoint::new(
+                h256_rev("f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"),
+                1
+            )
+        );
+        assert_eq!(outpoint.to_string(), s);
+        assert!("f4184fc5:1".parse::<OutPoint>().is_err());
+        assert!(s.replace(':', "-").parse::<OutPoint>().is_err());
+
+        #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+        struct Spend {
+            #[serde(with = "serde_outpoint")]
+            outpoint: OutPoint,
+        }
+        let spend = Spend { outpoint };
+        let json = serde_json::to_string(&spend).unwrap();
+        assert_eq!(json, format!(r#"{{"outpoint":"{}"}}"#, s));
+        assert_eq!(serde_json::from_str::<Spend>(&json).unwrap(), spend);
+    }
 }
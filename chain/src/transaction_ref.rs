@@ -0,0 +1,530 @@
+//! Borrowed view of a serialized transaction.
+//!
+//! The transaction is checked once when the view is created, and its fields are
+//! then read from the original bytes, so that scanning the outputs of a block
+//! allocates nothing.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use light_bitcoin_crypto::{DHash256, Digest};
+use light_bitcoin_primitives::{io, H256};
+use light_bitcoin_serialization::deserialize;
+
+use crate::transaction::{OutPoint, Transaction, WITNESS_FLAG, WITNESS_MARKER};
+
+/// Reads the fields of a serialized structure without copying them.
+#[derive(Debug, Clone)]
+pub(crate) struct Cursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Cursor { data, position: 0 }
+    }
+
+    fn at(data: &'a [u8], position: usize) -> Self {
+        Cursor { data, position }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    pub(crate) fn read_slice(&mut self, len: usize) -> Result<&'a [u8], io::Error> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(io::Error::UnexpectedEof)?;
+        let slice = &self.data[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    pub(crate) fn read_array<const N: usize>(&mut self) -> Result<[u8; N], io::Error> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.read_slice(N)?);
+        Ok(array)
+    }
+
+    pub(crate) fn peek_u8(&self) -> Result<u8, io::Error> {
+        self.data
+            .get(self.position)
+            .copied()
+            .ok_or(io::Error::UnexpectedEof)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, io::Error> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, io::Error> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, io::Error> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    /// Reads a compact size, which like `CompactInteger` must be canonical.
+    pub(crate) fn read_compact(&mut self) -> Result<usize, io::Error> {
+        let (value, min) = match self.read_u8()? {
+            i @ 0..=0xfc => (u64::from(i), 0),
+            0xfd => (u64::from(u16::from_le_bytes(self.read_array()?)), 0xfd),
+            0xfe => (u64::from(self.read_u32()?), 0x1_0000),
+            _ => (self.read_u64()?, 0x1_0000_0000),
+        };
+        if value < min {
+            return Err(io::Error::ReadMalformedData);
+        }
+        usize::try_from(value).map_err(|_| io::Error::ReadMalformedData)
+    }
+
+    /// Reads a length prefixed byte string
+    pub(crate) fn read_bytes(&mut self) -> Result<&'a [u8], io::Error> {
+        let len = self.read_compact()?;
+        self.read_slice(len)
+    }
+
+    fn read_input(&mut self) -> Result<InputRef<'a>, io::Error> {
+        Ok(InputRef {
+            previous_output: OutPoint {
+                txid: H256::from(self.read_array::<32>()?),
+                index: self.read_u32()?,
+            },
+            script_sig: self.read_bytes()?,
+            sequence: self.read_u32()?,
+        })
+    }
+
+    fn read_output(&mut self) -> Result<OutputRef<'a>, io::Error> {
+        Ok(OutputRef {
+            value: self.read_u64()?,
+            script_pubkey: self.read_bytes()?,
+        })
+    }
+}
+
+/// An input of a borrowed transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputRef<'a> {
+    pub previous_output: OutPoint,
+    pub script_sig: &'a [u8],
+    pub sequence: u32,
+}
+
+/// An output of a borrowed transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputRef<'a> {
+    pub value: u64,
+    pub script_pubkey: &'a [u8],
+}
+
+/// A serialized transaction, read in place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionRef<'a> {
+    data: &'a [u8],
+    /// Offset of the number of inputs, after the witness marker and flag
+    inputs: usize,
+    input_count: usize,
+    outputs: usize,
+    output_count: usize,
+    /// Offset of the witnesses, if there are any
+    witnesses: Option<usize>,
+    lock_time: usize,
+}
+
+impl<'a> TransactionRef<'a> {
+    /// Checks the transaction, which must be the whole data.
+    pub fn new(data: &'a [u8]) -> Result<Self, io::Error> {
+        let (transaction, rest) = Self::parse(data)?;
+        if rest.is_empty() {
+            Ok(transaction)
+        } else {
+            Err(io::Error::UnreadData)
+        }
+    }
+
+    /// Checks the transaction at the start of the data, and returns it with the
+    /// bytes after it.
+    pub fn parse(data: &'a [u8]) -> Result<(Self, &'a [u8]), io::Error> {
+        let mut cursor = Cursor::new(data);
+        cursor.read_u32()?;
+
+        // like the owned transaction, no inputs introduce the witness flag
+        let mut has_witness = false;
+        if cursor.peek_u8()? == WITNESS_MARKER {
+            cursor.read_u8()?;
+            if cursor.read_u8()? != WITNESS_FLAG {
+                return Err(io::Error::ReadMalformedData);
+            }
+            has_witness = true;
+        }
+
+        let inputs = cursor.position();
+        let input_count = cursor.read_compact()?;
+        for _ in 0..input_count {
+            cursor.read_input()?;
+        }
+        let outputs = cursor.position();
+        let output_count = cursor.read_compact()?;
+        for _ in 0..output_count {
+            cursor.read_output()?;
+        }
+        let witnesses = if has_witness {
+            let witnesses = cursor.position();
+            for _ in 0..input_count {
+                let items = cursor.read_compact()?;
+                for _ in 0..items {
+                    cursor.read_bytes()?;
+                }
+            }
+            Some(witnesses)
+        } else {
+            None
+        };
+        let lock_time = cursor.position();
+        cursor.read_u32()?;
+
+        let (data, rest) = data.split_at(cursor.position());
+        let transaction = TransactionRef {
+            data,
+            inputs,
+            input_count,
+            outputs,
+            output_count,
+            witnesses,
+            lock_time,
+        };
+        Ok((transaction, rest))
+    }
+
+    /// The serialized transaction, with its witnesses
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn version(&self) -> i32 {
+        i32::from_le_bytes(self.data[..4].try_into().expect("checked when parsed; qed"))
+    }
+
+    pub fn lock_time(&self) -> u32 {
+        let lock_time = &self.data[self.lock_time..self.lock_time + 4];
+        u32::from_le_bytes(lock_time.try_into().expect("checked when parsed; qed"))
+    }
+
+    pub fn has_witness(&self) -> bool {
+        self.witnesses.is_some()
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.input_count
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.output_count
+    }
+
+    pub fn inputs(&self) -> Inputs<'a> {
+        let mut cursor = Cursor::at(self.data, self.inputs);
+        cursor.read_compact().expect("checked when parsed; qed");
+        Inputs {
+            cursor,
+            remaining: self.input_count,
+        }
+    }
+
+    pub fn outputs(&self) -> Outputs<'a> {
+        let mut cursor = Cursor::at(self.data, self.outputs);
+        cursor.read_compact().expect("checked when parsed; qed");
+        Outputs {
+            cursor,
+            remaining: self.output_count,
+        }
+    }
+
+    /// The witness of every input, all empty when the transaction has no witnesses
+    pub fn witnesses(&self) -> Witnesses<'a> {
+        Witnesses {
+            cursor: Cursor::at(self.data, self.witnesses.unwrap_or(self.lock_time)),
+            has_witness: self.witnesses.is_some(),
+            remaining: self.input_count,
+        }
+    }
+
+    /// Hash of the transaction without its witnesses
+    pub fn txid(&self) -> H256 {
+        let mut hasher = DHash256::new();
+        hasher.update(&self.data[..4]);
+        hasher.update(&self.data[self.inputs..self.witnesses.unwrap_or(self.lock_time)]);
+        hasher.update(&self.data[self.lock_time..]);
+        hasher.finish()
+    }
+
+    /// Hash of the transaction with its witnesses
+    pub fn wtxid(&self) -> H256 {
+        let mut hasher = DHash256::new();
+        hasher.update(self.data);
+        hasher.finish()
+    }
+
+    /// Size of the serialized transaction, with its witnesses
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Copies the transaction into an owned one.
+    pub fn to_transaction(&self) -> Result<Transaction, io::Error> {
+        deserialize(self.data)
+    }
+}
+
+/// Iterator over the inputs of a borrowed transaction
+#[derive(Debug, Clone)]
+pub struct Inputs<'a> {
+    cursor: Cursor<'a>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Inputs<'a> {
+    type Item = InputRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(self.cursor.read_input().expect("checked when parsed; qed"))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Inputs<'_> {}
+
+/// Iterator over the outputs of a borrowed transaction
+#[derive(Debug, Clone)]
+pub struct Outputs<'a> {
+    cursor: Cursor<'a>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Outputs<'a> {
+    type Item = OutputRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(self.cursor.read_output().expect("checked when parsed; qed"))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Outputs<'_> {}
+
+/// Iterator over the witnesses of the inputs of a borrowed transaction
+#[derive(Debug, Clone)]
+pub struct Witnesses<'a> {
+    cursor: Cursor<'a>,
+    has_witness: bool,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Witnesses<'a> {
+    type Item = WitnessRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        if !self.has_witness {
+            return Some(WitnessRef {
+                cursor: self.cursor.clone(),
+                remaining: 0,
+            });
+        }
+        let items = self
+            .cursor
+            .read_compact()
+            .expect("checked when parsed; qed");
+        let witness = WitnessRef {
+            cursor: self.cursor.clone(),
+            remaining: items,
+        };
+        for _ in 0..items {
+            self.cursor.read_bytes().expect("checked when parsed; qed");
+        }
+        Some(witness)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Witnesses<'_> {}
+
+/// Iterator over the items of the witness of an input
+#[derive(Debug, Clone)]
+pub struct WitnessRef<'a> {
+    cursor: Cursor<'a>,
+    remaining: usize,
+}
+
+impl WitnessRef<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// Copies the items into an owned witness.
+    pub fn to_vec(&self) -> Vec<light_bitcoin_primitives::Bytes> {
+        self.clone().map(Into::into).collect()
+    }
+}
+
+impl<'a> Iterator for WitnessRef<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(self.cursor.read_bytes().expect("checked when parsed; qed"))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for WitnessRef<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{TransactionInput, TransactionOutput};
+    use light_bitcoin_serialization::{
+        serialize, serialize_with_flags, SERIALIZE_TRANSACTION_WITNESS,
+    };
+
+    fn transaction(witness: bool) -> Transaction {
+        let inputs = (0..3)
+            .map(|index| TransactionInput {
+                previous_output: OutPoint {
+                    txid: H256::repeat_byte(index as u8 + 1),
+                    index,
+                },
+                script_sig: vec![index as u8; index as usize * 100].into(),
+                sequence: 0xffff_fffe - index,
+                script_witness: if witness && index != 1 {
+                    vec![vec![7; 72].into(), vec![].into(), vec![2; 33].into()]
+                } else {
+                    vec![]
+                },
+            })
+            .collect();
+        let outputs = (0..2)
+            .map(|index| TransactionOutput {
+                value: 1000 * index,
+                script_pubkey: vec![0x51; 300 * index as usize].into(),
+            })
+            .collect();
+        Transaction {
+            version: 2,
+            inputs,
+            outputs,
+            lock_time: 800_000,
+        }
+    }
+
+    #[test]
+    fn test_transaction_ref() {
+        for witness in [false, true] {
+            let owned = transaction(witness);
+            let data = serialize_with_flags(&owned, SERIALIZE_TRANSACTION_WITNESS);
+            let view = TransactionRef::new(&data).unwrap();
+
+            assert_eq!(view.has_witness(), witness);
+            assert_eq!(view.version(), owned.version);
+            assert_eq!(view.lock_time(), owned.lock_time);
+            assert_eq!(view.size(), data.len());
+            assert_eq!(view.txid(), owned.hash());
+            assert_eq!(view.wtxid(), owned.witness_hash());
+            assert_eq!(view.to_transaction().as_ref(), Ok(&owned));
+
+            assert_eq!(view.inputs().len(), 3);
+            for ((input, witness), owned) in view.inputs().zip(view.witnesses()).zip(&owned.inputs)
+            {
+                assert_eq!(input.previous_output, owned.previous_output);
+                assert_eq!(input.script_sig, &owned.script_sig[..]);
+                assert_eq!(input.sequence, owned.sequence);
+                assert_eq!(witness.to_vec(), owned.script_witness);
+            }
+            assert_eq!(view.outputs().len(), 2);
+            for (output, owned) in view.outputs().zip(&owned.outputs) {
+                assert_eq!(output.value, owned.value);
+                assert_eq!(output.script_pubkey, &owned.script_pubkey[..]);
+            }
+        }
+
+        let legacy = serialize(&transaction(true));
+        assert_eq!(
+            TransactionRef::new(&legacy).unwrap().txid(),
+            transaction(true).hash()
+        );
+    }
+
+    #[test]
+    fn test_transaction_ref_errors() {
+        let data = serialize_with_flags(&transaction(true), SERIALIZE_TRANSACTION_WITNESS);
+        for len in 0..data.len() {
+            assert_eq!(
+                TransactionRef::new(&data[..len]),
+                Err(io::Error::UnexpectedEof)
+            );
+        }
+
+        let mut trailing = data.to_vec();
+        trailing.push(0);
+        assert_eq!(TransactionRef::new(&trailing), Err(io::Error::UnreadData));
+        let (view, rest) = TransactionRef::parse(&trailing).unwrap();
+        assert_eq!(view.as_bytes(), &data[..]);
+        assert_eq!(rest, [0]);
+
+        let mut flag = data.to_vec();
+        flag[5] = 2;
+        assert_eq!(
+            TransactionRef::new(&flag),
+            Err(io::Error::ReadMalformedData)
+        );
+    }
+
+    #[test]
+    fn test_transaction_ref_non_canonical() {
+        // an input count of zero encoded on three bytes is not the witness marker
+        let data = [
+            0x02, 0x00, 0x00, 0x00, 0xfd, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(
+            TransactionRef::new(&data),
+            Err(io::Error::ReadMalformedData)
+        );
+        assert_eq!(
+            deserialize::<_, Transaction>(&data[..]),
+            Err(io::Error::ReadMalformedData)
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! The forward secure ciphers of the BIP324 v2 transport.
+//!
+//! Both ciphers derive a new key from the current one after every
+//! [`REKEY_INTERVAL`] messages, so that a key leaked later does not decrypt
+//! the messages already sent.
+
+use core::fmt;
+
+use chacha20::{
+    cipher::{KeyIvInit, StreamCipher},
+    ChaCha20,
+};
+use chacha20poly1305::{aead::AeadInPlace, ChaCha20Poly1305, KeyInit, Tag};
+
+/// Number of messages encrypted with a key before rekeying
+pub const REKEY_INTERVAL: u64 = 224;
+
+/// Size of the Poly1305 tag appended to every packet
+pub const TAG_SIZE: usize = 16;
+
+/// The authentication tag of a packet does not match its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTag;
+
+impl fmt::Display for InvalidTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "Invalid authentication tag".fmt(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidTag {}
+
+fn nonce(low: u32, high: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..4].copy_from_slice(&low.to_le_bytes());
+    nonce[4..].copy_from_slice(&high.to_le_bytes());
+    nonce
+}
+
+/// ChaCha20 encrypting a stream of chunks, rekeyed every [`REKEY_INTERVAL`] chunks.
+///
+/// The chunks of a key use one continuous keystream, the next key is taken from
+/// that keystream after the last chunk.
+pub struct FSChaCha20 {
+    cipher: ChaCha20,
+    chunk_counter: u64,
+}
+
+impl FSChaCha20 {
+    pub fn new(key: [u8; 32]) -> Self {
+        FSChaCha20 {
+            cipher: ChaCha20::new(&key.into(), &nonce(0, 0).into()),
+            chunk_counter: 0,
+        }
+    }
+
+    /// Encrypts or decrypts the chunk in place.
+    pub fn crypt(&mut self, chunk: &mut [u8]) {
+        self.cipher.apply_keystream(chunk);
+        self.chunk_counter += 1;
+        if self.chunk_counter.is_multiple_of(REKEY_INTERVAL) {
+            let mut key = [0u8; 32];
+            self.cipher.apply_keystream(&mut key);
+            let epoch = self.chunk_counter / REKEY_INTERVAL;
+            self.cipher = ChaCha20::new(&key.into(), &nonce(0, epoch).into());
+        }
+    }
+}
+
+/// ChaCha20-Poly1305 encrypting packets, rekeyed every [`REKEY_INTERVAL`] packets.
+///
+/// The nonce of a packet is its position in the current key and the number of
+/// keys used before.
+pub struct FSChaCha20Poly1305 {
+    key: [u8; 32],
+    packet_counter: u64,
+}
+
+impl FSChaCha20Poly1305 {
+    pub fn new(key: [u8; 32]) -> Self {
+        FSChaCha20Poly1305 {
+            key,
+            packet_counter: 0,
+        }
+    }
+
+    /// Encrypts the packet in place, and returns its authentication tag.
+    pub fn encrypt(&mut self, aad: &[u8], packet: &mut [u8]) -> [u8; TAG_SIZE] {
+        let tag = ChaCha20Poly1305::new(&self.key.into())
+            .encrypt_in_place_detached(&self.nonce().into(), aad, packet)
+            .expect("packets are far below the size limit of the cipher; qed");
+        self.next_packet();
+        tag.into()
+    }
+
+    /// Decrypts the packet in place if the tag authenticates it with the aad.
+    ///
+    /// The packet is counted either way, a failed decryption means the
+    /// connection can not be used any more.
+    pub fn decrypt(
+        &mut self,
+        aad: &[u8],
+        packet: &mut [u8],
+        tag: &[u8; TAG_SIZE],
+    ) -> Result<(), InvalidTag> {
+        let result = ChaCha20Poly1305::new(&self.key.into()).decrypt_in_place_detached(
+            &self.nonce().into(),
+            aad,
+            packet,
+            Tag::from_slice(tag),
+        );
+        self.next_packet();
+        result.map_err(|_| InvalidTag)
+    }
+
+    fn nonce(&self) -> [u8; 12] {
+        nonce(
+            (self.packet_counter % REKEY_INTERVAL) as u32,
+            self.packet_counter / REKEY_INTERVAL,
+        )
+    }
+
+    fn next_packet(&mut self) {
+        self.packet_counter += 1;
+        if self.packet_counter.is_multiple_of(REKEY_INTERVAL) {
+            // the last epoch encrypts 32 zero bytes with a nonce no packet uses
+            let mut key = [0u8; 32];
+            let epoch = self.packet_counter / REKEY_INTERVAL - 1;
+            ChaCha20Poly1305::new(&self.key.into())
+                .encrypt_in_place_detached(&nonce(u32::MAX, epoch).into(), &[], &mut key)
+                .expect("32 bytes are below the size limit of the cipher; qed");
+            self.key = key;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use light_bitcoin_primitives::Bytes;
+
+    use super::*;
+
+    fn bytes(s: &str) -> Bytes {
+        s.parse().unwrap()
+    }
+
+    // expected chunks computed with the reference implementation of BIP324
+    #[test]
+    fn test_fschacha20_rekey() {
+        let expected = [
+            (0, "39fd2b"),
+            (1, "7cd8c4"),
+            (223, "f0b116"),
+            (224, "d91f1f"),
+            (447, "8b1d75"),
+            (448, "844ca9"),
+            (499, "1e9172"),
+        ];
+        let mut key = [0u8; 32];
+        key.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+        let mut cipher = FSChaCha20::new(key);
+        let mut decipher = FSChaCha20::new(key);
+        for i in 0..500usize {
+            let mut chunk = [i as u8; 3];
+            cipher.crypt(&mut chunk);
+            if let Some((_, out)) = expected.iter().find(|(n, _)| *n == i) {
+                assert_eq!(&chunk[..], &*bytes(out));
+            }
+            decipher.crypt(&mut chunk);
+            assert_eq!(chunk, [i as u8; 3]);
+        }
+    }
+
+    #[test]
+    fn test_fschacha20poly1305_rekey() {
+        let expected = [
+            (0, "5966e42b1d64c5d1eea8eb469bda6d198df5e445ae"),
+            (223, "23b0c1cb4f81c6d385a9f487fe245df313f2b491e8"),
+            (224, "88cff68543bb1a1e815f5c606485317ced8e294432"),
+            (448, "9b335a64d97b9a6f2ffd247795a8ea116112e30d10"),
+            (499, "33f239ddc73b29082f3bb2589a35db0c4e5e761dd7"),
+        ];
+        let mut cipher = FSChaCha20Poly1305::new([0x42; 32]);
+        let mut decipher = FSChaCha20Poly1305::new([0x42; 32]);
+        for i in 0..500usize {
+            let aad = [(i % 7) as u8];
+            let mut packet = [i as u8; 5];
+            let tag = cipher.encrypt(&aad, &mut packet);
+            if let Some((_, out)) = expected.iter().find(|(n, _)| *n == i) {
+                let mut encrypted = packet.to_vec();
+                encrypted.extend_from_slice(&tag);
+                assert_eq!(encrypted, bytes(out).take());
+            }
+            assert_eq!(decipher.decrypt(&aad, &mut packet, &tag), Ok(()));
+            assert_eq!(packet, [i as u8; 5]);
+        }
+
+        let mut cipher = FSChaCha20Poly1305::new([1; 32]);
+        let mut decipher = FSChaCha20Poly1305::new([1; 32]);
+        let mut packet = *b"hello";
+        let tag = cipher.encrypt(b"aad", &mut packet);
+        assert_eq!(decipher.decrypt(b"add", &mut packet, &tag), Err(InvalidTag));
+    }
+
+    // the initiator ciphers of the first row of the BIP324
+    // `packet_encoding_test_vectors.csv`, which encrypts the packet of index 1
+    #[test]
+    fn test_packet_encoding_vector() {
+        let key = |s: &str| -> [u8; 32] { bytes(s).take().try_into().unwrap() };
+        let mut length = FSChaCha20::new(key(
+            "9a6478b5fbab1f4dd2f78994b774c03211c78312786e602da75a0d1767fb55cf",
+        ));
+        let mut cipher = FSChaCha20Poly1305::new(key(
+            "7d0c7820ba6a4d29ce40baf2caa6035e04f1e1cefd59f3e7e59e9e5af84f1f51",
+        ));
+        let mut packet = vec![];
+        for contents in [&[][..], &[0x8e]] {
+            packet = (contents.len() as u32).to_le_bytes()[..3].to_vec();
+            length.crypt(&mut packet);
+            // the header byte, without the ignore bit
+            let mut plaintext = [&[0][..], contents].concat();
+            let tag = cipher.encrypt(&[], &mut plaintext);
+            packet.extend_from_slice(&plaintext);
+            packet.extend_from_slice(&tag);
+        }
+        assert_eq!(
+            packet,
+            bytes("7530d2a18720162ac09c25329a60d75adf36eda3c3").take()
+        );
+    }
+}
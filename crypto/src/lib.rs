@@ -1,9 +1,12 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+mod chacha;
+
 use core::hash::Hasher;
 
 use light_bitcoin_primitives::{H160, H256, H32};
 
+pub use self::chacha::{FSChaCha20, FSChaCha20Poly1305, InvalidTag, REKEY_INTERVAL, TAG_SIZE};
 pub use digest::Digest;
 use digest::{
     generic_array::{
@@ -12,6 +15,7 @@ use digest::{
     },
     Reset,
 };
+use hkdf::Hkdf;
 use ripemd160::Ripemd160;
 use sha1::Sha1;
 use sha2::Sha256;
@@ -178,6 +182,51 @@ pub fn siphash24(key0: u64, key1: u64, input: &[u8]) -> u64 {
     hasher.finish()
 }
 
+/// MurmurHash3 (x86, 32 bits), as used by BIP 37 bloom filters
+pub fn murmur3(seed: u32, input: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+
+    let mut hash = seed;
+    let mut blocks = input.chunks_exact(4);
+    for block in &mut blocks {
+        let mut k = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        hash ^= k;
+        hash = hash
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe654_6b64);
+    }
+
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .enumerate()
+            .fold(0u32, |k, (i, byte)| k | u32::from(*byte) << (8 * i));
+        hash ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+
+    hash ^= input.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+/// HKDF-SHA256 of RFC 5869, filling `okm` with the key expanded for `info`.
+///
+/// # Panics
+///
+/// When `okm` is longer than 255 hashes.
+pub fn hkdf_sha256(salt: &[u8], ikm: &[u8], info: &[u8], okm: &mut [u8]) {
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, okm)
+        .expect("okm is at most 255 hashes long; qed");
+}
+
 /// Data checksum
 #[inline]
 pub fn checksum(data: &[u8]) -> H32 {
@@ -237,8 +286,48 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    // test vectors from bitcoin core's src/test/hash_tests.cpp
+    #[test]
+    fn test_murmur3() {
+        let tests = [
+            (0x00000000, 0x00000000, ""),
+            (0x6a396f08, 0xfba4c795, ""),
+            (0x81f16f39, 0xffffffff, ""),
+            (0x514e28b7, 0x00000000, "00"),
+            (0xea3f0b17, 0xfba4c795, "00"),
+            (0xfd6cf10d, 0x00000000, "ff"),
+            (0x16c6b7ab, 0x00000000, "0011"),
+            (0x8eb51c3d, 0x00000000, "001122"),
+            (0xb4471bf8, 0x00000000, "00112233"),
+            (0xe2301fa8, 0x00000000, "0011223344"),
+            (0xfc2e4a15, 0x00000000, "001122334455"),
+            (0xb074502c, 0x00000000, "00112233445566"),
+            (0x8034d2a0, 0x00000000, "0011223344556677"),
+            (0xb4698def, 0x00000000, "001122334455667788"),
+        ];
+        for (expected, seed, input) in tests {
+            let input: Bytes = input.parse().unwrap();
+            assert_eq!(murmur3(seed, &input), expected);
+        }
+    }
+
     #[test]
     fn test_checksum() {
         assert_eq!(checksum(b"hello"), h32("9595c9df"));
     }
+
+    // test case 1 of RFC 5869
+    #[test]
+    fn test_hkdf_sha256() {
+        let ikm = [0x0b; 22];
+        let salt: Bytes = "000102030405060708090a0b0c".parse().unwrap();
+        let info: Bytes = "f0f1f2f3f4f5f6f7f8f9".parse().unwrap();
+        let mut okm = [0u8; 42];
+        hkdf_sha256(&salt, &ikm, &info, &mut okm);
+        let expected: Bytes =
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+                .parse()
+                .unwrap();
+        assert_eq!(&okm[..], &*expected);
+    }
 }
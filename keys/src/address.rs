@@ -12,8 +12,8 @@ use core::{convert::TryFrom, fmt, ops, str, str::FromStr};
 use bitcoin_bech32::constants::Network as Bech32Network;
 use bitcoin_bech32::{u5, WitnessProgram};
 use light_bitcoin_crypto::checksum;
-use light_bitcoin_primitives::{io, H160, H256};
-use light_bitcoin_serialization::{Deserializable, Reader, Serializable, Stream};
+use light_bitcoin_primitives::{H160, H256};
+use light_bitcoin_serialization::{Deserializable, Serializable};
 
 use codec::{Decode, Encode};
 
@@ -37,6 +37,8 @@ use crate::{AddressHash, XOnly};
     serde::Serialize,
     serde::Deserialize
 )]
+#[derive(Serializable, Deserializable)]
+#[serialize(tag = "u32")]
 pub enum Type {
     /// Pay to PubKey Hash
     /// Common P2PKH which begin with the number 1, eg: 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2.
@@ -73,29 +75,6 @@ impl Type {
     }
 }
 
-impl Serializable for Type {
-    fn serialize(&self, s: &mut Stream) {
-        let _stream = match *self {
-            Type::P2PKH => s.append(&Type::P2PKH),
-            Type::P2SH => s.append(&Type::P2SH),
-            Type::P2WPKH => s.append(&Type::P2WPKH),
-            Type::P2WSH => s.append(&Type::P2WSH),
-            Type::P2TR => s.append(&Type::P2TR),
-        };
-    }
-}
-
-impl Deserializable for Type {
-    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
-    where
-        Self: Sized,
-        T: io::Read,
-    {
-        let t: u32 = reader.read()?;
-        Type::from(t).ok_or(io::Error::ReadMalformedData)
-    }
-}
-
 #[derive(
     Ord,
     PartialOrd,
@@ -110,6 +89,8 @@ impl Deserializable for Type {
     serde::Serialize,
     serde::Deserialize
 )]
+#[derive(Serializable, Deserializable)]
+#[serialize(tag = "u32")]
 pub enum Network {
     // Bitcoin Mainnet
     Mainnet,
@@ -150,28 +131,6 @@ impl Network {
     }
 }
 
-impl Serializable for Network {
-    fn serialize(&self, s: &mut Stream) {
-        let _stream = match *self {
-            Network::Mainnet => s.append(&Network::Mainnet),
-            Network::Testnet => s.append(&Network::Testnet),
-            Network::DogeCoinMainnet => s.append(&Network::DogeCoinMainnet),
-            Network::DogeCoinTestnet => s.append(&Network::DogeCoinTestnet),
-        };
-    }
-}
-
-impl Deserializable for Network {
-    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
-    where
-        Self: Sized,
-        T: io::Read,
-    {
-        let t: u32 = reader.read()?;
-        Network::from(t).ok_or(io::Error::ReadMalformedData)
-    }
-}
-
 #[derive(
     Ord,
     PartialOrd,
@@ -186,58 +145,36 @@ impl Deserializable for Network {
     serde::Serialize,
     serde::Deserialize
 )]
+#[derive(Serializable, Deserializable)]
+#[serialize(tag = "u32")]
 pub enum AddressTypes {
     Legacy(AddressHash),
     WitnessV0ScriptHash(H256),
     WitnessV0KeyHash(H160),
-    WitnessV1Taproot(XOnly),
+    WitnessV1Taproot(#[serialize(with = "xonly")] XOnly),
 }
 
-impl Default for AddressTypes {
-    fn default() -> Self {
-        AddressTypes::Legacy(AddressHash::default())
+/// The x-only key of a taproot address, as its 32 bytes
+mod xonly {
+    use light_bitcoin_primitives::io;
+    use light_bitcoin_serialization::{Reader, Stream};
+
+    use crate::XOnly;
+
+    pub fn serialize(key: &XOnly, stream: &mut Stream) {
+        stream.append_slice(&key.0);
     }
-}
 
-impl Serializable for AddressTypes {
-    fn serialize(&self, s: &mut Stream) {
-        let _stream = match *self {
-            AddressTypes::Legacy(h) => s.append(&0).append(&h),
-            AddressTypes::WitnessV0ScriptHash(h) => s.append(&1).append(&h),
-            AddressTypes::WitnessV0KeyHash(h) => s.append(&2).append(&h),
-            AddressTypes::WitnessV1Taproot(h) => s.append(&3).append_slice(&h.0),
-        };
+    pub fn deserialize<T: io::Read>(reader: &mut Reader<T>) -> Result<XOnly, io::Error> {
+        let mut key = [0u8; 32];
+        reader.read_slice(&mut key)?;
+        Ok(XOnly(key))
     }
 }
 
-impl Deserializable for AddressTypes {
-    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
-    where
-        Self: Sized,
-        T: io::Read,
-    {
-        let t: u32 = reader.read()?;
-        match t {
-            0 => {
-                let h: H160 = reader.read()?;
-                Ok(AddressTypes::Legacy(h))
-            }
-            1 => {
-                let h: H256 = reader.read()?;
-                Ok(AddressTypes::WitnessV0ScriptHash(h))
-            }
-            2 => {
-                let h: H160 = reader.read()?;
-                Ok(AddressTypes::WitnessV0KeyHash(h))
-            }
-            3 => {
-                let mut keys = [0u8; 32];
-                reader.read_slice(&mut keys)?;
-
-                Ok(AddressTypes::WitnessV1Taproot(XOnly(keys)))
-            }
-            _ => Err(io::Error::ReadMalformedData),
-        }
+impl Default for AddressTypes {
+    fn default() -> Self {
+        AddressTypes::Legacy(AddressHash::default())
     }
 }
 
@@ -269,43 +206,35 @@ pub struct Address {
 
 impl fmt::Display for Address {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let network = match self.network {
-            Network::Mainnet => Bech32Network::Bitcoin,
-            _ => Bech32Network::Testnet,
+        let witness = match self.hash {
+            AddressTypes::Legacy(_) => return bs58::encode(self.layout().0).into_string().fmt(f),
+            AddressTypes::WitnessV0ScriptHash(h) => encode_witness_program(0, &h.0, self.network),
+            AddressTypes::WitnessV0KeyHash(h) => encode_witness_program(0, &h.0, self.network),
+            AddressTypes::WitnessV1Taproot(h) => encode_witness_program(1, &h.0, self.network),
         };
-        match self.hash {
-            AddressTypes::Legacy(_) => bs58::encode(self.layout().0).into_string().fmt(f),
-            AddressTypes::WitnessV0ScriptHash(h) => {
-                let witness = WitnessProgram::new(
-                    u5::try_from_u8(0).map_err(|_| fmt::Error)?,
-                    h.0.to_vec(),
-                    network,
-                )
-                .map_err(|_| fmt::Error)?;
-                witness.to_string().fmt(f)
-            }
-            AddressTypes::WitnessV0KeyHash(h) => {
-                let witness = WitnessProgram::new(
-                    u5::try_from_u8(0).map_err(|_| fmt::Error)?,
-                    h.0.to_vec(),
-                    network,
-                )
-                .map_err(|_| fmt::Error)?;
-                witness.to_string().fmt(f)
-            }
-            AddressTypes::WitnessV1Taproot(h) => {
-                let witness = WitnessProgram::new(
-                    u5::try_from_u8(1).map_err(|_| fmt::Error)?,
-                    h.0.to_vec(),
-                    network,
-                )
-                .map_err(|_| fmt::Error)?;
-                witness.to_string().fmt(f)
-            }
-        }
+        witness.map_err(|_| fmt::Error)?.fmt(f)
     }
 }
 
+/// Encodes a witness program as a bech32 address, or bech32m from version 1.
+///
+/// Unlike `Address`, any version is accepted, as for the outputs of the future segwit
+/// versions.
+pub fn encode_witness_program(
+    version: u8,
+    program: &[u8],
+    network: Network,
+) -> Result<String, Error> {
+    let network = match network {
+        Network::Mainnet => Bech32Network::Bitcoin,
+        _ => Bech32Network::Testnet,
+    };
+    let version = u5::try_from_u8(version).map_err(|_| Error::InvalidAddress)?;
+    let witness = WitnessProgram::new(version, program.to_vec(), network)
+        .map_err(|_| Error::InvalidAddress)?;
+    Ok(witness.to_string())
+}
+
 fn bs58_decode(s: &str) -> Result<Address, Error> {
     let hex = bs58::decode(s)
         .into_vec()
@@ -323,7 +252,13 @@ fn bech32_decode(s: &str) -> Result<Address, Error> {
     let (kind, hash) = if version == 1 {
         (
             Type::P2TR,
-            AddressTypes::WitnessV1Taproot(XOnly::try_from(witness.program())?),
+            // the output key is not required to be on the curve
+            AddressTypes::WitnessV1Taproot(XOnly(
+                witness
+                    .program()
+                    .try_into()
+                    .map_err(|_| Error::InvalidAddress)?,
+            )),
         )
     } else if witness.program().len() == 20 {
         (
@@ -489,4 +424,21 @@ mod tests {
             "9wbjG5xnc1MPEZX5kK7YQPqx9t8GVvYq6G".parse().unwrap()
         );
     }
+
+    #[test]
+    fn test_address_serialize() {
+        use light_bitcoin_serialization::{deserialize, serialize};
+
+        let address = Address {
+            kind: Type::P2TR,
+            network: Network::Testnet,
+            hash: AddressTypes::WitnessV1Taproot(XOnly([7; 32])),
+        };
+        let data = serialize(&address);
+        assert_eq!(data.len(), 4 + 4 + 4 + 32);
+        assert_eq!(&data[..12], &[4, 0, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(&data[12..], &[7; 32]);
+        assert_eq!(address.serialized_size(), data.len());
+        assert_eq!(deserialize::<_, Address>(data.as_ref()), Ok(address));
+    }
 }
@@ -18,6 +18,7 @@ pub enum Error {
     InvalidPrivate,
     InvalidAddress,
     FailedKeyGeneration,
+    InvalidTweak,
     // hex error
     InvalidHexCharacter,
     InvalidStringLength,
@@ -43,6 +44,7 @@ impl core::fmt::Display for Error {
             Error::InvalidPrivate => "Invalid Private",
             Error::InvalidAddress => "Invalid Address",
             Error::FailedKeyGeneration => "Key generation failed",
+            Error::InvalidTweak => "Invalid taproot tweak",
             Error::InvalidHexCharacter => "Invalid hex character",
             Error::InvalidStringLength => "Invalid string length",
             Error::OddLength => "Hex odd length",
@@ -1,27 +1,23 @@
 //! Bitcoin key pair.
 
-use core::fmt;
+use core::{convert::TryFrom, fmt};
 
 use crate::address::{Address, AddressTypes, Network, Type};
 use crate::error::Error;
+use crate::point::{PrivateKey, PublicKey};
 use crate::private::Private;
-use crate::public::Public;
-use crate::Secret;
-use codec::{Decode, Encode};
-use light_bitcoin_primitives::{H264, H520};
-
-#[derive(
-    Ord,
-    PartialOrd,
-    Eq,
-    PartialEq,
-    Debug,
-    Copy,
-    Clone,
-    Default,
-    Decode,
-    Encode,
-    scale_info::TypeInfo
+use crate::public::{Public, XOnly};
+use crate::schnorr::tap_tweak_hash;
+use crate::signature::SchnorrSignature;
+use crate::{Message, Secret};
+use libsecp256k1::curve::Affine;
+use light_bitcoin_primitives::{H256, H264, H520};
+use zeroize::Zeroizing;
+
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serialize-secrets",
+    derive(codec::Decode, codec::Encode, scale_info::TypeInfo)
 )]
 pub struct KeyPair {
     private: Private,
@@ -44,8 +40,7 @@ impl KeyPair {
     }
 
     pub fn from_private(private: Private) -> Result<KeyPair, Error> {
-        let secret_key = libsecp256k1::SecretKey::parse(private.secret.as_fixed_bytes())?;
-        let pub_key = libsecp256k1::PublicKey::from_secret_key(&secret_key);
+        let pub_key = PublicKey::create_from_private_key(&PrivateKey::try_from(&private)?);
         let public = if private.compressed {
             let public = H264::from_slice(&pub_key.serialize_compressed());
             Public::Compressed(public)
@@ -62,8 +57,7 @@ impl KeyPair {
         public: libsecp256k1::PublicKey,
         network: Network,
     ) -> Self {
-        let sec: libsecp256k1::curve::Scalar = sec.into();
-        let sec = sec.b32();
+        let sec = Zeroizing::new(PrivateKey::from(sec).serialize());
         let secret = Secret::from_slice(&sec[..]);
         let serialized = public.serialize();
         let public = H520::from_slice(&serialized);
@@ -78,6 +72,57 @@ impl KeyPair {
         }
     }
 
+    /// The x-only public key used as the taproot internal key
+    pub fn x_only(&self) -> Result<XOnly, Error> {
+        let secret_key = PrivateKey::try_from(&self.private)?;
+        Ok(PublicKey::create_from_private_key(&secret_key).into())
+    }
+
+    /// Tweak the key pair with the taproot tweak of its internal key, as described in [BIP341].
+    ///
+    /// The secret of the returned key pair is d' = d + t, where d is negated first
+    /// if the internal public key has an odd y coordinate. The returned public key is
+    /// the output key committed to by `merkle_root`.
+    ///
+    /// [BIP341]: https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki#constructing-and-spending-taproot-outputs
+    pub fn tap_tweak(&self, merkle_root: Option<H256>) -> Result<KeyPair, Error> {
+        // the secret scalars are `PrivateKey`s and their bytes `Zeroizing`, so that
+        // none is left in memory on any path
+        let d = PrivateKey::try_from(&self.private)?;
+        let mut p: Affine = PublicKey::create_from_private_key(&d).into();
+        p.x.normalize();
+        p.y.normalize();
+        let internal = XOnly::from(&mut p.x);
+
+        let d = if p.y.is_odd() { d.neg() } else { d };
+        let t = PrivateKey::from(tap_tweak_hash(&internal, merkle_root)?);
+        let tweaked = d.add_scalar(&t).map_err(|_| Error::InvalidTweak)?;
+        let tweaked = Zeroizing::new(tweaked.serialize());
+
+        KeyPair::from_private(Private {
+            network: self.private.network,
+            secret: Secret::from_slice(&tweaked[..]),
+            compressed: true,
+        })
+    }
+
+    /// Sign a message for a taproot key path spend, as described in [BIP341].
+    ///
+    /// The key pair is the untweaked internal key; `merkle_root` is the root of the
+    /// script tree committed to by the output, or `None` for key path only outputs.
+    ///
+    /// [BIP341]: https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki#constructing-and-spending-taproot-outputs
+    pub fn sign_schnorr(
+        &self,
+        message: &Message,
+        aux: &Message,
+        merkle_root: Option<H256>,
+    ) -> Result<SchnorrSignature, Error> {
+        self.tap_tweak(merkle_root)?
+            .private
+            .sign_schnorr(message, aux)
+    }
+
     pub fn address(&self) -> Address {
         Address {
             kind: Type::P2PKH,
@@ -93,6 +138,8 @@ mod tests {
 
     use super::*;
     use crate::CompactSignature;
+    use core::convert::TryFrom;
+    use light_bitcoin_primitives::h256;
 
     /// Tests from:
     /// https://github.com/bitcoin/bitcoin/blob/a6a860796a44a2805a58391a009ba22752f64e32/src/test/key_tests.cpp
@@ -229,4 +276,76 @@ mod tests {
         assert!(check_recover_compact(SECRET_2, message));
         assert!(check_recover_compact(SECRET_2C, message));
     }
+
+    /// Tests from:
+    /// https://github.com/bitcoin/bips/blob/master/bip-0341/wallet-test-vectors.json
+    const TAPROOT_INTERNAL_0: &str =
+        "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d";
+    const TAPROOT_OUTPUT_0: &str =
+        "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343";
+    const TAPROOT_INTERNAL_1: &str =
+        "187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27";
+    const TAPROOT_MERKLE_ROOT_1: &str =
+        "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21";
+    const TAPROOT_OUTPUT_1: &str =
+        "147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3";
+    const TAPROOT_SECRET_2: &str =
+        "6b973d88838f27366ed61c9ad6367663045cb456e28335c109e30717ae0c6baa";
+    const TAPROOT_TWEAKED_SECRET_2: &str =
+        "2405b971772ad26915c8dcdf10f238753a9b837e5f8e6a86fd7c0cce5b7296d9";
+
+    fn taproot_keypair(secret: &str) -> KeyPair {
+        KeyPair::from_private(Private {
+            network: Network::Mainnet,
+            secret: h256(secret).into(),
+            compressed: true,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_xonly_tap_tweak() {
+        let internal = XOnly::try_from(TAPROOT_INTERNAL_0).unwrap();
+        let (output, _) = internal.tap_tweak(None).unwrap();
+        assert_eq!(output, XOnly::try_from(TAPROOT_OUTPUT_0).unwrap());
+
+        let internal = XOnly::try_from(TAPROOT_INTERNAL_1).unwrap();
+        let (output, _) = internal
+            .tap_tweak(Some(h256(TAPROOT_MERKLE_ROOT_1)))
+            .unwrap();
+        assert_eq!(output, XOnly::try_from(TAPROOT_OUTPUT_1).unwrap());
+    }
+
+    #[test]
+    fn test_keypair_tap_tweak() {
+        let kp = taproot_keypair(TAPROOT_SECRET_2);
+        let tweaked = kp.tap_tweak(None).unwrap();
+        assert_eq!(
+            tweaked.private().secret,
+            h256(TAPROOT_TWEAKED_SECRET_2).into()
+        );
+
+        // the tweaked public key must match the tweaked internal key
+        let merkle_root = Some(h256(TAPROOT_MERKLE_ROOT_1));
+        let (output, odd) = kp.x_only().unwrap().tap_tweak(merkle_root).unwrap();
+        let tweaked = kp.tap_tweak(merkle_root).unwrap();
+        assert_eq!(tweaked.x_only().unwrap(), output);
+        let expected_prefix = if odd { 0x03 } else { 0x02 };
+        assert_eq!(tweaked.public()[0], expected_prefix);
+    }
+
+    #[test]
+    fn test_sign_schnorr_key_path() {
+        let kp = taproot_keypair(TAPROOT_SECRET_2);
+        let message = dhash256(b"Very deterministic message");
+        let aux = Message::default();
+
+        for merkle_root in [None, Some(h256(TAPROOT_MERKLE_ROOT_1))] {
+            let (output, _) = kp.x_only().unwrap().tap_tweak(merkle_root).unwrap();
+            let sig = kp.sign_schnorr(&message, &aux, merkle_root).unwrap();
+            assert_eq!(crate::verify_schnorr(&sig, &message, output), Ok(true));
+            // the untweaked internal key must not verify
+            assert!(crate::verify_schnorr(&sig, &message, kp.x_only().unwrap()).is_err());
+        }
+    }
 }
@@ -7,8 +7,10 @@ extern crate alloc;
 
 mod address;
 mod display;
+mod ellswift;
 mod error;
 mod keypair;
+mod point;
 mod private;
 mod public;
 mod schnorr;
@@ -17,11 +19,13 @@ mod tagged;
 
 use light_bitcoin_primitives::*;
 
-pub use self::address::{Address, AddressTypes, Network, Type};
+pub use self::address::{encode_witness_program, Address, AddressTypes, Network, Type};
 pub use self::display::DisplayLayout;
+pub use self::ellswift::ElligatorSwift;
 pub use self::error::Error;
 pub use self::keypair::KeyPair;
-pub use self::private::Private;
+pub use self::point::{PrivateKey, PublicKey};
+pub use self::private::{Private, Secret};
 pub use self::public::{Public, XOnly};
 pub use self::schnorr::*;
 pub use self::signature::{CompactSignature, SchnorrSignature, Signature};
@@ -29,7 +33,5 @@ pub use self::tagged::*;
 
 /// 20 bytes long hash derived from public `ripemd160(sha256(public))`
 pub type AddressHash = H160;
-/// 32 bytes long secret key
-pub type Secret = H256;
 /// 32 bytes long signable message
 pub type Message = H256;
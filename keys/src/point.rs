@@ -0,0 +1,581 @@
+//! Wrap [`Affine`] and [`Scalar`] into public key and private key
+//!
+//! The libsecp256k1 library is still available,
+//! but for ease of use, further encapsulation.
+//!
+//! Unlike [`Public`] and [`Private`], which keep the serialized form of a key,
+//! these types hold the curve point and scalar, so that keys can be added,
+//! multiplied and negated directly.
+
+use arrayref::{array_mut_ref, array_ref};
+use codec::{Decode, Encode};
+use core::{
+    cmp::Ordering,
+    convert::{TryFrom, TryInto},
+    fmt,
+    ops::Neg,
+};
+
+#[cfg(feature = "getrandom")]
+use rand_core::{OsRng, RngCore};
+
+use libsecp256k1::{
+    curve::{Affine, Field, Jacobian, Scalar},
+    util::{COMPRESSED_PUBLIC_KEY_SIZE, TAG_PUBKEY_EVEN, TAG_PUBKEY_FULL, TAG_PUBKEY_ODD},
+    ECMULT_CONTEXT, ECMULT_GEN_CONTEXT,
+};
+use serde::{
+    de::{Error as SerdeError, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+use crate::{error::Error, private::Private, public::Public, public::XOnly};
+
+/// A point on the secp256k1 curve
+#[derive(Debug, Clone, Eq, PartialEq, Decode, Encode, scale_info::TypeInfo)]
+pub struct PublicKey(pub Affine);
+
+/// A scalar modulo the secp256k1 curve order
+///
+/// The scalar is private, wiped on drop, compared in constant time and redacted in
+/// `Debug`.
+/// Serde and SCALE encoding are only available with the `serialize-secrets` feature.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serialize-secrets",
+    derive(Decode, Encode, scale_info::TypeInfo)
+)]
+pub struct PrivateKey(Scalar);
+
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PrivateKey(<redacted>)")
+    }
+}
+
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        // the limbs are always reduced, so equal scalars have equal limbs
+        self.0 .0[..].ct_eq(&other.0 .0[..]).into()
+    }
+}
+
+impl Eq for PrivateKey {}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.0 .0.zeroize();
+    }
+}
+
+impl Serialize for PublicKey {
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&hex::encode(self.serialize()))
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PublicKeyVisitor;
+        impl<'de> Visitor<'de> for PublicKeyVisitor {
+            type Value = PublicKey;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("struct PublicKey")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: SerdeError,
+            {
+                let v = hex::decode(v).map_err(|_| SerdeError::custom("struct PublicKey"))?;
+                if v.len() != 65 {
+                    return Err(SerdeError::custom("struct PublicKey"));
+                }
+                let mut keys = [0u8; 65];
+                keys.copy_from_slice(&v);
+                PublicKey::parse(&keys).map_err(|_| SerdeError::custom("struct PublicKey"))
+            }
+        }
+        deserializer.deserialize_str(PublicKeyVisitor)
+    }
+}
+
+impl PartialOrd<Self> for PublicKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PublicKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.serialize().cmp(&other.serialize())
+    }
+}
+
+#[cfg(feature = "serialize-secrets")]
+impl Serialize for PrivateKey {
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&hex::encode(self.serialize()))
+    }
+}
+
+#[cfg(feature = "serialize-secrets")]
+impl<'de> Deserialize<'de> for PrivateKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PrivateKeyVisitor;
+        impl<'de> Visitor<'de> for PrivateKeyVisitor {
+            type Value = PrivateKey;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("struct PrivateKey")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: SerdeError,
+            {
+                let v = hex::decode(v).map_err(|_| SerdeError::custom("struct PrivateKey"))?;
+                PrivateKey::parse_slice(&v).map_err(|_| SerdeError::custom("struct PrivateKey"))
+            }
+        }
+        deserializer.deserialize_str(PrivateKeyVisitor)
+    }
+}
+
+/// Public key multiplication and addition calculations
+impl PublicKey {
+    pub fn add_point(&self, rhs: &Self) -> Result<PublicKey, Error> {
+        let mut qj = Jacobian::default();
+        qj.set_infinity();
+        qj = qj.add_ge(&self.0);
+        qj = qj.add_ge(&rhs.0);
+
+        if qj.is_infinity() {
+            return Err(Error::InvalidPublic);
+        }
+        let q = Affine::from_gej(&qj);
+        Ok(PublicKey(q))
+    }
+
+    pub fn mul_scalar(&self, rhs: &PrivateKey) -> Result<PublicKey, Error> {
+        if rhs.0.is_zero() {
+            return Err(Error::InvalidSecret);
+        }
+        let mut r = Jacobian::default();
+        let zero = Scalar::from_int(0);
+        let pt = Jacobian::from_ge(&self.0);
+        ECMULT_CONTEXT.ecmult(&mut r, &pt, &rhs.0, &zero);
+
+        Ok(PublicKey(Affine::from_gej(&r)))
+    }
+}
+
+/// Secret key multiplication and addition calculations
+impl PrivateKey {
+    pub fn add_scalar(&self, rhs: &Self) -> Result<Self, Error> {
+        let v = self.0 + rhs.0;
+        if v.is_zero() {
+            return Err(Error::InvalidSecret);
+        }
+        Ok(PrivateKey(v))
+    }
+
+    pub fn mul_scalar(&self, rhs: &Self) -> Result<Self, Error> {
+        let v = self.0 * rhs.0;
+        if v.is_zero() {
+            return Err(Error::InvalidSecret);
+        }
+        Ok(PrivateKey(v))
+    }
+
+    pub fn mul_point(&self, rhs: &PublicKey) -> Result<PublicKey, Error> {
+        rhs.mul_scalar(self)
+    }
+}
+
+impl From<Affine> for PublicKey {
+    fn from(p: Affine) -> Self {
+        PublicKey(p)
+    }
+}
+
+impl From<PublicKey> for Affine {
+    fn from(p: PublicKey) -> Self {
+        p.0
+    }
+}
+
+impl From<Scalar> for PrivateKey {
+    fn from(s: Scalar) -> Self {
+        PrivateKey(s)
+    }
+}
+
+impl From<libsecp256k1::PublicKey> for PublicKey {
+    fn from(p: libsecp256k1::PublicKey) -> Self {
+        PublicKey(p.into())
+    }
+}
+
+impl From<libsecp256k1::SecretKey> for PrivateKey {
+    fn from(s: libsecp256k1::SecretKey) -> Self {
+        PrivateKey(s.into())
+    }
+}
+
+/// Parse [`PrivateKey`] from hex
+impl TryFrom<&str> for PrivateKey {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let x_bytes = hex::decode(value)?;
+        if x_bytes.len() != 32 {
+            return Err(Error::InvalidStringLength);
+        }
+        Self::parse_slice(&x_bytes[..])
+    }
+}
+
+/// Parse [`PublicKey`] from the hex of an x coordinate
+impl TryFrom<&str> for PublicKey {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let x_bytes = hex::decode(value)?;
+        if x_bytes.len() != 32 {
+            return Err(Error::InvalidStringLength);
+        }
+        let mut k = [0u8; 32];
+        k.copy_from_slice(&x_bytes);
+        PublicKey::parse_x_coor(&k)
+    }
+}
+
+impl TryFrom<Public> for PublicKey {
+    type Error = Error;
+
+    fn try_from(p: Public) -> Result<Self, Self::Error> {
+        PublicKey::parse_slice(&p)
+    }
+}
+
+impl TryFrom<PublicKey> for Public {
+    type Error = Error;
+
+    fn try_from(p: PublicKey) -> Result<Self, Self::Error> {
+        if p.0.is_infinity() {
+            return Err(Error::InvalidPublic);
+        }
+        Public::from_slice(&p.serialize_compressed())
+    }
+}
+
+/// Lift the x coordinate to the point with an even y coordinate
+impl TryFrom<XOnly> for PublicKey {
+    type Error = Error;
+
+    fn try_from(x: XOnly) -> Result<Self, Self::Error> {
+        PublicKey::parse_x_coor(&x.0)
+    }
+}
+
+impl From<PublicKey> for XOnly {
+    fn from(p: PublicKey) -> Self {
+        XOnly(p.x_coor())
+    }
+}
+
+impl TryFrom<&Private> for PrivateKey {
+    type Error = Error;
+
+    fn try_from(p: &Private) -> Result<Self, Self::Error> {
+        PrivateKey::parse(p.secret.as_fixed_bytes())
+    }
+}
+
+impl PublicKey {
+    pub fn serialize_compressed(&self) -> [u8; 33] {
+        debug_assert!(!self.0.is_infinity());
+
+        let mut ret = [0u8; 33];
+        let mut elem = self.0;
+
+        elem.x.normalize_var();
+        elem.y.normalize_var();
+        elem.x.fill_b32(array_mut_ref!(ret, 1, 32));
+        ret[0] = if elem.y.is_odd() {
+            TAG_PUBKEY_ODD
+        } else {
+            TAG_PUBKEY_EVEN
+        };
+
+        ret
+    }
+
+    pub fn serialize(&self) -> [u8; 65] {
+        debug_assert!(!self.0.is_infinity());
+
+        let mut ret = [0u8; 65];
+        let mut elem = self.0;
+
+        elem.x.normalize_var();
+        elem.y.normalize_var();
+        elem.x.fill_b32(array_mut_ref!(ret, 1, 32));
+        elem.y.fill_b32(array_mut_ref!(ret, 33, 32));
+        ret[0] = TAG_PUBKEY_FULL;
+
+        ret
+    }
+
+    pub fn x_coor(&self) -> [u8; 32] {
+        let mut x = self.0.x;
+        x.normalize();
+        x.b32()
+    }
+
+    pub fn y_coor(&self) -> [u8; 32] {
+        let mut y = self.0.y;
+        y.normalize();
+        y.b32()
+    }
+
+    pub fn is_odd_y(&self) -> bool {
+        let mut y = self.0.y;
+        y.normalize();
+        y.is_odd()
+    }
+
+    pub fn create_from_private_key(s: &PrivateKey) -> PublicKey {
+        let mut pj = Jacobian::default();
+        ECMULT_GEN_CONTEXT.ecmult_gen(&mut pj, &s.0);
+        let mut p = Affine::default();
+        p.set_gej(&pj);
+        PublicKey(p)
+    }
+
+    pub fn neg(&self) -> PublicKey {
+        PublicKey(self.0.neg())
+    }
+
+    pub fn parse(p: &[u8; 65]) -> Result<Self, Error> {
+        let mut x = Field::default();
+        let mut y = Field::default();
+        if !x.set_b32(array_ref!(p, 1, 32)) {
+            return Err(Error::InvalidPublic);
+        }
+
+        if !y.set_b32(array_ref!(p, 33, 32)) {
+            return Err(Error::InvalidPublic);
+        }
+        let mut elem = Affine::default();
+        elem.set_xy(&x, &y);
+
+        if elem.is_infinity() {
+            return Err(Error::InvalidPublic);
+        }
+
+        if !elem.is_valid_var() {
+            return Err(Error::InvalidPublic);
+        }
+        Ok(PublicKey(elem))
+    }
+
+    pub fn parse_slice(p: &[u8]) -> Result<Self, Error> {
+        match p.len() {
+            65 => Self::parse(p.try_into().expect("length checked; qed")),
+            33 => Self::parse_compressed(p.try_into().expect("length checked; qed")),
+            32 => Self::parse_x_coor(p.try_into().expect("length checked; qed")),
+            _ => Err(Error::InvalidPublic),
+        }
+    }
+
+    /// Convert [`x_coor`] to [`PublicKey`]
+    ///
+    /// Recover the public key from the x coordinate in the schnorr signature;
+    /// Reference ift_x(x): [BIP340]: https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki
+    pub fn parse_x_coor(x: &[u8; 32]) -> Result<Self, Error> {
+        let mut elem = Field::default();
+        let mut affine = Affine::default();
+        if elem.set_b32(x) && affine.set_xo_var(&elem, false) {
+            Ok(Self(affine))
+        } else {
+            Err(Error::XCoordinateNotExist)
+        }
+    }
+
+    pub fn parse_compressed(p: &[u8; COMPRESSED_PUBLIC_KEY_SIZE]) -> Result<PublicKey, Error> {
+        if !(p[0] == TAG_PUBKEY_EVEN || p[0] == TAG_PUBKEY_ODD) {
+            return Err(Error::InvalidPublic);
+        }
+        let mut x = Field::default();
+        if !x.set_b32(array_ref!(p, 1, 32)) {
+            return Err(Error::InvalidPublic);
+        }
+        let mut elem = Affine::default();
+        elem.set_xo_var(&x, p[0] == TAG_PUBKEY_ODD);
+        if elem.is_infinity() {
+            return Err(Error::InvalidPublic);
+        }
+        if elem.is_valid_var() {
+            Ok(PublicKey(elem))
+        } else {
+            Err(Error::InvalidPublic)
+        }
+    }
+}
+
+impl PrivateKey {
+    /// The scalar, for the curve operations of this crate
+    pub(crate) fn as_scalar(&self) -> &Scalar {
+        &self.0
+    }
+
+    pub fn serialize(&self) -> [u8; 32] {
+        self.0.b32()
+    }
+
+    pub fn parse(s: &[u8; 32]) -> Result<Self, Error> {
+        let mut r = Scalar::default();
+        if !bool::from(r.set_b32(s)) {
+            Ok(PrivateKey(r))
+        } else {
+            Err(Error::InvalidSecret)
+        }
+    }
+
+    pub fn parse_slice(s: &[u8]) -> Result<Self, Error> {
+        if s.len() != 32 {
+            return Err(Error::InvalidSecret);
+        }
+        let mut k = [0u8; 32];
+        k.copy_from_slice(s);
+        Self::parse(&k)
+    }
+
+    pub fn neg(&self) -> Self {
+        PrivateKey(self.0.neg())
+    }
+
+    pub fn from_int(v: u32) -> Self {
+        PrivateKey(Scalar::from_int(v))
+    }
+
+    #[cfg(feature = "getrandom")]
+    pub fn generate_random() -> Result<Self, Error> {
+        let mut key: [u8; 32] = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        Self::parse(&key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyPair;
+    use light_bitcoin_primitives::H264;
+
+    const SECRET: &str = "B7E151628AED2A6ABF7158809CF4F3C762E7160F38B4DA56A784D9045190CFEF";
+    const XONLY: &str = "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659";
+
+    #[test]
+    fn test_point_arithmetic() {
+        let a = PrivateKey::from_int(3);
+        let b = PrivateKey::from_int(5);
+        let g_a = PublicKey::create_from_private_key(&a);
+        let g_b = PublicKey::create_from_private_key(&b);
+
+        // aG + bG == (a + b)G
+        let sum = PublicKey::create_from_private_key(&a.add_scalar(&b).unwrap());
+        assert_eq!(g_a.add_point(&g_b).unwrap(), sum);
+        // b(aG) == (ab)G
+        let product = PublicKey::create_from_private_key(&a.mul_scalar(&b).unwrap());
+        assert_eq!(b.mul_point(&g_a).unwrap(), product);
+        assert_eq!(g_a.mul_scalar(&b).unwrap(), product);
+        // aG + (-aG) is the point at infinity
+        assert_eq!(g_a.add_point(&g_a.neg()), Err(Error::InvalidPublic));
+        assert_eq!(a.add_scalar(&a.neg()), Err(Error::InvalidSecret));
+    }
+
+    #[test]
+    fn test_point_conversions() {
+        let secret = PrivateKey::try_from(SECRET).unwrap();
+        let point = PublicKey::create_from_private_key(&secret);
+
+        let xonly = XOnly::try_from(XONLY).unwrap();
+        assert_eq!(XOnly::from(point.clone()), xonly);
+        assert_eq!(PublicKey::try_from(xonly).unwrap().x_coor(), point.x_coor());
+        assert_eq!(XOnly::try_from(&xonly.0[..]), Ok(xonly));
+        assert_eq!(XOnly::try_from(&xonly.0[..31]), Err(Error::InvalidXOnly));
+        assert_eq!(
+            XOnly::try_from(&[0xff; 32][..]),
+            Err(Error::XCoordinateNotExist)
+        );
+
+        let public = Public::try_from(point.clone()).unwrap();
+        assert_eq!(
+            public,
+            Public::Compressed(H264::from(point.serialize_compressed()))
+        );
+        assert_eq!(PublicKey::try_from(public).unwrap(), point);
+
+        let private = Private {
+            network: crate::Network::Mainnet,
+            secret: crate::Secret::from(secret.serialize()),
+            compressed: true,
+        };
+        assert_eq!(PrivateKey::try_from(&private).unwrap(), secret);
+        let kp = KeyPair::from_private(private).unwrap();
+        assert_eq!(PublicKey::try_from(*kp.public()).unwrap(), point);
+    }
+
+    #[test]
+    fn test_point_serde() {
+        let point = PublicKey::create_from_private_key(&PrivateKey::from_int(1));
+        let ser = serde_json::to_string(&point).unwrap();
+        assert_eq!(serde_json::from_str::<PublicKey>(&ser).unwrap(), point);
+    }
+
+    #[test]
+    #[cfg(feature = "serialize-secrets")]
+    fn test_secret_serde() {
+        let secret = PrivateKey::from_int(1);
+        let ser = serde_json::to_string(&secret).unwrap();
+        assert_eq!(serde_json::from_str::<PrivateKey>(&ser).unwrap(), secret);
+    }
+
+    #[test]
+    fn test_secret_debug_redacted() {
+        let secret = PrivateKey::try_from(SECRET).unwrap();
+        assert_eq!(format!("{:?}", secret), "PrivateKey(<redacted>)");
+        assert_ne!(secret, PrivateKey::from_int(1));
+    }
+
+    #[test]
+    fn test_secret_eq() {
+        let one = PrivateKey::from_int(1);
+        let two = PrivateKey::from_int(2);
+        assert_eq!(one.add_scalar(&two).unwrap(), PrivateKey::from_int(3));
+        // reduced modulo the curve order
+        assert_eq!(one.neg().add_scalar(&two).unwrap(), one);
+        assert_eq!(
+            PrivateKey::parse(&one.neg().serialize()).unwrap(),
+            one.neg()
+        );
+        assert_ne!(one, two);
+    }
+}
@@ -2,30 +2,87 @@
 
 #[cfg(not(feature = "std"))]
 use alloc::{vec, vec::Vec};
-use codec::{Decode, Encode};
 use core::{fmt, str};
 use light_bitcoin_crypto::checksum;
-use light_bitcoin_primitives::H520;
+use light_bitcoin_primitives::{H256, H520};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
 
 use crate::address::Network;
 use crate::display::DisplayLayout;
 use crate::error::Error;
-use crate::signature::{CompactSignature, Signature};
-use crate::{Message, Secret};
+use crate::schnorr::sign_with_aux;
+use crate::signature::{CompactSignature, SchnorrSignature, Signature};
+use crate::Message;
+
+/// 32 bytes long secret key
+///
+/// Wiped on drop, compared in constant time and redacted in `Debug`. It is not `Copy`,
+/// so that every copy is explicit and wiped too.
+#[derive(Clone, Default)]
+#[cfg_attr(
+    feature = "serialize-secrets",
+    derive(codec::Decode, codec::Encode, scale_info::TypeInfo)
+)]
+pub struct Secret(H256);
+
+impl Secret {
+    /// # Panics
+    ///
+    /// If the slice is not 32 bytes long.
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        Secret(H256::from_slice(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    pub fn as_fixed_bytes(&self) -> &[u8; 32] {
+        self.0.as_fixed_bytes()
+    }
+}
+
+impl From<[u8; 32]> for Secret {
+    fn from(bytes: [u8; 32]) -> Self {
+        Secret(bytes.into())
+    }
+}
+
+impl From<H256> for Secret {
+    fn from(hash: H256) -> Self {
+        Secret(hash)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(<redacted>)")
+    }
+}
+
+impl PartialEq for Secret {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes().ct_eq(other.as_bytes()).into()
+    }
+}
+
+impl Eq for Secret {}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.as_bytes_mut().zeroize();
+    }
+}
 
 /// Secret with additional network identifier and format type
-#[derive(
-    Ord,
-    PartialOrd,
-    Eq,
-    PartialEq,
-    Debug,
-    Copy,
-    Clone,
-    Default,
-    Decode,
-    Encode,
-    scale_info::TypeInfo
+///
+/// The secret is wiped on drop, compared in constant time and redacted in `Debug`.
+/// SCALE encoding is only available with the `serialize-secrets` feature.
+#[derive(Clone, Default)]
+#[cfg_attr(
+    feature = "serialize-secrets",
+    derive(codec::Decode, codec::Encode, scale_info::TypeInfo)
 )]
 pub struct Private {
     /// The network on which this key should be used.
@@ -36,6 +93,26 @@ pub struct Private {
     pub compressed: bool,
 }
 
+impl fmt::Debug for Private {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Private")
+            .field("network", &self.network)
+            .field("secret", &"<redacted>")
+            .field("compressed", &self.compressed)
+            .finish()
+    }
+}
+
+impl PartialEq for Private {
+    fn eq(&self, other: &Self) -> bool {
+        (self.secret == other.secret)
+            & (self.network == other.network)
+            & (self.compressed == other.compressed)
+    }
+}
+
+impl Eq for Private {}
+
 impl fmt::Display for Private {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         bs58::encode(self.layout().as_slice()).into_string().fmt(f)
@@ -77,6 +154,16 @@ impl Private {
         compact_signature[1..65].copy_from_slice(&data);
         Ok(H520::from(compact_signature).into())
     }
+
+    /// Create a BIP340 schnorr signature with the auxiliary random data `aux`
+    pub fn sign_schnorr(
+        &self,
+        message: &Message,
+        aux: &Message,
+    ) -> Result<SchnorrSignature, Error> {
+        let secret = libsecp256k1::SecretKey::parse(self.secret.as_fixed_bytes())?;
+        sign_with_aux(*message, *aux, secret)
+    }
 }
 
 impl DisplayLayout for Private {
@@ -148,7 +235,8 @@ mod tests {
     fn test_private_to_string() {
         let private = Private {
             network: Network::Mainnet,
-            secret: h256_rev("063377054c25f98bc538ac8dd2cf9064dd5d253a725ece0628a34e2f84803bd5"),
+            secret: h256_rev("063377054c25f98bc538ac8dd2cf9064dd5d253a725ece0628a34e2f84803bd5")
+                .into(),
             compressed: false,
         };
 
@@ -162,7 +250,8 @@ mod tests {
     fn test_private_from_str() {
         let private = Private {
             network: Network::Mainnet,
-            secret: h256_rev("063377054c25f98bc538ac8dd2cf9064dd5d253a725ece0628a34e2f84803bd5"),
+            secret: h256_rev("063377054c25f98bc538ac8dd2cf9064dd5d253a725ece0628a34e2f84803bd5")
+                .into(),
             compressed: false,
         };
 
@@ -173,4 +262,32 @@ mod tests {
                 .unwrap()
         );
     }
+
+    #[test]
+    fn test_private_debug_redacted() {
+        let private: Private = "5KSCKP8NUyBZPCCQusxRwgmz9sfvJQEgbGukmmHepWw5Bzp95mu"
+            .parse()
+            .unwrap();
+        let debug = format!("{:?}", private);
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains("063377054c25f98bc538ac8dd2cf9064dd5d253a725ece0628a34e2f84803bd5"));
+        assert!(!debug.contains("d53b80842f4ea32806ce5e723a255ddd6490cfd28dac38c58bf9254c05773306"));
+        assert_eq!(format!("{:?}", private.secret), "Secret(<redacted>)");
+    }
+
+    #[test]
+    fn test_private_eq() {
+        let private: Private = "5KSCKP8NUyBZPCCQusxRwgmz9sfvJQEgbGukmmHepWw5Bzp95mu"
+            .parse()
+            .unwrap();
+        let mut other = private.clone();
+        assert_eq!(private, other);
+        other.compressed = true;
+        assert_ne!(private, other);
+        let mut other = private.clone();
+        let mut bytes = *private.secret.as_fixed_bytes();
+        bytes[31] ^= 1;
+        other.secret = bytes.into();
+        assert_ne!(private, other);
+    }
 }
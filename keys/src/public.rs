@@ -5,18 +5,21 @@ use core::{
 };
 
 use light_bitcoin_crypto::dhash160;
-use light_bitcoin_primitives::{H264, H512, H520};
+use light_bitcoin_primitives::{H256, H264, H512, H520};
 
 use codec::{Decode, Encode};
 
 use crate::{
     error::Error,
-    schnorr::verify_schnorr,
+    schnorr::{tap_tweak_hash, verify_schnorr},
     signature::{CompactSignature, SchnorrSignature, Signature},
     tagged::HashInto,
     AddressHash, Message,
 };
-use libsecp256k1::curve::{Affine, Field};
+use libsecp256k1::{
+    curve::{Affine, Field, Jacobian, Scalar},
+    ECMULT_CONTEXT,
+};
 
 /// Secret public key
 #[derive(
@@ -182,6 +185,33 @@ impl XOnly {
 
         verify_schnorr(&signature, message, *self)
     }
+
+    /// Tweak an internal key into a taproot output key as described in [BIP341].
+    ///
+    /// Returns the output key Q = P + tG and whether Q has an odd y coordinate,
+    /// which is the parity bit of the control block in script path spends.
+    ///
+    /// [BIP341]: https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki#constructing-and-spending-taproot-outputs
+    pub fn tap_tweak(&self, merkle_root: Option<H256>) -> Result<(XOnly, bool), Error> {
+        let t = tap_tweak_hash(self, merkle_root)?;
+
+        // lift_x always returns the point with an even y coordinate
+        let pubkey: libsecp256k1::PublicKey = (*self).try_into()?;
+        let p: Affine = pubkey.into();
+        let mut pj = Jacobian::default();
+        pj.set_ge(&p);
+
+        // Q = P + int(t)G
+        let mut qj = Jacobian::default();
+        ECMULT_CONTEXT.ecmult(&mut qj, &pj, &Scalar::from_int(1), &t);
+        let mut q = Affine::from_gej(&qj);
+        if q.is_infinity() {
+            return Err(Error::InvalidTweak);
+        }
+        q.y.normalize();
+
+        Ok(((&mut q.x).into(), q.y.is_odd()))
+    }
 }
 
 /// Convert [`Field`] to [`XOnly`]
@@ -193,13 +223,13 @@ impl From<&mut Field> for XOnly {
     }
 }
 
+/// Parse [`XOnly`] from a slice, which must be 32 bytes
 impl TryFrom<&[u8]> for XOnly {
     type Error = Error;
 
     fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
-        let mut bytes = [0u8; 32];
-        bytes.copy_from_slice(slice);
-        Ok(Self(bytes))
+        let bytes: [u8; 32] = slice.try_into().map_err(|_| Error::InvalidXOnly)?;
+        bytes.try_into()
     }
 }
 
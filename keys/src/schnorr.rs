@@ -19,6 +19,7 @@ use libsecp256k1::{
     curve::{Affine, Jacobian, Scalar},
     PublicKey, SecretKey, ECMULT_CONTEXT,
 };
+use light_bitcoin_primitives::H256;
 
 /// Verify a schnorr signature
 pub fn verify_schnorr(
@@ -93,6 +94,25 @@ pub fn schnorrsig_challenge(rx: &XOnly, pkx: &XOnly, msg: &Message) -> Scalar {
     scalar
 }
 
+/// Compute the BIP341 tweak of an internal key
+/// t = hash_TapTweak(P_x|merkle_root), the merkle root is omitted for key path only outputs
+pub fn tap_tweak_hash(internal: &XOnly, merkle_root: Option<H256>) -> Result<Scalar, Error> {
+    let hash = sha2::Sha256::default().tagged(b"TapTweak").add(internal);
+    let tagged = match merkle_root {
+        Some(root) => hash.add(root.as_fixed_bytes()).finalize(),
+        None => hash.finalize(),
+    };
+
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&tagged);
+    let mut scalar = Scalar::default();
+    // The tweak must be less than the curve order
+    if bool::from(scalar.set_b32(&bytes)) {
+        return Err(Error::InvalidTweak);
+    }
+    Ok(scalar)
+}
+
 /// Generate nonce k and nonce point R
 pub fn nonce_function_bip340(
     bip340_sk: &Scalar,
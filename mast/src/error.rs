@@ -37,6 +37,21 @@ pub enum MastError {
     InvalidRedeemLength,
     // Invalid redeem script threshold
     InvalidThreshold,
+    /// Other errors of light-bitcoin-keys
+    KeysError(String),
+}
+
+impl From<light_bitcoin_keys::Error> for MastError {
+    fn from(e: light_bitcoin_keys::Error) -> Self {
+        match e {
+            light_bitcoin_keys::Error::InvalidPublic => MastError::InvalidPublicKey,
+            light_bitcoin_keys::Error::InvalidSecret => MastError::InvalidPrivateKey,
+            light_bitcoin_keys::Error::XCoordinateNotExist => MastError::XCoordinateNotExist,
+            light_bitcoin_keys::Error::InvalidHexCharacter => MastError::InvalidHexCharacter,
+            light_bitcoin_keys::Error::InvalidStringLength => MastError::InvalidStringLength,
+            e => MastError::KeysError(e.to_string()),
+        }
+    }
 }
 
 impl From<io::Error> for MastError {
@@ -19,10 +19,10 @@ use alloc::{
     vec::Vec,
 };
 
-use crate::key::{KeyAgg, PrivateKey, PublicKey};
+use crate::key::{KeyAgg, PublicKey};
 use digest::Digest;
 use hashes::hex::ToHex;
-use light_bitcoin_keys::{HashAdd, Tagged};
+use light_bitcoin_keys::{HashAdd, Tagged, XOnly};
 
 #[cfg(feature = "std")]
 use rayon::prelude::*;
@@ -238,24 +238,11 @@ fn lexicographical_compare(left_node: H256, right_node: H256) -> (H256, H256) {
 /// Compute tweak public key
 pub fn tweak_pubkey(inner_pubkey: &PublicKey, root: &H256) -> Result<PublicKey> {
     // P + hash_tweak(P||root)G
-    let mut stream = Stream::default();
-    stream.append_slice(&inner_pubkey.x_coor().to_vec());
-    stream.append_slice(root.as_bytes());
-    let out = stream.out();
-
-    let hash = sha2::Sha256::default()
-        .tagged(b"TapTweak")
-        .add(&out[..])
-        .finalize();
-    let tweak_key = hash.as_slice();
-    let mut bytes = [0u8; 32];
-    bytes.copy_from_slice(tweak_key);
-    let point = PublicKey::create_from_private_key(&PrivateKey::parse(&bytes)?);
-    if inner_pubkey.is_odd_y() {
-        Ok(point.add_point(&inner_pubkey.neg())?)
-    } else {
-        Ok(point.add_point(inner_pubkey)?)
-    }
+    let (output, odd) = XOnly::from(inner_pubkey.clone()).tap_tweak(Some(*root))?;
+    let mut compressed = [0u8; 33];
+    compressed[0] = if odd { 0x03 } else { 0x02 };
+    compressed[1..].copy_from_slice(&output.0);
+    Ok(PublicKey::parse_compressed(&compressed)?)
 }
 
 pub fn ceil_divide(dividend: u32, divisor: u32) -> u32 {
@@ -503,7 +490,7 @@ mod tests {
         let mut pks = vec![];
         for _ in 0..n {
             pks.push(PublicKey::create_from_private_key(
-                &PrivateKey::generate_random().unwrap(),
+                &crate::key::PrivateKey::generate_random().unwrap(),
             ));
         }
         let mast = Mast::new(pks, m, g).unwrap();
@@ -2,9 +2,10 @@
 //!
 //! More details:
 //! [`BIP340`]: https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki#design
-use super::{error::MastError, key::PrivateKey};
+use super::error::MastError;
 use codec::{Decode, Encode};
 use core::convert::{TryFrom, TryInto};
+use libsecp256k1::curve::Scalar;
 
 /// A standard for 64-byte Schnorr signatures over the elliptic curve secp256k1
 #[derive(
@@ -19,14 +20,17 @@ use core::convert::{TryFrom, TryInto};
     serde::Deserialize
 )]
 pub struct Signature {
-    pub rx: PrivateKey,
-    pub s: PrivateKey,
+    #[serde(with = "scalar_hex")]
+    pub rx: Scalar,
+    #[serde(with = "scalar_hex")]
+    pub s: Scalar,
 }
 
 impl Signature {
     pub fn serialize(&self) -> [u8; 64] {
         let mut keys = [0u8; 64];
-        keys.copy_from_slice(&[self.rx.serialize().to_vec(), self.s.serialize().to_vec()].concat());
+        keys[0..32].copy_from_slice(&self.rx.b32());
+        keys[32..64].copy_from_slice(&self.s.b32());
         keys
     }
     pub fn parse(k: &[u8]) -> Result<Signature, MastError> {
@@ -38,8 +42,8 @@ impl Signature {
         let mut s_slice = [0u8; 32];
         s_slice.copy_from_slice(&k[32..64]);
         Ok(Signature {
-            rx: PrivateKey::parse(&r_slice)?,
-            s: PrivateKey::parse(&s_slice)?,
+            rx: parse_scalar(&r_slice)?,
+            s: parse_scalar(&s_slice)?,
         })
     }
 }
@@ -64,8 +68,40 @@ impl TryFrom<[u8; 64]> for Signature {
         let mut s_bytes = [0u8; 32];
         s_bytes.copy_from_slice(&bytes[32..64]);
 
-        let rx = PrivateKey::parse(&rx_bytes)?;
-        let s = PrivateKey::parse(&s_bytes)?;
+        let rx = parse_scalar(&rx_bytes)?;
+        let s = parse_scalar(&s_bytes)?;
         Ok(Signature { rx, s })
     }
 }
+
+/// Parse a scalar, rejecting values that are not less than the curve order
+fn parse_scalar(bytes: &[u8; 32]) -> Result<Scalar, MastError> {
+    let mut scalar = Scalar::default();
+    if bool::from(scalar.set_b32(bytes)) {
+        return Err(MastError::InvalidPrivateKey);
+    }
+    Ok(scalar)
+}
+
+/// Serde of [`Scalar`] as a hex string
+mod scalar_hex {
+    #[cfg(not(feature = "std"))]
+    use alloc::string::String;
+    use libsecp256k1::curve::Scalar;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(scalar: &Scalar, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(scalar.b32()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Scalar, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let bytes = hex::decode(s).map_err(|_| Error::custom("scalar"))?;
+        if bytes.len() != 32 {
+            return Err(Error::custom("scalar"));
+        }
+        let mut b32 = [0u8; 32];
+        b32.copy_from_slice(&bytes);
+        super::parse_scalar(&b32).map_err(|_| Error::custom("scalar"))
+    }
+}
@@ -0,0 +1,324 @@
+//! Bloom filters of BIP 37, used by SPV clients to request the transactions
+//! relevant to them.
+
+use light_bitcoin_chain::{OutPoint, Transaction};
+use light_bitcoin_crypto::murmur3;
+use light_bitcoin_primitives::Bytes;
+use light_bitcoin_script::Script;
+use light_bitcoin_serialization::{serialize, Deserializable, Serializable};
+
+/// Maximum size of a bloom filter in bytes
+pub const MAX_BLOOM_FILTER_SIZE: usize = 36_000;
+/// Maximum number of hash functions of a bloom filter
+pub const MAX_HASH_FUNCS: u32 = 50;
+
+/// Never add outpoints to the filter
+pub const BLOOM_UPDATE_NONE: u8 = 0;
+/// Add the outpoint of every output matching the filter
+pub const BLOOM_UPDATE_ALL: u8 = 1;
+/// Add the outpoint of matching pay-to-pubkey and multisig outputs only
+pub const BLOOM_UPDATE_P2PUBKEY_ONLY: u8 = 2;
+/// Bits of the flags defining the update mode
+pub const BLOOM_UPDATE_MASK: u8 = 3;
+
+/// Multiplier of the hash function index in the murmur3 seed
+const SEED_MULTIPLIER: u32 = 0xfba4_c795;
+
+/// Bloom filter, serialized as in the `filterload` message
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serializable, Deserializable)]
+pub struct BloomFilter {
+    /// The filter bits
+    pub data: Bytes,
+    /// Number of hash functions
+    pub hash_funcs: u32,
+    /// Random value added to the seed of the hash functions
+    pub tweak: u32,
+    /// Update mode of the filter when matching transactions
+    pub flags: u8,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter sized to hold `elements` with the false positive rate
+    /// `fp_rate`, within the BIP 37 limits.
+    #[cfg(feature = "std")]
+    pub fn new(elements: u32, fp_rate: f64, tweak: u32, flags: u8) -> Self {
+        let ln2 = core::f64::consts::LN_2;
+        let elements = elements.max(1);
+        let bits = (-1.0 / (ln2 * ln2) * f64::from(elements) * fp_rate.ln())
+            .min((MAX_BLOOM_FILTER_SIZE * 8) as f64) as usize;
+        let size = bits / 8;
+        // like Core, the bits per element are truncated before multiplying by ln 2
+        let hash_funcs = ((((size * 8) as u32 / elements) as f64 * ln2) as u32).min(MAX_HASH_FUNCS);
+        BloomFilter {
+            data: vec![0; size].into(),
+            hash_funcs,
+            tweak,
+            flags,
+        }
+    }
+
+    /// Returns true if the filter is within the size limits of BIP 37.
+    pub fn is_within_size_constraints(&self) -> bool {
+        self.data.len() <= MAX_BLOOM_FILTER_SIZE && self.hash_funcs <= MAX_HASH_FUNCS
+    }
+
+    fn hash(&self, index: u32, data: &[u8]) -> usize {
+        let seed = index.wrapping_mul(SEED_MULTIPLIER).wrapping_add(self.tweak);
+        murmur3(seed, data) as usize % (self.data.len() * 8)
+    }
+
+    pub fn insert(&mut self, data: &[u8]) {
+        if self.data.is_empty() {
+            return;
+        }
+        for index in 0..self.hash_funcs {
+            let bit = self.hash(index, data);
+            self.data[bit >> 3] |= 1 << (bit & 7);
+        }
+    }
+
+    /// Returns true if the data may be in the filter. An empty filter matches everything.
+    pub fn contains(&self, data: &[u8]) -> bool {
+        if self.data.is_empty() {
+            return true;
+        }
+        (0..self.hash_funcs).all(|index| {
+            let bit = self.hash(index, data);
+            self.data[bit >> 3] & (1 << (bit & 7)) != 0
+        })
+    }
+
+    pub fn insert_outpoint(&mut self, outpoint: &OutPoint) {
+        self.insert(&serialize(outpoint));
+    }
+
+    pub fn contains_outpoint(&self, outpoint: &OutPoint) -> bool {
+        self.contains(&serialize(outpoint))
+    }
+
+    /// Returns true if the transaction matches the filter, by its txid, the data pushed
+    /// by its output scripts, the outpoints it spends or the data pushed by its input
+    /// scripts.
+    ///
+    /// Depending on the flags, the outpoints of the matching outputs are added to the
+    /// filter, so that the transactions spending them match too.
+    pub fn is_relevant_and_update(&mut self, tx: &Transaction) -> bool {
+        let txid = tx.hash();
+        let mut found = self.contains(txid.as_bytes());
+
+        for (index, output) in tx.outputs.iter().enumerate() {
+            let script = Script::new(output.script_pubkey.clone());
+            if !self.contains_push_data(&script) {
+                continue;
+            }
+            found = true;
+            let update = match self.flags & BLOOM_UPDATE_MASK {
+                BLOOM_UPDATE_ALL => true,
+                BLOOM_UPDATE_P2PUBKEY_ONLY => {
+                    script.is_pay_to_public_key() || script.is_multisig_script()
+                }
+                _ => false,
+            };
+            if update {
+                self.insert_outpoint(&OutPoint {
+                    txid,
+                    index: index as u32,
+                });
+            }
+        }
+        if found {
+            return true;
+        }
+
+        tx.inputs.iter().any(|input| {
+            self.contains_outpoint(&input.previous_output)
+                || self.contains_push_data(&Script::new(input.script_sig.clone()))
+        })
+    }
+
+    /// Returns true if any data pushed by the script is in the filter. Parsing stops at
+    /// the first invalid opcode.
+    fn contains_push_data(&self, script: &Script) -> bool {
+        script
+            .iter()
+            .map_while(Result::ok)
+            .filter_map(|instruction| instruction.data)
+            .any(|data| !data.is_empty() && self.contains(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use light_bitcoin_chain::{TransactionInput, TransactionOutput};
+    use light_bitcoin_primitives::H256;
+    use light_bitcoin_script::Builder;
+    use light_bitcoin_serialization::deserialize;
+
+    fn elements() -> Vec<Bytes> {
+        [
+            "99108ad8ed9bb6274d3980bab5a85c048f0950c8",
+            "b5a2c786d9ef4658287ced5914b37a1b4aa32eee",
+            "b9300670b4c5366e95b2699e8b18bc75e5f729c5",
+        ]
+        .iter()
+        .map(|element| element.parse().unwrap())
+        .collect()
+    }
+
+    // test vectors from bitcoin core's src/test/bloom_tests.cpp
+    #[test]
+    fn test_bloom_create_insert_serialize() {
+        for (tweak, expected) in [
+            (0, "03614e9b050000000000000001"),
+            (2147483649, "03ce4299050000000100008001"),
+        ] {
+            let mut filter = BloomFilter::new(3, 0.01, tweak, BLOOM_UPDATE_ALL);
+            for element in elements() {
+                filter.insert(&element);
+                assert!(filter.contains(&element));
+            }
+            if tweak == 0 {
+                let other: Bytes = "19108ad8ed9bb6274d3980bab5a85c048f0950c8".parse().unwrap();
+                assert!(!filter.contains(&other));
+            }
+            assert!(filter.is_within_size_constraints());
+            assert_eq!(filter.hash_funcs, 5);
+
+            let serialized = serialize(&filter);
+            assert_eq!(serialized, expected.parse().unwrap());
+            assert_eq!(
+                deserialize::<_, BloomFilter>(serialized.as_ref()).unwrap(),
+                filter
+            );
+        }
+    }
+
+    #[test]
+    fn test_bloom_create_insert_key() {
+        let public: Bytes = "045b81f0017e2091e2edcd5eecf10d5bdd120a5514cb3ee65b8447ec18bfc4575c6d5bf415e54e03b1067934a0f0ba76b01c6b9ab227142ee1d543764b69d901e0".parse().unwrap();
+        let public_hash: Bytes = "477abbacd4113f2e6b100526222eedd953c26a64".parse().unwrap();
+        let mut filter = BloomFilter::new(2, 0.001, 0, BLOOM_UPDATE_ALL);
+        filter.insert(&public);
+        filter.insert(&public_hash);
+        assert_eq!(filter.hash_funcs, 8);
+        assert_eq!(
+            serialize(&filter),
+            "038fc16b080000000000000001".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_bloom_hash_funcs() {
+        // 32 bits for 3 elements: 10 bits per element, 6.93 rounded down
+        let filter = BloomFilter::new(3, 0.002, 0, BLOOM_UPDATE_NONE);
+        assert_eq!(filter.data.len(), 4);
+        assert_eq!(filter.hash_funcs, 6);
+    }
+
+    #[test]
+    fn test_bloom_size_limits() {
+        let filter = BloomFilter::new(1_000_000, 0.000_001, 0, BLOOM_UPDATE_NONE);
+        assert_eq!(filter.data.len(), MAX_BLOOM_FILTER_SIZE);
+        assert!(filter.hash_funcs <= MAX_HASH_FUNCS);
+        assert!(filter.is_within_size_constraints());
+
+        // an empty filter matches everything
+        assert!(BloomFilter::default().contains(&[1, 2, 3]));
+    }
+
+    fn transaction(
+        previous_output: OutPoint,
+        script_sig: Bytes,
+        outputs: Vec<Bytes>,
+    ) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                previous_output,
+                script_sig,
+                sequence: 0xffff_ffff,
+                script_witness: vec![],
+            }],
+            outputs: outputs
+                .into_iter()
+                .map(|script_pubkey| TransactionOutput {
+                    value: 1000,
+                    script_pubkey,
+                })
+                .collect(),
+            lock_time: 0,
+        }
+    }
+
+    fn spend(tx: &Transaction, index: u32) -> Transaction {
+        let previous_output = OutPoint {
+            txid: tx.hash(),
+            index,
+        };
+        transaction(previous_output, Bytes::new(), vec![vec![0x51].into()])
+    }
+
+    #[test]
+    fn test_bloom_match_transaction() {
+        let pubkey = [2u8; 33];
+        let pubkey_hash = [3u8; 20];
+        let p2pkh = Builder::build_p2pkh(&pubkey_hash.into()).to_bytes();
+        let p2pk = Builder::default()
+            .push_data(&pubkey)
+            .push_opcode(light_bitcoin_script::Opcode::OP_CHECKSIG)
+            .into_script()
+            .to_bytes();
+        let funding = transaction(
+            OutPoint {
+                txid: H256::repeat_byte(1),
+                index: 0,
+            },
+            Builder::default().push_data(&[4u8; 33]).into_bytes(),
+            vec![p2pkh, p2pk],
+        );
+
+        let new_filter = |flags| BloomFilter::new(10, 0.000_001, 0, flags);
+
+        // by txid, without update
+        let mut filter = new_filter(BLOOM_UPDATE_ALL);
+        filter.insert(funding.hash().as_bytes());
+        assert!(filter.is_relevant_and_update(&funding));
+        assert!(!filter.is_relevant_and_update(&spend(&funding, 0)));
+
+        // by input script data and by spent outpoint
+        let mut filter = new_filter(BLOOM_UPDATE_NONE);
+        filter.insert(&[4u8; 33]);
+        assert!(filter.is_relevant_and_update(&funding));
+        let mut filter = new_filter(BLOOM_UPDATE_NONE);
+        filter.insert_outpoint(&funding.inputs[0].previous_output);
+        assert!(filter.is_relevant_and_update(&funding));
+
+        // by output script data, adding the matching outpoints
+        let mut filter = new_filter(BLOOM_UPDATE_ALL);
+        filter.insert(&pubkey_hash);
+        assert!(filter.is_relevant_and_update(&funding));
+        assert!(filter.is_relevant_and_update(&spend(&funding, 0)));
+        assert!(!filter.is_relevant_and_update(&spend(&funding, 1)));
+
+        let mut filter = new_filter(BLOOM_UPDATE_NONE);
+        filter.insert(&pubkey_hash);
+        filter.insert(&pubkey);
+        assert!(filter.is_relevant_and_update(&funding));
+        assert!(!filter.is_relevant_and_update(&spend(&funding, 0)));
+        assert!(!filter.is_relevant_and_update(&spend(&funding, 1)));
+
+        // only the pay to pubkey output is added
+        let mut filter = new_filter(BLOOM_UPDATE_P2PUBKEY_ONLY);
+        filter.insert(&pubkey_hash);
+        filter.insert(&pubkey);
+        assert!(filter.is_relevant_and_update(&funding));
+        assert!(!filter.is_relevant_and_update(&spend(&funding, 0)));
+        assert!(filter.is_relevant_and_update(&spend(&funding, 1)));
+
+        let mut filter = new_filter(BLOOM_UPDATE_ALL);
+        filter.insert(&[5u8; 20]);
+        assert!(!filter.is_relevant_and_update(&funding));
+    }
+}
@@ -5,22 +5,29 @@
 #[cfg(not(feature = "std"))]
 extern crate alloc;
 
+pub mod bloom;
+
 #[cfg(not(feature = "std"))]
-use alloc::{string::String, vec, vec::Vec};
+use alloc::{collections::BTreeSet, string::String, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
+
 use codec::{Decode, Encode};
 use core::fmt;
-use light_bitcoin_chain::merkle_node_hash;
+use light_bitcoin_chain::{merkle_node_hash, BlockHeader, IndexedBlock, Transaction};
 use light_bitcoin_primitives::{hash_rev, io, H256};
 use light_bitcoin_serialization::{
     deserialize, serialize, Deserializable, Reader, Serializable, Stream,
 };
 
+pub use self::bloom::BloomFilter;
+
 /// The maximum allowed weight for a block, see BIP 141 (network rule)
 const MAX_BLOCK_WEIGHT: u32 = 4_000_000;
 /// The minimum transaction weight for a valid serialized transaction
 const MIN_TRANSACTION_WEIGHT: u32 = 4 * 60;
 
-#[derive(Debug, scale_info::TypeInfo)]
+#[derive(Debug, PartialEq, Eq, scale_info::TypeInfo)]
 #[cfg_attr(feature = "std", derive(Decode, Encode))]
 pub enum Error {
     /// When header merkle root don't match to the root calculated from the partial merkle tree
@@ -29,6 +36,10 @@ pub enum Error {
     NoTransactions,
     /// When there are too many transactions
     TooManyTransactions,
+    /// When the transaction is not matched by the partial merkle tree
+    TransactionNotIncluded,
+    /// When the transaction is 64 bytes long, and could be an inner node of the merkle tree
+    AmbiguousTransaction,
     /// General format error
     BadFormat(String),
 }
@@ -42,6 +53,8 @@ impl core::fmt::Display for Error {
             Error::MerkleRootMismatch => f.write_str("header merkle root don't match to the root calculated from the partial merkle tree"),
             Error::NoTransactions => f.write_str("partial merkle tree contains no transactions"),
             Error::TooManyTransactions => f.write_str("there are too many transactions"),
+            Error::TransactionNotIncluded => f.write_str("transaction is not included in the partial merkle tree"),
+            Error::AmbiguousTransaction => f.write_str("64 bytes transaction is ambiguous with an inner merkle node"),
             Error::BadFormat(err) => f.write_str(err)
         }
     }
@@ -366,38 +379,128 @@ impl codec::Decode for PartialMerkleTree {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use hashbrown::HashSet;
+/// Data structure that represents a block header paired to a partial merkle tree,
+/// the `merkleblock` message of BIP 37.
+#[derive(PartialEq, Eq, Clone, Debug, Default, scale_info::TypeInfo)]
+#[derive(Serializable, Deserializable)]
+pub struct MerkleBlock {
+    /// The block header
+    pub header: BlockHeader,
+    /// Transactions making up a partial merkle tree
+    pub pmt: PartialMerkleTree,
+}
 
-    use light_bitcoin_chain::{merkle_root, Block, BlockHeader};
-    use light_bitcoin_primitives::{h256_rev, H256};
-    use light_bitcoin_serialization::{deserialize, serialize, Deserializable, Serializable};
-    use rand::prelude::*;
+impl MerkleBlock {
+    /// Create a MerkleBlock from a block, including the transactions whose txid is in
+    /// `match_txids`.
+    pub fn from_block(block: &IndexedBlock, match_txids: &BTreeSet<H256>) -> Self {
+        let (txids, matches): (Vec<_>, Vec<_>) = block
+            .transactions
+            .iter()
+            .map(|tx| (tx.hash, match_txids.contains(&tx.hash)))
+            .unzip();
+        MerkleBlock {
+            header: block.header.raw,
+            pmt: PartialMerkleTree::from_txids(&txids, &matches),
+        }
+    }
 
-    use super::PartialMerkleTree;
+    /// Create a MerkleBlock from a block, including the transactions matching the bloom
+    /// filter, which is updated as in the `merkleblock` reply to a `getdata`.
+    /// Also returns the index and txid of the matched transactions.
+    pub fn from_block_with_filter(
+        block: &IndexedBlock,
+        filter: &mut BloomFilter,
+    ) -> (Self, Vec<(u32, H256)>) {
+        let mut matched = vec![];
+        let (txids, matches): (Vec<_>, Vec<_>) = block
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                let is_match = filter.is_relevant_and_update(&tx.raw);
+                if is_match {
+                    matched.push((index as u32, tx.hash));
+                }
+                (tx.hash, is_match)
+            })
+            .unzip();
+        let merkle_block = MerkleBlock {
+            header: block.header.raw,
+            pmt: PartialMerkleTree::from_txids(&txids, &matches),
+        };
+        (merkle_block, matched)
+    }
+
+    /// Extract the matching txid's represented by this partial merkle tree
+    /// and their respective indices within the partial tree.
+    /// Returns error in case of failure or when the merkle root does not match the header.
+    pub fn extract_matches(
+        &self,
+        matches: &mut Vec<H256>,
+        indexes: &mut Vec<u32>,
+    ) -> Result<(), Error> {
+        let merkle_root = self.pmt.extract_matches(matches, indexes)?;
+        if merkle_root == self.header.merkle_root_hash {
+            Ok(())
+        } else {
+            Err(Error::MerkleRootMismatch)
+        }
+    }
+}
 
-    #[derive(PartialEq, Eq, Clone, Debug, Serializable, Deserializable)]
-    struct MerkleBlock {
-        header: BlockHeader,
-        pmt: PartialMerkleTree,
+impl codec::Encode for MerkleBlock {
+    fn encode(&self) -> Vec<u8> {
+        let value = serialize::<MerkleBlock>(self);
+        value.encode()
     }
+}
 
-    impl MerkleBlock {
-        fn from_block(block: &Block, match_txids: &HashSet<H256>) -> Self {
-            let header = block.header;
+impl codec::EncodeLike for MerkleBlock {}
 
-            let mut matches = Vec::with_capacity(block.transactions.len());
-            let mut hashes = Vec::with_capacity(block.transactions.len());
+impl codec::Decode for MerkleBlock {
+    fn decode<I: codec::Input>(value: &mut I) -> Result<Self, codec::Error> {
+        let value: Vec<u8> = codec::Decode::decode(value)?;
+        deserialize(Reader::new(&value)).map_err(|_| "deserialize MerkleBlock error".into())
+    }
+}
 
-            for hash in block.transactions.iter().map(|tx| tx.hash()) {
-                matches.push(match_txids.contains(&hash));
-                hashes.push(hash);
-            }
-            let pmt = PartialMerkleTree::from_txids(&hashes, &matches);
-            MerkleBlock { header, pmt }
-        }
+/// Verify that the transaction is included in the block of the header,
+/// returning its index in the block.
+///
+/// The transaction is taken instead of its txid, because a 64 bytes transaction
+/// can be interpreted as an inner node of the merkle tree, whose children could
+/// then be proven as transactions. Such transactions are rejected.
+pub fn verify_tx_inclusion(
+    header: &BlockHeader,
+    pmt: &PartialMerkleTree,
+    tx: &Transaction,
+) -> Result<u32, Error> {
+    if tx.serialized_size() == 64 {
+        return Err(Error::AmbiguousTransaction);
     }
+    let txid = tx.hash();
+
+    let mut matches = vec![];
+    let mut indexes = vec![];
+    if pmt.extract_matches(&mut matches, &mut indexes)? != header.merkle_root_hash {
+        return Err(Error::MerkleRootMismatch);
+    }
+    matches
+        .iter()
+        .position(|hash| *hash == txid)
+        .map(|position| indexes[position])
+        .ok_or(Error::TransactionNotIncluded)
+}
+
+#[cfg(test)]
+mod tests {
+    use light_bitcoin_chain::{merkle_root, Block, OutPoint, TransactionInput, TransactionOutput};
+    use light_bitcoin_primitives::{h256_rev, H256};
+    use light_bitcoin_serialization::{deserialize, serialize};
+    use rand::prelude::*;
+
+    use super::*;
 
     impl PartialMerkleTree {
         /// Flip one bit in one of the hashes - this should break the authentication
@@ -536,7 +639,7 @@ mod tests {
         let txid2 = h256_rev("f9fc751cb7dc372406a9f8d738d5e6f8f63bab71986a39cf36ee70ee17036d07");
         let txids = vec![txid1, txid2].into_iter().collect();
 
-        let merkle_block = MerkleBlock::from_block(&block, &txids);
+        let merkle_block = MerkleBlock::from_block(&block.clone().into(), &txids);
         assert_eq!(merkle_block.header.hash(), block.hash());
 
         let mut matches: Vec<H256> = vec![];
@@ -566,7 +669,7 @@ mod tests {
         .into_iter()
         .collect();
 
-        let merkle_block = MerkleBlock::from_block(&block, &txids);
+        let merkle_block = MerkleBlock::from_block(&block.clone().into(), &txids);
 
         assert_eq!(merkle_block.header.hash(), block.hash());
 
@@ -582,6 +685,102 @@ mod tests {
         assert_eq!(indexes.len(), 0);
     }
 
+    #[test]
+    fn merkle_block_verify_tx_inclusion() {
+        let block = get_block_13b8a();
+        let tx = &block.transactions[8];
+        let txids = vec![tx.hash()].into_iter().collect();
+        let merkle_block = MerkleBlock::from_block(&block.clone().into(), &txids);
+        assert_eq!(
+            merkle_block.extract_matches(&mut vec![], &mut vec![]),
+            Ok(())
+        );
+        assert_eq!(
+            verify_tx_inclusion(&merkle_block.header, &merkle_block.pmt, tx),
+            Ok(8)
+        );
+        assert_eq!(
+            verify_tx_inclusion(
+                &merkle_block.header,
+                &merkle_block.pmt,
+                &block.transactions[1]
+            ),
+            Err(Error::TransactionNotIncluded)
+        );
+
+        let mut header = merkle_block.header;
+        header.merkle_root_hash = H256::zero();
+        assert_eq!(
+            verify_tx_inclusion(&header, &merkle_block.pmt, tx),
+            Err(Error::MerkleRootMismatch)
+        );
+        let forged = MerkleBlock {
+            header,
+            pmt: merkle_block.pmt,
+        };
+        assert_eq!(
+            forged.extract_matches(&mut vec![], &mut vec![]),
+            Err(Error::MerkleRootMismatch)
+        );
+    }
+
+    #[test]
+    fn merkle_block_construct_from_filter() {
+        let block: IndexedBlock = get_block_13b8a().into();
+        let mut filter = BloomFilter::new(10, 0.000_001, 0, bloom::BLOOM_UPDATE_ALL);
+        filter.insert(block.transactions[8].hash.as_bytes());
+
+        let (merkle_block, matched) = MerkleBlock::from_block_with_filter(&block, &mut filter);
+        assert_eq!(matched, vec![(8, block.transactions[8].hash)]);
+
+        let mut matches = vec![];
+        let mut indexes = vec![];
+        assert_eq!(
+            merkle_block.extract_matches(&mut matches, &mut indexes),
+            Ok(())
+        );
+        assert_eq!(matches, vec![block.transactions[8].hash]);
+        assert_eq!(indexes, vec![8]);
+    }
+
+    #[test]
+    fn verify_tx_inclusion_rejects_64_bytes_transaction() {
+        // 1 input with an empty script and 1 output with a 4 bytes script
+        let tx = Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    txid: H256::repeat_byte(1),
+                    index: 0,
+                },
+                script_sig: Default::default(),
+                sequence: 0xffff_ffff,
+                script_witness: vec![],
+            }],
+            outputs: vec![TransactionOutput {
+                value: 0,
+                script_pubkey: vec![0x6a, 0x02, 0x00, 0x00].into(),
+            }],
+            lock_time: 0,
+        };
+        assert_eq!(tx.serialized_size(), 64);
+
+        // the transaction is the single leaf, and the merkle root, of this block
+        let block: IndexedBlock = Block::new(
+            BlockHeader {
+                merkle_root_hash: tx.hash(),
+                ..Default::default()
+            },
+            vec![tx.clone()],
+        )
+        .into();
+        let merkle_block = MerkleBlock::from_block(&block, &vec![tx.hash()].into_iter().collect());
+        assert_eq!(
+            verify_tx_inclusion(&merkle_block.header, &merkle_block.pmt, &tx),
+            Err(Error::AmbiguousTransaction)
+        );
+    }
+
     // Block 100,002 (0000000000013b8ab2cd513b0261a14096412195a72a0c4827d229dcc7e0f7af) with 9 txs.
     // https://blockchain.info/rawblock/0000000000013b8ab2cd513b0261a14096412195a72a0c4827d229dcc7e0f7af
     // https://blockchain.info/rawblock/0000000000013b8ab2cd513b0261a14096412195a72a0c4827d229dcc7e0f7af?format=hex
@@ -0,0 +1,186 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use light_bitcoin_crypto::checksum;
+use light_bitcoin_primitives::Bytes;
+use light_bitcoin_serialization::{Reader, Stream};
+
+use crate::error::Error;
+use crate::message::{
+    MessageHeader, NetworkMessage, MAX_MESSAGE_PAYLOAD_SIZE, MESSAGE_HEADER_SIZE,
+};
+
+/// Serializes the message with its header for the network of the magic.
+pub fn serialize_message(magic: u32, message: &NetworkMessage) -> Bytes {
+    let payload = message.payload();
+    let header = MessageHeader {
+        magic,
+        command: message.command(),
+        length: payload.len() as u32,
+        checksum: checksum(&payload),
+    };
+    let mut stream = Stream::new();
+    stream.append(&header).append_slice(&payload);
+    stream.out()
+}
+
+/// Decoder of the messages of a byte stream, without doing any IO.
+///
+/// The bytes received from the peer are given to `feed` as they arrive, in chunks of
+/// any size, and the complete messages are taken out with `decode`.
+#[derive(Debug, Clone)]
+pub struct MessageDecoder {
+    magic: u32,
+    buffer: Vec<u8>,
+}
+
+impl MessageDecoder {
+    pub fn new(magic: u32) -> Self {
+        MessageDecoder {
+            magic,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Number of bytes received and not decoded yet
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns the next message, or `None` until all of its bytes are received.
+    ///
+    /// An invalid magic, command or payload length means the stream can not be
+    /// decoded any further, and the connection should be closed. A message with an
+    /// invalid checksum or a malformed payload is dropped, and the next messages can
+    /// still be decoded.
+    pub fn decode(&mut self) -> Result<Option<NetworkMessage>, Error> {
+        if self.buffer.len() < MESSAGE_HEADER_SIZE {
+            return Ok(None);
+        }
+
+        // the header has a fixed size, reading it can only fail on the command
+        let header: MessageHeader = Reader::new(&self.buffer[..MESSAGE_HEADER_SIZE])
+            .read()
+            .map_err(|_| Error::InvalidCommand)?;
+        if header.magic != self.magic {
+            return Err(Error::InvalidMagic(header.magic));
+        }
+        let length = header.length as usize;
+        if length > MAX_MESSAGE_PAYLOAD_SIZE {
+            return Err(Error::PayloadTooLarge(header.length));
+        }
+        if self.buffer.len() < MESSAGE_HEADER_SIZE + length {
+            return Ok(None);
+        }
+
+        let payload: Vec<u8> = self
+            .buffer
+            .drain(..MESSAGE_HEADER_SIZE + length)
+            .skip(MESSAGE_HEADER_SIZE)
+            .collect();
+        if checksum(&payload) != header.checksum {
+            return Err(Error::InvalidChecksum);
+        }
+        NetworkMessage::from_payload(&header.command, &payload).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Network;
+    use crate::types::{Inventory, InventoryType};
+    use light_bitcoin_primitives::{io, H256};
+
+    #[test]
+    fn test_serialize_verack() {
+        let verack = serialize_message(Network::Regtest.magic(), &NetworkMessage::Verack);
+        assert_eq!(
+            verack,
+            "fabfb5da76657261636b000000000000000000005df6e0e2"
+                .parse()
+                .unwrap()
+        );
+        let ping = serialize_message(Network::Mainnet.magic(), &NetworkMessage::Ping(0));
+        assert_eq!(&ping[..4], &[0xf9, 0xbe, 0xb4, 0xd9]);
+        assert_eq!(ping.len(), MESSAGE_HEADER_SIZE + 8);
+    }
+
+    #[test]
+    fn test_decode_partial_buffers() {
+        let magic = Network::Regtest.magic();
+        let messages = vec![
+            NetworkMessage::Verack,
+            NetworkMessage::Ping(7),
+            NetworkMessage::Inv(vec![Inventory::new(
+                InventoryType::Block,
+                H256::repeat_byte(9),
+            )]),
+            NetworkMessage::WtxidRelay,
+        ];
+        let bytes: Vec<u8> = messages
+            .iter()
+            .flat_map(|message| serialize_message(magic, message).take())
+            .collect();
+
+        for chunk_size in [1, 5, 24, 25, 1000] {
+            let mut decoder = MessageDecoder::new(magic);
+            let mut decoded = vec![];
+            for chunk in bytes.chunks(chunk_size) {
+                decoder.feed(chunk);
+                while let Some(message) = decoder.decode().unwrap() {
+                    decoded.push(message);
+                }
+            }
+            assert_eq!(decoded, messages);
+            assert_eq!(decoder.buffered(), 0);
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let magic = Network::Regtest.magic();
+
+        let mut decoder = MessageDecoder::new(Network::Mainnet.magic());
+        decoder.feed(&serialize_message(magic, &NetworkMessage::Verack));
+        assert_eq!(decoder.decode(), Err(Error::InvalidMagic(magic)));
+
+        // the message with a bad checksum is dropped
+        let mut ping = serialize_message(magic, &NetworkMessage::Ping(1)).take();
+        ping[20] ^= 1;
+        let mut decoder = MessageDecoder::new(magic);
+        decoder.feed(&ping);
+        decoder.feed(&serialize_message(magic, &NetworkMessage::Pong(1)));
+        assert_eq!(decoder.decode(), Err(Error::InvalidChecksum));
+        assert_eq!(decoder.decode(), Ok(Some(NetworkMessage::Pong(1))));
+
+        // truncated payload
+        let mut stream = Stream::new();
+        stream.append(&MessageHeader {
+            magic,
+            command: NetworkMessage::Ping(0).command(),
+            length: 4,
+            checksum: checksum(&[0; 4]),
+        });
+        let mut decoder = MessageDecoder::new(magic);
+        decoder.feed(&stream.out());
+        decoder.feed(&[0; 4]);
+        assert_eq!(decoder.decode(), Err(Error::Io(io::Error::UnexpectedEof)));
+
+        let mut oversized = serialize_message(magic, &NetworkMessage::Verack).take();
+        oversized[16..20].copy_from_slice(&4_000_001u32.to_le_bytes());
+        let mut decoder = MessageDecoder::new(magic);
+        decoder.feed(&oversized);
+        assert_eq!(decoder.decode(), Err(Error::PayloadTooLarge(4_000_001)));
+
+        let mut invalid = serialize_message(magic, &NetworkMessage::Verack).take();
+        invalid[4] = 0;
+        let mut decoder = MessageDecoder::new(magic);
+        decoder.feed(&invalid);
+        assert_eq!(decoder.decode(), Err(Error::InvalidCommand));
+    }
+}
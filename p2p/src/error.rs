@@ -0,0 +1,51 @@
+use core::fmt;
+use light_bitcoin_primitives::io;
+
+/// Network message errors
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The message does not start with the magic of the network
+    InvalidMagic(u32),
+    /// The command is not a NUL padded ascii string of at most 12 characters
+    InvalidCommand,
+    /// The payload length is above `MAX_MESSAGE_PAYLOAD_SIZE`
+    PayloadTooLarge(u32),
+    /// The payload does not match the checksum of the header
+    InvalidChecksum,
+    /// The payload is malformed for the command
+    Io(io::Error),
+    /// The peer started a v1 connection instead of a v2 one
+    V1Handshake,
+    /// No v2 garbage terminator is received after the maximum garbage size
+    GarbageTooLong,
+    /// The v2 packet fails authentication
+    InvalidPacket,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Error::InvalidMagic(magic) => return write!(f, "Invalid network magic {:08x}", magic),
+            Error::InvalidCommand => "Invalid command",
+            Error::PayloadTooLarge(len) => {
+                return write!(f, "Payload of {} bytes is too large", len)
+            }
+            Error::InvalidChecksum => "Invalid payload checksum",
+            Error::Io(err) => return err.fmt(f),
+            Error::V1Handshake => "Peer uses the v1 transport",
+            Error::GarbageTooLong => "Garbage terminator not found",
+            Error::InvalidPacket => "Invalid packet authentication tag",
+        };
+
+        msg.fmt(f)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
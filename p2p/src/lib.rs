@@ -0,0 +1,30 @@
+//! Messages of the bitcoin peer to peer protocol, a decoder of the message stream of
+//! a connection, the encrypted v2 transport, and the state machine of a connection
+//! syncing headers.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+mod codec;
+mod error;
+mod message;
+mod network;
+mod types;
+mod v2;
+
+pub mod peer;
+
+pub use self::codec::{serialize_message, MessageDecoder};
+pub use self::error::Error;
+pub use self::message::{
+    Command, MessageHeader, NetworkMessage, MAX_HEADERS_RESULTS, MAX_MESSAGE_PAYLOAD_SIZE,
+    MESSAGE_HEADER_SIZE,
+};
+pub use self::network::Network;
+pub use self::types::*;
+pub use self::v2::{
+    decode_contents, encode_contents, short_id, PacketCipher, V2Transport, ELLSWIFT_SIZE,
+    GARBAGE_TERMINATOR_SIZE, HEADER_SIZE, LENGTH_SIZE, MAX_GARBAGE_SIZE, PACKET_OVERHEAD,
+};
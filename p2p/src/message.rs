@@ -0,0 +1,469 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use core::{fmt, str};
+use light_bitcoin_chain::bip152::{BlockTransactions, BlockTransactionsRequest, HeaderAndShortIds};
+use light_bitcoin_chain::{Block, BlockHeader, Transaction};
+use light_bitcoin_primitives::{io, Bytes, H32};
+use light_bitcoin_serialization::{
+    CompactInteger, Deserializable, Reader, Serializable, Stream, SERIALIZE_TRANSACTION_WITNESS,
+};
+
+use crate::error::Error;
+use crate::types::{
+    AddrV2Message, GetHeadersMessage, Inventory, SendCmpct, VersionMessage, MAX_ADDR_SIZE,
+    MAX_INV_SIZE,
+};
+
+/// Size of the message header
+pub const MESSAGE_HEADER_SIZE: usize = 24;
+/// Maximum size of a message payload
+pub const MAX_MESSAGE_PAYLOAD_SIZE: usize = 4_000_000;
+/// Maximum number of headers of a `headers` message
+pub const MAX_HEADERS_RESULTS: usize = 2_000;
+
+/// Command of a message, an ascii string NUL padded to 12 bytes
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Command([u8; 12]);
+
+impl Command {
+    pub fn new(name: &str) -> Result<Self, Error> {
+        if name.is_empty() || name.len() > 12 || !name.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(Error::InvalidCommand);
+        }
+        let mut command = [0u8; 12];
+        command[..name.len()].copy_from_slice(name.as_bytes());
+        Ok(Command(command))
+    }
+
+    fn from_bytes(command: [u8; 12]) -> Result<Self, Error> {
+        let len = command.iter().position(|b| *b == 0).unwrap_or(12);
+        if len == 0
+            || !command[..len].iter().all(u8::is_ascii_graphic)
+            || command[len..].iter().any(|b| *b != 0)
+        {
+            return Err(Error::InvalidCommand);
+        }
+        Ok(Command(command))
+    }
+
+    pub fn as_str(&self) -> &str {
+        let len = self.0.iter().position(|b| *b == 0).unwrap_or(12);
+        // checked when created
+        str::from_utf8(&self.0[..len]).expect("command is ascii")
+    }
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl Serializable for Command {
+    fn serialize(&self, stream: &mut Stream) {
+        stream.append_slice(&self.0);
+    }
+
+    fn serialized_size(&self) -> usize {
+        12
+    }
+}
+
+impl Deserializable for Command {
+    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
+    where
+        T: io::Read,
+    {
+        let mut command = [0u8; 12];
+        reader.read_slice(&mut command)?;
+        Command::from_bytes(command).map_err(|_| io::Error::ReadMalformedData)
+    }
+}
+
+/// Header of every message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serializable, Deserializable)]
+pub struct MessageHeader {
+    pub magic: u32,
+    pub command: Command,
+    /// Size of the payload
+    pub length: u32,
+    /// First 4 bytes of the double sha256 of the payload
+    pub checksum: H32,
+}
+
+/// Messages of the peer to peer protocol.
+///
+/// Transactions and blocks are serialized with their witness, and compact blocks are
+/// the version 2 ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkMessage {
+    Version(VersionMessage),
+    Verack,
+    Ping(u64),
+    Pong(u64),
+    Inv(Vec<Inventory>),
+    GetData(Vec<Inventory>),
+    GetHeaders(GetHeadersMessage),
+    Headers(Vec<BlockHeader>),
+    SendHeaders,
+    Block(Block),
+    Tx(Transaction),
+    SendAddrV2,
+    AddrV2(Vec<AddrV2Message>),
+    SendCmpct(SendCmpct),
+    CmpctBlock(HeaderAndShortIds),
+    GetBlockTxn(BlockTransactionsRequest),
+    BlockTxn(BlockTransactions),
+    /// Minimum fee rate of the transactions to announce, in satoshis per 1000 vbytes
+    FeeFilter(u64),
+    WtxidRelay,
+    /// Message with a command unknown to this implementation
+    Unknown {
+        command: Command,
+        payload: Bytes,
+    },
+}
+
+impl NetworkMessage {
+    pub fn command(&self) -> Command {
+        let name = match self {
+            NetworkMessage::Version(_) => "version",
+            NetworkMessage::Verack => "verack",
+            NetworkMessage::Ping(_) => "ping",
+            NetworkMessage::Pong(_) => "pong",
+            NetworkMessage::Inv(_) => "inv",
+            NetworkMessage::GetData(_) => "getdata",
+            NetworkMessage::GetHeaders(_) => "getheaders",
+            NetworkMessage::Headers(_) => "headers",
+            NetworkMessage::SendHeaders => "sendheaders",
+            NetworkMessage::Block(_) => "block",
+            NetworkMessage::Tx(_) => "tx",
+            NetworkMessage::SendAddrV2 => "sendaddrv2",
+            NetworkMessage::AddrV2(_) => "addrv2",
+            NetworkMessage::SendCmpct(_) => "sendcmpct",
+            NetworkMessage::CmpctBlock(_) => "cmpctblock",
+            NetworkMessage::GetBlockTxn(_) => "getblocktxn",
+            NetworkMessage::BlockTxn(_) => "blocktxn",
+            NetworkMessage::FeeFilter(_) => "feefilter",
+            NetworkMessage::WtxidRelay => "wtxidrelay",
+            NetworkMessage::Unknown { command, .. } => return *command,
+        };
+        Command::new(name).expect("known commands are valid")
+    }
+
+    /// Returns the serialized payload of the message.
+    pub fn payload(&self) -> Bytes {
+        let mut stream = Stream::with_flags(SERIALIZE_TRANSACTION_WITNESS);
+        match self {
+            NetworkMessage::Version(version) => stream.append(version),
+            NetworkMessage::Verack
+            | NetworkMessage::SendHeaders
+            | NetworkMessage::SendAddrV2
+            | NetworkMessage::WtxidRelay => &mut stream,
+            NetworkMessage::Ping(nonce) | NetworkMessage::Pong(nonce) => stream.append(nonce),
+            NetworkMessage::Inv(inventory) | NetworkMessage::GetData(inventory) => {
+                stream.append_list(inventory)
+            }
+            NetworkMessage::GetHeaders(get_headers) => stream.append(get_headers),
+            NetworkMessage::Headers(headers) => {
+                stream.append(&CompactInteger::from(headers.len()));
+                for header in headers {
+                    // headers are sent as blocks without transactions
+                    stream.append(header).append(&CompactInteger::from(0u8));
+                }
+                &mut stream
+            }
+            NetworkMessage::Block(block) => stream.append(block),
+            NetworkMessage::Tx(tx) => stream.append(tx),
+            NetworkMessage::AddrV2(addrs) => stream.append_list(addrs),
+            NetworkMessage::SendCmpct(send_cmpct) => stream.append(send_cmpct),
+            NetworkMessage::CmpctBlock(block) => stream.append(block),
+            NetworkMessage::GetBlockTxn(request) => stream.append(request),
+            NetworkMessage::BlockTxn(transactions) => stream.append(transactions),
+            NetworkMessage::FeeFilter(fee_rate) => stream.append(fee_rate),
+            NetworkMessage::Unknown { payload, .. } => stream.append_slice(payload),
+        };
+        stream.out()
+    }
+
+    /// Parses the payload of a message with the command. The whole payload must be read.
+    pub fn from_payload(command: &Command, payload: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(payload);
+        let message = match command.as_str() {
+            "version" => NetworkMessage::Version(reader.read()?),
+            "verack" => NetworkMessage::Verack,
+            "ping" => NetworkMessage::Ping(reader.read()?),
+            "pong" => NetworkMessage::Pong(reader.read()?),
+            "inv" => NetworkMessage::Inv(reader.read_list_max(MAX_INV_SIZE)?),
+            "getdata" => NetworkMessage::GetData(reader.read_list_max(MAX_INV_SIZE)?),
+            "getheaders" => NetworkMessage::GetHeaders(reader.read()?),
+            "headers" => NetworkMessage::Headers(read_headers(&mut reader)?),
+            "sendheaders" => NetworkMessage::SendHeaders,
+            "block" => NetworkMessage::Block(reader.read()?),
+            "tx" => NetworkMessage::Tx(reader.read()?),
+            "sendaddrv2" => NetworkMessage::SendAddrV2,
+            "addrv2" => NetworkMessage::AddrV2(reader.read_list_max(MAX_ADDR_SIZE)?),
+            "sendcmpct" => NetworkMessage::SendCmpct(reader.read()?),
+            "cmpctblock" => NetworkMessage::CmpctBlock(reader.read()?),
+            "getblocktxn" => NetworkMessage::GetBlockTxn(reader.read()?),
+            "blocktxn" => NetworkMessage::BlockTxn(reader.read()?),
+            "feefilter" => NetworkMessage::FeeFilter(reader.read()?),
+            "wtxidrelay" => NetworkMessage::WtxidRelay,
+            _ => {
+                return Ok(NetworkMessage::Unknown {
+                    command: *command,
+                    payload: payload.into(),
+                })
+            }
+        };
+
+        if reader.is_finished() {
+            Ok(message)
+        } else {
+            Err(Error::Io(io::Error::UnreadData))
+        }
+    }
+}
+
+fn read_headers<T: io::Read>(reader: &mut Reader<T>) -> Result<Vec<BlockHeader>, io::Error> {
+    let len: usize = reader.read::<CompactInteger>()?.into();
+    if len > MAX_HEADERS_RESULTS {
+        return Err(io::Error::ReadMalformedData);
+    }
+    let mut headers = Vec::with_capacity(len);
+    for _ in 0..len {
+        headers.push(reader.read()?);
+        if u64::from(reader.read::<CompactInteger>()?) != 0 {
+            return Err(io::Error::ReadMalformedData);
+        }
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AddrV2, InventoryType, NetAddress, Services};
+    use light_bitcoin_chain::{OutPoint, TransactionInput, TransactionOutput};
+    use light_bitcoin_primitives::H256;
+    use light_bitcoin_serialization::{deserialize, serialize};
+
+    fn roundtrip(message: NetworkMessage) {
+        let payload = message.payload();
+        assert_eq!(
+            NetworkMessage::from_payload(&message.command(), &payload).unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn test_command() {
+        let command = Command::new("getheaders").unwrap();
+        assert_eq!(
+            serialize(&command),
+            "676574686561646572730000".parse().unwrap()
+        );
+        assert_eq!(command.as_str(), "getheaders");
+        assert_eq!(Command::new("toolongcommand"), Err(Error::InvalidCommand));
+        assert_eq!(Command::new("a b"), Err(Error::InvalidCommand));
+        assert_eq!(Command::new(""), Err(Error::InvalidCommand));
+
+        let padded: &[u8] = b"ping\0\0\0\0\0\0\0\0";
+        assert_eq!(deserialize::<_, Command>(padded).unwrap().as_str(), "ping");
+        let garbage: &[u8] = b"ping\0\0\0\0\0\0\0x";
+        assert!(deserialize::<_, Command>(garbage).is_err());
+        let empty: &[u8] = &[0u8; 12];
+        assert!(deserialize::<_, Command>(empty).is_err());
+    }
+
+    #[test]
+    fn test_inventory_type() {
+        for value in [0, 1, 5, 7, 0x4000_0001, 0x4000_0002, 0x4000_0003] {
+            assert_eq!(u32::from(InventoryType::from(value)), value);
+        }
+        assert_eq!(InventoryType::from(1), InventoryType::Tx);
+        match InventoryType::from(7) {
+            InventoryType::Unknown(unknown) => assert_eq!(unknown.value(), 7),
+            inv_type => panic!("unexpected {:?}", inv_type),
+        }
+    }
+
+    #[test]
+    fn test_version_message() {
+        // version message of a bitcoin core node to a regtest peer
+        let payload: Bytes = concat!(
+            "7f1101000904000000000000a2c6ce5f000000000000000000000000",
+            "00000000000000000000ffff7f000001480c09040000000000000000",
+            "000000000000000000000000000000002c0d3d8a6b0c3df5102f5361",
+            "746f7368693a302e32312e302f6600000001"
+        )
+        .parse()
+        .unwrap();
+        let command = Command::new("version").unwrap();
+        let message = NetworkMessage::from_payload(&command, &payload).unwrap();
+        let version = match &message {
+            NetworkMessage::Version(version) => version.clone(),
+            _ => panic!("expected a version message"),
+        };
+        assert_eq!(version.version, 70015);
+        assert_eq!(
+            version.services,
+            Services::NETWORK | Services::WITNESS | Services::NETWORK_LIMITED
+        );
+        assert_eq!(version.receiver.port, 18444);
+        assert_eq!(version.receiver.ip[10..], [0xff, 0xff, 127, 0, 0, 1]);
+        assert_eq!(version.user_agent, "/Satoshi:0.21.0/");
+        assert_eq!(version.start_height, 102);
+        assert!(version.relay);
+        assert_eq!(message.payload(), payload);
+
+        // relay is optional
+        let payload = &payload[..payload.len() - 1];
+        match NetworkMessage::from_payload(&command, payload).unwrap() {
+            NetworkMessage::Version(version) => assert!(version.relay),
+            _ => panic!("expected a version message"),
+        }
+    }
+
+    #[test]
+    fn test_messages_roundtrip() {
+        let header: BlockHeader = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff7f2002000000".parse().unwrap();
+
+        roundtrip(NetworkMessage::Verack);
+        roundtrip(NetworkMessage::Ping(0x0102_0304_0506_0708));
+        roundtrip(NetworkMessage::Pong(42));
+        roundtrip(NetworkMessage::Inv(vec![
+            Inventory::new(InventoryType::WitnessBlock, header.hash()),
+            Inventory::new(InventoryType::WTx, H256::repeat_byte(1)),
+            Inventory::new(InventoryType::from(7), H256::repeat_byte(2)),
+        ]));
+        roundtrip(NetworkMessage::GetData(vec![Inventory::new(
+            InventoryType::WitnessTx,
+            H256::repeat_byte(3),
+        )]));
+        roundtrip(NetworkMessage::GetHeaders(GetHeadersMessage {
+            version: 70016,
+            block_locator_hashes: vec![header.hash()],
+            hash_stop: H256::default(),
+        }));
+        roundtrip(NetworkMessage::Headers(vec![header, header]));
+        roundtrip(NetworkMessage::SendCmpct(SendCmpct {
+            announce: true,
+            version: 2,
+        }));
+        roundtrip(NetworkMessage::FeeFilter(1000));
+        roundtrip(NetworkMessage::WtxidRelay);
+        roundtrip(NetworkMessage::SendAddrV2);
+        roundtrip(NetworkMessage::Version(VersionMessage {
+            version: 70016,
+            services: Services::WITNESS,
+            timestamp: 1_700_000_000,
+            receiver: NetAddress::default(),
+            sender: NetAddress::new(Services::WITNESS, [1; 16], 8333),
+            nonce: 7,
+            user_agent: "/light-bitcoin/".into(),
+            start_height: 0,
+            relay: false,
+        }));
+        roundtrip(NetworkMessage::Unknown {
+            command: Command::new("mempool").unwrap(),
+            payload: vec![1, 2, 3].into(),
+        });
+
+        let headers = NetworkMessage::Headers(vec![]).payload();
+        assert_eq!(headers, vec![0].into());
+        let extra = vec![0, 1].into_iter().collect::<Vec<u8>>();
+        assert_eq!(
+            NetworkMessage::from_payload(&Command::new("verack").unwrap(), &extra),
+            Err(Error::Io(io::Error::UnreadData))
+        );
+    }
+
+    #[test]
+    fn test_block_and_tx_with_witness() {
+        let tx = Transaction {
+            version: 2,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    txid: H256::repeat_byte(1),
+                    index: 0,
+                },
+                script_sig: Bytes::new(),
+                sequence: 0xffff_fffd,
+                script_witness: vec![vec![2; 72].into(), vec![3; 33].into()],
+            }],
+            outputs: vec![TransactionOutput {
+                value: 50_000,
+                script_pubkey: "0014a5a8b3e2c1a2b3d4e5f60718293a4b5c6d7e8f90"
+                    .parse()
+                    .unwrap(),
+            }],
+            lock_time: 0,
+        };
+        assert!(tx.has_witness());
+        roundtrip(NetworkMessage::Tx(tx.clone()));
+
+        let block = Block::new(BlockHeader::default(), vec![tx]);
+        let message = NetworkMessage::Block(block);
+        roundtrip(message.clone());
+        // the witness is sent
+        assert_eq!(
+            message.payload(),
+            light_bitcoin_serialization::serialize_with_flags(
+                &match message {
+                    NetworkMessage::Block(block) => block,
+                    _ => unreachable!(),
+                },
+                SERIALIZE_TRANSACTION_WITNESS
+            )
+        );
+    }
+
+    // test vectors from bitcoin core's src/test/net_tests.cpp
+    #[test]
+    fn test_addrv2() {
+        for (addr, expected) in [
+            (AddrV2::Ipv4([1, 2, 3, 4]), "010401020304"),
+            (
+                AddrV2::Ipv6([
+                    0x1a, 0x1b, 0x2a, 0x2b, 0x3a, 0x3b, 0x4a, 0x4b, 0x5a, 0x5b, 0x6a, 0x6b, 0x7a,
+                    0x7b, 0x8a, 0x8b,
+                ]),
+                "02101a1b2a2b3a3b4a4b5a5b6a6b7a7b8a8b",
+            ),
+            (
+                AddrV2::TorV3([0x53; 32]),
+                "04205353535353535353535353535353535353535353535353535353535353535353",
+            ),
+            (AddrV2::Unknown(0xaa, vec![1, 2].into()), "aa020102"),
+        ] {
+            let serialized = serialize(&addr);
+            assert_eq!(serialized, expected.parse().unwrap());
+            assert_eq!(deserialize::<_, AddrV2>(serialized.as_ref()).unwrap(), addr);
+        }
+
+        // known networks must have their address length
+        let invalid: Bytes = "0105010203040506".parse().unwrap();
+        assert!(deserialize::<_, AddrV2>(invalid.as_ref()).is_err());
+
+        let message = AddrV2Message {
+            time: 0x5f00_0000,
+            services: Services::NETWORK | Services::WITNESS,
+            addr: AddrV2::Ipv4([127, 0, 0, 1]),
+            port: 18444,
+        };
+        assert_eq!(
+            serialize(&message),
+            "0000005f0901047f000001480c".parse().unwrap()
+        );
+        roundtrip(NetworkMessage::AddrV2(vec![message]));
+    }
+}
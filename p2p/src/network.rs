@@ -0,0 +1,41 @@
+/// Bitcoin networks, identified by the magic at the start of their messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    /// The default signet
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    /// Returns the magic of the network, as a little endian integer.
+    pub fn magic(&self) -> u32 {
+        match *self {
+            Network::Mainnet => 0xd9b4_bef9,
+            Network::Testnet => 0x0709_110b,
+            Network::Signet => 0x40cf_030a,
+            Network::Regtest => 0xdab5_bffa,
+        }
+    }
+
+    pub fn from_magic(magic: u32) -> Option<Self> {
+        [
+            Network::Mainnet,
+            Network::Testnet,
+            Network::Signet,
+            Network::Regtest,
+        ]
+        .into_iter()
+        .find(|network| network.magic() == magic)
+    }
+
+    pub fn default_port(&self) -> u16 {
+        match *self {
+            Network::Mainnet => 8333,
+            Network::Testnet => 18333,
+            Network::Signet => 38333,
+            Network::Regtest => 18444,
+        }
+    }
+}
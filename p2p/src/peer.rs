@@ -0,0 +1,947 @@
+//! Connection to a peer, from the version handshake to the sync of its headers.
+//!
+//! The state machine does no IO and reads no clock: the bytes received are given to
+//! `receive`, the bytes to send are taken from `poll_transmit`, and the current time is
+//! passed in, so that it can run on any transport and be tested against a fake peer.
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+
+use light_bitcoin_chain::{BlockHeader, IndexedBlockHeader};
+use light_bitcoin_primitives::{Bytes, H256};
+use light_bitcoin_verification::{BestChainUpdate, HeaderChain, HeaderImport, HeaderStore};
+
+use crate::codec::{serialize_message, MessageDecoder};
+use crate::error::Error;
+use crate::message::{Command, NetworkMessage, MAX_HEADERS_RESULTS};
+use crate::network::Network;
+use crate::types::{
+    GetHeadersMessage, InventoryType, NetAddress, SendCmpct, Services, VersionMessage,
+};
+
+/// Protocol version of the messages sent
+pub const PROTOCOL_VERSION: u32 = 70016;
+/// Lowest protocol version of a peer, the first one with `getheaders`
+pub const MIN_PEER_PROTOCOL_VERSION: u32 = 31800;
+/// First protocol version with `sendheaders`
+pub const SENDHEADERS_VERSION: u32 = 70012;
+/// First protocol version with `wtxidrelay`
+pub const WTXID_RELAY_VERSION: u32 = 70016;
+/// Number of headers not connecting to the chain a peer can announce in a row
+pub const MAX_UNCONNECTING_HEADERS: u32 = 10;
+
+/// Settings of the local node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerConfig {
+    pub magic: u32,
+    pub services: Services,
+    pub user_agent: String,
+    /// Whether the peer should announce transactions
+    pub relay: bool,
+    /// Services the peer must advertise
+    pub required_services: Services,
+    /// Seconds to complete the handshake
+    pub handshake_timeout: u32,
+    /// Seconds to answer a `getheaders`
+    pub stall_timeout: u32,
+}
+
+impl PeerConfig {
+    pub fn new(network: Network) -> Self {
+        PeerConfig {
+            magic: network.magic(),
+            services: Services::NONE,
+            user_agent: "/light-bitcoin/".into(),
+            relay: false,
+            required_services: Services::NETWORK | Services::WITNESS,
+            handshake_timeout: 60,
+            stall_timeout: 120,
+        }
+    }
+}
+
+/// Features announced by the peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PeerFeatures {
+    /// New blocks are announced with `headers` instead of `inv`
+    pub send_headers: bool,
+    /// Transactions are announced by wtxid
+    pub wtxid_relay: bool,
+    /// Addresses are sent with `addrv2`
+    pub addr_v2: bool,
+    /// Highest compact block version supported
+    pub compact_blocks: Option<SendCmpct>,
+    /// Minimum fee rate of the transactions to announce, in satoshis per 1000 vbytes
+    pub fee_filter: Option<u64>,
+}
+
+/// Reason to close the connection
+#[derive(Debug, PartialEq)]
+pub enum DisconnectReason {
+    /// The bytes received can not be decoded
+    InvalidMessage(Error),
+    /// The message is not expected at this point of the handshake
+    UnexpectedMessage(Command),
+    /// The version nonce is the one sent, the connection is to ourselves
+    SelfConnection,
+    /// The protocol version of the peer is below `MIN_PEER_PROTOCOL_VERSION`
+    ObsoleteVersion(u32),
+    /// The peer does not advertise the required services
+    MissingServices(Services),
+    /// A header is invalid
+    InvalidHeader(light_bitcoin_verification::Error),
+    /// The headers of a `headers` message do not follow each other
+    NonContinuousHeaders,
+    /// The peer keeps announcing headers not connecting to the chain
+    TooManyUnconnectingHeaders,
+    /// The handshake did not complete in time
+    HandshakeTimeout,
+    /// The peer did not answer a `getheaders` in time
+    Stalled,
+}
+
+/// Events of the connection, for the application
+#[derive(Debug, PartialEq)]
+pub enum PeerEvent {
+    /// The handshake is complete
+    Connected,
+    /// Headers received from the peer changed the best chain
+    BestChain(BestChainUpdate),
+    /// All the headers of the peer are received
+    Synced,
+    /// A message not handled by the state machine
+    Message(NetworkMessage),
+    /// The connection should be closed
+    Disconnected(DisconnectReason),
+}
+
+/// Returns the block locator of the best chain, as built by bitcoin core: the hashes of
+/// the last headers one by one, then going back with a doubling step, down to the first
+/// header of the chain.
+pub fn block_locator<S: HeaderStore>(chain: &HeaderChain<S>) -> Vec<H256> {
+    let best = chain.best();
+    // the heights of the best chain are contiguous from its first header
+    let (mut low, mut high) = (0, best.height);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if chain.header_at(middle).is_some() {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    let first = low;
+
+    let mut hashes = Vec::new();
+    let mut height = best.height;
+    let mut step = 1;
+    while let Some(entry) = chain.header_at(height) {
+        hashes.push(entry.header.hash);
+        if height == first {
+            break;
+        }
+        height = height.saturating_sub(step).max(first);
+        if hashes.len() > 10 {
+            step *= 2;
+        }
+    }
+    hashes
+}
+
+/// A `getheaders` waiting for an answer
+struct HeadersRequest {
+    sent_at: u32,
+    /// The answer starts after one of these hashes
+    locator: Vec<H256>,
+}
+
+/// State machine of a connection to a peer, syncing its headers into the chain.
+pub struct Peer<S> {
+    config: PeerConfig,
+    chain: HeaderChain<S>,
+    nonce: u64,
+    created_at: u32,
+    decoder: MessageDecoder,
+    transmit: VecDeque<Bytes>,
+    events: VecDeque<PeerEvent>,
+    version_sent: bool,
+    remote: Option<VersionMessage>,
+    verack_received: bool,
+    features: PeerFeatures,
+    headers_request: Option<HeadersRequest>,
+    unconnecting_headers: u32,
+    synced: bool,
+    disconnected: bool,
+}
+
+impl<S: HeaderStore> Peer<S> {
+    /// Creates the state machine of a new connection at time `now`. `nonce` should be
+    /// random, it detects connections to ourselves.
+    pub fn new(config: PeerConfig, chain: HeaderChain<S>, nonce: u64, now: u32) -> Self {
+        Peer {
+            decoder: MessageDecoder::new(config.magic),
+            config,
+            chain,
+            nonce,
+            created_at: now,
+            transmit: VecDeque::new(),
+            events: VecDeque::new(),
+            version_sent: false,
+            remote: None,
+            verack_received: false,
+            features: PeerFeatures::default(),
+            headers_request: None,
+            unconnecting_headers: 0,
+            synced: false,
+            disconnected: false,
+        }
+    }
+
+    /// Starts the handshake of an outbound connection. Inbound connections wait for the
+    /// `version` of the peer.
+    pub fn connect(&mut self, now: u32) {
+        if !self.version_sent {
+            self.send_version(now);
+        }
+    }
+
+    pub fn chain(&self) -> &HeaderChain<S> {
+        &self.chain
+    }
+
+    pub fn into_chain(self) -> HeaderChain<S> {
+        self.chain
+    }
+
+    /// The `version` message of the peer
+    pub fn remote_version(&self) -> Option<&VersionMessage> {
+        self.remote.as_ref()
+    }
+
+    pub fn features(&self) -> &PeerFeatures {
+        &self.features
+    }
+
+    /// Returns true once `version` and `verack` are exchanged both ways.
+    pub fn is_connected(&self) -> bool {
+        self.verack_received && !self.disconnected
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    /// Handles bytes received from the peer, in chunks of any size.
+    pub fn receive(&mut self, data: &[u8], now: u32) {
+        if self.disconnected {
+            return;
+        }
+        self.decoder.feed(data);
+        loop {
+            match self.decoder.decode() {
+                Ok(Some(message)) => self.handle_message(message, now),
+                Ok(None) => break,
+                // the message is dropped, as bitcoin core does
+                Err(Error::InvalidChecksum) => continue,
+                Err(err) => return self.disconnect(DisconnectReason::InvalidMessage(err)),
+            }
+            if self.disconnected {
+                break;
+            }
+        }
+    }
+
+    /// Checks the timeouts at time `now`. Should be called periodically.
+    pub fn tick(&mut self, now: u32) {
+        if self.disconnected {
+            return;
+        }
+        if !self.verack_received
+            && now
+                >= self
+                    .created_at
+                    .saturating_add(self.config.handshake_timeout)
+        {
+            self.disconnect(DisconnectReason::HandshakeTimeout);
+        } else if self
+            .headers_request
+            .as_ref()
+            .is_some_and(|request| now >= request.sent_at.saturating_add(self.config.stall_timeout))
+        {
+            self.disconnect(DisconnectReason::Stalled);
+        }
+    }
+
+    /// Queues a message to the peer.
+    pub fn send(&mut self, message: NetworkMessage) {
+        if !self.disconnected {
+            self.transmit
+                .push_back(serialize_message(self.config.magic, &message));
+        }
+    }
+
+    /// Returns the next bytes to send to the peer.
+    pub fn poll_transmit(&mut self) -> Option<Bytes> {
+        self.transmit.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<PeerEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn handle_message(&mut self, message: NetworkMessage, now: u32) {
+        if self.disconnected {
+            return;
+        }
+        match message {
+            NetworkMessage::Version(version) => self.on_version(version, now),
+            NetworkMessage::Verack => self.on_verack(now),
+            message if self.remote.is_none() => {
+                self.disconnect(DisconnectReason::UnexpectedMessage(message.command()))
+            }
+            // feature negotiation is only allowed between version and verack
+            NetworkMessage::WtxidRelay | NetworkMessage::SendAddrV2 if self.verack_received => {
+                self.disconnect(DisconnectReason::UnexpectedMessage(message.command()))
+            }
+            NetworkMessage::WtxidRelay => {
+                self.features.wtxid_relay = self.negotiated_version() >= WTXID_RELAY_VERSION
+            }
+            NetworkMessage::SendAddrV2 => self.features.addr_v2 = true,
+            // other messages are ignored until the handshake is complete
+            _ if !self.verack_received => {}
+            NetworkMessage::Ping(nonce) => self.send(NetworkMessage::Pong(nonce)),
+            NetworkMessage::Pong(_) => {}
+            NetworkMessage::SendHeaders => self.features.send_headers = true,
+            NetworkMessage::SendCmpct(send_cmpct) => {
+                if self
+                    .features
+                    .compact_blocks
+                    .is_none_or(|current| send_cmpct.version > current.version)
+                {
+                    self.features.compact_blocks = Some(send_cmpct);
+                }
+            }
+            NetworkMessage::FeeFilter(fee_rate) => self.features.fee_filter = Some(fee_rate),
+            NetworkMessage::Headers(headers) => self.on_headers(headers, now),
+            NetworkMessage::Inv(inventory) => {
+                let announced = inventory.iter().any(|inv| {
+                    matches!(
+                        inv.inv_type,
+                        InventoryType::Block | InventoryType::WitnessBlock
+                    ) && self.chain.header(&inv.hash).is_none()
+                });
+                if announced && self.headers_request.is_none() {
+                    self.send_getheaders(None, now);
+                }
+                self.events
+                    .push_back(PeerEvent::Message(NetworkMessage::Inv(inventory)));
+            }
+            message => self.events.push_back(PeerEvent::Message(message)),
+        }
+    }
+
+    fn negotiated_version(&self) -> u32 {
+        self.remote.as_ref().map_or(PROTOCOL_VERSION, |remote| {
+            remote.version.min(PROTOCOL_VERSION)
+        })
+    }
+
+    fn send_version(&mut self, now: u32) {
+        let version = VersionMessage {
+            version: PROTOCOL_VERSION,
+            services: self.config.services,
+            timestamp: i64::from(now),
+            receiver: NetAddress::default(),
+            sender: NetAddress::new(self.config.services, [0; 16], 0),
+            nonce: self.nonce,
+            user_agent: self.config.user_agent.clone(),
+            start_height: self.chain.best().height as i32,
+            relay: self.config.relay,
+        };
+        self.send(NetworkMessage::Version(version));
+        self.version_sent = true;
+    }
+
+    fn on_version(&mut self, version: VersionMessage, now: u32) {
+        if self.remote.is_some() {
+            return self.disconnect(DisconnectReason::UnexpectedMessage(
+                NetworkMessage::Version(version).command(),
+            ));
+        }
+        if version.nonce == self.nonce {
+            return self.disconnect(DisconnectReason::SelfConnection);
+        }
+        if version.version < MIN_PEER_PROTOCOL_VERSION {
+            return self.disconnect(DisconnectReason::ObsoleteVersion(version.version));
+        }
+        if !version.services.has(self.config.required_services) {
+            return self.disconnect(DisconnectReason::MissingServices(version.services));
+        }
+
+        if !self.version_sent {
+            self.send_version(now);
+        }
+        self.remote = Some(version);
+        if self.negotiated_version() >= WTXID_RELAY_VERSION {
+            self.send(NetworkMessage::WtxidRelay);
+        }
+        self.send(NetworkMessage::SendAddrV2);
+        self.send(NetworkMessage::Verack);
+    }
+
+    fn on_verack(&mut self, now: u32) {
+        if self.remote.is_none() || self.verack_received {
+            return self.disconnect(DisconnectReason::UnexpectedMessage(
+                NetworkMessage::Verack.command(),
+            ));
+        }
+        self.verack_received = true;
+        if self.negotiated_version() >= SENDHEADERS_VERSION {
+            self.send(NetworkMessage::SendHeaders);
+        }
+        self.events.push_back(PeerEvent::Connected);
+        self.send_getheaders(None, now);
+    }
+
+    /// Requests the headers following the locator of the best chain, preceded by `last`
+    /// when continuing from the last header received.
+    fn send_getheaders(&mut self, last: Option<H256>, now: u32) {
+        let mut block_locator_hashes: Vec<H256> = last.into_iter().collect();
+        block_locator_hashes.extend(block_locator(&self.chain));
+        self.headers_request = Some(HeadersRequest {
+            sent_at: now,
+            locator: block_locator_hashes.clone(),
+        });
+        self.send(NetworkMessage::GetHeaders(GetHeadersMessage {
+            version: PROTOCOL_VERSION,
+            block_locator_hashes,
+            hash_stop: H256::zero(),
+        }));
+    }
+
+    /// Whether `headers` answer the `getheaders` waiting for an answer, rather than
+    /// announce new blocks.
+    fn is_headers_reply(&self, headers: &[BlockHeader]) -> bool {
+        self.headers_request.as_ref().is_some_and(|request| {
+            headers
+                .first()
+                .is_none_or(|first| request.locator.contains(&first.previous_header_hash))
+        })
+    }
+
+    fn on_headers(&mut self, headers: Vec<BlockHeader>, now: u32) {
+        // only an answer stops the stall timer, announcements do not
+        if self.is_headers_reply(&headers) {
+            self.headers_request = None;
+        }
+        let Some(first) = headers.first() else {
+            if self.headers_request.is_none() {
+                self.set_synced();
+            }
+            return;
+        };
+        if headers
+            .windows(2)
+            .any(|pair| pair[1].previous_header_hash != pair[0].hash())
+        {
+            return self.disconnect(DisconnectReason::NonContinuousHeaders);
+        }
+
+        // an announcement not connecting to the chain, the headers in between are requested
+        if self.chain.header(&first.previous_header_hash).is_none() {
+            self.unconnecting_headers += 1;
+            if self.unconnecting_headers > MAX_UNCONNECTING_HEADERS {
+                return self.disconnect(DisconnectReason::TooManyUnconnectingHeaders);
+            }
+            if self.headers_request.is_none() {
+                self.send_getheaders(None, now);
+            }
+            return;
+        }
+        self.unconnecting_headers = 0;
+
+        let count = headers.len();
+        let mut last = H256::zero();
+        for header in headers {
+            let header: IndexedBlockHeader = header.into();
+            last = header.hash;
+            match self.chain.import_header(header, now) {
+                Ok(HeaderImport::Connected(Some(update))) => {
+                    self.events.push_back(PeerEvent::BestChain(update))
+                }
+                Ok(_) => {}
+                Err(err) => return self.disconnect(DisconnectReason::InvalidHeader(err)),
+            }
+        }
+
+        // with a request still waiting, its answer continues the sync
+        if self.headers_request.is_none() {
+            if count == MAX_HEADERS_RESULTS {
+                self.send_getheaders(Some(last), now);
+            } else {
+                self.set_synced();
+            }
+        }
+    }
+
+    fn set_synced(&mut self) {
+        if !self.synced {
+            self.synced = true;
+            self.events.push_back(PeerEvent::Synced);
+        }
+    }
+
+    fn disconnect(&mut self, reason: DisconnectReason) {
+        self.disconnected = true;
+        self.headers_request = None;
+        self.events.push_back(PeerEvent::Disconnected(reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use light_bitcoin_verification::test_helpers::{chain, genesis, mine, mine_chain, START_TIME};
+    use light_bitcoin_verification::{
+        ConsensusParams, Error as VerificationError, MemoryHeaderStore,
+    };
+
+    const NONCE: u64 = 0x1234;
+
+    fn remote_version(version: u32, services: Services) -> VersionMessage {
+        VersionMessage {
+            version,
+            services,
+            timestamp: i64::from(START_TIME),
+            receiver: NetAddress::default(),
+            sender: NetAddress::default(),
+            nonce: 0x5678,
+            user_agent: "/Satoshi:26.0.0/".into(),
+            start_height: 0,
+            relay: true,
+        }
+    }
+
+    /// A peer serving the headers of its chain, over the wire encoding
+    struct FakePeer {
+        headers: Vec<IndexedBlockHeader>,
+        decoder: MessageDecoder,
+        received: Vec<NetworkMessage>,
+        /// Whether `getheaders` are answered
+        responsive: bool,
+    }
+
+    impl FakePeer {
+        fn new(headers: Vec<IndexedBlockHeader>) -> Self {
+            FakePeer {
+                headers,
+                decoder: MessageDecoder::new(Network::Regtest.magic()),
+                received: vec![],
+                responsive: true,
+            }
+        }
+
+        fn send(&self, peer: &mut Peer<MemoryHeaderStore>, message: NetworkMessage, now: u32) {
+            let bytes = serialize_message(Network::Regtest.magic(), &message);
+            // delivered in two parts, to go through the partial buffers of the decoder
+            let (head, tail) = bytes.split_at(bytes.len() / 2);
+            peer.receive(head, now);
+            peer.receive(tail, now);
+        }
+
+        fn respond(&mut self, message: &NetworkMessage) -> Vec<NetworkMessage> {
+            match message {
+                NetworkMessage::Version(_) => vec![
+                    NetworkMessage::Version(remote_version(
+                        PROTOCOL_VERSION,
+                        Services::NETWORK | Services::WITNESS,
+                    )),
+                    NetworkMessage::WtxidRelay,
+                    NetworkMessage::SendAddrV2,
+                    NetworkMessage::Verack,
+                ],
+                NetworkMessage::Verack => vec![
+                    NetworkMessage::SendHeaders,
+                    NetworkMessage::SendCmpct(SendCmpct {
+                        announce: false,
+                        version: 2,
+                    }),
+                    NetworkMessage::FeeFilter(1000),
+                ],
+                NetworkMessage::GetHeaders(get_headers) if self.responsive => {
+                    let start = get_headers
+                        .block_locator_hashes
+                        .iter()
+                        .find_map(|hash| self.headers.iter().position(|h| h.hash == *hash))
+                        .map_or(0, |index| index + 1);
+                    let headers = self.headers[start..]
+                        .iter()
+                        .take(MAX_HEADERS_RESULTS)
+                        .map(|header| header.raw)
+                        .collect();
+                    vec![NetworkMessage::Headers(headers)]
+                }
+                _ => vec![],
+            }
+        }
+
+        /// Exchanges messages until the peer has nothing left to send
+        fn run(&mut self, peer: &mut Peer<MemoryHeaderStore>, now: u32) {
+            while let Some(bytes) = peer.poll_transmit() {
+                self.decoder.feed(&bytes);
+                while let Some(message) = self.decoder.decode().unwrap() {
+                    for response in self.respond(&message) {
+                        self.send(peer, response, now);
+                    }
+                    self.received.push(message);
+                }
+            }
+        }
+    }
+
+    fn take_events(peer: &mut Peer<MemoryHeaderStore>) -> Vec<PeerEvent> {
+        core::iter::from_fn(|| peer.poll_event()).collect()
+    }
+
+    fn new_peer() -> Peer<MemoryHeaderStore> {
+        Peer::new(
+            PeerConfig::new(Network::Regtest),
+            chain(),
+            NONCE,
+            START_TIME,
+        )
+    }
+
+    #[test]
+    fn test_block_locator() {
+        let mut chain = chain();
+        let headers = mine_chain(&genesis(), 30, 1);
+        for header in &headers {
+            chain.import_header(*header, u32::MAX).unwrap();
+        }
+        let heights: Vec<u32> = block_locator(&chain)
+            .iter()
+            .map(|hash| chain.header(hash).unwrap().height)
+            .collect();
+        assert_eq!(
+            heights,
+            vec![30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19, 17, 13, 5, 0]
+        );
+
+        // a chain starting above the genesis ends its locator with its first header
+        let start = headers[24];
+        let chain = HeaderChain::new(
+            MemoryHeaderStore::default(),
+            ConsensusParams::regtest(),
+            start,
+            25,
+        );
+        assert_eq!(block_locator(&chain), vec![start.hash]);
+    }
+
+    #[test]
+    fn test_handshake_and_headers_sync() {
+        let headers = mine_chain(&genesis(), 2500, 1);
+        let now = headers.last().unwrap().raw.time;
+        let mut fake = FakePeer::new(headers.clone());
+        let mut peer = new_peer();
+        peer.connect(now);
+        fake.run(&mut peer, now);
+
+        let commands: Vec<Command> = fake.received[..5]
+            .iter()
+            .map(NetworkMessage::command)
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                "version",
+                "wtxidrelay",
+                "sendaddrv2",
+                "verack",
+                "sendheaders"
+            ]
+            .map(|name| Command::new(name).unwrap())
+        );
+        let get_headers = fake
+            .received
+            .iter()
+            .filter(|message| matches!(message, NetworkMessage::GetHeaders(_)))
+            .count();
+        assert_eq!(get_headers, 2);
+
+        assert!(peer.is_connected());
+        assert!(peer.is_synced());
+        assert_eq!(peer.chain().best().header, headers[2499]);
+        assert_eq!(
+            peer.features(),
+            &PeerFeatures {
+                send_headers: true,
+                wtxid_relay: true,
+                addr_v2: true,
+                compact_blocks: Some(SendCmpct {
+                    announce: false,
+                    version: 2
+                }),
+                fee_filter: Some(1000),
+            }
+        );
+
+        let events = take_events(&mut peer);
+        assert_eq!(events[0], PeerEvent::Connected);
+        assert_eq!(events.last(), Some(&PeerEvent::Synced));
+        let connected: Vec<H256> = events
+            .into_iter()
+            .filter_map(|event| match event {
+                PeerEvent::BestChain(update) => Some(update.connected),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert_eq!(
+            connected,
+            headers.iter().map(|header| header.hash).collect::<Vec<_>>()
+        );
+
+        // a new block is announced
+        let new = mine(headers[2499].hash, headers[2499].raw.time + 600, 1);
+        fake.send(&mut peer, NetworkMessage::Headers(vec![new.raw]), now);
+        assert_eq!(peer.chain().best().header, new);
+        assert_eq!(
+            take_events(&mut peer),
+            vec![PeerEvent::BestChain(BestChainUpdate {
+                disconnected: vec![],
+                connected: vec![new.hash],
+            })]
+        );
+
+        // pings are answered
+        fake.send(&mut peer, NetworkMessage::Ping(9), now);
+        fake.run(&mut peer, now);
+        assert_eq!(fake.received.last(), Some(&NetworkMessage::Pong(9)));
+    }
+
+    #[test]
+    fn test_unconnecting_headers() {
+        let headers = mine_chain(&genesis(), 20, 1);
+        let now = headers[19].raw.time;
+        let mut fake = FakePeer::new(headers[..10].to_vec());
+        let mut peer = new_peer();
+        peer.connect(now);
+        fake.run(&mut peer, now);
+        assert_eq!(peer.chain().best().height, 10);
+
+        // the announcement of header 20 requests the headers in between
+        fake.headers = headers.clone();
+        fake.send(
+            &mut peer,
+            NetworkMessage::Headers(vec![headers[19].raw]),
+            now,
+        );
+        fake.run(&mut peer, now);
+        assert_eq!(peer.chain().best().height, 20);
+
+        // a fork whose first header is never sent
+        let fork = mine_chain(&headers[0], 2, 2);
+        let announcement = NetworkMessage::Headers(vec![fork[1].raw]);
+        for _ in 0..MAX_UNCONNECTING_HEADERS {
+            fake.send(&mut peer, announcement.clone(), now);
+        }
+        assert!(!peer.is_disconnected());
+        fake.send(&mut peer, announcement, now);
+        assert_eq!(
+            take_events(&mut peer).pop(),
+            Some(PeerEvent::Disconnected(
+                DisconnectReason::TooManyUnconnectingHeaders
+            ))
+        );
+    }
+
+    #[test]
+    fn test_invalid_headers() {
+        let headers = mine_chain(&genesis(), 3, 1);
+        let now = headers[2].raw.time;
+
+        let mut fake = FakePeer::new(vec![]);
+        let mut peer = new_peer();
+        peer.connect(now);
+        fake.run(&mut peer, now);
+        fake.send(
+            &mut peer,
+            NetworkMessage::Headers(vec![headers[0].raw, headers[2].raw]),
+            now,
+        );
+        assert_eq!(
+            take_events(&mut peer).pop(),
+            Some(PeerEvent::Disconnected(
+                DisconnectReason::NonContinuousHeaders
+            ))
+        );
+
+        let mut peer = new_peer();
+        peer.connect(now);
+        fake.run(&mut peer, now);
+        let mut invalid = headers[0].raw;
+        invalid.time = START_TIME;
+        fake.send(&mut peer, NetworkMessage::Headers(vec![invalid]), now);
+        assert_eq!(
+            take_events(&mut peer).pop(),
+            Some(PeerEvent::Disconnected(DisconnectReason::InvalidHeader(
+                VerificationError::ProofOfWork
+            )))
+        );
+    }
+
+    #[test]
+    fn test_handshake_rejections() {
+        let cases = [
+            (
+                VersionMessage {
+                    nonce: NONCE,
+                    ..remote_version(PROTOCOL_VERSION, Services::NETWORK | Services::WITNESS)
+                },
+                DisconnectReason::SelfConnection,
+            ),
+            (
+                remote_version(31799, Services::NETWORK | Services::WITNESS),
+                DisconnectReason::ObsoleteVersion(31799),
+            ),
+            (
+                remote_version(PROTOCOL_VERSION, Services::NETWORK),
+                DisconnectReason::MissingServices(Services::NETWORK),
+            ),
+        ];
+        for (version, reason) in cases {
+            let mut peer = new_peer();
+            peer.handle_message(NetworkMessage::Version(version), START_TIME);
+            assert!(peer.is_disconnected());
+            assert_eq!(
+                take_events(&mut peer),
+                vec![PeerEvent::Disconnected(reason)]
+            );
+        }
+
+        // messages before the version
+        let mut peer = new_peer();
+        peer.handle_message(NetworkMessage::Ping(1), START_TIME);
+        assert_eq!(
+            take_events(&mut peer),
+            vec![PeerEvent::Disconnected(
+                DisconnectReason::UnexpectedMessage(Command::new("ping").unwrap())
+            )]
+        );
+
+        // bytes of another network
+        let mut peer = new_peer();
+        peer.receive(
+            &serialize_message(Network::Mainnet.magic(), &NetworkMessage::Verack),
+            START_TIME,
+        );
+        assert_eq!(
+            take_events(&mut peer),
+            vec![PeerEvent::Disconnected(DisconnectReason::InvalidMessage(
+                Error::InvalidMagic(Network::Mainnet.magic())
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_inbound_handshake_with_old_peer() {
+        let mut peer = new_peer();
+        peer.handle_message(
+            NetworkMessage::Version(remote_version(70011, Services::NETWORK | Services::WITNESS)),
+            START_TIME,
+        );
+        let mut decoder = MessageDecoder::new(Network::Regtest.magic());
+        while let Some(bytes) = peer.poll_transmit() {
+            decoder.feed(&bytes);
+        }
+        let mut commands = vec![];
+        while let Some(message) = decoder.decode().unwrap() {
+            commands.push(message.command());
+        }
+        // no wtxidrelay below its version
+        assert_eq!(
+            commands,
+            ["version", "sendaddrv2", "verack"].map(|name| Command::new(name).unwrap())
+        );
+
+        // the feature negotiation is over after verack
+        peer.handle_message(NetworkMessage::Verack, START_TIME);
+        peer.handle_message(NetworkMessage::WtxidRelay, START_TIME);
+        assert_eq!(
+            take_events(&mut peer),
+            vec![
+                PeerEvent::Connected,
+                PeerEvent::Disconnected(DisconnectReason::UnexpectedMessage(
+                    Command::new("wtxidrelay").unwrap()
+                ))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let mut peer = new_peer();
+        peer.connect(START_TIME);
+        peer.tick(START_TIME + 59);
+        assert!(!peer.is_disconnected());
+        peer.tick(START_TIME + 60);
+        assert_eq!(
+            take_events(&mut peer),
+            vec![PeerEvent::Disconnected(DisconnectReason::HandshakeTimeout)]
+        );
+
+        // the fake peer does not answer getheaders
+        let mut fake = FakePeer::new(vec![]);
+        fake.responsive = false;
+        let mut peer = new_peer();
+        peer.connect(START_TIME);
+        fake.run(&mut peer, START_TIME);
+        assert!(peer.is_connected());
+        peer.tick(START_TIME + 119);
+        assert!(!peer.is_disconnected());
+        peer.tick(START_TIME + 120);
+        assert_eq!(
+            take_events(&mut peer).pop(),
+            Some(PeerEvent::Disconnected(DisconnectReason::Stalled))
+        );
+
+        // announcements do not clear the stall timer, unlike answers
+        let headers = mine_chain(&genesis(), 2, 1);
+        let mut fake = FakePeer::new(vec![]);
+        fake.responsive = false;
+        let mut peer = new_peer();
+        peer.connect(START_TIME);
+        fake.run(&mut peer, START_TIME);
+        for now in [START_TIME + 60, START_TIME + 100] {
+            fake.send(
+                &mut peer,
+                NetworkMessage::Headers(vec![headers[1].raw]),
+                now,
+            );
+            // the headers in between are already requested
+            assert_eq!(peer.poll_transmit(), None);
+        }
+        peer.tick(START_TIME + 120);
+        assert_eq!(
+            take_events(&mut peer).pop(),
+            Some(PeerEvent::Disconnected(DisconnectReason::Stalled))
+        );
+
+        // an answer clears the stall timer
+        let mut fake = FakePeer::new(vec![]);
+        let mut peer = new_peer();
+        peer.connect(START_TIME);
+        fake.run(&mut peer, START_TIME);
+        peer.tick(START_TIME + 1000);
+        assert!(!peer.is_disconnected());
+        assert!(peer.is_synced());
+    }
+}
@@ -0,0 +1,421 @@
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+use core::ops;
+use light_bitcoin_primitives::{io, Bytes, H256};
+use light_bitcoin_serialization::{CompactInteger, Deserializable, Reader, Serializable, Stream};
+
+/// Maximum number of entries of an `inv` or `getdata` message
+pub const MAX_INV_SIZE: usize = 50_000;
+/// Maximum number of entries of an `addrv2` message
+pub const MAX_ADDR_SIZE: usize = 1_000;
+/// Maximum size of an address of an `addrv2` message
+pub const MAX_ADDRV2_SIZE: usize = 512;
+
+/// Service flags advertised by a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Services(pub u64);
+
+impl Services {
+    pub const NONE: Services = Services(0);
+    /// The node serves the full block chain
+    pub const NETWORK: Services = Services(1 << 0);
+    /// The node serves BIP 37 bloom filtered connections
+    pub const BLOOM: Services = Services(1 << 2);
+    /// The node serves blocks and transactions with their witness
+    pub const WITNESS: Services = Services(1 << 3);
+    /// The node serves BIP 157 compact block filters
+    pub const COMPACT_FILTERS: Services = Services(1 << 6);
+    /// The node serves the last 288 blocks only
+    pub const NETWORK_LIMITED: Services = Services(1 << 10);
+    /// The node supports the BIP 324 v2 transport
+    pub const P2P_V2: Services = Services(1 << 11);
+
+    /// Returns true if all the services of `other` are advertised.
+    pub fn has(&self, other: Services) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl ops::BitOr for Services {
+    type Output = Services;
+
+    fn bitor(self, other: Services) -> Services {
+        Services(self.0 | other.0)
+    }
+}
+
+impl ops::BitOrAssign for Services {
+    fn bitor_assign(&mut self, other: Services) {
+        self.0 |= other.0;
+    }
+}
+
+impl Serializable for Services {
+    fn serialize(&self, stream: &mut Stream) {
+        stream.append(&self.0);
+    }
+
+    fn serialized_size(&self) -> usize {
+        8
+    }
+}
+
+impl Deserializable for Services {
+    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
+    where
+        T: io::Read,
+    {
+        reader.read().map(Services)
+    }
+}
+
+fn read_port<T: io::Read>(reader: &mut Reader<T>) -> Result<u16, io::Error> {
+    let mut port = [0u8; 2];
+    reader.read_slice(&mut port)?;
+    Ok(u16::from_be_bytes(port))
+}
+
+/// Network address of a `version` message, with the port in big endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct NetAddress {
+    pub services: Services,
+    /// IPv6 address, or IPv4 mapped IPv6 address
+    pub ip: [u8; 16],
+    pub port: u16,
+}
+
+impl NetAddress {
+    pub fn new(services: Services, ip: [u8; 16], port: u16) -> Self {
+        NetAddress { services, ip, port }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::net::SocketAddr> for NetAddress {
+    fn from(addr: std::net::SocketAddr) -> Self {
+        let ip = match addr.ip() {
+            std::net::IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+            std::net::IpAddr::V6(ip) => ip,
+        };
+        NetAddress::new(Services::NONE, ip.octets(), addr.port())
+    }
+}
+
+impl Serializable for NetAddress {
+    fn serialize(&self, stream: &mut Stream) {
+        stream
+            .append(&self.services)
+            .append_slice(&self.ip)
+            .append_slice(&self.port.to_be_bytes());
+    }
+
+    fn serialized_size(&self) -> usize {
+        26
+    }
+}
+
+impl Deserializable for NetAddress {
+    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
+    where
+        T: io::Read,
+    {
+        let services = reader.read()?;
+        let mut ip = [0u8; 16];
+        reader.read_slice(&mut ip)?;
+        let port = read_port(reader)?;
+        Ok(NetAddress { services, ip, port })
+    }
+}
+
+/// The `version` message, opening the handshake
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionMessage {
+    pub version: u32,
+    pub services: Services,
+    pub timestamp: i64,
+    pub receiver: NetAddress,
+    pub sender: NetAddress,
+    pub nonce: u64,
+    pub user_agent: String,
+    pub start_height: i32,
+    /// Whether transactions should be announced before a `filterload`. Defaults to true
+    /// when missing.
+    pub relay: bool,
+}
+
+impl Serializable for VersionMessage {
+    fn serialize(&self, stream: &mut Stream) {
+        stream
+            .append(&self.version)
+            .append(&self.services)
+            .append(&self.timestamp)
+            .append(&self.receiver)
+            .append(&self.sender)
+            .append(&self.nonce)
+            .append(&self.user_agent)
+            .append(&self.start_height)
+            .append(&self.relay);
+    }
+}
+
+impl Deserializable for VersionMessage {
+    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
+    where
+        T: io::Read,
+    {
+        Ok(VersionMessage {
+            version: reader.read()?,
+            services: reader.read()?,
+            timestamp: reader.read()?,
+            receiver: reader.read()?,
+            sender: reader.read()?,
+            nonce: reader.read()?,
+            user_agent: reader.read()?,
+            start_height: reader.read()?,
+            relay: if reader.is_finished() {
+                true
+            } else {
+                reader.read()?
+            },
+        })
+    }
+}
+
+/// Type of an inventory entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InventoryType {
+    Error,
+    Tx,
+    Block,
+    FilteredBlock,
+    CompactBlock,
+    /// Transaction announced by wtxid, after `wtxidrelay`
+    WTx,
+    WitnessTx,
+    WitnessBlock,
+    Unknown(UnknownInventoryType),
+}
+
+/// A type of inventory entry without a variant of `InventoryType`, only created from
+/// the values of no other variant so that the types compare by value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UnknownInventoryType(u32);
+
+impl UnknownInventoryType {
+    pub fn value(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for InventoryType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => InventoryType::Error,
+            1 => InventoryType::Tx,
+            2 => InventoryType::Block,
+            3 => InventoryType::FilteredBlock,
+            4 => InventoryType::CompactBlock,
+            5 => InventoryType::WTx,
+            0x4000_0001 => InventoryType::WitnessTx,
+            0x4000_0002 => InventoryType::WitnessBlock,
+            value => InventoryType::Unknown(UnknownInventoryType(value)),
+        }
+    }
+}
+
+impl From<InventoryType> for u32 {
+    fn from(inv_type: InventoryType) -> Self {
+        match inv_type {
+            InventoryType::Error => 0,
+            InventoryType::Tx => 1,
+            InventoryType::Block => 2,
+            InventoryType::FilteredBlock => 3,
+            InventoryType::CompactBlock => 4,
+            InventoryType::WTx => 5,
+            InventoryType::WitnessTx => 0x4000_0001,
+            InventoryType::WitnessBlock => 0x4000_0002,
+            InventoryType::Unknown(unknown) => unknown.value(),
+        }
+    }
+}
+
+/// Entry of the `inv`, `getdata` and `notfound` messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Inventory {
+    pub inv_type: InventoryType,
+    pub hash: H256,
+}
+
+impl Inventory {
+    pub fn new(inv_type: InventoryType, hash: H256) -> Self {
+        Inventory { inv_type, hash }
+    }
+}
+
+impl Serializable for Inventory {
+    fn serialize(&self, stream: &mut Stream) {
+        stream.append(&u32::from(self.inv_type)).append(&self.hash);
+    }
+
+    fn serialized_size(&self) -> usize {
+        36
+    }
+}
+
+impl Deserializable for Inventory {
+    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
+    where
+        T: io::Read,
+    {
+        Ok(Inventory {
+            inv_type: reader.read::<u32>()?.into(),
+            hash: reader.read()?,
+        })
+    }
+}
+
+/// The `getheaders` message
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serializable, Deserializable)]
+pub struct GetHeadersMessage {
+    pub version: u32,
+    /// Hashes of the best chain of the sender, from the tip back to the genesis
+    pub block_locator_hashes: Vec<H256>,
+    /// Hash of the last header to return, or zero for as many as possible
+    pub hash_stop: H256,
+}
+
+/// The `sendcmpct` message of BIP 152
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Serializable, Deserializable)]
+pub struct SendCmpct {
+    /// Whether new blocks should be announced with `cmpctblock`
+    pub announce: bool,
+    pub version: u64,
+}
+
+/// Address of an `addrv2` message, by BIP 155 network id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AddrV2 {
+    Ipv4([u8; 4]),
+    Ipv6([u8; 16]),
+    /// Deprecated Tor v2 onion address
+    TorV2([u8; 10]),
+    TorV3([u8; 32]),
+    I2p([u8; 32]),
+    Cjdns([u8; 16]),
+    /// Address of a network unknown to this implementation
+    Unknown(u8, Bytes),
+}
+
+impl AddrV2 {
+    fn network_id(&self) -> u8 {
+        match *self {
+            AddrV2::Ipv4(_) => 1,
+            AddrV2::Ipv6(_) => 2,
+            AddrV2::TorV2(_) => 3,
+            AddrV2::TorV3(_) => 4,
+            AddrV2::I2p(_) => 5,
+            AddrV2::Cjdns(_) => 6,
+            AddrV2::Unknown(network_id, _) => network_id,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            AddrV2::Ipv4(addr) => addr,
+            AddrV2::Ipv6(addr) => addr,
+            AddrV2::TorV2(addr) => addr,
+            AddrV2::TorV3(addr) => addr,
+            AddrV2::I2p(addr) => addr,
+            AddrV2::Cjdns(addr) => addr,
+            AddrV2::Unknown(_, addr) => addr,
+        }
+    }
+}
+
+impl Serializable for AddrV2 {
+    fn serialize(&self, stream: &mut Stream) {
+        let addr = self.as_bytes();
+        stream
+            .append(&self.network_id())
+            .append(&CompactInteger::from(addr.len()))
+            .append_slice(addr);
+    }
+
+    fn serialized_size(&self) -> usize {
+        let len = self.as_bytes().len();
+        1 + CompactInteger::from(len).serialized_size() + len
+    }
+}
+
+impl Deserializable for AddrV2 {
+    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
+    where
+        T: io::Read,
+    {
+        fn fixed<const N: usize>(addr: &[u8]) -> Result<[u8; N], io::Error> {
+            addr.try_into().map_err(|_| io::Error::ReadMalformedData)
+        }
+
+        let network_id: u8 = reader.read()?;
+        let len: usize = reader.read::<CompactInteger>()?.into();
+        if len > MAX_ADDRV2_SIZE {
+            return Err(io::Error::ReadMalformedData);
+        }
+        let mut addr = Bytes::new_with_len(len);
+        reader.read_slice(&mut addr)?;
+
+        Ok(match network_id {
+            1 => AddrV2::Ipv4(fixed(&addr)?),
+            2 => AddrV2::Ipv6(fixed(&addr)?),
+            3 => AddrV2::TorV2(fixed(&addr)?),
+            4 => AddrV2::TorV3(fixed(&addr)?),
+            5 => AddrV2::I2p(fixed(&addr)?),
+            6 => AddrV2::Cjdns(fixed(&addr)?),
+            network_id => AddrV2::Unknown(network_id, addr),
+        })
+    }
+}
+
+/// Entry of the `addrv2` message
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AddrV2Message {
+    /// Time the node was last seen
+    pub time: u32,
+    /// Services of the node, serialized as a compact size
+    pub services: Services,
+    pub addr: AddrV2,
+    pub port: u16,
+}
+
+impl Serializable for AddrV2Message {
+    fn serialize(&self, stream: &mut Stream) {
+        stream
+            .append(&self.time)
+            .append(&CompactInteger::from(self.services.0))
+            .append(&self.addr)
+            .append_slice(&self.port.to_be_bytes());
+    }
+
+    fn serialized_size(&self) -> usize {
+        4 + CompactInteger::from(self.services.0).serialized_size()
+            + self.addr.serialized_size()
+            + 2
+    }
+}
+
+impl Deserializable for AddrV2Message {
+    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
+    where
+        T: io::Read,
+    {
+        Ok(AddrV2Message {
+            time: reader.read()?,
+            services: Services(reader.read::<CompactInteger>()?.into()),
+            addr: reader.read()?,
+            port: read_port(reader)?,
+        })
+    }
+}
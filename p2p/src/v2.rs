@@ -0,0 +1,724 @@
+//! The encrypted v2 transport of [BIP324].
+//!
+//! Both sides start by sending an ElligatorSwift encoded public key followed by
+//! random garbage. Once the key of the other side is received, the shared secret
+//! gives the keys of the packet ciphers and the terminators of the garbage of
+//! each side. Every message is then sent as a packet: its length encrypted with
+//! `FSChaCha20`, and a header byte and the contents encrypted and authenticated
+//! with `FSChaCha20Poly1305`.
+//!
+//! Like [`MessageDecoder`](crate::MessageDecoder), the transport does no IO: the
+//! bytes received are given to `feed` and decoded with `decode`, and the bytes to
+//! send are taken from `poll_transmit`.
+//!
+//! [BIP324]: https://github.com/bitcoin/bips/blob/master/bip-0324.mediawiki
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::mem;
+
+use light_bitcoin_crypto::{hkdf_sha256, FSChaCha20, FSChaCha20Poly1305, TAG_SIZE};
+use light_bitcoin_keys::{ElligatorSwift, PrivateKey};
+use light_bitcoin_primitives::Bytes;
+use light_bitcoin_serialization::{serialize, Reader};
+use zeroize::Zeroizing;
+
+use crate::error::Error;
+use crate::message::{Command, NetworkMessage, MAX_MESSAGE_PAYLOAD_SIZE};
+
+/// Size of an encoded public key
+pub const ELLSWIFT_SIZE: usize = 64;
+/// Maximum size of the garbage sent after the public key
+pub const MAX_GARBAGE_SIZE: usize = 4095;
+/// Size of the terminator ending the garbage
+pub const GARBAGE_TERMINATOR_SIZE: usize = 16;
+/// Size of the encrypted length of a packet
+pub const LENGTH_SIZE: usize = 3;
+/// Size of the header byte of a packet
+pub const HEADER_SIZE: usize = 1;
+/// Bytes added by the encryption to the contents of a packet
+pub const PACKET_OVERHEAD: usize = LENGTH_SIZE + HEADER_SIZE + TAG_SIZE;
+
+/// Bit of the header byte marking a decoy packet to ignore
+const IGNORE_BIT: u8 = 0x80;
+
+/// Longest contents of a packet: a message type byte, a command and a payload
+const MAX_CONTENTS_SIZE: usize = 1 + 12 + MAX_MESSAGE_PAYLOAD_SIZE;
+
+/// Commands of the one byte message types, the message type 1 is the first one
+const SHORT_IDS: [&str; 28] = [
+    "addr",
+    "block",
+    "blocktxn",
+    "cmpctblock",
+    "feefilter",
+    "filteradd",
+    "filterclear",
+    "filterload",
+    "getblocks",
+    "getblocktxn",
+    "getdata",
+    "getheaders",
+    "headers",
+    "inv",
+    "mempool",
+    "merkleblock",
+    "notfound",
+    "ping",
+    "pong",
+    "sendcmpct",
+    "tx",
+    "getcfilters",
+    "cfilter",
+    "getcfheaders",
+    "cfheaders",
+    "getcfcheckpt",
+    "cfcheckpt",
+    "addrv2",
+];
+
+/// Returns the one byte message type of the command, if it has one.
+pub fn short_id(command: &Command) -> Option<u8> {
+    SHORT_IDS
+        .iter()
+        .position(|name| *name == command.as_str())
+        .map(|index| index as u8 + 1)
+}
+
+/// Encodes the message type and the payload of the message as the contents of a packet.
+pub fn encode_contents(message: &NetworkMessage) -> Vec<u8> {
+    let command = message.command();
+    let mut contents = match short_id(&command) {
+        Some(id) => vec![id],
+        None => {
+            let mut contents = vec![0];
+            contents.extend_from_slice(&serialize(&command));
+            contents
+        }
+    };
+    contents.extend_from_slice(&message.payload());
+    contents
+}
+
+/// Decodes the contents of a packet, or returns `None` for a message type without
+/// a command, which is reserved for future messages and ignored.
+pub fn decode_contents(contents: &[u8]) -> Result<Option<NetworkMessage>, Error> {
+    let (message_type, rest) = contents.split_first().ok_or(Error::InvalidCommand)?;
+    let (command, payload) = match message_type {
+        0 => {
+            if rest.len() < 12 {
+                return Err(Error::InvalidCommand);
+            }
+            let command: Command = Reader::new(&rest[..12])
+                .read()
+                .map_err(|_| Error::InvalidCommand)?;
+            (command, &rest[12..])
+        }
+        id => match SHORT_IDS.get(*id as usize - 1) {
+            Some(name) => (Command::new(name).expect("short ids are valid"), rest),
+            None => return Ok(None),
+        },
+    };
+    NetworkMessage::from_payload(&command, payload).map(Some)
+}
+
+/// The ciphers of both directions of a connection, derived from the shared secret.
+pub struct PacketCipher {
+    send_length: FSChaCha20,
+    send_packet: FSChaCha20Poly1305,
+    recv_length: FSChaCha20,
+    recv_packet: FSChaCha20Poly1305,
+    send_garbage_terminator: [u8; GARBAGE_TERMINATOR_SIZE],
+    recv_garbage_terminator: [u8; GARBAGE_TERMINATOR_SIZE],
+    session_id: [u8; 32],
+}
+
+impl PacketCipher {
+    pub fn new(shared_secret: &[u8; 32], magic: u32, initiator: bool) -> Self {
+        let mut salt = b"bitcoin_v2_shared_secret".to_vec();
+        salt.extend_from_slice(&magic.to_le_bytes());
+        let expand = |info: &[u8]| {
+            let mut key = [0u8; 32];
+            hkdf_sha256(&salt, shared_secret, info, &mut key);
+            key
+        };
+
+        let initiator_l = FSChaCha20::new(expand(b"initiator_L"));
+        let initiator_p = FSChaCha20Poly1305::new(expand(b"initiator_P"));
+        let responder_l = FSChaCha20::new(expand(b"responder_L"));
+        let responder_p = FSChaCha20Poly1305::new(expand(b"responder_P"));
+        let terminators = expand(b"garbage_terminators");
+        let mut initiator_terminator = [0u8; GARBAGE_TERMINATOR_SIZE];
+        initiator_terminator.copy_from_slice(&terminators[..GARBAGE_TERMINATOR_SIZE]);
+        let mut responder_terminator = [0u8; GARBAGE_TERMINATOR_SIZE];
+        responder_terminator.copy_from_slice(&terminators[GARBAGE_TERMINATOR_SIZE..]);
+        let session_id = expand(b"session_id");
+
+        if initiator {
+            PacketCipher {
+                send_length: initiator_l,
+                send_packet: initiator_p,
+                recv_length: responder_l,
+                recv_packet: responder_p,
+                send_garbage_terminator: initiator_terminator,
+                recv_garbage_terminator: responder_terminator,
+                session_id,
+            }
+        } else {
+            PacketCipher {
+                send_length: responder_l,
+                send_packet: responder_p,
+                recv_length: initiator_l,
+                recv_packet: initiator_p,
+                send_garbage_terminator: responder_terminator,
+                recv_garbage_terminator: initiator_terminator,
+                session_id,
+            }
+        }
+    }
+
+    /// Identifier of the connection, the same on both sides
+    pub fn session_id(&self) -> &[u8; 32] {
+        &self.session_id
+    }
+
+    pub fn send_garbage_terminator(&self) -> &[u8; GARBAGE_TERMINATOR_SIZE] {
+        &self.send_garbage_terminator
+    }
+
+    pub fn recv_garbage_terminator(&self) -> &[u8; GARBAGE_TERMINATOR_SIZE] {
+        &self.recv_garbage_terminator
+    }
+
+    /// Encrypts the contents into a packet, marked as a decoy if `ignore` is set.
+    pub fn encrypt(&mut self, contents: &[u8], aad: &[u8], ignore: bool) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(contents.len() + PACKET_OVERHEAD);
+        packet.extend_from_slice(&(contents.len() as u32).to_le_bytes()[..LENGTH_SIZE]);
+        self.send_length.crypt(&mut packet[..LENGTH_SIZE]);
+        packet.push(if ignore { IGNORE_BIT } else { 0 });
+        packet.extend_from_slice(contents);
+        let tag = self.send_packet.encrypt(aad, &mut packet[LENGTH_SIZE..]);
+        packet.extend_from_slice(&tag);
+        packet
+    }
+
+    /// Decrypts the length of the contents of the next packet.
+    ///
+    /// The length of every packet must be decrypted once, before the packet.
+    pub fn decrypt_length(&mut self, length: &[u8; LENGTH_SIZE]) -> usize {
+        let mut bytes = [0u8; 4];
+        bytes[..LENGTH_SIZE].copy_from_slice(length);
+        self.recv_length.crypt(&mut bytes[..LENGTH_SIZE]);
+        u32::from_le_bytes(bytes) as usize
+    }
+
+    /// Decrypts the packet following its length: the header byte, the contents and
+    /// the tag. Returns whether the packet is a decoy, and its contents.
+    pub fn decrypt<'a>(
+        &mut self,
+        aad: &[u8],
+        packet: &'a mut [u8],
+    ) -> Result<(bool, &'a [u8]), Error> {
+        if packet.len() < HEADER_SIZE + TAG_SIZE {
+            return Err(Error::InvalidPacket);
+        }
+        let (packet, tag) = packet.split_at_mut(packet.len() - TAG_SIZE);
+        let tag: &[u8; TAG_SIZE] = (&*tag).try_into().expect("split at the tag size; qed");
+        self.recv_packet
+            .decrypt(aad, packet, tag)
+            .map_err(|_| Error::InvalidPacket)?;
+        Ok((packet[0] & IGNORE_BIT != 0, &packet[HEADER_SIZE..]))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting for the public key of the other side
+    KeyExchange,
+    /// Waiting for the garbage terminator of the other side
+    Garbage,
+    /// Waiting for the version packet of the other side
+    Version,
+    /// Exchanging messages
+    Established,
+}
+
+/// The v2 transport of one side of a connection.
+pub struct V2Transport {
+    magic: u32,
+    initiator: bool,
+    state: State,
+    secret: Option<PrivateKey>,
+    ellswift: ElligatorSwift,
+    key_sent: bool,
+    garbage: Vec<u8>,
+    cipher: Option<PacketCipher>,
+    /// Garbage of the other side, authenticated by its first packet
+    received_garbage: Vec<u8>,
+    /// Length of the packet being received, once decrypted
+    packet_length: Option<usize>,
+    buffer: Vec<u8>,
+    transmit: Vec<u8>,
+    /// Packets sent before the ciphers are known
+    pending: Vec<(bool, Vec<u8>)>,
+}
+
+impl V2Transport {
+    /// Starts the side of a connection, the initiator being the side which opened it.
+    ///
+    /// `randomness` is used to encode the public key of the secret, and `garbage` is
+    /// sent after it. Both should be random, and the secret new, for every connection.
+    ///
+    /// # Panics
+    ///
+    /// When the garbage is longer than `MAX_GARBAGE_SIZE`.
+    pub fn new(
+        magic: u32,
+        initiator: bool,
+        secret: PrivateKey,
+        randomness: &[u8; 32],
+        garbage: Vec<u8>,
+    ) -> Self {
+        assert!(garbage.len() <= MAX_GARBAGE_SIZE, "garbage is too long");
+        let ellswift = ElligatorSwift::from_private_key(&secret, randomness);
+        let mut transport = V2Transport {
+            magic,
+            initiator,
+            state: State::KeyExchange,
+            secret: Some(secret),
+            ellswift,
+            key_sent: false,
+            garbage,
+            cipher: None,
+            received_garbage: Vec::new(),
+            packet_length: None,
+            buffer: Vec::new(),
+            transmit: Vec::new(),
+            pending: Vec::new(),
+        };
+        // the responder waits to know the other side does not use the v1 transport
+        if initiator {
+            transport.send_key();
+        }
+        transport
+    }
+
+    /// Whether the version packets were exchanged
+    pub fn is_established(&self) -> bool {
+        self.state == State::Established
+    }
+
+    /// Identifier of the connection, once the keys are exchanged
+    pub fn session_id(&self) -> Option<&[u8; 32]> {
+        self.cipher.as_ref().map(PacketCipher::session_id)
+    }
+
+    /// Sends the message, after the handshake if it is not done yet.
+    pub fn send(&mut self, message: &NetworkMessage) {
+        self.send_packet(encode_contents(message), false);
+    }
+
+    /// Sends a packet with the contents which the other side ignores.
+    pub fn send_decoy(&mut self, contents: &[u8]) {
+        self.send_packet(contents.to_vec(), true);
+    }
+
+    /// Returns the bytes to send to the other side.
+    pub fn poll_transmit(&mut self) -> Option<Bytes> {
+        if self.transmit.is_empty() {
+            None
+        } else {
+            Some(mem::take(&mut self.transmit).into())
+        }
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the next message, or `None` until all of its bytes are received.
+    ///
+    /// Decoy packets and the version packet are skipped. Any error means the
+    /// connection can not be used any more, and should be closed.
+    pub fn decode(&mut self) -> Result<Option<NetworkMessage>, Error> {
+        loop {
+            match self.state {
+                State::KeyExchange => {
+                    if !self.key_sent {
+                        if self.is_v1_prefix()? {
+                            return Ok(None);
+                        }
+                        self.send_key();
+                    }
+                    if self.buffer.len() < ELLSWIFT_SIZE {
+                        return Ok(None);
+                    }
+                    let mut theirs = [0u8; ELLSWIFT_SIZE];
+                    theirs.copy_from_slice(&self.buffer[..ELLSWIFT_SIZE]);
+                    self.buffer.drain(..ELLSWIFT_SIZE);
+                    self.exchange_keys(ElligatorSwift(theirs));
+                    self.state = State::Garbage;
+                }
+                State::Garbage => {
+                    let cipher = self.cipher.as_ref().expect("keys are exchanged; qed");
+                    let terminator = cipher.recv_garbage_terminator();
+                    let searched = self
+                        .buffer
+                        .len()
+                        .min(MAX_GARBAGE_SIZE + GARBAGE_TERMINATOR_SIZE);
+                    let position = self.buffer[..searched]
+                        .windows(GARBAGE_TERMINATOR_SIZE)
+                        .position(|window| window == terminator);
+                    let position = match position {
+                        Some(position) => position,
+                        None if searched == MAX_GARBAGE_SIZE + GARBAGE_TERMINATOR_SIZE => {
+                            return Err(Error::GarbageTooLong)
+                        }
+                        None => return Ok(None),
+                    };
+                    self.received_garbage = self.buffer.drain(..position).collect();
+                    self.buffer.drain(..GARBAGE_TERMINATOR_SIZE);
+                    self.state = State::Version;
+                }
+                State::Version | State::Established => {
+                    let (ignore, contents) = match self.decode_packet()? {
+                        Some(packet) => packet,
+                        None => return Ok(None),
+                    };
+                    if ignore {
+                        continue;
+                    }
+                    // the contents of the version packet are reserved for future features
+                    if self.state == State::Version {
+                        self.state = State::Established;
+                        continue;
+                    }
+                    if let Some(message) = decode_contents(&contents)? {
+                        return Ok(Some(message));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether the first bytes received can be the start of a v1 version message.
+    /// The responder sends its key once they can not.
+    fn is_v1_prefix(&self) -> Result<bool, Error> {
+        let mut v1_prefix = [0u8; GARBAGE_TERMINATOR_SIZE];
+        v1_prefix[..4].copy_from_slice(&self.magic.to_le_bytes());
+        v1_prefix[4..11].copy_from_slice(b"version");
+        let received = self.buffer.len().min(v1_prefix.len());
+        if self.buffer[..received] != v1_prefix[..received] {
+            Ok(false)
+        } else if received == v1_prefix.len() {
+            Err(Error::V1Handshake)
+        } else {
+            Ok(true)
+        }
+    }
+
+    fn send_key(&mut self) {
+        self.key_sent = true;
+        self.transmit.extend_from_slice(&self.ellswift.0);
+        self.transmit.extend_from_slice(&self.garbage);
+    }
+
+    fn exchange_keys(&mut self, theirs: ElligatorSwift) {
+        let secret = self.secret.take().expect("keys are exchanged once; qed");
+        // wiped once the keys of the ciphers are derived
+        let shared_secret = Zeroizing::new(if self.initiator {
+            ElligatorSwift::shared_secret(&self.ellswift, &theirs, &secret, true)
+        } else {
+            ElligatorSwift::shared_secret(&theirs, &self.ellswift, &secret, false)
+        });
+        let mut cipher = PacketCipher::new(&shared_secret, self.magic, self.initiator);
+
+        // our garbage is authenticated by the version packet
+        let garbage = mem::take(&mut self.garbage);
+        self.transmit
+            .extend_from_slice(cipher.send_garbage_terminator());
+        self.transmit
+            .extend_from_slice(&cipher.encrypt(&[], &garbage, false));
+        for (ignore, contents) in self.pending.drain(..) {
+            self.transmit
+                .extend_from_slice(&cipher.encrypt(&contents, &[], ignore));
+        }
+        self.cipher = Some(cipher);
+    }
+
+    fn send_packet(&mut self, contents: Vec<u8>, ignore: bool) {
+        match self.cipher.as_mut() {
+            Some(cipher) => {
+                let packet = cipher.encrypt(&contents, &[], ignore);
+                self.transmit.extend_from_slice(&packet);
+            }
+            None => self.pending.push((ignore, contents)),
+        }
+    }
+
+    fn decode_packet(&mut self) -> Result<Option<(bool, Vec<u8>)>, Error> {
+        let cipher = self.cipher.as_mut().expect("keys are exchanged; qed");
+        let length = match self.packet_length {
+            Some(length) => length,
+            None => {
+                if self.buffer.len() < LENGTH_SIZE {
+                    return Ok(None);
+                }
+                let mut length = [0u8; LENGTH_SIZE];
+                length.copy_from_slice(&self.buffer[..LENGTH_SIZE]);
+                self.buffer.drain(..LENGTH_SIZE);
+                let length = cipher.decrypt_length(&length);
+                if length > MAX_CONTENTS_SIZE {
+                    return Err(Error::PayloadTooLarge(length as u32));
+                }
+                self.packet_length = Some(length);
+                length
+            }
+        };
+
+        let packet_size = HEADER_SIZE + length + TAG_SIZE;
+        if self.buffer.len() < packet_size {
+            return Ok(None);
+        }
+        self.packet_length = None;
+        let mut packet: Vec<u8> = self.buffer.drain(..packet_size).collect();
+        // only the first packet authenticates the garbage
+        let aad = mem::take(&mut self.received_garbage);
+        let (ignore, contents) = cipher.decrypt(&aad, &mut packet)?;
+        Ok(Some((ignore, contents.to_vec())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Network;
+    use crate::types::{Inventory, InventoryType};
+    use light_bitcoin_primitives::H256;
+
+    fn transport(initiator: bool, garbage: Vec<u8>) -> V2Transport {
+        let seed = if initiator { 1 } else { 2 };
+        V2Transport::new(
+            Network::Mainnet.magic(),
+            initiator,
+            PrivateKey::parse(&[seed; 32]).unwrap(),
+            &[seed + 10; 32],
+            garbage,
+        )
+    }
+
+    /// Delivers the bytes sent by each side to the other in chunks, and returns the
+    /// messages received by the initiator and by the responder.
+    fn exchange(
+        initiator: &mut V2Transport,
+        responder: &mut V2Transport,
+        chunk_size: usize,
+    ) -> (Vec<NetworkMessage>, Vec<NetworkMessage>) {
+        fn deliver(
+            from: &mut V2Transport,
+            to: &mut V2Transport,
+            chunk_size: usize,
+        ) -> Vec<NetworkMessage> {
+            let mut received = vec![];
+            while let Some(bytes) = from.poll_transmit() {
+                for chunk in bytes.chunks(chunk_size) {
+                    to.feed(chunk);
+                    while let Some(message) = to.decode().unwrap() {
+                        received.push(message);
+                    }
+                }
+            }
+            received
+        }
+
+        let (mut to_initiator, mut to_responder) = (vec![], vec![]);
+        for _ in 0..3 {
+            to_responder.extend(deliver(initiator, responder, chunk_size));
+            to_initiator.extend(deliver(responder, initiator, chunk_size));
+        }
+        (to_initiator, to_responder)
+    }
+
+    #[test]
+    fn test_contents() {
+        let ping = encode_contents(&NetworkMessage::Ping(7));
+        assert_eq!(ping, [18, 7, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(decode_contents(&ping), Ok(Some(NetworkMessage::Ping(7))));
+
+        let verack = encode_contents(&NetworkMessage::Verack);
+        assert_eq!(verack, b"\x00verack\x00\x00\x00\x00\x00\x00");
+        assert_eq!(decode_contents(&verack), Ok(Some(NetworkMessage::Verack)));
+
+        assert_eq!(short_id(&Command::new("addr").unwrap()), Some(1));
+        assert_eq!(short_id(&Command::new("addrv2").unwrap()), Some(28));
+        assert_eq!(short_id(&Command::new("version").unwrap()), None);
+
+        // unassigned message types are ignored
+        assert_eq!(decode_contents(&[29, 1, 2]), Ok(None));
+        assert_eq!(decode_contents(&[]), Err(Error::InvalidCommand));
+        assert_eq!(decode_contents(b"\x00verack"), Err(Error::InvalidCommand));
+    }
+
+    // expected packets computed with the reference implementation of BIP324
+    #[test]
+    fn test_packet_cipher() {
+        let shared_secret: [u8; 32] =
+            hex::decode("3c548d4212e53d1fc76837090667cf59f5be5601a3b1b4348feb4275edaeaa2a")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let magic = Network::Mainnet.magic();
+        let mut initiator = PacketCipher::new(&shared_secret, magic, true);
+        let mut responder = PacketCipher::new(&shared_secret, magic, false);
+
+        assert_eq!(
+            hex::encode(initiator.send_garbage_terminator()),
+            "acf4f3dc0a9b0f2f0782df5c45b99e22"
+        );
+        assert_eq!(
+            hex::encode(initiator.recv_garbage_terminator()),
+            "36b8b5a539fb565b5233a6a9b5bd6128"
+        );
+        assert_eq!(
+            responder.send_garbage_terminator(),
+            initiator.recv_garbage_terminator()
+        );
+        assert_eq!(
+            hex::encode(initiator.session_id()),
+            "78bccc61db107839c91106bdf743a2a8e6399779b4025fbe02853c0343214961"
+        );
+        assert_eq!(responder.session_id(), initiator.session_id());
+
+        let packets = [
+            (
+                &b"garbage"[..],
+                vec![],
+                "7fcd7b9eebd5972719855e12b9fe51a43a450ebe",
+            ),
+            (
+                &b""[..],
+                encode_contents(&NetworkMessage::Ping(7)),
+                "419328095ef6dafcafe8a0fe311bb8fa4180373d1c82c3332656c93451",
+            ),
+        ];
+        for (aad, contents, expected) in packets {
+            let mut packet = initiator.encrypt(&contents, aad, false);
+            assert_eq!(hex::encode(&packet), expected);
+
+            let length: [u8; LENGTH_SIZE] = packet[..LENGTH_SIZE].try_into().unwrap();
+            assert_eq!(responder.decrypt_length(&length), contents.len());
+            let (ignore, decrypted) = responder.decrypt(aad, &mut packet[LENGTH_SIZE..]).unwrap();
+            assert!(!ignore);
+            assert_eq!(decrypted, &contents[..]);
+        }
+    }
+
+    // the first row of the BIP324 `packet_encoding_test_vectors.csv`
+    #[test]
+    fn test_packet_encoding_vector() {
+        let secret: [u8; 32] =
+            hex::decode("61062ea5071d800bbfd59e2e8b53d47d194b095ae5a4df04936b49772ef0d4d7")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let ellswift = |s: &str| ElligatorSwift(hex::decode(s).unwrap().try_into().unwrap());
+        let ours = ellswift("ec0adff257bbfe500c188c80b4fdd640f6b45a482bbc15fc7cef5931deff0aa186f6eb9bba7b85dc4dcc28b28722de1e3d9108b985e2967045668f66098e475b");
+        let theirs = ellswift("a4a94dfce69b4a2a0a099313d10f9f7e7d649d60501c9e1d274c300e0d89aafaffffffffffffffffffffffffffffffffffffffffffffffffffffffff8faf88d5");
+        let secret = PrivateKey::parse(&secret).unwrap();
+        let shared_secret = ElligatorSwift::shared_secret(&ours, &theirs, &secret, true);
+        assert_eq!(
+            hex::encode(shared_secret),
+            "c6992a117f5edbea70c3f511d32d26b9798be4b81a62eaee1a5acaa8459a3592"
+        );
+
+        let mut cipher = PacketCipher::new(&shared_secret, Network::Mainnet.magic(), true);
+        assert_eq!(
+            hex::encode(cipher.send_garbage_terminator()),
+            "faef555dfcdb936425d84aba524758f3"
+        );
+        assert_eq!(
+            hex::encode(cipher.recv_garbage_terminator()),
+            "02cb8ff24307a6e27de3b4e7ea3fa65b"
+        );
+        assert_eq!(
+            hex::encode(cipher.session_id()),
+            "ce72dffb015da62b0d0f5474cab8bc72605225b0cee3f62312ec680ec5f41ba5"
+        );
+        // the vector encrypts the packet of index 1, after an empty one
+        cipher.encrypt(&[], &[], false);
+        assert_eq!(
+            hex::encode(cipher.encrypt(&[0x8e], &[], false)),
+            "7530d2a18720162ac09c25329a60d75adf36eda3c3"
+        );
+    }
+
+    #[test]
+    fn test_handshake_and_messages() {
+        let inv = NetworkMessage::Inv(vec![Inventory::new(
+            InventoryType::Block,
+            H256::repeat_byte(3),
+        )]);
+        for chunk_size in [1, 7, 64, 10_000] {
+            let mut initiator = transport(true, vec![0xaa; 100]);
+            let mut responder = transport(false, vec![0xbb; MAX_GARBAGE_SIZE]);
+
+            // sent before the handshake, after the version packet
+            initiator.send_decoy(b"decoy");
+            initiator.send(&NetworkMessage::Verack);
+            responder.send(&NetworkMessage::Ping(1));
+
+            let (to_initiator, to_responder) = exchange(&mut initiator, &mut responder, chunk_size);
+            assert!(initiator.is_established() && responder.is_established());
+            assert_eq!(initiator.session_id(), responder.session_id());
+            assert_eq!(to_initiator, vec![NetworkMessage::Ping(1)]);
+            assert_eq!(to_responder, vec![NetworkMessage::Verack]);
+
+            responder.send_decoy(&[0; 300]);
+            responder.send(&inv);
+            for nonce in 0..300 {
+                initiator.send(&NetworkMessage::Pong(nonce));
+            }
+            let (to_initiator, to_responder) = exchange(&mut initiator, &mut responder, chunk_size);
+            assert_eq!(to_initiator, vec![inv.clone()]);
+            assert_eq!(
+                to_responder,
+                (0..300).map(NetworkMessage::Pong).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_handshake_errors() {
+        // a v1 version message sent to the responder
+        let mut responder = transport(false, vec![]);
+        let version = crate::serialize_message(Network::Mainnet.magic(), &NetworkMessage::Verack);
+        let mut v1 = version.take();
+        v1[4..16].copy_from_slice(b"version\0\0\0\0\0");
+        responder.feed(&v1[..10]);
+        assert_eq!(responder.decode(), Ok(None));
+        assert_eq!(responder.poll_transmit(), None);
+        responder.feed(&v1[10..]);
+        assert_eq!(responder.decode(), Err(Error::V1Handshake));
+
+        // no garbage terminator
+        let mut initiator = transport(true, vec![]);
+        let mut responder = transport(false, vec![]);
+        let key = initiator.poll_transmit().unwrap();
+        responder.feed(&key);
+        assert_eq!(responder.decode(), Ok(None));
+        responder.feed(&[0; MAX_GARBAGE_SIZE + GARBAGE_TERMINATOR_SIZE - 1]);
+        assert_eq!(responder.decode(), Ok(None));
+        responder.feed(&[0]);
+        assert_eq!(responder.decode(), Err(Error::GarbageTooLong));
+
+        // tampered packet
+        let mut initiator = transport(true, vec![]);
+        let mut responder = transport(false, vec![]);
+        exchange(&mut initiator, &mut responder, 1000);
+        initiator.send(&NetworkMessage::Ping(9));
+        let mut packet = initiator.poll_transmit().unwrap().take();
+        packet[5] ^= 1;
+        responder.feed(&packet);
+        assert_eq!(responder.decode(), Err(Error::InvalidPacket));
+    }
+}
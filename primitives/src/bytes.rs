@@ -133,20 +133,17 @@ impl<'de> serde::de::Visitor<'de> for BytesVisitor {
     type Value = Bytes;
 
     fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(formatter, "a 0x-prefixed hex-encoded vector of bytes")
+        write!(formatter, "a hex-encoded vector of bytes")
     }
 
     fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
-        if v.len() >= 2 {
-            Ok(Bytes(
-                hex::decode(v).map_err(|_| serde::de::Error::custom("invalid hex"))?,
-            ))
-        } else {
-            Err(serde::de::Error::custom("invalid format"))
-        }
+        // empty scripts and witness items are empty strings
+        Ok(Bytes(
+            hex::decode(v).map_err(|_| serde::de::Error::custom("invalid hex"))?,
+        ))
     }
 
     fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
@@ -24,6 +24,7 @@ pub enum Error {
     ReadMalformedData,
     UnreadData,
     OutOfLength,
+    TooDeep,
 }
 
 #[cfg(feature = "std")]
@@ -62,6 +63,7 @@ impl Error {
             Error::UnreadData => "unread data",
 
             Error::OutOfLength => "out of length",
+            Error::TooDeep => "nesting too deep",
         }
     }
 }
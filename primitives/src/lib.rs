@@ -7,6 +7,7 @@ mod bytes;
 mod compact;
 mod hash;
 pub mod io;
+pub mod serde_hex;
 
 pub use primitive_types::U256;
 
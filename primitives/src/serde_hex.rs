@@ -0,0 +1,205 @@
+//! Human-readable serde modes, for use with `#[serde(with = "...")]`.
+//!
+//! Human-readable formats such as JSON get hex strings, with hashes in the
+//! reversed byte order shown by Bitcoin Core and block explorers. Other formats
+//! keep the encoding of the type itself.
+
+/// A hash as reversed hex, like txids and block hashes are displayed.
+pub mod hash_rev {
+    #[cfg(not(feature = "std"))]
+    use alloc::string::String;
+
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T, S>(hash: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]> + Serialize,
+        S: Serializer,
+    {
+        if !serializer.is_human_readable() {
+            return hash.serialize(serializer);
+        }
+        let mut bytes = hash.as_ref().to_vec();
+        bytes.reverse();
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: AsMut<[u8]> + Default + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        if !deserializer.is_human_readable() {
+            return T::deserialize(deserializer);
+        }
+        let s = String::deserialize(deserializer)?;
+        let mut hash = T::default();
+        hex::decode_to_slice(&s, hash.as_mut()).map_err(de::Error::custom)?;
+        Ok(crate::hash_rev(hash))
+    }
+}
+
+/// An optional hash as reversed hex, `null` when missing.
+pub mod option_hash_rev {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T, S>(hash: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]> + Serialize,
+        S: Serializer,
+    {
+        match hash {
+            Some(hash) => serializer.serialize_some(&Wrapper(hash)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: AsMut<[u8]> + Default + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let hash = Option::<Wrapper<T>>::deserialize(deserializer)?;
+        Ok(hash.map(|Wrapper(hash)| hash))
+    }
+
+    struct Wrapper<T>(T);
+
+    impl<T: AsRef<[u8]> + Serialize> Serialize for Wrapper<&T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            super::hash_rev::serialize(self.0, serializer)
+        }
+    }
+
+    impl<'de, T: AsMut<[u8]> + Default + Deserialize<'de>> Deserialize<'de> for Wrapper<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            super::hash_rev::deserialize(deserializer).map(Wrapper)
+        }
+    }
+}
+
+/// Bytes such as scripts and witness items as hex, without `0x` prefix.
+pub mod bytes {
+    #[cfg(not(feature = "std"))]
+    use alloc::{string::String, vec::Vec};
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]>,
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hex::encode(bytes))
+        } else {
+            serializer.serialize_bytes(bytes.as_ref())
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: From<Vec<u8>>,
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            hex::decode(s).map(Into::into).map_err(de::Error::custom)
+        } else {
+            Vec::<u8>::deserialize(deserializer).map(Into::into)
+        }
+    }
+}
+
+/// A list of bytes, like a witness, as a list of hex strings.
+pub mod bytes_list {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use serde::ser::SerializeSeq;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T, S>(items: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]>,
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(items.len()))?;
+        for item in items {
+            seq.serialize_element(&Wrapper(item.as_ref()))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: From<Vec<u8>>,
+        D: Deserializer<'de>,
+    {
+        let items = Vec::<Wrapper<Vec<u8>>>::deserialize(deserializer)?;
+        Ok(items.into_iter().map(|Wrapper(item)| item.into()).collect())
+    }
+
+    struct Wrapper<T>(T);
+
+    impl Serialize for Wrapper<&[u8]> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            super::bytes::serialize(&self.0, serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Wrapper<Vec<u8>> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            super::bytes::deserialize(deserializer).map(Wrapper)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{h256, h256_rev, Bytes, H256};
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Input {
+        #[serde(with = "super::hash_rev")]
+        txid: H256,
+        #[serde(with = "super::option_hash_rev")]
+        block_hash: Option<H256>,
+        #[serde(with = "super::bytes")]
+        script: Vec<u8>,
+        #[serde(with = "super::bytes_list")]
+        witness: Vec<Bytes>,
+    }
+
+    #[test]
+    fn test_serde_hex() {
+        let input = Input {
+            txid: h256_rev("f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"),
+            block_hash: None,
+            script: vec![0x51],
+            witness: vec![Bytes::new(), vec![0xab, 0xcd].into()],
+        };
+        let json = serde_json::to_string(&input).unwrap();
+        assert_eq!(
+            json,
+            r#"{"txid":"f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16","block_hash":null,"script":"51","witness":["","abcd"]}"#
+        );
+        assert_eq!(serde_json::from_str::<Input>(&json).unwrap(), input);
+
+        let input = Input {
+            block_hash: Some(h256(
+                "6fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000",
+            )),
+            ..input
+        };
+        let json = serde_json::to_value(&input).unwrap();
+        assert_eq!(
+            json["block_hash"],
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(serde_json::from_value::<Input>(json).unwrap(), input);
+
+        let json = r#"{"txid":"00","block_hash":null,"script":"","witness":[]}"#;
+        assert!(serde_json::from_str::<Input>(json).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use core::fmt;
+use light_bitcoin_primitives::io;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The magic bytes are not `psbt\xff`
+    InvalidMagic,
+    /// The same key appears twice in one map
+    DuplicateKey,
+    /// The key data is malformed for its key type
+    InvalidKey,
+    /// The value is malformed for its key type
+    InvalidValue,
+    /// The preimage does not hash to its key
+    InvalidPreimage,
+    /// The PSBT version is not supported
+    UnsupportedVersion,
+    /// The unsigned transaction is missing from a version 0 PSBT
+    MissingUnsignedTx,
+    /// The unsigned transaction has a script_sig or witness
+    UnsignedTxHasScriptData,
+    /// A field is not allowed in this PSBT version
+    UnexpectedField,
+    /// A field required by this PSBT version is missing
+    MissingField,
+    /// The number of maps does not match the transaction
+    CountMismatch,
+    /// The string is not valid base64
+    InvalidBase64,
+    /// The PSBTs being combined are for different transactions
+    TxMismatch,
+    /// Inputs or outputs can not be added to the transaction
+    NotModifiable,
+    /// The lock time requirements of the inputs can not be satisfied together
+    LockTimeConflict,
+    /// The spent output of an input is unknown
+    MissingUtxo,
+    /// The non-witness UTXO is not the transaction being spent
+    UtxoMismatch,
+    /// A redeem or witness script does not match the spent output
+    ScriptMismatch,
+    /// The input can not be finalized with the data present
+    CannotFinalize,
+    /// An input has not been finalized
+    NotFinalized,
+    /// The input index is out of range
+    IndexOutOfRange,
+    Io(io::Error),
+    Keys(light_bitcoin_keys::Error),
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Error::InvalidMagic => "Invalid PSBT magic",
+            Error::DuplicateKey => "Duplicate key",
+            Error::InvalidKey => "Invalid key",
+            Error::InvalidValue => "Invalid value",
+            Error::InvalidPreimage => "Invalid preimage",
+            Error::UnsupportedVersion => "Unsupported PSBT version",
+            Error::MissingUnsignedTx => "Missing unsigned transaction",
+            Error::UnsignedTxHasScriptData => "Unsigned transaction has script data",
+            Error::UnexpectedField => "Unexpected field",
+            Error::MissingField => "Missing field",
+            Error::CountMismatch => "Map count mismatch",
+            Error::InvalidBase64 => "Invalid base64",
+            Error::TxMismatch => "Transaction mismatch",
+            Error::NotModifiable => "Transaction is not modifiable",
+            Error::LockTimeConflict => "Conflicting lock time requirements",
+            Error::MissingUtxo => "Missing UTXO",
+            Error::UtxoMismatch => "UTXO mismatch",
+            Error::ScriptMismatch => "Script mismatch",
+            Error::CannotFinalize => "Cannot finalize input",
+            Error::NotFinalized => "Input is not finalized",
+            Error::IndexOutOfRange => "Index out of range",
+            Error::Io(err) => return err.fmt(f),
+            Error::Keys(err) => return err.fmt(f),
+        };
+
+        msg.fmt(f)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<light_bitcoin_keys::Error> for Error {
+    fn from(err: light_bitcoin_keys::Error) -> Self {
+        Error::Keys(err)
+    }
+}
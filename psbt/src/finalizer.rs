@@ -0,0 +1,183 @@
+//! Finalizer role
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use light_bitcoin_primitives::{Bytes, H160};
+use light_bitcoin_script::{compute_leaf_hash, Builder, Opcode, Script};
+
+use crate::error::Error;
+use crate::input::Input;
+use crate::psbt::Psbt;
+
+impl Psbt {
+    /// Finalizer: builds the final scripts of every input that is not finalized yet.
+    pub fn finalize(&mut self) -> Result<(), Error> {
+        for index in 0..self.inputs.len() {
+            self.finalize_input(index)?;
+        }
+        Ok(())
+    }
+
+    /// Finalizer: builds the final script_sig and witness of an input from its signatures,
+    /// then clears the fields that are only needed for signing.
+    ///
+    /// Supports P2PKH, P2PK and multisig scripts, either bare, in P2SH or in P2WSH,
+    /// P2WPKH, P2SH-P2WPKH, and taproot key path or `<key> OP_CHECKSIG` and `multi_a`
+    /// script path spends.
+    pub fn finalize_input(&mut self, index: usize) -> Result<(), Error> {
+        let previous_output = self.previous_output(index)?;
+        let input = self.inputs.get_mut(index).ok_or(Error::IndexOutOfRange)?;
+        if input.is_finalized() {
+            return Ok(());
+        }
+
+        let spent = input.spent_output(&previous_output)?;
+        let script_pubkey: Script = spent.script_pubkey.into();
+        let (script_sig, witness) = if script_pubkey.is_pay_to_witness_taproot() {
+            (None, Some(satisfy_taproot(input)?))
+        } else if script_pubkey.is_pay_to_script_hash() {
+            let redeem_script = input.redeem_script.as_ref().ok_or(Error::CannotFinalize)?;
+            if is_witness_v0(redeem_script) {
+                let script_sig = Builder::default().push_data(redeem_script).into_script();
+                (
+                    Some(script_sig),
+                    Some(satisfy_witness_v0(input, redeem_script)?),
+                )
+            } else {
+                let mut items = satisfy(input, redeem_script)?;
+                items.push(redeem_script.to_bytes());
+                (Some(push_items(&items)), None)
+            }
+        } else if is_witness_v0(&script_pubkey) {
+            (None, Some(satisfy_witness_v0(input, &script_pubkey)?))
+        } else {
+            (Some(push_items(&satisfy(input, &script_pubkey)?)), None)
+        };
+
+        input.final_script_sig = script_sig;
+        input.final_script_witness = witness;
+        input.clear_signing_data();
+        Ok(())
+    }
+}
+
+fn is_witness_v0(script: &Script) -> bool {
+    script.is_pay_to_witness_key_hash() || script.is_pay_to_witness_script_hash()
+}
+
+/// Builds a script_sig pushing `items`
+fn push_items(items: &[Bytes]) -> Script {
+    items
+        .iter()
+        .fold(Builder::default(), |builder, item| {
+            if item.is_empty() {
+                builder.push_opcode(Opcode::OP_0)
+            } else {
+                builder.push_data(item)
+            }
+        })
+        .into_script()
+}
+
+/// Witness stack of a P2WPKH or P2WSH program
+fn satisfy_witness_v0(input: &Input, program: &Script) -> Result<Vec<Bytes>, Error> {
+    if program.is_pay_to_witness_key_hash() {
+        let script_code = Builder::build_p2pkh(&H160::from_slice(&program[2..22]));
+        return satisfy(input, &script_code);
+    }
+
+    let witness_script = input.witness_script.as_ref().ok_or(Error::CannotFinalize)?;
+    let mut items = satisfy(input, witness_script)?;
+    items.push(witness_script.to_bytes());
+    Ok(items)
+}
+
+/// Stack items satisfying a P2PKH, P2PK or multisig script with the partial signatures
+fn satisfy(input: &Input, script: &Script) -> Result<Vec<Bytes>, Error> {
+    if script.is_pay_to_public_key_hash() {
+        return input
+            .partial_sigs
+            .iter()
+            .find(|(public, _)| public.address_hash()[..] == script[3..23])
+            .map(|(public, sig)| vec![sig.clone(), public.to_vec().into()])
+            .ok_or(Error::CannotFinalize);
+    }
+
+    if script.is_pay_to_public_key() {
+        let len = script[0] as usize;
+        return input
+            .partial_sigs
+            .iter()
+            .find(|(public, _)| public[..] == script[1..=len])
+            .map(|(_, sig)| vec![sig.clone()])
+            .ok_or(Error::CannotFinalize);
+    }
+
+    if let Some((publics, required, _)) = script.parse_redeem_script() {
+        // CHECKMULTISIG pops one element more than it uses
+        let mut items = vec![Bytes::new()];
+        items.extend(
+            publics
+                .iter()
+                .filter_map(|public| {
+                    input
+                        .partial_sigs
+                        .iter()
+                        .find(|(key, _)| key[..] == public[..])
+                        .map(|(_, sig)| sig.clone())
+                })
+                .take(required as usize),
+        );
+        if items.len() != required as usize + 1 {
+            return Err(Error::CannotFinalize);
+        }
+        return Ok(items);
+    }
+
+    Err(Error::CannotFinalize)
+}
+
+/// Witness of a taproot input, the key path if signed and otherwise the
+/// smallest satisfiable leaf
+fn satisfy_taproot(input: &Input) -> Result<Vec<Bytes>, Error> {
+    if let Some(ref sig) = input.tap_key_sig {
+        return Ok(vec![sig.clone()]);
+    }
+
+    input
+        .tap_scripts
+        .iter()
+        .filter_map(|(control, (script, leaf_version))| {
+            let mut witness = satisfy_leaf(input, script, *leaf_version)?;
+            witness.push(script.to_bytes());
+            witness.push(control.clone());
+            Some(witness)
+        })
+        .min_by_key(|witness| witness.iter().map(|item| item.len()).sum::<usize>())
+        .ok_or(Error::CannotFinalize)
+}
+
+/// Signatures satisfying a `<key> OP_CHECKSIG` or `multi_a` leaf script, deepest first
+fn satisfy_leaf(input: &Input, script: &Script, leaf_version: u8) -> Option<Vec<Bytes>> {
+    let (keys, required) = script.parse_tap_leaf_keys()?;
+    let leaf_hash = compute_leaf_hash(leaf_version, script);
+
+    let mut remaining = required;
+    let mut sigs = keys
+        .iter()
+        .map(|key| match input.tap_script_sigs.get(&(*key, leaf_hash)) {
+            Some(sig) if remaining > 0 => {
+                remaining -= 1;
+                sig.clone()
+            }
+            _ => Bytes::new(),
+        })
+        .collect::<Vec<_>>();
+    if remaining > 0 {
+        return None;
+    }
+    // The first key consumes the top of the stack
+    sigs.reverse();
+    Some(sigs)
+}
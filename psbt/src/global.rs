@@ -0,0 +1,233 @@
+//! Global map of a PSBT
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use light_bitcoin_chain::Transaction;
+use light_bitcoin_primitives::Bytes;
+use light_bitcoin_serialization::{serialize, CompactInteger};
+
+use crate::error::Error;
+use crate::raw::{decode, expect_empty, Key, KeySource, Pair};
+
+pub const PSBT_GLOBAL_UNSIGNED_TX: u64 = 0x00;
+pub const PSBT_GLOBAL_XPUB: u64 = 0x01;
+pub const PSBT_GLOBAL_TX_VERSION: u64 = 0x02;
+pub const PSBT_GLOBAL_FALLBACK_LOCKTIME: u64 = 0x03;
+pub const PSBT_GLOBAL_INPUT_COUNT: u64 = 0x04;
+pub const PSBT_GLOBAL_OUTPUT_COUNT: u64 = 0x05;
+pub const PSBT_GLOBAL_TX_MODIFIABLE: u64 = 0x06;
+pub const PSBT_GLOBAL_VERSION: u64 = 0xfb;
+pub const PSBT_GLOBAL_PROPRIETARY: u64 = 0xfc;
+
+/// Inputs may be added to or removed from a version 2 PSBT
+pub const TX_MODIFIABLE_INPUTS: u8 = 0x01;
+/// Outputs may be added to or removed from a version 2 PSBT
+pub const TX_MODIFIABLE_OUTPUTS: u8 = 0x02;
+/// The PSBT has a signature with `SIGHASH_SINGLE`
+pub const TX_MODIFIABLE_SIGHASH_SINGLE: u8 = 0x04;
+
+/// Length of a serialized BIP32 extended public key
+const XPUB_LEN: usize = 78;
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Global {
+    /// The unsigned transaction, only present in version 0
+    pub unsigned_tx: Option<Transaction>,
+    /// Serialized extended public keys and their key sources
+    pub xpubs: BTreeMap<Bytes, KeySource>,
+    /// The transaction version, version 2 only
+    pub tx_version: Option<i32>,
+    /// The lock time used when no input requires one, version 2 only
+    pub fallback_locktime: Option<u32>,
+    /// `TX_MODIFIABLE_*` flags, version 2 only
+    pub tx_modifiable: Option<u8>,
+    /// The PSBT version number
+    pub version: u32,
+    pub proprietary: BTreeMap<Bytes, Bytes>,
+    pub unknown: BTreeMap<Key, Bytes>,
+}
+
+/// Global fields and the input and output counts of a version 2 PSBT
+pub(crate) struct GlobalMap {
+    pub global: Global,
+    pub input_count: Option<usize>,
+    pub output_count: Option<usize>,
+}
+
+impl Global {
+    pub(crate) fn from_pairs(pairs: Vec<Pair>) -> Result<GlobalMap, Error> {
+        let mut global = Global::default();
+        let mut input_count = None;
+        let mut output_count = None;
+        for Pair { key, value } in pairs {
+            match key.type_value {
+                PSBT_GLOBAL_UNSIGNED_TX => {
+                    expect_empty(&key)?;
+                    let tx: Transaction = decode(&value)?;
+                    if tx
+                        .inputs
+                        .iter()
+                        .any(|input| !input.script_sig.is_empty() || input.has_witness())
+                    {
+                        return Err(Error::UnsignedTxHasScriptData);
+                    }
+                    global.unsigned_tx = Some(tx);
+                }
+                PSBT_GLOBAL_XPUB => {
+                    if key.key.len() != XPUB_LEN {
+                        return Err(Error::InvalidKey);
+                    }
+                    global.xpubs.insert(key.key, KeySource::from_slice(&value)?);
+                }
+                PSBT_GLOBAL_TX_VERSION => {
+                    expect_empty(&key)?;
+                    global.tx_version = Some(decode(&value)?);
+                }
+                PSBT_GLOBAL_FALLBACK_LOCKTIME => {
+                    expect_empty(&key)?;
+                    global.fallback_locktime = Some(decode(&value)?);
+                }
+                PSBT_GLOBAL_INPUT_COUNT => {
+                    expect_empty(&key)?;
+                    input_count = Some(decode::<CompactInteger>(&value)?.into());
+                }
+                PSBT_GLOBAL_OUTPUT_COUNT => {
+                    expect_empty(&key)?;
+                    output_count = Some(decode::<CompactInteger>(&value)?.into());
+                }
+                PSBT_GLOBAL_TX_MODIFIABLE => {
+                    expect_empty(&key)?;
+                    global.tx_modifiable = Some(decode(&value)?);
+                }
+                PSBT_GLOBAL_VERSION => {
+                    expect_empty(&key)?;
+                    global.version = decode(&value)?;
+                }
+                PSBT_GLOBAL_PROPRIETARY => {
+                    global.proprietary.insert(key.key, value);
+                }
+                _ => {
+                    global.unknown.insert(key, value);
+                }
+            }
+        }
+
+        Ok(GlobalMap {
+            global,
+            input_count,
+            output_count,
+        })
+    }
+
+    pub(crate) fn to_pairs(&self, input_count: usize, output_count: usize) -> Vec<Pair> {
+        let mut pairs = Vec::new();
+        if let Some(ref tx) = self.unsigned_tx {
+            pairs.push(Pair::new(
+                PSBT_GLOBAL_UNSIGNED_TX,
+                Bytes::new(),
+                serialize(tx),
+            ));
+        }
+        for (xpub, source) in &self.xpubs {
+            pairs.push(Pair::new(PSBT_GLOBAL_XPUB, xpub.clone(), source.to_bytes()));
+        }
+        if let Some(tx_version) = self.tx_version {
+            pairs.push(Pair::new(
+                PSBT_GLOBAL_TX_VERSION,
+                Bytes::new(),
+                serialize(&tx_version),
+            ));
+        }
+        if let Some(locktime) = self.fallback_locktime {
+            pairs.push(Pair::new(
+                PSBT_GLOBAL_FALLBACK_LOCKTIME,
+                Bytes::new(),
+                serialize(&locktime),
+            ));
+        }
+        if self.version >= 2 {
+            pairs.push(Pair::new(
+                PSBT_GLOBAL_INPUT_COUNT,
+                Bytes::new(),
+                serialize(&CompactInteger::from(input_count)),
+            ));
+            pairs.push(Pair::new(
+                PSBT_GLOBAL_OUTPUT_COUNT,
+                Bytes::new(),
+                serialize(&CompactInteger::from(output_count)),
+            ));
+        }
+        if let Some(modifiable) = self.tx_modifiable {
+            pairs.push(Pair::new(
+                PSBT_GLOBAL_TX_MODIFIABLE,
+                Bytes::new(),
+                serialize(&modifiable),
+            ));
+        }
+        if self.version != 0 {
+            pairs.push(Pair::new(
+                PSBT_GLOBAL_VERSION,
+                Bytes::new(),
+                serialize(&self.version),
+            ));
+        }
+        for (key, value) in &self.proprietary {
+            pairs.push(Pair::new(
+                PSBT_GLOBAL_PROPRIETARY,
+                key.clone(),
+                value.clone(),
+            ));
+        }
+        for (key, value) in &self.unknown {
+            pairs.push(Pair {
+                key: key.clone(),
+                value: value.clone(),
+            });
+        }
+        pairs
+    }
+
+    /// Checks the fields against the PSBT version
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        match self.version {
+            0 => {
+                if self.unsigned_tx.is_none() {
+                    return Err(Error::MissingUnsignedTx);
+                }
+                if self.tx_version.is_some()
+                    || self.fallback_locktime.is_some()
+                    || self.tx_modifiable.is_some()
+                {
+                    return Err(Error::UnexpectedField);
+                }
+            }
+            2 => {
+                if self.unsigned_tx.is_some() {
+                    return Err(Error::UnexpectedField);
+                }
+                if self.tx_version.is_none() {
+                    return Err(Error::MissingField);
+                }
+            }
+            _ => return Err(Error::UnsupportedVersion),
+        }
+        Ok(())
+    }
+
+    /// Merges the fields of `other`, keeping the existing ones on conflict
+    pub(crate) fn combine(&mut self, other: Global) {
+        merge(&mut self.xpubs, other.xpubs);
+        merge(&mut self.proprietary, other.proprietary);
+        merge(&mut self.unknown, other.unknown);
+    }
+}
+
+/// Inserts the entries of `other` that are missing from `map`
+pub(crate) fn merge<K: Ord, V>(map: &mut BTreeMap<K, V>, other: BTreeMap<K, V>) {
+    for (key, value) in other {
+        map.entry(key).or_insert(value);
+    }
+}
@@ -0,0 +1,547 @@
+//! Per-input map of a PSBT
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use light_bitcoin_chain::{
+    constants::LOCKTIME_THRESHOLD, OutPoint, Transaction, TransactionOutput,
+};
+use light_bitcoin_crypto::{dhash160, dhash256, ripemd160, sha256};
+use light_bitcoin_keys::{Public, XOnly};
+use light_bitcoin_primitives::{Bytes, H160, H256};
+use light_bitcoin_script::Script;
+use light_bitcoin_serialization::{
+    serialize, serialize_with_flags, Reader, Stream, SERIALIZE_TRANSACTION_WITNESS,
+};
+
+use crate::error::Error;
+use crate::global::merge;
+use crate::raw::{
+    decode, decode_tap_key_origin, encode_tap_key_origin, expect_empty, Key, KeySource, Pair,
+};
+
+pub const PSBT_IN_NON_WITNESS_UTXO: u64 = 0x00;
+pub const PSBT_IN_WITNESS_UTXO: u64 = 0x01;
+pub const PSBT_IN_PARTIAL_SIG: u64 = 0x02;
+pub const PSBT_IN_SIGHASH_TYPE: u64 = 0x03;
+pub const PSBT_IN_REDEEM_SCRIPT: u64 = 0x04;
+pub const PSBT_IN_WITNESS_SCRIPT: u64 = 0x05;
+pub const PSBT_IN_BIP32_DERIVATION: u64 = 0x06;
+pub const PSBT_IN_FINAL_SCRIPTSIG: u64 = 0x07;
+pub const PSBT_IN_FINAL_SCRIPTWITNESS: u64 = 0x08;
+pub const PSBT_IN_RIPEMD160: u64 = 0x0a;
+pub const PSBT_IN_SHA256: u64 = 0x0b;
+pub const PSBT_IN_HASH160: u64 = 0x0c;
+pub const PSBT_IN_HASH256: u64 = 0x0d;
+pub const PSBT_IN_PREVIOUS_TXID: u64 = 0x0e;
+pub const PSBT_IN_OUTPUT_INDEX: u64 = 0x0f;
+pub const PSBT_IN_SEQUENCE: u64 = 0x10;
+pub const PSBT_IN_REQUIRED_TIME_LOCKTIME: u64 = 0x11;
+pub const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u64 = 0x12;
+pub const PSBT_IN_TAP_KEY_SIG: u64 = 0x13;
+pub const PSBT_IN_TAP_SCRIPT_SIG: u64 = 0x14;
+pub const PSBT_IN_TAP_LEAF_SCRIPT: u64 = 0x15;
+pub const PSBT_IN_TAP_BIP32_DERIVATION: u64 = 0x16;
+pub const PSBT_IN_TAP_INTERNAL_KEY: u64 = 0x17;
+pub const PSBT_IN_TAP_MERKLE_ROOT: u64 = 0x18;
+pub const PSBT_IN_PROPRIETARY: u64 = 0xfc;
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Input {
+    /// The full transaction being spent from
+    pub non_witness_utxo: Option<Transaction>,
+    /// The output being spent, for segwit inputs
+    pub witness_utxo: Option<TransactionOutput>,
+    /// ECDSA signatures with their sighash byte, by public key
+    pub partial_sigs: BTreeMap<Public, Bytes>,
+    pub sighash_type: Option<u32>,
+    pub redeem_script: Option<Script>,
+    pub witness_script: Option<Script>,
+    pub bip32_derivation: BTreeMap<Public, KeySource>,
+    pub final_script_sig: Option<Script>,
+    pub final_script_witness: Option<Vec<Bytes>>,
+    pub ripemd160_preimages: BTreeMap<H160, Bytes>,
+    pub sha256_preimages: BTreeMap<H256, Bytes>,
+    pub hash160_preimages: BTreeMap<H160, Bytes>,
+    pub hash256_preimages: BTreeMap<H256, Bytes>,
+    /// Txid of the spent output, version 2 only
+    pub previous_txid: Option<H256>,
+    /// Index of the spent output, version 2 only
+    pub output_index: Option<u32>,
+    /// Sequence number, version 2 only
+    pub sequence: Option<u32>,
+    /// Minimum timestamp lock time, version 2 only
+    pub required_time_locktime: Option<u32>,
+    /// Minimum block height lock time, version 2 only
+    pub required_height_locktime: Option<u32>,
+    /// Key path schnorr signature, with the sighash byte if it is not default
+    pub tap_key_sig: Option<Bytes>,
+    /// Script path schnorr signatures, by x-only key and leaf hash
+    pub tap_script_sigs: BTreeMap<(XOnly, H256), Bytes>,
+    /// Leaf scripts and versions, by control block
+    pub tap_scripts: BTreeMap<Bytes, (Script, u8)>,
+    /// Leaf hashes and key source of x-only keys
+    pub tap_key_origins: BTreeMap<XOnly, (Vec<H256>, KeySource)>,
+    pub tap_internal_key: Option<XOnly>,
+    pub tap_merkle_root: Option<H256>,
+    pub proprietary: BTreeMap<Bytes, Bytes>,
+    pub unknown: BTreeMap<Key, Bytes>,
+}
+
+impl Input {
+    /// Whether a finalizer has already produced the final scripts
+    pub fn is_finalized(&self) -> bool {
+        self.final_script_sig.is_some() || self.final_script_witness.is_some()
+    }
+
+    /// The output spent by this input, which spends `previous_output`
+    pub fn spent_output(&self, previous_output: &OutPoint) -> Result<TransactionOutput, Error> {
+        if let Some(ref output) = self.witness_utxo {
+            return Ok(output.clone());
+        }
+        let tx = self.non_witness_utxo.as_ref().ok_or(Error::MissingUtxo)?;
+        if tx.hash() != previous_output.txid {
+            return Err(Error::UtxoMismatch);
+        }
+        tx.outputs
+            .get(previous_output.index as usize)
+            .cloned()
+            .ok_or(Error::UtxoMismatch)
+    }
+
+    pub(crate) fn from_pairs(pairs: Vec<Pair>) -> Result<Self, Error> {
+        let mut input = Input::default();
+        for Pair { key, value } in pairs {
+            match key.type_value {
+                PSBT_IN_NON_WITNESS_UTXO => {
+                    expect_empty(&key)?;
+                    input.non_witness_utxo = Some(decode(&value)?);
+                }
+                PSBT_IN_WITNESS_UTXO => {
+                    expect_empty(&key)?;
+                    input.witness_utxo = Some(decode(&value)?);
+                }
+                PSBT_IN_PARTIAL_SIG => {
+                    let public = Public::from_slice(&key.key).map_err(|_| Error::InvalidKey)?;
+                    input.partial_sigs.insert(public, value);
+                }
+                PSBT_IN_SIGHASH_TYPE => {
+                    expect_empty(&key)?;
+                    input.sighash_type = Some(decode(&value)?);
+                }
+                PSBT_IN_REDEEM_SCRIPT => {
+                    expect_empty(&key)?;
+                    input.redeem_script = Some(value.into());
+                }
+                PSBT_IN_WITNESS_SCRIPT => {
+                    expect_empty(&key)?;
+                    input.witness_script = Some(value.into());
+                }
+                PSBT_IN_BIP32_DERIVATION => {
+                    let public = Public::from_slice(&key.key).map_err(|_| Error::InvalidKey)?;
+                    input
+                        .bip32_derivation
+                        .insert(public, KeySource::from_slice(&value)?);
+                }
+                PSBT_IN_FINAL_SCRIPTSIG => {
+                    expect_empty(&key)?;
+                    input.final_script_sig = Some(value.into());
+                }
+                PSBT_IN_FINAL_SCRIPTWITNESS => {
+                    expect_empty(&key)?;
+                    let mut reader = Reader::new(&value);
+                    let witness = reader.read_list().map_err(|_| Error::InvalidValue)?;
+                    if !reader.is_finished() {
+                        return Err(Error::InvalidValue);
+                    }
+                    input.final_script_witness = Some(witness);
+                }
+                PSBT_IN_RIPEMD160 => {
+                    let hash = preimage_hash(&key, ripemd160(&value))?;
+                    input.ripemd160_preimages.insert(hash, value);
+                }
+                PSBT_IN_SHA256 => {
+                    let hash = preimage_hash(&key, sha256(&value))?;
+                    input.sha256_preimages.insert(hash, value);
+                }
+                PSBT_IN_HASH160 => {
+                    let hash = preimage_hash(&key, dhash160(&value))?;
+                    input.hash160_preimages.insert(hash, value);
+                }
+                PSBT_IN_HASH256 => {
+                    let hash = preimage_hash(&key, dhash256(&value))?;
+                    input.hash256_preimages.insert(hash, value);
+                }
+                PSBT_IN_PREVIOUS_TXID => {
+                    expect_empty(&key)?;
+                    input.previous_txid = Some(decode(&value)?);
+                }
+                PSBT_IN_OUTPUT_INDEX => {
+                    expect_empty(&key)?;
+                    input.output_index = Some(decode(&value)?);
+                }
+                PSBT_IN_SEQUENCE => {
+                    expect_empty(&key)?;
+                    input.sequence = Some(decode(&value)?);
+                }
+                PSBT_IN_REQUIRED_TIME_LOCKTIME => {
+                    expect_empty(&key)?;
+                    let locktime: u32 = decode(&value)?;
+                    if locktime < LOCKTIME_THRESHOLD {
+                        return Err(Error::InvalidValue);
+                    }
+                    input.required_time_locktime = Some(locktime);
+                }
+                PSBT_IN_REQUIRED_HEIGHT_LOCKTIME => {
+                    expect_empty(&key)?;
+                    let locktime: u32 = decode(&value)?;
+                    if locktime == 0 || locktime >= LOCKTIME_THRESHOLD {
+                        return Err(Error::InvalidValue);
+                    }
+                    input.required_height_locktime = Some(locktime);
+                }
+                PSBT_IN_TAP_KEY_SIG => {
+                    expect_empty(&key)?;
+                    check_schnorr_sig(&value)?;
+                    input.tap_key_sig = Some(value);
+                }
+                PSBT_IN_TAP_SCRIPT_SIG => {
+                    if key.key.len() != 64 {
+                        return Err(Error::InvalidKey);
+                    }
+                    check_schnorr_sig(&value)?;
+                    let xonly = x_only(&key.key[..32])?;
+                    let leaf_hash = H256::from_slice(&key.key[32..]);
+                    input.tap_script_sigs.insert((xonly, leaf_hash), value);
+                }
+                PSBT_IN_TAP_LEAF_SCRIPT => {
+                    let control = &key.key;
+                    if control.len() < 33 || (control.len() - 33) % 32 != 0 || value.is_empty() {
+                        return Err(Error::InvalidKey);
+                    }
+                    let (script, leaf_version) = value.split_at(value.len() - 1);
+                    if leaf_version[0] != control[0] & 0xfe {
+                        return Err(Error::InvalidValue);
+                    }
+                    input
+                        .tap_scripts
+                        .insert(key.key, (script.to_vec().into(), leaf_version[0]));
+                }
+                PSBT_IN_TAP_BIP32_DERIVATION => {
+                    let xonly = x_only(&key.key)?;
+                    input
+                        .tap_key_origins
+                        .insert(xonly, decode_tap_key_origin(&value)?);
+                }
+                PSBT_IN_TAP_INTERNAL_KEY => {
+                    expect_empty(&key)?;
+                    input.tap_internal_key = Some(x_only(&value).map_err(|_| Error::InvalidValue)?);
+                }
+                PSBT_IN_TAP_MERKLE_ROOT => {
+                    expect_empty(&key)?;
+                    input.tap_merkle_root = Some(decode(&value)?);
+                }
+                PSBT_IN_PROPRIETARY => {
+                    input.proprietary.insert(key.key, value);
+                }
+                _ => {
+                    input.unknown.insert(key, value);
+                }
+            }
+        }
+        Ok(input)
+    }
+
+    pub(crate) fn to_pairs(&self) -> Vec<Pair> {
+        let mut pairs = Vec::new();
+        if let Some(ref tx) = self.non_witness_utxo {
+            pairs.push(Pair::new(
+                PSBT_IN_NON_WITNESS_UTXO,
+                Bytes::new(),
+                serialize_with_flags(tx, SERIALIZE_TRANSACTION_WITNESS),
+            ));
+        }
+        if let Some(ref output) = self.witness_utxo {
+            pairs.push(Pair::new(
+                PSBT_IN_WITNESS_UTXO,
+                Bytes::new(),
+                serialize(output),
+            ));
+        }
+        for (public, sig) in &self.partial_sigs {
+            pairs.push(Pair::new(
+                PSBT_IN_PARTIAL_SIG,
+                public.to_vec().into(),
+                sig.clone(),
+            ));
+        }
+        if let Some(sighash_type) = self.sighash_type {
+            pairs.push(Pair::new(
+                PSBT_IN_SIGHASH_TYPE,
+                Bytes::new(),
+                serialize(&sighash_type),
+            ));
+        }
+        if let Some(ref script) = self.redeem_script {
+            pairs.push(Pair::new(
+                PSBT_IN_REDEEM_SCRIPT,
+                Bytes::new(),
+                script.to_bytes(),
+            ));
+        }
+        if let Some(ref script) = self.witness_script {
+            pairs.push(Pair::new(
+                PSBT_IN_WITNESS_SCRIPT,
+                Bytes::new(),
+                script.to_bytes(),
+            ));
+        }
+        for (public, source) in &self.bip32_derivation {
+            pairs.push(Pair::new(
+                PSBT_IN_BIP32_DERIVATION,
+                public.to_vec().into(),
+                source.to_bytes(),
+            ));
+        }
+        if let Some(ref script) = self.final_script_sig {
+            pairs.push(Pair::new(
+                PSBT_IN_FINAL_SCRIPTSIG,
+                Bytes::new(),
+                script.to_bytes(),
+            ));
+        }
+        if let Some(ref witness) = self.final_script_witness {
+            let mut stream = Stream::default();
+            stream.append_list(witness);
+            pairs.push(Pair::new(
+                PSBT_IN_FINAL_SCRIPTWITNESS,
+                Bytes::new(),
+                stream.out(),
+            ));
+        }
+        for (hash, preimage) in &self.ripemd160_preimages {
+            pairs.push(Pair::new(
+                PSBT_IN_RIPEMD160,
+                hash.as_bytes().to_vec().into(),
+                preimage.clone(),
+            ));
+        }
+        for (hash, preimage) in &self.sha256_preimages {
+            pairs.push(Pair::new(
+                PSBT_IN_SHA256,
+                hash.as_bytes().to_vec().into(),
+                preimage.clone(),
+            ));
+        }
+        for (hash, preimage) in &self.hash160_preimages {
+            pairs.push(Pair::new(
+                PSBT_IN_HASH160,
+                hash.as_bytes().to_vec().into(),
+                preimage.clone(),
+            ));
+        }
+        for (hash, preimage) in &self.hash256_preimages {
+            pairs.push(Pair::new(
+                PSBT_IN_HASH256,
+                hash.as_bytes().to_vec().into(),
+                preimage.clone(),
+            ));
+        }
+        if let Some(ref txid) = self.previous_txid {
+            pairs.push(Pair::new(
+                PSBT_IN_PREVIOUS_TXID,
+                Bytes::new(),
+                serialize(txid),
+            ));
+        }
+        if let Some(index) = self.output_index {
+            pairs.push(Pair::new(
+                PSBT_IN_OUTPUT_INDEX,
+                Bytes::new(),
+                serialize(&index),
+            ));
+        }
+        if let Some(sequence) = self.sequence {
+            pairs.push(Pair::new(
+                PSBT_IN_SEQUENCE,
+                Bytes::new(),
+                serialize(&sequence),
+            ));
+        }
+        if let Some(locktime) = self.required_time_locktime {
+            pairs.push(Pair::new(
+                PSBT_IN_REQUIRED_TIME_LOCKTIME,
+                Bytes::new(),
+                serialize(&locktime),
+            ));
+        }
+        if let Some(locktime) = self.required_height_locktime {
+            pairs.push(Pair::new(
+                PSBT_IN_REQUIRED_HEIGHT_LOCKTIME,
+                Bytes::new(),
+                serialize(&locktime),
+            ));
+        }
+        if let Some(ref sig) = self.tap_key_sig {
+            pairs.push(Pair::new(PSBT_IN_TAP_KEY_SIG, Bytes::new(), sig.clone()));
+        }
+        for ((xonly, leaf_hash), sig) in &self.tap_script_sigs {
+            let mut key = xonly.0.to_vec();
+            key.extend_from_slice(leaf_hash.as_bytes());
+            pairs.push(Pair::new(PSBT_IN_TAP_SCRIPT_SIG, key.into(), sig.clone()));
+        }
+        for (control, (script, leaf_version)) in &self.tap_scripts {
+            let mut value = script.to_vec();
+            value.push(*leaf_version);
+            pairs.push(Pair::new(
+                PSBT_IN_TAP_LEAF_SCRIPT,
+                control.clone(),
+                value.into(),
+            ));
+        }
+        for (xonly, (leaf_hashes, source)) in &self.tap_key_origins {
+            pairs.push(Pair::new(
+                PSBT_IN_TAP_BIP32_DERIVATION,
+                xonly.0.to_vec().into(),
+                encode_tap_key_origin(leaf_hashes, source),
+            ));
+        }
+        if let Some(ref xonly) = self.tap_internal_key {
+            pairs.push(Pair::new(
+                PSBT_IN_TAP_INTERNAL_KEY,
+                Bytes::new(),
+                xonly.0.to_vec().into(),
+            ));
+        }
+        if let Some(ref root) = self.tap_merkle_root {
+            pairs.push(Pair::new(
+                PSBT_IN_TAP_MERKLE_ROOT,
+                Bytes::new(),
+                serialize(root),
+            ));
+        }
+        for (key, value) in &self.proprietary {
+            pairs.push(Pair::new(PSBT_IN_PROPRIETARY, key.clone(), value.clone()));
+        }
+        for (key, value) in &self.unknown {
+            pairs.push(Pair {
+                key: key.clone(),
+                value: value.clone(),
+            });
+        }
+        pairs
+    }
+
+    /// Checks the fields against the PSBT version
+    pub(crate) fn validate(&self, version: u32) -> Result<(), Error> {
+        let v2_fields = self.previous_txid.is_some()
+            || self.output_index.is_some()
+            || self.sequence.is_some()
+            || self.required_time_locktime.is_some()
+            || self.required_height_locktime.is_some();
+        match version {
+            0 if v2_fields => Err(Error::UnexpectedField),
+            2 if self.previous_txid.is_none() || self.output_index.is_none() => {
+                Err(Error::MissingField)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Merges the fields of `other`, keeping the existing ones on conflict
+    pub(crate) fn combine(&mut self, other: Input) {
+        macro_rules! combine_options {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field.is_none() {
+                        self.$field = other.$field;
+                    }
+                )*
+            };
+        }
+        macro_rules! combine_maps {
+            ($($field:ident),*) => {
+                $(merge(&mut self.$field, other.$field);)*
+            };
+        }
+
+        combine_options!(
+            non_witness_utxo,
+            witness_utxo,
+            sighash_type,
+            redeem_script,
+            witness_script,
+            final_script_sig,
+            final_script_witness,
+            previous_txid,
+            output_index,
+            sequence,
+            required_time_locktime,
+            required_height_locktime,
+            tap_key_sig,
+            tap_internal_key,
+            tap_merkle_root
+        );
+        combine_maps!(
+            partial_sigs,
+            bip32_derivation,
+            ripemd160_preimages,
+            sha256_preimages,
+            hash160_preimages,
+            hash256_preimages,
+            tap_script_sigs,
+            tap_scripts,
+            tap_key_origins,
+            proprietary,
+            unknown
+        );
+    }
+
+    /// Drops everything but the UTXOs, final scripts and unknown fields, as the finalizer does
+    pub(crate) fn clear_signing_data(&mut self) {
+        self.partial_sigs.clear();
+        self.sighash_type = None;
+        self.redeem_script = None;
+        self.witness_script = None;
+        self.bip32_derivation.clear();
+        self.ripemd160_preimages.clear();
+        self.sha256_preimages.clear();
+        self.hash160_preimages.clear();
+        self.hash256_preimages.clear();
+        self.tap_key_sig = None;
+        self.tap_script_sigs.clear();
+        self.tap_scripts.clear();
+        self.tap_key_origins.clear();
+        self.tap_internal_key = None;
+        self.tap_merkle_root = None;
+    }
+}
+
+/// Checks the key data of a preimage entry against the hash of the preimage
+fn preimage_hash<H: AsRef<[u8]>>(key: &Key, hash: H) -> Result<H, Error> {
+    if key.key.len() != hash.as_ref().len() {
+        return Err(Error::InvalidKey);
+    }
+    if key.key[..] != *hash.as_ref() {
+        return Err(Error::InvalidPreimage);
+    }
+    Ok(hash)
+}
+
+/// An x-only key must be 32 bytes, and on the curve
+pub(crate) fn x_only(bytes: &[u8]) -> Result<XOnly, Error> {
+    let bytes: [u8; 32] = bytes.try_into().map_err(|_| Error::InvalidKey)?;
+    XOnly::try_from(bytes).map_err(|_| Error::InvalidKey)
+}
+
+/// A schnorr signature is 64 bytes, followed by the sighash byte unless it is `SIGHASH_DEFAULT`
+pub(crate) fn check_schnorr_sig(sig: &[u8]) -> Result<(), Error> {
+    match sig.len() {
+        64 => Ok(()),
+        65 if sig[64] != 0 => Ok(()),
+        _ => Err(Error::InvalidValue),
+    }
+}
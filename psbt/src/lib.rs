@@ -0,0 +1,22 @@
+//! Partially signed bitcoin transactions.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+mod error;
+mod finalizer;
+mod global;
+mod input;
+mod output;
+mod psbt;
+mod raw;
+mod signer;
+
+pub use self::error::Error;
+pub use self::global::*;
+pub use self::input::*;
+pub use self::output::*;
+pub use self::psbt::{Psbt, PSBT_MAGIC};
+pub use self::raw::{Key, KeySource, Pair};
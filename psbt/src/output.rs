@@ -0,0 +1,232 @@
+//! Per-output map of a PSBT
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use light_bitcoin_keys::{Public, XOnly};
+use light_bitcoin_primitives::{Bytes, H256};
+use light_bitcoin_script::Script;
+use light_bitcoin_serialization::{serialize, Reader, Stream};
+
+use crate::error::Error;
+use crate::global::merge;
+use crate::input::x_only;
+use crate::raw::{
+    decode, decode_tap_key_origin, encode_tap_key_origin, expect_empty, Key, KeySource, Pair,
+};
+
+pub const PSBT_OUT_REDEEM_SCRIPT: u64 = 0x00;
+pub const PSBT_OUT_WITNESS_SCRIPT: u64 = 0x01;
+pub const PSBT_OUT_BIP32_DERIVATION: u64 = 0x02;
+pub const PSBT_OUT_AMOUNT: u64 = 0x03;
+pub const PSBT_OUT_SCRIPT: u64 = 0x04;
+pub const PSBT_OUT_TAP_INTERNAL_KEY: u64 = 0x05;
+pub const PSBT_OUT_TAP_TREE: u64 = 0x06;
+pub const PSBT_OUT_TAP_BIP32_DERIVATION: u64 = 0x07;
+pub const PSBT_OUT_PROPRIETARY: u64 = 0xfc;
+
+/// Maximum depth of a taproot script tree, see BIP341
+const TAPROOT_CONTROL_MAX_NODE_COUNT: u8 = 128;
+
+/// A leaf of a taproot script tree, listed depth first
+#[derive(Clone, Debug, PartialEq)]
+pub struct TapLeaf {
+    pub depth: u8,
+    pub leaf_version: u8,
+    pub script: Script,
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Output {
+    pub redeem_script: Option<Script>,
+    pub witness_script: Option<Script>,
+    pub bip32_derivation: BTreeMap<Public, KeySource>,
+    /// The output value, version 2 only
+    pub amount: Option<u64>,
+    /// The output script, version 2 only
+    pub script: Option<Script>,
+    pub tap_internal_key: Option<XOnly>,
+    pub tap_tree: Option<Vec<TapLeaf>>,
+    pub tap_key_origins: BTreeMap<XOnly, (Vec<H256>, KeySource)>,
+    pub proprietary: BTreeMap<Bytes, Bytes>,
+    pub unknown: BTreeMap<Key, Bytes>,
+}
+
+impl Output {
+    pub(crate) fn from_pairs(pairs: Vec<Pair>) -> Result<Self, Error> {
+        let mut output = Output::default();
+        for Pair { key, value } in pairs {
+            match key.type_value {
+                PSBT_OUT_REDEEM_SCRIPT => {
+                    expect_empty(&key)?;
+                    output.redeem_script = Some(value.into());
+                }
+                PSBT_OUT_WITNESS_SCRIPT => {
+                    expect_empty(&key)?;
+                    output.witness_script = Some(value.into());
+                }
+                PSBT_OUT_BIP32_DERIVATION => {
+                    let public = Public::from_slice(&key.key).map_err(|_| Error::InvalidKey)?;
+                    output
+                        .bip32_derivation
+                        .insert(public, KeySource::from_slice(&value)?);
+                }
+                PSBT_OUT_AMOUNT => {
+                    expect_empty(&key)?;
+                    output.amount = Some(decode(&value)?);
+                }
+                PSBT_OUT_SCRIPT => {
+                    expect_empty(&key)?;
+                    output.script = Some(value.into());
+                }
+                PSBT_OUT_TAP_INTERNAL_KEY => {
+                    expect_empty(&key)?;
+                    output.tap_internal_key =
+                        Some(x_only(&value).map_err(|_| Error::InvalidValue)?);
+                }
+                PSBT_OUT_TAP_TREE => {
+                    expect_empty(&key)?;
+                    output.tap_tree = Some(decode_tap_tree(&value)?);
+                }
+                PSBT_OUT_TAP_BIP32_DERIVATION => {
+                    let xonly = x_only(&key.key)?;
+                    output
+                        .tap_key_origins
+                        .insert(xonly, decode_tap_key_origin(&value)?);
+                }
+                PSBT_OUT_PROPRIETARY => {
+                    output.proprietary.insert(key.key, value);
+                }
+                _ => {
+                    output.unknown.insert(key, value);
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    pub(crate) fn to_pairs(&self) -> Vec<Pair> {
+        let mut pairs = Vec::new();
+        if let Some(ref script) = self.redeem_script {
+            pairs.push(Pair::new(
+                PSBT_OUT_REDEEM_SCRIPT,
+                Bytes::new(),
+                script.to_bytes(),
+            ));
+        }
+        if let Some(ref script) = self.witness_script {
+            pairs.push(Pair::new(
+                PSBT_OUT_WITNESS_SCRIPT,
+                Bytes::new(),
+                script.to_bytes(),
+            ));
+        }
+        for (public, source) in &self.bip32_derivation {
+            pairs.push(Pair::new(
+                PSBT_OUT_BIP32_DERIVATION,
+                public.to_vec().into(),
+                source.to_bytes(),
+            ));
+        }
+        if let Some(amount) = self.amount {
+            pairs.push(Pair::new(PSBT_OUT_AMOUNT, Bytes::new(), serialize(&amount)));
+        }
+        if let Some(ref script) = self.script {
+            pairs.push(Pair::new(PSBT_OUT_SCRIPT, Bytes::new(), script.to_bytes()));
+        }
+        if let Some(ref xonly) = self.tap_internal_key {
+            pairs.push(Pair::new(
+                PSBT_OUT_TAP_INTERNAL_KEY,
+                Bytes::new(),
+                xonly.0.to_vec().into(),
+            ));
+        }
+        if let Some(ref leaves) = self.tap_tree {
+            let mut stream = Stream::default();
+            for leaf in leaves {
+                stream
+                    .append(&leaf.depth)
+                    .append(&leaf.leaf_version)
+                    .append(&leaf.script.to_bytes());
+            }
+            pairs.push(Pair::new(PSBT_OUT_TAP_TREE, Bytes::new(), stream.out()));
+        }
+        for (xonly, (leaf_hashes, source)) in &self.tap_key_origins {
+            pairs.push(Pair::new(
+                PSBT_OUT_TAP_BIP32_DERIVATION,
+                xonly.0.to_vec().into(),
+                encode_tap_key_origin(leaf_hashes, source),
+            ));
+        }
+        for (key, value) in &self.proprietary {
+            pairs.push(Pair::new(PSBT_OUT_PROPRIETARY, key.clone(), value.clone()));
+        }
+        for (key, value) in &self.unknown {
+            pairs.push(Pair {
+                key: key.clone(),
+                value: value.clone(),
+            });
+        }
+        pairs
+    }
+
+    /// Checks the fields against the PSBT version
+    pub(crate) fn validate(&self, version: u32) -> Result<(), Error> {
+        match version {
+            0 if self.amount.is_some() || self.script.is_some() => Err(Error::UnexpectedField),
+            2 if self.amount.is_none() || self.script.is_none() => Err(Error::MissingField),
+            _ => Ok(()),
+        }
+    }
+
+    /// Merges the fields of `other`, keeping the existing ones on conflict
+    pub(crate) fn combine(&mut self, other: Output) {
+        if self.redeem_script.is_none() {
+            self.redeem_script = other.redeem_script;
+        }
+        if self.witness_script.is_none() {
+            self.witness_script = other.witness_script;
+        }
+        if self.amount.is_none() {
+            self.amount = other.amount;
+        }
+        if self.script.is_none() {
+            self.script = other.script;
+        }
+        if self.tap_internal_key.is_none() {
+            self.tap_internal_key = other.tap_internal_key;
+        }
+        if self.tap_tree.is_none() {
+            self.tap_tree = other.tap_tree;
+        }
+        merge(&mut self.bip32_derivation, other.bip32_derivation);
+        merge(&mut self.tap_key_origins, other.tap_key_origins);
+        merge(&mut self.proprietary, other.proprietary);
+        merge(&mut self.unknown, other.unknown);
+    }
+}
+
+fn decode_tap_tree(value: &[u8]) -> Result<Vec<TapLeaf>, Error> {
+    let mut reader = Reader::new(value);
+    let mut leaves = Vec::new();
+    while !reader.is_finished() {
+        let depth: u8 = reader.read().map_err(|_| Error::InvalidValue)?;
+        let leaf_version: u8 = reader.read().map_err(|_| Error::InvalidValue)?;
+        let script: Bytes = reader.read().map_err(|_| Error::InvalidValue)?;
+        if depth > TAPROOT_CONTROL_MAX_NODE_COUNT || leaf_version & 1 != 0 {
+            return Err(Error::InvalidValue);
+        }
+        leaves.push(TapLeaf {
+            depth,
+            leaf_version,
+            script: script.into(),
+        });
+    }
+    if leaves.is_empty() {
+        return Err(Error::InvalidValue);
+    }
+    Ok(leaves)
+}
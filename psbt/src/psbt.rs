@@ -0,0 +1,765 @@
+//! Partially signed bitcoin transaction
+
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec, vec::Vec};
+use core::{fmt, str};
+
+use light_bitcoin_chain::{
+    constants::SEQUENCE_FINAL, OutPoint, Transaction, TransactionInput, TransactionOutput,
+};
+use light_bitcoin_primitives::{io, Bytes};
+use light_bitcoin_serialization::{Deserializable, Reader, Serializable, Stream};
+
+use crate::error::Error;
+use crate::global::{Global, GlobalMap, TX_MODIFIABLE_INPUTS, TX_MODIFIABLE_OUTPUTS};
+use crate::input::Input;
+use crate::output::Output;
+use crate::raw::{read_map, write_map};
+
+/// Magic bytes at the start of every PSBT
+pub const PSBT_MAGIC: [u8; 5] = *b"psbt\xff";
+
+/// A partially signed bitcoin transaction, see [BIP174], [BIP370] and [BIP371].
+///
+/// [BIP174]: https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki
+/// [BIP370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+/// [BIP371]: https://github.com/bitcoin/bips/blob/master/bip-0371.mediawiki
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Psbt {
+    pub global: Global,
+    pub inputs: Vec<Input>,
+    pub outputs: Vec<Output>,
+}
+
+impl Psbt {
+    /// Creates a version 0 PSBT for an unsigned transaction.
+    pub fn from_unsigned_tx(tx: Transaction) -> Result<Self, Error> {
+        if tx
+            .inputs
+            .iter()
+            .any(|input| !input.script_sig.is_empty() || input.has_witness())
+        {
+            return Err(Error::UnsignedTxHasScriptData);
+        }
+
+        Ok(Psbt {
+            inputs: vec![Input::default(); tx.inputs.len()],
+            outputs: vec![Output::default(); tx.outputs.len()],
+            global: Global {
+                unsigned_tx: Some(tx),
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Creates an empty version 2 PSBT whose inputs and outputs can be added.
+    pub fn new_v2(tx_version: i32, fallback_locktime: Option<u32>) -> Self {
+        Psbt {
+            global: Global {
+                tx_version: Some(tx_version),
+                fallback_locktime,
+                tx_modifiable: Some(TX_MODIFIABLE_INPUTS | TX_MODIFIABLE_OUTPUTS),
+                version: 2,
+                ..Default::default()
+            },
+            inputs: vec![],
+            outputs: vec![],
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.global.version
+    }
+
+    /// Adds an input spending `previous_output`, returning its map for the updater.
+    pub fn add_input(
+        &mut self,
+        previous_output: OutPoint,
+        sequence: u32,
+    ) -> Result<&mut Input, Error> {
+        let input = match self.global.unsigned_tx {
+            Some(ref mut tx) => {
+                tx.inputs.push(TransactionInput {
+                    previous_output,
+                    script_sig: Bytes::new(),
+                    sequence,
+                    script_witness: vec![],
+                });
+                Input::default()
+            }
+            None => {
+                if self.global.tx_modifiable.unwrap_or(0) & TX_MODIFIABLE_INPUTS == 0 {
+                    return Err(Error::NotModifiable);
+                }
+                Input {
+                    previous_txid: Some(previous_output.txid),
+                    output_index: Some(previous_output.index),
+                    sequence: Some(sequence),
+                    ..Default::default()
+                }
+            }
+        };
+        self.inputs.push(input);
+        Ok(self.inputs.last_mut().expect("input was just pushed; qed"))
+    }
+
+    /// Adds an output, returning its map for the updater.
+    pub fn add_output(&mut self, output: TransactionOutput) -> Result<&mut Output, Error> {
+        let output = match self.global.unsigned_tx {
+            Some(ref mut tx) => {
+                tx.outputs.push(output);
+                Output::default()
+            }
+            None => {
+                if self.global.tx_modifiable.unwrap_or(0) & TX_MODIFIABLE_OUTPUTS == 0 {
+                    return Err(Error::NotModifiable);
+                }
+                Output {
+                    amount: Some(output.value),
+                    script: Some(output.script_pubkey.into()),
+                    ..Default::default()
+                }
+            }
+        };
+        self.outputs.push(output);
+        Ok(self
+            .outputs
+            .last_mut()
+            .expect("output was just pushed; qed"))
+    }
+
+    /// The outpoint spent by an input.
+    pub fn previous_output(&self, index: usize) -> Result<OutPoint, Error> {
+        match self.global.unsigned_tx {
+            Some(ref tx) => tx
+                .inputs
+                .get(index)
+                .map(|input| input.previous_output)
+                .ok_or(Error::IndexOutOfRange),
+            None => {
+                let input = self.inputs.get(index).ok_or(Error::IndexOutOfRange)?;
+                match (input.previous_txid, input.output_index) {
+                    (Some(txid), Some(index)) => Ok(OutPoint { txid, index }),
+                    _ => Err(Error::MissingField),
+                }
+            }
+        }
+    }
+
+    /// The unsigned transaction, rebuilt from the input and output maps for version 2.
+    pub fn unsigned_tx(&self) -> Result<Transaction, Error> {
+        if let Some(ref tx) = self.global.unsigned_tx {
+            return Ok(tx.clone());
+        }
+
+        let inputs = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                Ok(TransactionInput {
+                    previous_output: self.previous_output(index)?,
+                    script_sig: Bytes::new(),
+                    sequence: input.sequence.unwrap_or(SEQUENCE_FINAL),
+                    script_witness: vec![],
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|output| match (output.amount, &output.script) {
+                (Some(value), Some(script)) => Ok(TransactionOutput {
+                    value,
+                    script_pubkey: script.to_bytes(),
+                }),
+                _ => Err(Error::MissingField),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Transaction {
+            version: self.global.tx_version.ok_or(Error::MissingField)?,
+            inputs,
+            outputs,
+            lock_time: self.lock_time()?,
+        })
+    }
+
+    /// Lock time of a version 2 PSBT, as determined by BIP370.
+    ///
+    /// A height lock time is preferred when every input accepts one.
+    fn lock_time(&self) -> Result<u32, Error> {
+        let required = self.inputs.iter().filter(|input| {
+            input.required_time_locktime.is_some() || input.required_height_locktime.is_some()
+        });
+        if required.clone().next().is_none() {
+            return Ok(self.global.fallback_locktime.unwrap_or(0));
+        }
+
+        if let Some(heights) = required
+            .clone()
+            .map(|input| input.required_height_locktime)
+            .collect::<Option<Vec<_>>>()
+        {
+            return Ok(heights.into_iter().max().unwrap_or(0));
+        }
+        required
+            .map(|input| input.required_time_locktime)
+            .collect::<Option<Vec<_>>>()
+            .and_then(|times| times.into_iter().max())
+            .ok_or(Error::LockTimeConflict)
+    }
+
+    /// Parses a PSBT, which must take up all of `bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let psbt = Self::read(&mut reader)?;
+        if !reader.is_finished() {
+            return Err(Error::Io(io::Error::UnreadData));
+        }
+        Ok(psbt)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut stream = Stream::default();
+        self.serialize(&mut stream);
+        stream.out()
+    }
+
+    pub fn from_base64(s: &str) -> Result<Self, Error> {
+        let bytes = base64::decode(s).map_err(|_| Error::InvalidBase64)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn to_base64(&self) -> String {
+        base64::encode(self.to_bytes())
+    }
+
+    fn read<T: io::Read>(reader: &mut Reader<T>) -> Result<Self, Error> {
+        let mut magic = [0u8; 5];
+        reader.read_slice(&mut magic)?;
+        if magic != PSBT_MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+
+        let GlobalMap {
+            global,
+            input_count,
+            output_count,
+        } = Global::from_pairs(read_map(reader)?)?;
+        global.validate()?;
+        let (input_count, output_count) = match global.unsigned_tx {
+            Some(ref tx) => {
+                if input_count.is_some() || output_count.is_some() {
+                    return Err(Error::UnexpectedField);
+                }
+                (tx.inputs.len(), tx.outputs.len())
+            }
+            None => (
+                input_count.ok_or(Error::MissingField)?,
+                output_count.ok_or(Error::MissingField)?,
+            ),
+        };
+
+        let mut inputs = Vec::new();
+        for _ in 0..input_count {
+            let input = Input::from_pairs(read_map(reader)?)?;
+            input.validate(global.version)?;
+            inputs.push(input);
+        }
+        let mut outputs = Vec::new();
+        for _ in 0..output_count {
+            let output = Output::from_pairs(read_map(reader)?)?;
+            output.validate(global.version)?;
+            outputs.push(output);
+        }
+
+        Ok(Psbt {
+            global,
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Combiner: merges the maps of another PSBT for the same transaction.
+    ///
+    /// Fields already present in `self` are kept when both PSBTs have the same key.
+    pub fn combine(&mut self, other: Psbt) -> Result<(), Error> {
+        if self.version() != other.version()
+            || self.inputs.len() != other.inputs.len()
+            || self.outputs.len() != other.outputs.len()
+            || self.unsigned_tx()?.hash() != other.unsigned_tx()?.hash()
+        {
+            return Err(Error::TxMismatch);
+        }
+
+        self.global.combine(other.global);
+        for (input, other) in self.inputs.iter_mut().zip(other.inputs) {
+            input.combine(other);
+        }
+        for (output, other) in self.outputs.iter_mut().zip(other.outputs) {
+            output.combine(other);
+        }
+        Ok(())
+    }
+
+    /// Extractor: builds the signed transaction from the finalized inputs.
+    pub fn extract_tx(&self) -> Result<Transaction, Error> {
+        let mut tx = self.unsigned_tx()?;
+        for (txin, input) in tx.inputs.iter_mut().zip(self.inputs.iter()) {
+            if !input.is_finalized() {
+                return Err(Error::NotFinalized);
+            }
+            if let Some(ref script_sig) = input.final_script_sig {
+                txin.script_sig = script_sig.to_bytes();
+            }
+            if let Some(ref witness) = input.final_script_witness {
+                txin.script_witness = witness.clone();
+            }
+        }
+        Ok(tx)
+    }
+}
+
+impl Serializable for Psbt {
+    fn serialize(&self, stream: &mut Stream) {
+        stream.append_slice(&PSBT_MAGIC);
+        write_map(
+            stream,
+            &self.global.to_pairs(self.inputs.len(), self.outputs.len()),
+        );
+        for input in &self.inputs {
+            write_map(stream, &input.to_pairs());
+        }
+        for output in &self.outputs {
+            write_map(stream, &output.to_pairs());
+        }
+    }
+}
+
+impl Deserializable for Psbt {
+    fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
+    where
+        Self: Sized,
+        T: io::Read,
+    {
+        Psbt::read(reader).map_err(|err| match err {
+            Error::Io(err) => err,
+            _ => io::Error::ReadMalformedData,
+        })
+    }
+}
+
+impl fmt::Display for Psbt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_base64())
+    }
+}
+
+impl str::FromStr for Psbt {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Psbt::from_base64(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{
+        PSBT_IN_TAP_BIP32_DERIVATION, PSBT_IN_TAP_INTERNAL_KEY, PSBT_IN_TAP_SCRIPT_SIG,
+    };
+    use crate::output::{PSBT_OUT_TAP_BIP32_DERIVATION, PSBT_OUT_TAP_INTERNAL_KEY};
+    use crate::raw::Key;
+    use light_bitcoin_chain::constants::LOCKTIME_THRESHOLD;
+    use light_bitcoin_crypto::sha256;
+    use light_bitcoin_keys::{KeyPair, Message, Network, Private};
+    use light_bitcoin_primitives::{H160, H256};
+    use light_bitcoin_script::{
+        check_taproot_tx, compute_leaf_hash, Builder, Opcode, Script, SignatureVersion,
+        TransactionInputSigner,
+    };
+
+    fn keypair(byte: u8) -> KeyPair {
+        KeyPair::from_private(Private {
+            network: Network::Mainnet,
+            secret: H256::repeat_byte(byte).into(),
+            compressed: true,
+        })
+        .unwrap()
+    }
+
+    fn spending_tx(previous_output: OutPoint) -> Transaction {
+        Transaction {
+            version: 2,
+            inputs: vec![TransactionInput {
+                previous_output,
+                script_sig: Bytes::new(),
+                sequence: 0xffff_fffd,
+                script_witness: vec![],
+            }],
+            outputs: vec![TransactionOutput {
+                value: 90_000,
+                script_pubkey: Builder::build_p2wpkh(&H160::repeat_byte(1)).to_bytes(),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    fn psbt_spending(spent: &TransactionOutput) -> Psbt {
+        let previous_output = OutPoint::new(H256::repeat_byte(0xaa), 1);
+        let mut psbt = Psbt::from_unsigned_tx(spending_tx(previous_output)).unwrap();
+        psbt.inputs[0].witness_utxo = Some(spent.clone());
+        psbt
+    }
+
+    fn assert_roundtrip(psbt: &Psbt) {
+        let decoded: Psbt = psbt.to_string().parse().unwrap();
+        assert_eq!(&decoded, psbt);
+    }
+
+    #[test]
+    fn test_serialize_v0() {
+        let mut psbt = psbt_spending(&TransactionOutput::default());
+        psbt.global
+            .proprietary
+            .insert(vec![1].into(), vec![2].into());
+        psbt.inputs[0]
+            .unknown
+            .insert(Key::new(0xf0, vec![3].into()), vec![4].into());
+        assert_roundtrip(&psbt);
+
+        let bytes = psbt.to_bytes();
+        assert_eq!(&bytes[..7], b"psbt\xff\x01\x00");
+        assert_eq!(Psbt::from_bytes(&bytes[1..]), Err(Error::InvalidMagic));
+        assert_eq!(
+            Psbt::from_base64("cHNidP8="),
+            Err(Error::Io(io::Error::UnexpectedEof))
+        );
+        assert_eq!(Psbt::from_base64("psbt!"), Err(Error::InvalidBase64));
+
+        let mut invalid = psbt.clone();
+        invalid.outputs[0].amount = Some(1);
+        assert_eq!(
+            Psbt::from_bytes(&invalid.to_bytes()),
+            Err(Error::UnexpectedField)
+        );
+        let mut invalid = psbt;
+        invalid.global.version = 1;
+        assert_eq!(
+            Psbt::from_bytes(&invalid.to_bytes()),
+            Err(Error::UnsupportedVersion)
+        );
+    }
+
+    #[test]
+    fn test_constructor_v2() {
+        let mut psbt = Psbt::new_v2(2, Some(100));
+        psbt.add_input(OutPoint::new(H256::repeat_byte(1), 0), 0xffff_fffe)
+            .unwrap();
+        psbt.add_input(OutPoint::new(H256::repeat_byte(2), 1), 0xffff_fffe)
+            .unwrap()
+            .required_height_locktime = Some(200);
+        psbt.add_output(TransactionOutput {
+            value: 1000,
+            script_pubkey: Builder::build_p2wpkh(&H160::repeat_byte(1)).to_bytes(),
+        })
+        .unwrap();
+        assert_roundtrip(&psbt);
+
+        let tx = psbt.unsigned_tx().unwrap();
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(tx.inputs[1].previous_output.index, 1);
+        assert_eq!(tx.outputs[0].value, 1000);
+        assert_eq!(tx.lock_time, 200);
+
+        psbt.inputs[0].required_time_locktime = Some(LOCKTIME_THRESHOLD + 1);
+        assert_eq!(psbt.unsigned_tx(), Err(Error::LockTimeConflict));
+        psbt.inputs[1].required_time_locktime = Some(LOCKTIME_THRESHOLD + 2);
+        assert_eq!(
+            psbt.unsigned_tx().unwrap().lock_time,
+            LOCKTIME_THRESHOLD + 2
+        );
+
+        psbt.global.tx_modifiable = Some(0);
+        assert!(psbt.add_output(TransactionOutput::default()).is_err());
+
+        let mut invalid = psbt;
+        invalid.outputs[0].script = None;
+        assert_eq!(
+            Psbt::from_bytes(&invalid.to_bytes()),
+            Err(Error::MissingField)
+        );
+    }
+
+    #[test]
+    fn test_p2pkh() {
+        let kp = keypair(1);
+        let prev_tx = Transaction {
+            version: 1,
+            inputs: vec![TransactionInput::coinbase(vec![1].into())],
+            outputs: vec![TransactionOutput {
+                value: 100_000,
+                script_pubkey: Builder::build_p2pkh(&kp.public().address_hash()).to_bytes(),
+            }],
+            lock_time: 0,
+        };
+        let previous_output = OutPoint::new(prev_tx.hash(), 0);
+        let mut psbt = Psbt::from_unsigned_tx(spending_tx(previous_output)).unwrap();
+        psbt.inputs[0].non_witness_utxo = Some(prev_tx.clone());
+        assert_eq!(
+            psbt.inputs[0].spent_output(&OutPoint::new(H256::default(), 0)),
+            Err(Error::UtxoMismatch)
+        );
+
+        assert_eq!(psbt.sign(&keypair(2), &Message::default()), Ok(0));
+        assert_eq!(psbt.sign(&kp, &Message::default()), Ok(1));
+        assert_roundtrip(&psbt);
+        psbt.finalize().unwrap();
+        let tx = psbt.extract_tx().unwrap();
+
+        let signer = TransactionInputSigner::from(psbt.unsigned_tx().unwrap());
+        let script_pubkey = prev_tx.outputs[0].script_pubkey.clone().into();
+        let hash = signer.signature_hash(0, 0, &script_pubkey, SignatureVersion::Base, 1);
+        let script_sig: Script = tx.inputs[0].script_sig.clone().into();
+        let items = script_sig
+            .iter()
+            .map(|instruction| instruction.unwrap().data.unwrap().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(items.len(), 2);
+        assert_eq!(&items[1][..], &kp.public()[..]);
+        let (sig, sighash) = items[0].split_at(items[0].len() - 1);
+        assert_eq!(sighash, &[1]);
+        assert!(kp.public().verify(&hash, &sig.to_vec().into()).unwrap());
+    }
+
+    #[test]
+    fn test_p2wpkh() {
+        let kp = keypair(1);
+        let spent = TransactionOutput {
+            value: 100_000,
+            script_pubkey: Builder::build_p2wpkh(&kp.public().address_hash()).to_bytes(),
+        };
+        let mut psbt = psbt_spending(&spent);
+        assert_eq!(psbt.extract_tx(), Err(Error::NotFinalized));
+        assert_eq!(psbt.finalize(), Err(Error::CannotFinalize));
+
+        assert_eq!(psbt.sign(&kp, &Message::default()), Ok(1));
+        psbt.finalize().unwrap();
+        assert!(psbt.inputs[0].partial_sigs.is_empty());
+        assert_roundtrip(&psbt);
+        let tx = psbt.extract_tx().unwrap();
+
+        let signer = TransactionInputSigner::from(psbt.unsigned_tx().unwrap());
+        let script_code = Builder::build_p2pkh(&kp.public().address_hash());
+        let hash =
+            signer.signature_hash(0, spent.value, &script_code, SignatureVersion::WitnessV0, 1);
+        let witness = &tx.inputs[0].script_witness;
+        assert_eq!(witness.len(), 2);
+        assert_eq!(&witness[1][..], &kp.public()[..]);
+        let (sig, _) = witness[0].split_at(witness[0].len() - 1);
+        assert!(kp.public().verify(&hash, &sig.to_vec().into()).unwrap());
+    }
+
+    #[test]
+    fn test_p2wsh_multisig_combine() {
+        let (kp1, kp2) = (keypair(1), keypair(2));
+        let witness_script = Builder::default()
+            .push_opcode(Opcode::OP_2)
+            .push_bytes(kp1.public())
+            .push_bytes(kp2.public())
+            .push_opcode(Opcode::OP_2)
+            .push_opcode(Opcode::OP_CHECKMULTISIG)
+            .into_script();
+        let spent = TransactionOutput {
+            value: 100_000,
+            script_pubkey: Builder::build_p2wsh(&sha256(&witness_script)).to_bytes(),
+        };
+        let mut psbt = psbt_spending(&spent);
+        psbt.inputs[0].witness_script = Some(witness_script.clone());
+
+        let mut other = psbt.clone();
+        assert_eq!(psbt.sign(&kp1, &Message::default()), Ok(1));
+        assert_eq!(other.sign(&kp2, &Message::default()), Ok(1));
+        assert_eq!(psbt.finalize(), Err(Error::CannotFinalize));
+
+        let mut unrelated = psbt_spending(&TransactionOutput::default());
+        unrelated.global.unsigned_tx.as_mut().unwrap().lock_time = 1;
+        assert_eq!(psbt.clone().combine(unrelated), Err(Error::TxMismatch));
+
+        psbt.combine(other).unwrap();
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 2);
+        psbt.finalize().unwrap();
+        let witness = &psbt.extract_tx().unwrap().inputs[0].script_witness;
+        assert_eq!(witness.len(), 4);
+        assert!(witness[0].is_empty());
+        assert_eq!(witness[3], witness_script.to_bytes());
+    }
+
+    #[test]
+    fn test_taproot_key_path() {
+        let kp = keypair(1);
+        let internal = kp.x_only().unwrap();
+        let (output, _) = internal.tap_tweak(None).unwrap();
+        let spent = TransactionOutput {
+            value: 100_000,
+            script_pubkey: Builder::build_p2tr(&output).to_bytes(),
+        };
+        let mut psbt = psbt_spending(&spent);
+        assert_eq!(psbt.sign(&kp, &Message::default()), Ok(0));
+
+        psbt.inputs[0].tap_internal_key = Some(internal);
+        assert_eq!(psbt.sign(&kp, &Message::default()), Ok(1));
+        assert_eq!(psbt.inputs[0].tap_key_sig.as_ref().unwrap().len(), 64);
+        assert_roundtrip(&psbt);
+        psbt.finalize().unwrap();
+        let tx = psbt.extract_tx().unwrap();
+        assert_eq!(check_taproot_tx(&tx, &[spent]), Ok(true));
+    }
+
+    #[test]
+    fn test_taproot_script_path() {
+        let (kp, internal_kp) = (keypair(1), keypair(2));
+        let xonly = kp.x_only().unwrap();
+        let internal = internal_kp.x_only().unwrap();
+        let script = Builder::default()
+            .push_bytes(&xonly.0)
+            .push_opcode(Opcode::OP_CHECKSIG)
+            .into_script();
+        let leaf_hash = compute_leaf_hash(0xc0, &script);
+        let (output, odd) = internal.tap_tweak(Some(leaf_hash)).unwrap();
+        let mut control = vec![0xc0 | odd as u8];
+        control.extend_from_slice(&internal.0);
+        let spent = TransactionOutput {
+            value: 100_000,
+            script_pubkey: Builder::build_p2tr(&output).to_bytes(),
+        };
+
+        let mut psbt = psbt_spending(&spent);
+        let input = &mut psbt.inputs[0];
+        input.tap_internal_key = Some(internal);
+        input.tap_merkle_root = Some(leaf_hash);
+        input
+            .tap_scripts
+            .insert(control.clone().into(), (script.clone(), 0xc0));
+        assert_eq!(psbt.sign(&kp, &Message::default()), Ok(1));
+        assert!(psbt.inputs[0].tap_key_sig.is_none());
+        assert_roundtrip(&psbt);
+
+        psbt.finalize().unwrap();
+        assert!(psbt.inputs[0].tap_scripts.is_empty());
+        let tx = psbt.extract_tx().unwrap();
+        let witness = &tx.inputs[0].script_witness;
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness[1], script.to_bytes());
+        assert_eq!(&witness[2][..], &control[..]);
+        assert_eq!(check_taproot_tx(&tx, &[spent]), Ok(true));
+    }
+
+    /// Valid PSBTs of BIP174: a P2PKH input, a finalized P2PKH input with a
+    /// P2SH-P2WPKH input, a P2PKH input with a sighash type, and the same two
+    /// inputs with the key origins of the outputs.
+    const BIP174_VALID: [&str; 4] = [
+        "cHNidP8BAHUCAAAAASaBcTce3/KF6Tet7qSze3gADAVmy7OtZGQXE8pCFxv2AAAAAAD+////AtPf9QUAAAAAGXapFNDFmQPFusKGh2DpD9UhpGZap2UgiKwA4fUFAAAAABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHh7MuEwAAAQD9pQEBAAAAAAECiaPHHqtNIOA3G7ukzGmPopXJRjr6Ljl/hTPMti+VZ+UBAAAAFxYAFL4Y0VKpsBIDna89p95PUzSe7LmF/////4b4qkOnHf8USIk6UwpyN+9rRgi7st0tAXHmOuxqSJC0AQAAABcWABT+Pp7xp0XpdNkCxDVZQ6vLNL1TU/////8CAMLrCwAAAAAZdqkUhc/xCX/Z4Ai7NK9wnGIZeziXikiIrHL++E4sAAAAF6kUM5cluiHv1irHU6m80GfWx6ajnQWHAkcwRAIgJxK+IuAnDzlPVoMR3HyppolwuAJf3TskAinwf4pfOiQCIAGLONfc0xTnNMkna9b7QPZzMlvEuqFEyADS8vAtsnZcASED0uFWdJQbrUqZY3LLh+GFbTZSYG2YVi/jnF6efkE/IQUCSDBFAiEA0SuFLYXc2WHS9fSrZgZU327tzHlMDDPOXMMJ/7X85Y0CIGczio4OFyXBl/saiK9Z9R5E5CVbIBZ8hoQDHAXR8lkqASECI7cr7vCWXRC+B3jv7NYfysb3mk6haTkzgHNEZPhPKrMAAAAAAAAA",
+        "cHNidP8BAKACAAAAAqsJSaCMWvfEm4IS9Bfi8Vqz9cM9zxU4IagTn4d6W3vkAAAAAAD+////qwlJoIxa98SbghL0F+LxWrP1wz3PFTghqBOfh3pbe+QBAAAAAP7///8CYDvqCwAAAAAZdqkUdopAu9dAy+gdmI5x3ipNXHE5ax2IrI4kAAAAAAAAGXapFG9GILVT+glechue4O/p+gOcykWXiKwAAAAAAAEHakcwRAIgR1lmF5fAGwNrJZKJSGhiGDR9iYZLcZ4ff89X0eURZYcCIFMJ6r9Wqk2Ikf/REf3xM286KdqGbX+EhtdVRs7tr5MZASEDXNxh/HupccC1AaZGoqg7ECy0OIEhfKaC3Ibi1z+ogpIAAQEgAOH1BQAAAAAXqRQ1RebjO4MsRwUPJNPuuTycA5SLx4cBBBYAFIXRNTfy4mVAWjTbr6nj3aAfuCMIAAAA",
+        "cHNidP8BAHUCAAAAASaBcTce3/KF6Tet7qSze3gADAVmy7OtZGQXE8pCFxv2AAAAAAD+////AtPf9QUAAAAAGXapFNDFmQPFusKGh2DpD9UhpGZap2UgiKwA4fUFAAAAABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHh7MuEwAAAQD9pQEBAAAAAAECiaPHHqtNIOA3G7ukzGmPopXJRjr6Ljl/hTPMti+VZ+UBAAAAFxYAFL4Y0VKpsBIDna89p95PUzSe7LmF/////4b4qkOnHf8USIk6UwpyN+9rRgi7st0tAXHmOuxqSJC0AQAAABcWABT+Pp7xp0XpdNkCxDVZQ6vLNL1TU/////8CAMLrCwAAAAAZdqkUhc/xCX/Z4Ai7NK9wnGIZeziXikiIrHL++E4sAAAAF6kUM5cluiHv1irHU6m80GfWx6ajnQWHAkcwRAIgJxK+IuAnDzlPVoMR3HyppolwuAJf3TskAinwf4pfOiQCIAGLONfc0xTnNMkna9b7QPZzMlvEuqFEyADS8vAtsnZcASED0uFWdJQbrUqZY3LLh+GFbTZSYG2YVi/jnF6efkE/IQUCSDBFAiEA0SuFLYXc2WHS9fSrZgZU327tzHlMDDPOXMMJ/7X85Y0CIGczio4OFyXBl/saiK9Z9R5E5CVbIBZ8hoQDHAXR8lkqASECI7cr7vCWXRC+B3jv7NYfysb3mk6haTkzgHNEZPhPKrMAAAAAAQMEAQAAAAAAAA==",
+        "cHNidP8BAKACAAAAAqsJSaCMWvfEm4IS9Bfi8Vqz9cM9zxU4IagTn4d6W3vkAAAAAAD+////qwlJoIxa98SbghL0F+LxWrP1wz3PFTghqBOfh3pbe+QBAAAAAP7///8CYDvqCwAAAAAZdqkUdopAu9dAy+gdmI5x3ipNXHE5ax2IrI4kAAAAAAAAGXapFG9GILVT+glechue4O/p+gOcykWXiKwAAAAAAAEA3wIAAAABJoFxNx7f8oXpN63upLN7eAAMBWbLs61kZBcTykIXG/YAAAAAakcwRAIgcLIkUSPmv0dNYMW1DAQ9TGkaXSQ18Jo0p2YqncJReQoCIAEynKnazygL3zB0DsA5BCJCLIHLRYOUV663b8Eu3ZWzASECZX0RjTNXuOD0ws1G23s59tnDjZpwq8ubLeXcjb/kzjH+////AtPf9QUAAAAAGXapFNDFmQPFusKGh2DpD9UhpGZap2UgiKwA4fUFAAAAABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHh7MuEwAAAQEgAOH1BQAAAAAXqRQ1RebjO4MsRwUPJNPuuTycA5SLx4cBBBYAFIXRNTfy4mVAWjTbr6nj3aAfuCMIACICAurVlmh8qAYEPtw94RbN8p1eklfBls0FXPaYyNAr8k6ZELSmumcAAACAAAAAgAIAAIAAIgIDlPYr6d8ZlSxVh3aK63aYBhrSxKJciU9H2MFitNchPQUQtKa6ZwAAAIABAACAAgAAgAA=",
+    ];
+
+    /// Invalid PSBT of BIP174: a scriptSig in the unsigned transaction
+    const BIP174_FILLED_SCRIPT_SIG: &str = "cHNidP8BAP0KAQIAAAACqwlJoIxa98SbghL0F+LxWrP1wz3PFTghqBOfh3pbe+QAAAAAakcwRAIgR1lmF5fAGwNrJZKJSGhiGDR9iYZLcZ4ff89X0eURZYcCIFMJ6r9Wqk2Ikf/REf3xM286KdqGbX+EhtdVRs7tr5MZASEDXNxh/HupccC1AaZGoqg7ECy0OIEhfKaC3Ibi1z+ogpL+////qwlJoIxa98SbghL0F+LxWrP1wz3PFTghqBOfh3pbe+QBAAAAAP7///8CYDvqCwAAAAAZdqkUdopAu9dAy+gdmI5x3ipNXHE5ax2IrI4kAAAAAAAAGXapFG9GILVT+glechue4O/p+gOcykWXiKwAAAAAAAEA3wIAAAABJoFxNx7f8oXpN63upLN7eAAMBWbLs61kZBcTykIXG/YAAAAAakcwRAIgcLIkUSPmv0dNYMW1DAQ9TGkaXSQ18Jo0p2YqncJReQoCIAEynKnazygL3zB0DsA5BCJCLIHLRYOUV663b8Eu3ZWzASECZX0RjTNXuOD0ws1G23s59tnDjZpwq8ubLeXcjb/kzjH+////AtPf9QUAAAAAGXapFNDFmQPFusKGh2DpD9UhpGZap2UgiKwA4fUFAAAAABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHh7MuEwAAAQEgAOH1BQAAAAAXqRQ1RebjO4MsRwUPJNPuuTycA5SLx4cBBBYAFIXRNTfy4mVAWjTbr6nj3aAfuCMIAAAA";
+
+    /// Invalid PSBT of BIP174: a network transaction, not a PSBT
+    const BIP174_NETWORK_TX: &str = "AgAAAAEmgXE3Ht/yhek3re6ks3t4AAwFZsuzrWRkFxPKQhcb9gAAAABqRzBEAiBwsiRRI+a/R01gxbUMBD1MaRpdJDXwmjSnZiqdwlF5CgIgATKcqdrPKAvfMHQOwDkEIkIsgctFg5RXrrdvwS7dlbMBIQJlfRGNM1e44PTCzUbbezn22cONmnCry5st5dyNv+TOMf7///8C09/1BQAAAAAZdqkU0MWZA8W6woaHYOkP1SGkZlqnZSCIrADh9QUAAAAAF6kUNUXm4zuDLEcFDyTT7rk8nAOUi8eHsy4TAA==";
+
+    #[test]
+    fn test_bip174_vectors() {
+        for vector in BIP174_VALID {
+            let psbt = Psbt::from_base64(vector).unwrap();
+            // the witnesses of the non-witness utxos are kept
+            assert_eq!(psbt.to_base64(), vector);
+        }
+
+        assert_eq!(
+            Psbt::from_base64(BIP174_FILLED_SCRIPT_SIG),
+            Err(Error::UnsignedTxHasScriptData)
+        );
+        assert_eq!(
+            Psbt::from_base64(BIP174_NETWORK_TX),
+            Err(Error::InvalidMagic)
+        );
+        // missing the maps of the outputs
+        let bytes = base64::decode(BIP174_VALID[0]).unwrap();
+        assert_eq!(
+            Psbt::from_bytes(&bytes[..bytes.len() - 2]),
+            Err(Error::Io(io::Error::UnexpectedEof))
+        );
+    }
+
+    #[test]
+    fn test_taproot_invalid_keys() {
+        // the invalid taproot fields of BIP371: keys of the wrong length or
+        // not on the curve
+        let psbt = psbt_spending(&TransactionOutput::default());
+        let xonly = keypair(1).x_only().unwrap().0;
+        let not_on_curve = [0xff; 32];
+
+        let cases: [(u64, &[u8], &[u8], Error); 6] = [
+            (
+                PSBT_IN_TAP_INTERNAL_KEY,
+                &[],
+                &xonly[..31],
+                Error::InvalidValue,
+            ),
+            (
+                PSBT_IN_TAP_INTERNAL_KEY,
+                &[],
+                &not_on_curve,
+                Error::InvalidValue,
+            ),
+            (
+                PSBT_IN_TAP_BIP32_DERIVATION,
+                &xonly[..31],
+                &[0; 5],
+                Error::InvalidKey,
+            ),
+            (
+                PSBT_IN_TAP_SCRIPT_SIG,
+                &[0; 63],
+                &[0; 64],
+                Error::InvalidKey,
+            ),
+            (
+                PSBT_OUT_TAP_INTERNAL_KEY,
+                &[],
+                &[2; 33],
+                Error::InvalidValue,
+            ),
+            (
+                PSBT_OUT_TAP_BIP32_DERIVATION,
+                &[2; 33],
+                &[0; 5],
+                Error::InvalidKey,
+            ),
+        ];
+        for (index, (type_value, key, value, error)) in cases.into_iter().enumerate() {
+            let mut invalid = psbt.clone();
+            let key = Key::new(type_value, key.to_vec().into());
+            if index < 4 {
+                invalid.inputs[0].unknown.insert(key, value.to_vec().into());
+            } else {
+                invalid.outputs[0]
+                    .unknown
+                    .insert(key, value.to_vec().into());
+            }
+            assert_eq!(Psbt::from_bytes(&invalid.to_bytes()), Err(error));
+        }
+    }
+
+    #[test]
+    fn test_finalize_out_of_range() {
+        let mut psbt = psbt_spending(&TransactionOutput::default());
+        assert_eq!(psbt.finalize_input(1), Err(Error::IndexOutOfRange));
+    }
+}
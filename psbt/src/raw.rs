@@ -0,0 +1,205 @@
+//! Raw key-value pairs of a PSBT map
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeSet, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
+
+use light_bitcoin_primitives::{io, Bytes, H256, H32};
+use light_bitcoin_serialization::{
+    deserialize, CompactInteger, Deserializable, Reader, Serializable, Stream,
+};
+
+use crate::error::Error;
+
+/// Key of a PSBT map entry, the key type followed by the key data
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug, Default)]
+pub struct Key {
+    pub type_value: u64,
+    pub key: Bytes,
+}
+
+impl Key {
+    pub fn new(type_value: u64, key: Bytes) -> Self {
+        Key { type_value, key }
+    }
+
+    fn to_bytes(&self) -> Bytes {
+        let mut stream = Stream::default();
+        stream
+            .append(&CompactInteger::from(self.type_value))
+            .append_slice(&self.key);
+        stream.out()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let type_value: CompactInteger = reader.read()?;
+        let offset = type_value.serialized_size();
+        Ok(Key {
+            type_value: type_value.into(),
+            key: bytes[offset..].to_vec().into(),
+        })
+    }
+}
+
+/// A single entry of a PSBT map
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Pair {
+    pub key: Key,
+    pub value: Bytes,
+}
+
+impl Pair {
+    pub fn new(type_value: u64, key: Bytes, value: Bytes) -> Self {
+        Pair {
+            key: Key::new(type_value, key),
+            value,
+        }
+    }
+}
+
+/// Reads the pairs of a map up to its separator, rejecting duplicate keys
+pub(crate) fn read_map<T: io::Read>(reader: &mut Reader<T>) -> Result<Vec<Pair>, Error> {
+    let mut keys = BTreeSet::new();
+    let mut pairs = Vec::new();
+    loop {
+        let key: Bytes = reader.read()?;
+        if key.is_empty() {
+            return Ok(pairs);
+        }
+
+        let key = Key::from_bytes(&key)?;
+        if !keys.insert(key.clone()) {
+            return Err(Error::DuplicateKey);
+        }
+        let value: Bytes = reader.read()?;
+        pairs.push(Pair { key, value });
+    }
+}
+
+/// Writes the pairs of a map followed by its separator
+pub(crate) fn write_map(stream: &mut Stream, pairs: &[Pair]) {
+    for pair in pairs {
+        stream.append(&pair.key.to_bytes()).append(&pair.value);
+    }
+    stream.append(&0u8);
+}
+
+/// Deserializes a value that must be consumed entirely
+pub(crate) fn decode<T: Deserializable>(value: &[u8]) -> Result<T, Error> {
+    deserialize(value).map_err(|_| Error::InvalidValue)
+}
+
+/// Checks that a key has no key data
+pub(crate) fn expect_empty(key: &Key) -> Result<(), Error> {
+    if key.key.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidKey)
+    }
+}
+
+/// Master key fingerprint and derivation path of a key
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug, Default)]
+pub struct KeySource {
+    pub fingerprint: H32,
+    pub path: Vec<u32>,
+}
+
+impl KeySource {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 4 {
+            return Err(Error::InvalidValue);
+        }
+        let indexes = bytes[4..].chunks_exact(4);
+        if !indexes.remainder().is_empty() {
+            return Err(Error::InvalidValue);
+        }
+        let path = indexes
+            .map(|index| u32::from_le_bytes([index[0], index[1], index[2], index[3]]))
+            .collect();
+        Ok(KeySource {
+            fingerprint: H32::from_slice(&bytes[0..4]),
+            path,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut bytes = self.fingerprint.as_bytes().to_vec();
+        for index in &self.path {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+        bytes.into()
+    }
+}
+
+/// Decodes the leaf hashes and key source of a taproot key origin
+pub(crate) fn decode_tap_key_origin(value: &[u8]) -> Result<(Vec<H256>, KeySource), Error> {
+    let mut reader = Reader::new(value);
+    let count: CompactInteger = reader.read().map_err(|_| Error::InvalidValue)?;
+    let count = usize::from(count);
+    let offset = count
+        .checked_mul(32)
+        .and_then(|len| len.checked_add(CompactInteger::from(count).serialized_size()))
+        .filter(|offset| *offset <= value.len())
+        .ok_or(Error::InvalidValue)?;
+    let leaf_hashes = value[offset - count * 32..offset]
+        .chunks(32)
+        .map(H256::from_slice)
+        .collect();
+    Ok((leaf_hashes, KeySource::from_slice(&value[offset..])?))
+}
+
+/// Encodes the leaf hashes and key source of a taproot key origin
+pub(crate) fn encode_tap_key_origin(leaf_hashes: &[H256], source: &KeySource) -> Bytes {
+    let mut stream = Stream::default();
+    stream.append_list(leaf_hashes);
+    stream.append_slice(&source.to_bytes());
+    stream.out()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_roundtrip() {
+        let pairs = vec![
+            Pair::new(0x01, vec![1, 2].into(), vec![3].into()),
+            Pair::new(0xfc, Bytes::new(), Bytes::new()),
+        ];
+        let mut stream = Stream::default();
+        write_map(&mut stream, &pairs);
+        let bytes = stream.out();
+        assert_eq!(&bytes[..], &[3, 1, 1, 2, 1, 3, 1, 0xfc, 0, 0][..]);
+
+        let mut reader = Reader::new(&bytes[..]);
+        assert_eq!(read_map(&mut reader).unwrap(), pairs);
+        assert!(reader.is_finished());
+    }
+
+    #[test]
+    fn test_duplicate_key() {
+        let pair = Pair::new(0x01, Bytes::new(), vec![1].into());
+        let mut stream = Stream::default();
+        write_map(&mut stream, &[pair.clone(), pair]);
+        let bytes = stream.out();
+        assert_eq!(
+            read_map(&mut Reader::new(&bytes[..])),
+            Err(Error::DuplicateKey)
+        );
+    }
+
+    #[test]
+    fn test_key_source() {
+        let source = KeySource {
+            fingerprint: H32::from([0xd9, 0x0c, 0x6a, 0x4f]),
+            path: vec![0x8000_0000, 1],
+        };
+        let bytes = source.to_bytes();
+        assert_eq!(bytes.len(), 12);
+        assert_eq!(KeySource::from_slice(&bytes).unwrap(), source);
+        assert_eq!(KeySource::from_slice(&bytes[..6]), Err(Error::InvalidValue));
+    }
+}
@@ -0,0 +1,234 @@
+//! Signer role
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use light_bitcoin_chain::TransactionOutput;
+use light_bitcoin_crypto::{dhash160, sha256};
+use light_bitcoin_keys::{KeyPair, Message, Public, SchnorrSignature, XOnly};
+use light_bitcoin_primitives::{Bytes, H160};
+use light_bitcoin_script::{
+    compute_leaf_hash, Builder, Opcode, Script, ScriptExecutionData, SignatureVersion,
+    TransactionInputSigner,
+};
+
+use crate::error::Error;
+use crate::input::Input;
+use crate::psbt::Psbt;
+
+/// Sighash type used for ECDSA signatures when the input does not set one
+const SIGHASH_ALL: u32 = 1;
+/// Sighash base type that commits to the output at the input index
+const SIGHASH_SINGLE: u8 = 3;
+
+impl Psbt {
+    /// Signer: signs every input that `keypair` can sign and returns the number of
+    /// signatures added.
+    ///
+    /// ECDSA signatures are added to `partial_sigs`. For taproot inputs the key path
+    /// is signed when `keypair` is the internal key, and every leaf script containing
+    /// its x-only key is signed for the script path. `aux` is the auxiliary randomness
+    /// of [BIP340] schnorr signatures.
+    ///
+    /// [BIP340]: https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki
+    pub fn sign(&mut self, keypair: &KeyPair, aux: &Message) -> Result<usize, Error> {
+        let tx = self.unsigned_tx()?;
+        let spent_outputs = self
+            .inputs
+            .iter()
+            .zip(tx.inputs.iter())
+            .map(|(input, txin)| input.spent_output(&txin.previous_output).ok())
+            .collect::<Vec<_>>();
+        let signer = TransactionInputSigner::from(tx);
+
+        let mut signed = 0;
+        for (index, input) in self.inputs.iter_mut().enumerate() {
+            if input.is_finalized() {
+                continue;
+            }
+            let spent = match spent_outputs[index] {
+                Some(ref spent) => spent,
+                None => continue,
+            };
+
+            let script_pubkey: Script = spent.script_pubkey.clone().into();
+            signed += if script_pubkey.is_pay_to_witness_taproot() {
+                let spent_outputs = spent_outputs
+                    .iter()
+                    .cloned()
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(Error::MissingUtxo)?;
+                sign_taproot(
+                    input,
+                    &signer,
+                    index,
+                    &spent_outputs,
+                    &script_pubkey,
+                    keypair,
+                    aux,
+                )?
+            } else {
+                sign_ecdsa(input, &signer, index, spent, &script_pubkey, keypair)?
+            };
+        }
+        Ok(signed)
+    }
+}
+
+fn sign_ecdsa(
+    input: &mut Input,
+    signer: &TransactionInputSigner,
+    index: usize,
+    spent: &TransactionOutput,
+    script_pubkey: &Script,
+    keypair: &KeyPair,
+) -> Result<usize, Error> {
+    let public = keypair.public();
+    let (script_code, sigversion) = match script_code(input, script_pubkey, public)? {
+        Some(script_code) => script_code,
+        None => return Ok(0),
+    };
+
+    let sighash_type = input.sighash_type.unwrap_or(SIGHASH_ALL);
+    let hash = signer.signature_hash(index, spent.value, &script_code, sigversion, sighash_type);
+    let mut signature: Vec<u8> = keypair.private().sign(&hash)?.into();
+    signature.push(sighash_type as u8);
+    input.partial_sigs.insert(*public, signature.into());
+    Ok(1)
+}
+
+/// The script code and signature version `public` signs with, or `None` if the
+/// input is not spendable by it.
+fn script_code(
+    input: &Input,
+    script_pubkey: &Script,
+    public: &Public,
+) -> Result<Option<(Script, SignatureVersion)>, Error> {
+    let script = if script_pubkey.is_pay_to_script_hash() {
+        let redeem_script = match input.redeem_script {
+            Some(ref redeem_script) => redeem_script,
+            None => return Ok(None),
+        };
+        if dhash160(redeem_script).as_bytes() != &script_pubkey[2..22] {
+            return Err(Error::ScriptMismatch);
+        }
+        redeem_script.clone()
+    } else {
+        script_pubkey.clone()
+    };
+
+    let address_hash = public.address_hash();
+    if script.is_pay_to_witness_key_hash() {
+        if script[2..22] != address_hash[..] {
+            return Ok(None);
+        }
+        return Ok(Some((
+            Builder::build_p2pkh(&H160::from_slice(&script[2..22])),
+            SignatureVersion::WitnessV0,
+        )));
+    }
+    if script.is_pay_to_witness_script_hash() {
+        let witness_script = match input.witness_script {
+            Some(ref witness_script) => witness_script,
+            None => return Ok(None),
+        };
+        if sha256(witness_script).as_bytes() != &script[2..34] {
+            return Err(Error::ScriptMismatch);
+        }
+        return Ok(contains_push(witness_script, public)
+            .then(|| (witness_script.clone(), SignatureVersion::WitnessV0)));
+    }
+    if script.is_pay_to_public_key_hash() {
+        return Ok((script[3..23] == address_hash[..]).then_some((script, SignatureVersion::Base)));
+    }
+    Ok(contains_push(&script, public).then_some((script, SignatureVersion::Base)))
+}
+
+fn sign_taproot(
+    input: &mut Input,
+    signer: &TransactionInputSigner,
+    index: usize,
+    spent_outputs: &[TransactionOutput],
+    script_pubkey: &Script,
+    keypair: &KeyPair,
+    aux: &Message,
+) -> Result<usize, Error> {
+    let hash_type = match input.sighash_type {
+        Some(sighash_type) if sighash_type > 0xff => return Err(Error::InvalidValue),
+        Some(sighash_type) => sighash_type as u8,
+        None => 0,
+    };
+    if hash_type & 3 == SIGHASH_SINGLE && index >= signer.outputs.len() {
+        return Ok(0);
+    }
+
+    let xonly = keypair.x_only()?;
+    let mut signed = 0;
+    if input.tap_internal_key == Some(xonly) {
+        let (output_key, _) = xonly.tap_tweak(input.tap_merkle_root)?;
+        if output_key.0[..] == script_pubkey[2..34] {
+            let hash = signer.signature_hash_schnorr(
+                index,
+                spent_outputs,
+                SignatureVersion::Taproot,
+                hash_type,
+                &ScriptExecutionData::default(),
+            );
+            let signature = keypair.sign_schnorr(&hash, aux, input.tap_merkle_root)?;
+            input.tap_key_sig = Some(schnorr_signature(signature, hash_type));
+            signed += 1;
+        }
+    }
+
+    for (script, leaf_version) in input.tap_scripts.values() {
+        if !contains_x_only(script, &xonly) {
+            continue;
+        }
+        let leaf_hash = compute_leaf_hash(*leaf_version, script);
+        let execdata = ScriptExecutionData {
+            m_tapleaf_hash_init: true,
+            m_tapleaf_hash: leaf_hash,
+            ..Default::default()
+        };
+        let hash = signer.signature_hash_schnorr(
+            index,
+            spent_outputs,
+            SignatureVersion::TapScript,
+            hash_type,
+            &execdata,
+        );
+        let signature = keypair.private().sign_schnorr(&hash, aux)?;
+        input
+            .tap_script_sigs
+            .insert((xonly, leaf_hash), schnorr_signature(signature, hash_type));
+        signed += 1;
+    }
+    Ok(signed)
+}
+
+/// Serializes a schnorr signature, appending the sighash byte unless it is `SIGHASH_DEFAULT`
+fn schnorr_signature(signature: SchnorrSignature, hash_type: u8) -> Bytes {
+    let signature: [u8; 64] = signature.into();
+    let mut bytes = signature.to_vec();
+    if hash_type != 0 {
+        bytes.push(hash_type);
+    }
+    bytes.into()
+}
+
+/// Whether the script pushes `data`
+fn contains_push(script: &Script, data: &[u8]) -> bool {
+    script
+        .iter()
+        .any(|instruction| matches!(instruction, Ok(instruction) if instruction.data == Some(data)))
+}
+
+/// Whether a leaf script pushes an x-only key.
+///
+/// Leaf scripts are scanned byte by byte since tapscript opcodes such as
+/// `OP_CHECKSIGADD` are not known to the legacy script parser.
+fn contains_x_only(script: &Script, xonly: &XOnly) -> bool {
+    script
+        .windows(33)
+        .any(|window| window[0] == Opcode::OP_PUSHBYTES_32 as u8 && window[1..] == xonly.0[..])
+}
@@ -0,0 +1,624 @@
+//! Coin selection algorithms, following Bitcoin Core's wallet.
+//!
+//! Candidates are compared by their effective value, which is their value minus the
+//! fee of spending them at the current fee rate. Selections are scored with the
+//! waste metric: the fee paid now for the inputs above what spending them at the long
+//! term fee rate would cost, plus either the cost of the change output or the excess
+//! value given to the fee when there is no change.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use core::cmp::Reverse;
+
+use light_bitcoin_chain::{OutPoint, TransactionOutput};
+
+use crate::script::Script;
+use crate::transaction_builder::{dust_threshold, output_size, vsize, InputType};
+use crate::Error;
+
+/// Change value that the change producing algorithms aim for, to avoid creating tiny outputs
+pub const MIN_CHANGE: u64 = 50_000;
+
+/// Number of branches explored by branch and bound before giving up
+const BNB_TOTAL_TRIES: usize = 100_000;
+/// Number of random subsets tried by knapsack
+const KNAPSACK_ITERATIONS: usize = 1_000;
+
+/// An unspent output that may be selected
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub previous_output: OutPoint,
+    pub output: TransactionOutput,
+    /// How the output will be spent, which determines the weight of its input
+    pub input_type: InputType,
+}
+
+impl Candidate {
+    pub fn new(
+        previous_output: OutPoint,
+        output: TransactionOutput,
+        input_type: InputType,
+    ) -> Self {
+        Candidate {
+            previous_output,
+            output,
+            input_type,
+        }
+    }
+
+    /// Fee of spending the candidate at `fee_rate` sat/vB
+    pub fn fee(&self, fee_rate: u64) -> u64 {
+        vsize(self.input_type.weight()) as u64 * fee_rate
+    }
+
+    /// Value of the candidate minus the fee of spending it at `fee_rate` sat/vB
+    pub fn effective_value(&self, fee_rate: u64) -> i64 {
+        self.output.value as i64 - self.fee(fee_rate) as i64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoinSelectionParams {
+    /// Value the inputs must fund: the outputs and the fee of the transaction without
+    /// its inputs and change
+    pub target: u64,
+    /// Fee rate of the transaction in sat/vB
+    pub fee_rate: u64,
+    /// Fee rate in sat/vB expected to spend the outputs in the future
+    pub long_term_fee_rate: u64,
+    /// Fee of the change output at `fee_rate`
+    pub change_fee: u64,
+    /// Fee of creating the change output now and spending it at `long_term_fee_rate`
+    pub cost_of_change: u64,
+    /// Smallest change output worth creating, smaller change is given to the fee
+    pub min_viable_change: u64,
+    /// Change value that the change producing algorithms aim for
+    pub min_change: u64,
+}
+
+impl CoinSelectionParams {
+    /// Parameters of a transaction paying `target` whose change goes to `change_script`
+    /// and will be spent as `change_input`.
+    pub fn new(
+        target: u64,
+        fee_rate: u64,
+        long_term_fee_rate: u64,
+        change_script: &Script,
+        change_input: InputType,
+    ) -> Self {
+        let change_fee = vsize(4 * output_size(change_script.len())) as u64 * fee_rate;
+        let change_spend_fee = vsize(change_input.weight()) as u64 * long_term_fee_rate;
+        CoinSelectionParams {
+            target,
+            fee_rate,
+            long_term_fee_rate,
+            change_fee,
+            cost_of_change: change_fee + change_spend_fee,
+            min_viable_change: dust_threshold(change_script),
+            min_change: MIN_CHANGE,
+        }
+    }
+}
+
+/// Selected candidates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub selected: Vec<Candidate>,
+    /// Waste metric of the selection, lower is better
+    pub waste: i64,
+    /// Value of the change output, 0 if the excess is given to the fee
+    pub change: u64,
+}
+
+impl Selection {
+    fn new(selected: Vec<Candidate>, params: &CoinSelectionParams) -> Self {
+        let effective_value = selected
+            .iter()
+            .map(|candidate| candidate.effective_value(params.fee_rate))
+            .sum::<i64>();
+        let excess = effective_value - params.target as i64;
+        let change = excess - params.change_fee as i64;
+        let change = if change >= params.min_viable_change as i64 {
+            change as u64
+        } else {
+            0
+        };
+
+        let waste = selected
+            .iter()
+            .map(|candidate| {
+                candidate.fee(params.fee_rate) as i64
+                    - candidate.fee(params.long_term_fee_rate) as i64
+            })
+            .sum::<i64>()
+            + if change > 0 {
+                params.cost_of_change as i64
+            } else {
+                excess
+            };
+
+        Selection {
+            selected,
+            waste,
+            change,
+        }
+    }
+
+    /// Total value of the selected outputs
+    pub fn selected_value(&self) -> u64 {
+        self.selected
+            .iter()
+            .map(|candidate| candidate.output.value)
+            .sum()
+    }
+}
+
+/// Selects the candidates with the lowest waste among branch and bound, knapsack and
+/// single random draw. `seed` drives the randomized algorithms.
+pub fn select_coins(
+    candidates: &[Candidate],
+    params: &CoinSelectionParams,
+    seed: u64,
+) -> Result<Selection, Error> {
+    let mut rng = Rng(seed);
+    let results = [
+        branch_and_bound(candidates, params),
+        knapsack(candidates, params, rng.next()),
+        single_random_draw(candidates, params, rng.next()),
+    ];
+
+    let mut best: Option<Selection> = None;
+    let mut error = Error::InsufficientFunds;
+    for result in results {
+        match result {
+            // ties go to the selection spending more inputs
+            Ok(selection) => match best {
+                Some(ref current)
+                    if (current.waste, usize::MAX - current.selected.len())
+                        <= (selection.waste, usize::MAX - selection.selected.len()) => {}
+                _ => best = Some(selection),
+            },
+            Err(err) => error = err,
+        }
+    }
+    best.ok_or(error)
+}
+
+/// Searches for a selection without change whose effective value is between the target
+/// and the target plus the cost of change, minimizing the waste.
+pub fn branch_and_bound(
+    candidates: &[Candidate],
+    params: &CoinSelectionParams,
+) -> Result<Selection, Error> {
+    let mut pool = positive_candidates(candidates, params.fee_rate);
+    pool.sort_by_key(|&(_, value)| Reverse(value));
+
+    let target = params.target as i64;
+    let upper_bound = target + params.cost_of_change as i64;
+    let mut curr_available = pool.iter().map(|(_, value)| value).sum::<i64>();
+    if curr_available < target {
+        return Err(Error::InsufficientFunds);
+    }
+    let input_waste = |index: usize| {
+        let candidate = pool[index].0;
+        candidate.fee(params.fee_rate) as i64 - candidate.fee(params.long_term_fee_rate) as i64
+    };
+    let fee_rate_high = params.fee_rate > params.long_term_fee_rate;
+
+    let mut curr_value = 0;
+    let mut curr_waste = 0;
+    let mut curr_selection: Vec<usize> = Vec::new();
+    let mut best_selection: Option<Vec<usize>> = None;
+    let mut best_waste = i64::MAX;
+
+    let mut index = 0;
+    for _ in 0..BNB_TOTAL_TRIES {
+        let mut backtrack = false;
+        if curr_value + curr_available < target
+            || curr_value > upper_bound
+            || (fee_rate_high && curr_waste > best_waste)
+        {
+            backtrack = true;
+        } else if curr_value >= target {
+            let waste = curr_waste + curr_value - target;
+            if waste <= best_waste {
+                best_selection = Some(curr_selection.clone());
+                best_waste = waste;
+            }
+            backtrack = true;
+        }
+
+        if backtrack {
+            let last = match curr_selection.last() {
+                Some(&last) => last,
+                None => break,
+            };
+            // the candidates after the last included one are available again
+            index -= 1;
+            while index > last {
+                curr_available += pool[index].1;
+                index -= 1;
+            }
+            // exclude the last included candidate
+            curr_value -= pool[index].1;
+            curr_waste -= input_waste(index);
+            curr_selection.pop();
+        } else {
+            curr_available -= pool[index].1;
+            // skip candidates equivalent to an excluded previous one
+            if curr_selection.last().is_none_or(|&last| last + 1 == index)
+                || pool[index].1 != pool[index - 1].1
+                || input_waste(index) != input_waste(index - 1)
+            {
+                curr_selection.push(index);
+                curr_value += pool[index].1;
+                curr_waste += input_waste(index);
+            }
+        }
+        index += 1;
+    }
+
+    let selection = best_selection.ok_or(Error::NoChangelessSelection)?;
+    Ok(Selection::new(
+        selection
+            .into_iter()
+            .map(|index| pool[index].0.clone())
+            .collect(),
+        params,
+    ))
+}
+
+/// Bitcoin Core's knapsack solver, approximating the smallest subset that pays for the
+/// target and the change output while leaving at least `min_change`.
+pub fn knapsack(
+    candidates: &[Candidate],
+    params: &CoinSelectionParams,
+    seed: u64,
+) -> Result<Selection, Error> {
+    let mut rng = Rng(seed);
+    let mut pool = positive_candidates(candidates, params.fee_rate);
+    rng.shuffle(&mut pool);
+
+    let target = (params.target + params.change_fee) as i64;
+    let min_change = params.min_change as i64;
+    let mut applicable = Vec::new();
+    let mut lowest_larger: Option<(&Candidate, i64)> = None;
+    let mut total_lower = 0;
+    for (candidate, value) in pool {
+        if value == target {
+            return Ok(Selection::new(vec![candidate.clone()], params));
+        } else if value < target + min_change {
+            applicable.push((candidate, value));
+            total_lower += value;
+        } else if lowest_larger.is_none_or(|(_, lowest)| value < lowest) {
+            lowest_larger = Some((candidate, value));
+        }
+    }
+
+    let select =
+        |selected: Vec<&Candidate>| Selection::new(selected.into_iter().cloned().collect(), params);
+    if total_lower == target {
+        return Ok(select(applicable.into_iter().map(|(c, _)| c).collect()));
+    }
+    if total_lower < target {
+        return lowest_larger
+            .map(|(candidate, _)| select(vec![candidate]))
+            .ok_or(Error::InsufficientFunds);
+    }
+
+    applicable.sort_by_key(|&(_, value)| Reverse(value));
+    let values = applicable
+        .iter()
+        .map(|(_, value)| *value)
+        .collect::<Vec<_>>();
+    let (mut best, mut best_value) =
+        approximate_best_subset(&mut rng, &values, total_lower, target);
+    if best_value != target && total_lower >= target + min_change {
+        (best, best_value) =
+            approximate_best_subset(&mut rng, &values, total_lower, target + min_change);
+    }
+
+    match lowest_larger {
+        Some((candidate, value))
+            if (best_value != target && best_value < target + min_change)
+                || value <= best_value =>
+        {
+            Ok(select(vec![candidate]))
+        }
+        _ => Ok(select(
+            applicable
+                .into_iter()
+                .zip(best)
+                .filter(|(_, included)| *included)
+                .map(|((candidate, _), _)| candidate)
+                .collect(),
+        )),
+    }
+}
+
+/// Randomly includes values to find the subset with the smallest total of at least `target`
+fn approximate_best_subset(
+    rng: &mut Rng,
+    values: &[i64],
+    total_lower: i64,
+    target: i64,
+) -> (Vec<bool>, i64) {
+    let mut best = vec![true; values.len()];
+    let mut best_value = total_lower;
+
+    for _ in 0..KNAPSACK_ITERATIONS {
+        if best_value == target {
+            break;
+        }
+        let mut included = vec![false; values.len()];
+        let mut total = 0;
+        let mut reached_target = false;
+        for pass in 0..2 {
+            if reached_target {
+                break;
+            }
+            for (i, &value) in values.iter().enumerate() {
+                // the first pass includes values at random, the second one the rest
+                let include = if pass == 0 {
+                    rng.next() & 1 == 1
+                } else {
+                    !included[i]
+                };
+                if !include {
+                    continue;
+                }
+                total += value;
+                included[i] = true;
+                if total >= target {
+                    reached_target = true;
+                    if total < best_value {
+                        best_value = total;
+                        best = included.clone();
+                    }
+                    total -= value;
+                    included[i] = false;
+                }
+            }
+        }
+    }
+    (best, best_value)
+}
+
+/// Selects the candidates with the largest effective value first until the target and
+/// the change output are paid for.
+pub fn largest_first(
+    candidates: &[Candidate],
+    params: &CoinSelectionParams,
+) -> Result<Selection, Error> {
+    let mut pool = positive_candidates(candidates, params.fee_rate);
+    pool.sort_by_key(|&(_, value)| Reverse(value));
+    accumulate(pool, params, (params.target + params.change_fee) as i64)
+}
+
+/// Selects random candidates until the target, the change output and `min_change` are
+/// paid for.
+pub fn single_random_draw(
+    candidates: &[Candidate],
+    params: &CoinSelectionParams,
+    seed: u64,
+) -> Result<Selection, Error> {
+    let mut pool = positive_candidates(candidates, params.fee_rate);
+    Rng(seed).shuffle(&mut pool);
+    let target = params.target + params.change_fee + params.min_change;
+    accumulate(pool, params, target as i64)
+}
+
+/// Takes candidates in order until their effective value reaches `target`
+fn accumulate(
+    pool: Vec<(&Candidate, i64)>,
+    params: &CoinSelectionParams,
+    target: i64,
+) -> Result<Selection, Error> {
+    let mut selected = Vec::new();
+    let mut total = 0;
+    for (candidate, value) in pool {
+        selected.push(candidate.clone());
+        total += value;
+        if total >= target {
+            return Ok(Selection::new(selected, params));
+        }
+    }
+    Err(Error::InsufficientFunds)
+}
+
+/// Candidates worth spending at `fee_rate` with their effective value
+fn positive_candidates(candidates: &[Candidate], fee_rate: u64) -> Vec<(&Candidate, i64)> {
+    candidates
+        .iter()
+        .map(|candidate| (candidate, candidate.effective_value(fee_rate)))
+        .filter(|(_, value)| *value > 0)
+        .collect()
+}
+
+/// SplitMix64 generator, randomness for coin selection only needs to avoid patterns
+/// and tests need it reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use light_bitcoin_primitives::H256;
+
+    fn candidates(values: &[u64]) -> Vec<Candidate> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| {
+                Candidate::new(
+                    OutPoint {
+                        txid: H256::repeat_byte(1),
+                        index: index as u32,
+                    },
+                    TransactionOutput {
+                        value,
+                        script_pubkey: Default::default(),
+                    },
+                    InputType::P2WPKH,
+                )
+            })
+            .collect()
+    }
+
+    fn values(selection: &Selection) -> Vec<u64> {
+        let mut values = selection
+            .selected
+            .iter()
+            .map(|candidate| candidate.output.value)
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+        values
+    }
+
+    /// Parameters without fees, so effective values are the values
+    fn params(target: u64, cost_of_change: u64) -> CoinSelectionParams {
+        CoinSelectionParams {
+            target,
+            fee_rate: 0,
+            long_term_fee_rate: 0,
+            change_fee: 0,
+            cost_of_change,
+            min_viable_change: 3,
+            min_change: 0,
+        }
+    }
+
+    #[test]
+    fn test_branch_and_bound() {
+        let pool = candidates(&[1, 2, 3, 4]);
+        let selection = branch_and_bound(&pool, &params(5, 0)).unwrap();
+        assert_eq!(selection.selected.len(), 2);
+        assert_eq!(selection.waste, 0);
+        assert_eq!(selection.change, 0);
+
+        let selection = branch_and_bound(&pool, &params(10, 0)).unwrap();
+        assert_eq!(values(&selection), vec![1, 2, 3, 4]);
+
+        // the excess is within the cost of change
+        let pool = candidates(&[5, 9, 13]);
+        let selection = branch_and_bound(&pool, &params(12, 2)).unwrap();
+        assert_eq!(values(&selection), vec![13]);
+        assert_eq!(selection.waste, 1);
+
+        assert_eq!(
+            branch_and_bound(&pool, &params(12, 0)),
+            Err(Error::NoChangelessSelection)
+        );
+        assert_eq!(
+            branch_and_bound(&pool, &params(28, 0)),
+            Err(Error::InsufficientFunds)
+        );
+    }
+
+    #[test]
+    fn test_branch_and_bound_fees() {
+        // each P2WPKH input is 69 vB
+        let pool = candidates(&[10_000, 20_000, 30_690, 40_000]);
+        let params = CoinSelectionParams {
+            target: 30_000,
+            fee_rate: 10,
+            long_term_fee_rate: 5,
+            change_fee: 310,
+            cost_of_change: 500,
+            min_viable_change: 294,
+            min_change: MIN_CHANGE,
+        };
+        let selection = branch_and_bound(&pool, &params).unwrap();
+        assert_eq!(values(&selection), vec![30_690]);
+        assert_eq!(selection.waste, 69 * 5);
+    }
+
+    #[test]
+    fn test_knapsack() {
+        let pool = candidates(&[1, 2, 5, 10, 20]);
+        let selection = knapsack(&pool, &params(20, 0), 1).unwrap();
+        assert_eq!(values(&selection), vec![20]);
+
+        let selection = knapsack(&pool, &params(7, 0), 1).unwrap();
+        assert_eq!(values(&selection), vec![2, 5]);
+
+        // all the smaller candidates together
+        let selection = knapsack(&pool, &params(38, 0), 1).unwrap();
+        assert_eq!(values(&selection), vec![1, 2, 5, 10, 20]);
+
+        // the lowest larger candidate beats a subset far above the target
+        let mut with_min_change = params(6, 0);
+        with_min_change.min_change = 10;
+        let selection = knapsack(&candidates(&[4, 4, 4, 16]), &with_min_change, 1).unwrap();
+        assert_eq!(values(&selection), vec![16]);
+
+        assert_eq!(
+            knapsack(&pool, &params(39, 0), 1),
+            Err(Error::InsufficientFunds)
+        );
+    }
+
+    #[test]
+    fn test_largest_first_and_single_random_draw() {
+        let pool = candidates(&[3, 8, 1, 5]);
+        let selection = largest_first(&pool, &params(10, 0)).unwrap();
+        assert_eq!(values(&selection), vec![5, 8]);
+        assert_eq!(selection.change, 3);
+
+        for seed in 0..10 {
+            let selection = single_random_draw(&pool, &params(10, 0), seed).unwrap();
+            assert!(selection.selected_value() >= 10);
+        }
+        assert_eq!(
+            single_random_draw(&pool, &params(18, 0), 0),
+            Err(Error::InsufficientFunds)
+        );
+    }
+
+    #[test]
+    fn test_select_coins() {
+        let pool = candidates(&[4, 8, 1, 6]);
+        // 8 + 1 is the only selection without excess or change
+        let selection = select_coins(&pool, &params(9, 2), 7).unwrap();
+        assert_eq!(values(&selection), vec![1, 8]);
+        assert_eq!(selection.waste, 0);
+    }
+
+    #[test]
+    fn test_waste() {
+        let pool = candidates(&[100_000]);
+        let mut params = params(90_000, 1_000);
+        params.fee_rate = 10;
+        params.long_term_fee_rate = 2;
+        params.change_fee = 310;
+        params.min_viable_change = 294;
+
+        // input fee difference plus the cost of change
+        let selection = Selection::new(pool.clone(), &params);
+        assert_eq!(selection.change, 100_000 - 690 - 90_000 - 310);
+        assert_eq!(selection.waste, 69 * 8 + 1_000);
+
+        // the excess is given to the fee
+        params.target = 99_000;
+        let selection = Selection::new(pool, &params);
+        assert_eq!(selection.change, 0);
+        assert_eq!(selection.waste, 69 * 8 + 100_000 - 690 - 99_000);
+    }
+}
@@ -73,6 +73,14 @@ pub enum Error {
     EmptyWitness,
     InvalidSignature,
     VerifyCommitment,
+
+    // Transaction builder errors
+    NoInputs,
+    InsufficientFunds,
+    FeeOverflow,
+
+    // Coin selection errors
+    NoChangelessSelection,
 }
 
 #[cfg(feature = "std")]
@@ -157,6 +165,14 @@ impl core::fmt::Display for Error {
             Error::EmptyWitness => "Witness is empty".fmt(f),
             Error::InvalidSignature => "Signature resolution failed".fmt(f),
             Error::VerifyCommitment => "Failure to verify commitment".fmt(f),
+
+            // Transaction builder errors
+            Error::NoInputs => "Transaction has no inputs".fmt(f),
+            Error::InsufficientFunds => "Inputs do not cover the outputs and fee".fmt(f),
+            Error::FeeOverflow => "Fee does not fit in 64 bits".fmt(f),
+
+            // Coin selection errors
+            Error::NoChangelessSelection => "No input selection without change found".fmt(f),
         }
     }
 }
@@ -0,0 +1,844 @@
+//! Transactions and blocks in the JSON of Bitcoin Core, as returned by
+//! `decoderawtransaction` and `getblock` with verbosity 2.
+//!
+//! Hashes are displayed reversed, like Core does, and values are in BTC. The
+//! fields which depend on the chain, like `confirmations` or `height`, are not
+//! produced, and are ignored when parsing. So is the `desc` of the scripts.
+
+#[cfg(not(feature = "std"))]
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+use light_bitcoin_chain::{
+    Block, BlockHeader, OutPoint, Transaction, TransactionInput, TransactionOutput,
+};
+use light_bitcoin_keys::{encode_witness_program, Address, Network};
+use light_bitcoin_primitives::serde_hex::{hash_rev, option_hash_rev};
+use light_bitcoin_primitives::{Bytes, Compact, H256};
+use light_bitcoin_serialization::{serialize, serialize_with_flags, SERIALIZE_TRANSACTION_WITNESS};
+use serde::{Deserialize, Serialize};
+
+use crate::opcode::Opcode;
+use crate::script::{Script, ScriptType, MAX_SCRIPT_SIZE};
+
+/// Errors building a transaction or a block from its JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A field needed to build the transaction is missing
+    MissingField(&'static str),
+    /// The computed hash is not the one of the JSON
+    HashMismatch,
+    /// The number of transactions is not the one of the list
+    TransactionCountMismatch,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingField(field) => write!(f, "Missing field {}", field),
+            Error::HashMismatch => "Hash does not match the fields".fmt(f),
+            Error::TransactionCountMismatch => {
+                "Transaction count does not match the transactions".fmt(f)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// A transaction, as returned by `decoderawtransaction`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionJson {
+    #[serde(with = "hash_rev")]
+    pub txid: H256,
+    /// The wtxid
+    #[serde(with = "hash_rev")]
+    pub hash: H256,
+    pub version: i32,
+    pub size: usize,
+    pub vsize: usize,
+    pub weight: usize,
+    #[serde(rename = "locktime")]
+    pub lock_time: u32,
+    pub vin: Vec<InputJson>,
+    pub vout: Vec<OutputJson>,
+    /// The serialized transaction, only in the transactions of a block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hex: Option<Bytes>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputJson {
+    /// The script of the input of a coinbase
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<Bytes>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "option_hash_rev"
+    )]
+    pub txid: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vout: Option<u32>,
+    #[serde(rename = "scriptSig", default, skip_serializing_if = "Option::is_none")]
+    pub script_sig: Option<ScriptSigJson>,
+    #[serde(rename = "txinwitness", default, skip_serializing_if = "Vec::is_empty")]
+    pub witness: Vec<Bytes>,
+    pub sequence: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptSigJson {
+    pub asm: String,
+    pub hex: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputJson {
+    /// The value, in satoshis but written in BTC
+    #[serde(with = "btc")]
+    pub value: u64,
+    pub n: u32,
+    #[serde(rename = "scriptPubKey")]
+    pub script_pubkey: ScriptPubKeyJson,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptPubKeyJson {
+    pub asm: String,
+    pub hex: Bytes,
+    /// The address paid, when the script has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// A block header, with the fields of `getblockheader` which do not depend on
+/// the chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockHeaderJson {
+    #[serde(with = "hash_rev")]
+    pub hash: H256,
+    pub version: i32,
+    #[serde(rename = "versionHex", default)]
+    pub version_hex: String,
+    #[serde(rename = "merkleroot", with = "hash_rev")]
+    pub merkle_root: H256,
+    pub time: u32,
+    pub nonce: u32,
+    #[serde(with = "bits")]
+    pub bits: Compact,
+    #[serde(default)]
+    pub difficulty: f64,
+    /// The previous block, missing for the genesis block
+    #[serde(
+        rename = "previousblockhash",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "option_hash_rev"
+    )]
+    pub previous_block_hash: Option<H256>,
+}
+
+/// A block, as returned by `getblock` with verbosity 2
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockJson {
+    #[serde(flatten)]
+    pub header: BlockHeaderJson,
+    #[serde(rename = "nTx")]
+    pub transaction_count: usize,
+    #[serde(rename = "strippedsize")]
+    pub stripped_size: usize,
+    pub size: usize,
+    pub weight: usize,
+    #[serde(rename = "tx")]
+    pub transactions: Vec<TransactionJson>,
+}
+
+impl TransactionJson {
+    /// The addresses of the outputs are the ones of the network.
+    pub fn new(transaction: &Transaction, network: Network) -> Self {
+        let is_coinbase = transaction.is_coinbase();
+        TransactionJson {
+            txid: transaction.hash(),
+            hash: transaction.witness_hash(),
+            version: transaction.version,
+            size: transaction.size(),
+            vsize: transaction.vsize(),
+            weight: transaction.weight(),
+            lock_time: transaction.lock_time,
+            vin: transaction
+                .inputs
+                .iter()
+                .map(|input| InputJson::new(input, is_coinbase))
+                .collect(),
+            vout: transaction
+                .outputs
+                .iter()
+                .enumerate()
+                .map(|(n, output)| OutputJson::new(output, n as u32, network))
+                .collect(),
+            hex: None,
+        }
+    }
+
+    /// Builds the transaction back, and checks that it has the txid of the JSON.
+    pub fn to_transaction(&self) -> Result<Transaction, Error> {
+        let transaction = Transaction {
+            version: self.version,
+            inputs: self
+                .vin
+                .iter()
+                .map(InputJson::to_input)
+                .collect::<Result<_, _>>()?,
+            outputs: self.vout.iter().map(OutputJson::to_output).collect(),
+            lock_time: self.lock_time,
+        };
+        if transaction.hash() != self.txid || transaction.witness_hash() != self.hash {
+            return Err(Error::HashMismatch);
+        }
+        Ok(transaction)
+    }
+}
+
+impl InputJson {
+    fn new(input: &TransactionInput, is_coinbase: bool) -> Self {
+        let (coinbase, txid, vout, script_sig) = if is_coinbase {
+            (Some(input.script_sig.clone()), None, None, None)
+        } else {
+            let script_sig = ScriptSigJson {
+                asm: asm(&input.script_sig, true),
+                hex: input.script_sig.clone(),
+            };
+            (
+                None,
+                Some(input.previous_output.txid),
+                Some(input.previous_output.index),
+                Some(script_sig),
+            )
+        };
+        InputJson {
+            coinbase,
+            txid,
+            vout,
+            script_sig,
+            witness: input.script_witness.clone(),
+            sequence: input.sequence,
+        }
+    }
+
+    fn to_input(&self) -> Result<TransactionInput, Error> {
+        let (previous_output, script_sig) = match self.coinbase {
+            Some(ref coinbase) => (OutPoint::null(), coinbase.clone()),
+            None => {
+                let txid = self.txid.ok_or(Error::MissingField("txid"))?;
+                let index = self.vout.ok_or(Error::MissingField("vout"))?;
+                let script_sig = self
+                    .script_sig
+                    .as_ref()
+                    .ok_or(Error::MissingField("scriptSig"))?;
+                (OutPoint::new(txid, index), script_sig.hex.clone())
+            }
+        };
+        Ok(TransactionInput {
+            previous_output,
+            script_sig,
+            sequence: self.sequence,
+            script_witness: self.witness.clone(),
+        })
+    }
+}
+
+impl OutputJson {
+    fn new(output: &TransactionOutput, n: u32, network: Network) -> Self {
+        let script: Script = output.script_pubkey.clone().into();
+        OutputJson {
+            value: output.value,
+            n,
+            script_pubkey: ScriptPubKeyJson {
+                asm: asm(&script, false),
+                hex: output.script_pubkey.clone(),
+                address: address(&script, network),
+                kind: script_type_name(&script).into(),
+            },
+        }
+    }
+
+    fn to_output(&self) -> TransactionOutput {
+        TransactionOutput {
+            value: self.value,
+            script_pubkey: self.script_pubkey.hex.clone(),
+        }
+    }
+}
+
+impl BlockHeaderJson {
+    pub fn new(header: &BlockHeader) -> Self {
+        let previous_block_hash = header.previous_header_hash;
+        BlockHeaderJson {
+            hash: header.hash(),
+            version: header.version as i32,
+            version_hex: format!("{:08x}", header.version),
+            merkle_root: header.merkle_root_hash,
+            time: header.time,
+            nonce: header.nonce,
+            bits: header.bits,
+            difficulty: header.bits.to_f64(),
+            previous_block_hash: if previous_block_hash.is_zero() {
+                None
+            } else {
+                Some(previous_block_hash)
+            },
+        }
+    }
+
+    /// Builds the header back, and checks that it has the hash of the JSON.
+    pub fn to_header(&self) -> Result<BlockHeader, Error> {
+        let header = BlockHeader {
+            version: self.version as u32,
+            previous_header_hash: self.previous_block_hash.unwrap_or_default(),
+            merkle_root_hash: self.merkle_root,
+            time: self.time,
+            bits: self.bits,
+            nonce: self.nonce,
+        };
+        if header.hash() != self.hash {
+            return Err(Error::HashMismatch);
+        }
+        Ok(header)
+    }
+}
+
+impl BlockJson {
+    /// The addresses of the outputs are the ones of the network.
+    pub fn new(block: &Block, network: Network) -> Self {
+        let stripped_size = serialize(block).len();
+        let size = serialize_with_flags(block, SERIALIZE_TRANSACTION_WITNESS).len();
+        BlockJson {
+            header: BlockHeaderJson::new(&block.header),
+            transaction_count: block.transactions.len(),
+            stripped_size,
+            size,
+            weight: stripped_size * 3 + size,
+            transactions: block
+                .transactions
+                .iter()
+                .map(|transaction| TransactionJson {
+                    hex: Some(serialize_with_flags(
+                        transaction,
+                        SERIALIZE_TRANSACTION_WITNESS,
+                    )),
+                    ..TransactionJson::new(transaction, network)
+                })
+                .collect(),
+        }
+    }
+
+    /// Builds the block back, checking the hashes of the header and of the
+    /// transactions, the merkle root and the number of transactions.
+    pub fn to_block(&self) -> Result<Block, Error> {
+        if self.transaction_count != self.transactions.len() {
+            return Err(Error::TransactionCountMismatch);
+        }
+        let transactions = self
+            .transactions
+            .iter()
+            .map(TransactionJson::to_transaction)
+            .collect::<Result<_, _>>()?;
+        let block = Block::new(self.header.to_header()?, transactions);
+        if block.merkle_root() != block.header.merkle_root_hash {
+            return Err(Error::HashMismatch);
+        }
+        Ok(block)
+    }
+}
+
+/// The script as the assembly of Core, pushes in hex and small numbers in
+/// decimal.
+///
+/// With `decode_sighash`, like for the scripts of the inputs, a push which is
+/// a signature shows its sighash type in brackets instead of its last byte.
+pub fn asm(script: &[u8], decode_sighash: bool) -> String {
+    let unspendable =
+        script.first() == Some(&(Opcode::OP_RETURN as u8)) || script.len() > MAX_SCRIPT_SIZE;
+    let mut parts = Vec::new();
+    let mut rest = script;
+    while let Some((&opcode, tail)) = rest.split_first() {
+        rest = tail;
+        if opcode > Opcode::OP_PUSHDATA4 as u8 {
+            parts.push(opcode_name(opcode));
+            continue;
+        }
+
+        let (len, header) = match opcode {
+            0x4c => (rest.first().map(|len| usize::from(*len)), 1usize),
+            0x4d => (
+                rest.get(..2)
+                    .map(|len| usize::from(u16::from_le_bytes([len[0], len[1]]))),
+                2,
+            ),
+            0x4e => (
+                rest.get(..4)
+                    .map(|len| u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize),
+                4,
+            ),
+            _ => (Some(usize::from(opcode)), 0),
+        };
+        let data = match len.and_then(|len| rest.get(header..header.checked_add(len)?)) {
+            Some(data) => data,
+            None => {
+                parts.push("[error]".into());
+                break;
+            }
+        };
+        rest = &rest[header + data.len()..];
+
+        if data.len() <= 4 {
+            parts.push(format!("{}", script_num(data)));
+        } else if decode_sighash && !unspendable && is_signature(data) {
+            let (sighash, signature) = data.split_last().expect("signatures are not empty; qed");
+            parts.push(format!(
+                "{}[{}]",
+                hex::encode(signature),
+                sighash_name(*sighash).expect("checked by is_signature; qed")
+            ));
+        } else {
+            parts.push(hex::encode(data));
+        }
+    }
+    parts.join(" ")
+}
+
+fn opcode_name(opcode: u8) -> String {
+    match opcode {
+        0x4f => "-1".into(),
+        0x51..=0x60 => format!("{}", opcode - 0x50),
+        0xba => "OP_CHECKSIGADD".into(),
+        0xff => "OP_INVALIDOPCODE".into(),
+        0x50 | 0x61..=0xb9 => format!(
+            "{:?}",
+            Opcode::from_u8(opcode).expect("opcodes up to OP_NOP10 are defined; qed")
+        ),
+        _ => "OP_UNKNOWN".into(),
+    }
+}
+
+/// A number of at most 4 bytes, little endian with the sign in the last bit.
+fn script_num(data: &[u8]) -> i64 {
+    let mut value = 0i64;
+    for (i, byte) in data.iter().enumerate() {
+        value |= i64::from(*byte) << (8 * i);
+    }
+    match data.last() {
+        Some(last) if last & 0x80 != 0 => -(value & !(0x80i64 << (8 * (data.len() - 1)))),
+        _ => value,
+    }
+}
+
+fn sighash_name(sighash: u8) -> Option<&'static str> {
+    let name = match sighash {
+        0x01 => "ALL",
+        0x02 => "NONE",
+        0x03 => "SINGLE",
+        0x81 => "ALL|ANYONECANPAY",
+        0x82 => "NONE|ANYONECANPAY",
+        0x83 => "SINGLE|ANYONECANPAY",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// A strict DER signature followed by a defined sighash type, see BIP66.
+fn is_signature(sig: &[u8]) -> bool {
+    if sig.len() < 9 || sig.len() > 73 || sig[0] != 0x30 || usize::from(sig[1]) != sig.len() - 3 {
+        return false;
+    }
+    let len_r = usize::from(sig[3]);
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = usize::from(sig[5 + len_r]);
+    if len_r + len_s + 7 != sig.len() {
+        return false;
+    }
+
+    let integer = |start: usize, len: usize| {
+        sig[start - 2] == 0x02
+            && len != 0
+            && sig[start] & 0x80 == 0
+            && !(len > 1 && sig[start] == 0 && sig[start + 1] & 0x80 == 0)
+    };
+    integer(4, len_r) && integer(len_r + 6, len_s) && sighash_name(sig[sig.len() - 1]).is_some()
+}
+
+fn script_type_name(script: &Script) -> &'static str {
+    match script.script_type() {
+        ScriptType::PubKey => "pubkey",
+        ScriptType::PubKeyHash => "pubkeyhash",
+        ScriptType::ScriptHash => "scripthash",
+        ScriptType::Multisig => "multisig",
+        ScriptType::NullData => "nulldata",
+        ScriptType::WitnessV0Scripthash => "witness_v0_scripthash",
+        ScriptType::WitnessV0Keyhash => "witness_v0_keyhash",
+        ScriptType::WitnessV1Taproot => "witness_v1_taproot",
+        ScriptType::WitnessUnknown => "witness_unknown",
+        ScriptType::NonStandard if unknown_witness_program(script).is_some() => "witness_unknown",
+        ScriptType::NonStandard => "nonstandard",
+    }
+}
+
+/// A witness program of a version without consensus meaning yet. Like in Core,
+/// version 0 programs of other lengths are nonstandard.
+fn unknown_witness_program(script: &Script) -> Option<(u8, &[u8])> {
+    script
+        .parse_witness_program()
+        .filter(|(version, _)| *version != 0)
+}
+
+/// The address of the scripts with a single destination, without the bare
+/// public keys, including the witness programs of unknown versions.
+fn address(script: &Script, network: Network) -> Option<String> {
+    match script.script_type() {
+        ScriptType::PubKeyHash
+        | ScriptType::ScriptHash
+        | ScriptType::WitnessV0Keyhash
+        | ScriptType::WitnessV0Scripthash
+        | ScriptType::WitnessV1Taproot => {}
+        ScriptType::WitnessUnknown | ScriptType::NonStandard => {
+            let (version, program) = unknown_witness_program(script)?;
+            return encode_witness_program(version, program, network).ok();
+        }
+        _ => return None,
+    }
+    let destination = script.extract_destinations().ok()?.pop()?;
+    let address = Address {
+        kind: destination.kind,
+        network,
+        hash: destination.hash,
+    };
+    Some(format!("{}", address))
+}
+
+/// Satoshis as a number of BTC, read with the decimal and range rules of `Amount`.
+mod btc {
+    #[cfg(not(feature = "std"))]
+    use alloc::format;
+
+    use light_bitcoin_chain::constants::SATOSHIS_IN_COIN;
+    use light_bitcoin_chain::{Amount, Denomination};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(*value as f64 / SATOSHIS_IN_COIN as f64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        // rounded to the satoshi, then parsed like any other BTC amount
+        let value = format!("{:.8}", f64::deserialize(deserializer)?);
+        Amount::from_str_in(&value, Denomination::Bitcoin)
+            .map(Amount::to_sat)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// The compact target as 8 hex digits.
+mod bits {
+    #[cfg(not(feature = "std"))]
+    use alloc::{format, string::String};
+
+    use light_bitcoin_primitives::Compact;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bits: &Compact, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:08x}", u32::from(*bits)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Compact, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        u32::from_str_radix(&s, 16)
+            .map(Compact::from)
+            .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use light_bitcoin_serialization::deserialize;
+    use serde_json::json;
+
+    // the first transaction between two people, in block 170
+    const TX_170: &str = "0100000001c997a5e56e104102fa209c6a852dd90660a20b2d9c352423edce25857fcd3704000000004847304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901ffffffff0200ca9a3b00000000434104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac00000000";
+    // the genesis block of testnet3
+    const TESTNET_GENESIS: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff001d1aa4ae180101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+    #[test]
+    fn test_transaction_json() {
+        let tx: Transaction = deserialize(hex::decode(TX_170).unwrap().as_slice()).unwrap();
+        let value = serde_json::to_value(TransactionJson::new(&tx, Network::Mainnet)).unwrap();
+        let expected = json!({
+            "txid": "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+            "hash": "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+            "version": 1,
+            "size": 275,
+            "vsize": 275,
+            "weight": 1100,
+            "locktime": 0,
+            "vin": [{
+                "txid": "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9",
+                "vout": 0,
+                "scriptSig": {
+                    "asm": "304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d09[ALL]",
+                    "hex": "47304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901"
+                },
+                "sequence": 4294967295u32
+            }],
+            "vout": [{
+                "value": 10.0,
+                "n": 0,
+                "scriptPubKey": {
+                    "asm": "04ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84c OP_CHECKSIG",
+                    "hex": "4104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac",
+                    "type": "pubkey"
+                }
+            }, {
+                "value": 40.0,
+                "n": 1,
+                "scriptPubKey": {
+                    "asm": "0411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3 OP_CHECKSIG",
+                    "hex": "410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac",
+                    "type": "pubkey"
+                }
+            }]
+        });
+        assert_eq!(value, expected);
+
+        // the decimals of Core, and the fields this library does not produce
+        let mut core = expected;
+        core["vout"][0]["value"] = serde_json::from_str("10.00000000").unwrap();
+        core["vout"][0]["scriptPubKey"]["desc"] = json!("pk(04ae1a62fe09c5f5)#00000000");
+        let parsed: TransactionJson = serde_json::from_value(core.clone()).unwrap();
+        assert_eq!(parsed.to_transaction(), Ok(tx));
+
+        core["locktime"] = json!(1);
+        let parsed: TransactionJson = serde_json::from_value(core).unwrap();
+        assert_eq!(parsed.to_transaction(), Err(Error::HashMismatch));
+    }
+
+    #[test]
+    fn test_output_value_json() {
+        let output = |value: serde_json::Value| {
+            serde_json::from_value::<OutputJson>(json!({
+                "value": value,
+                "n": 0,
+                "scriptPubKey": { "asm": "OP_TRUE", "hex": "51", "type": "nonstandard" }
+            }))
+            .map(|output| output.value)
+        };
+        assert_eq!(output(json!(0.1 + 0.2)).unwrap(), 30_000_000);
+        assert_eq!(output(json!(0.00000001)).unwrap(), 1);
+        assert_eq!(
+            output(json!(21_000_000.0)).unwrap(),
+            light_bitcoin_chain::constants::MAX_MONEY
+        );
+        assert!(output(json!(21_000_000.00000001)).is_err());
+        assert!(output(json!(-0.00000001)).is_err());
+    }
+
+    #[test]
+    fn test_segwit_transaction_json() {
+        let tx = Transaction {
+            version: 2,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint::new(H256::repeat_byte(1), 3),
+                script_sig: Bytes::new(),
+                sequence: 0xffff_fffd,
+                script_witness: vec![Bytes::new(), vec![0x51].into()],
+            }],
+            outputs: vec![
+                TransactionOutput {
+                    value: 12_345,
+                    script_pubkey: "0014751e76e8199196d454941c45d1b3a323f1433bd6"
+                        .parse()
+                        .unwrap(),
+                },
+                TransactionOutput {
+                    value: 1,
+                    script_pubkey:
+                        "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+                            .parse()
+                            .unwrap(),
+                },
+                TransactionOutput {
+                    value: 0,
+                    script_pubkey: "6a0568656c6c6f".parse().unwrap(),
+                },
+            ],
+            lock_time: 800_000,
+        };
+        let json = TransactionJson::new(&tx, Network::Mainnet);
+        let value = serde_json::to_value(&json).unwrap();
+        let wtxid = light_bitcoin_primitives::hash_rev(tx.witness_hash());
+        assert_eq!(value["hash"], json!(hex::encode(wtxid)));
+        assert_eq!(
+            value["vin"][0],
+            json!({
+                "txid": "0101010101010101010101010101010101010101010101010101010101010101",
+                "vout": 3,
+                "scriptSig": { "asm": "", "hex": "" },
+                "txinwitness": ["", "51"],
+                "sequence": 4294967293u32
+            })
+        );
+        let outputs = [
+            (
+                0.00012345,
+                "0 751e76e8199196d454941c45d1b3a323f1433bd6",
+                "witness_v0_keyhash",
+                Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"),
+            ),
+            (
+                0.00000001,
+                "1 79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+                "witness_v1_taproot",
+                Some("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"),
+            ),
+            (0.0, "OP_RETURN 68656c6c6f", "nulldata", None),
+        ];
+        for (output, (btc, asm, kind, address)) in
+            value["vout"].as_array().unwrap().iter().zip(outputs)
+        {
+            assert_eq!(output["value"], json!(btc));
+            assert_eq!(output["scriptPubKey"]["asm"], json!(asm));
+            assert_eq!(output["scriptPubKey"]["type"], json!(kind));
+            assert_eq!(
+                output["scriptPubKey"].get("address"),
+                address.map(|a| json!(a)).as_ref()
+            );
+        }
+
+        let parsed: TransactionJson = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(parsed, json);
+        assert_eq!(parsed.to_transaction(), Ok(tx));
+
+        // the witness is not part of the txid, only of the wtxid
+        let mut changed = value;
+        changed["vin"][0]["txinwitness"] = json!(["", "52"]);
+        let parsed: TransactionJson = serde_json::from_value(changed).unwrap();
+        assert_eq!(parsed.to_transaction(), Err(Error::HashMismatch));
+    }
+
+    #[test]
+    fn test_block_json() {
+        let block: Block = deserialize(hex::decode(TESTNET_GENESIS).unwrap().as_slice()).unwrap();
+        let value = serde_json::to_value(BlockJson::new(&block, Network::Testnet)).unwrap();
+        assert_eq!(
+            value["hash"],
+            json!("000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943")
+        );
+        assert_eq!(value["version"], json!(1));
+        assert_eq!(value["versionHex"], json!("00000001"));
+        assert_eq!(
+            value["merkleroot"],
+            json!("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+        );
+        assert_eq!(value["time"], json!(1296688602));
+        assert_eq!(value["nonce"], json!(414098458));
+        assert_eq!(value["bits"], json!("1d00ffff"));
+        assert_eq!(value["difficulty"], json!(1.0));
+        assert_eq!(value.get("previousblockhash"), None);
+        assert_eq!(value["nTx"], json!(1));
+        assert_eq!(value["strippedsize"], json!(285));
+        assert_eq!(value["size"], json!(285));
+        assert_eq!(value["weight"], json!(1140));
+
+        let coinbase = &value["tx"][0];
+        assert_eq!(
+            coinbase["txid"],
+            json!("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+        );
+        assert_eq!(
+            coinbase["vin"],
+            json!([{
+                "coinbase": "04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73",
+                "sequence": 4294967295u32
+            }])
+        );
+        assert_eq!(coinbase["vout"][0]["value"], json!(50.0));
+        assert_eq!(coinbase["hex"], json!(TESTNET_GENESIS[162..]));
+
+        // fields of getblock that depend on the chain
+        let mut core = value;
+        core["confirmations"] = json!(100);
+        core["height"] = json!(0);
+        core["chainwork"] =
+            json!("0000000000000000000000000000000000000000000000000000000100010001");
+        let parsed: BlockJson = serde_json::from_value(core.clone()).unwrap();
+        assert_eq!(parsed.to_block(), Ok(block));
+
+        let mut other = parsed.clone();
+        other.transaction_count = 2;
+        assert_eq!(other.to_block(), Err(Error::TransactionCountMismatch));
+        // the transactions are checked against the merkle root of the header
+        other.transactions.push(other.transactions[0].clone());
+        assert_eq!(other.to_block(), Err(Error::HashMismatch));
+
+        core["nonce"] = json!(0);
+        let parsed: BlockJson = serde_json::from_value(core).unwrap();
+        assert_eq!(parsed.to_block(), Err(Error::HashMismatch));
+    }
+
+    #[test]
+    fn test_witness_unknown_address() {
+        // BIP350 test vectors
+        let cases = [
+            ("6002751e", Some("bc1sw50qgdz25j")),
+            (
+                "5210751e76e8199196d454941c45d1b3a323",
+                Some("bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs"),
+            ),
+            // version 0 programs of other lengths
+            ("0010751e76e8199196d454941c45d1b3a323", None),
+        ];
+        for (script, expected) in cases {
+            let script = Script::from(hex::decode(script).unwrap());
+            assert_eq!(address(&script, Network::Mainnet).as_deref(), expected);
+            let name = if expected.is_some() {
+                "witness_unknown"
+            } else {
+                "nonstandard"
+            };
+            assert_eq!(script_type_name(&script), name);
+        }
+    }
+
+    #[test]
+    fn test_asm() {
+        let cases = [
+            ("", ""),
+            ("00", "0"),
+            ("4f", "-1"),
+            ("5160", "1 16"),
+            // numbers of up to 4 bytes
+            ("01810280000180", "-1 128 0"),
+            ("03ffff7f", "8388607"),
+            ("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac", "OP_DUP OP_HASH160 62e907b15cbf27d5425399ebf6f0fb50ebb88f18 OP_EQUALVERIFY OP_CHECKSIG"),
+            ("b1b2ba", "OP_CHECKLOCKTIMEVERIFY OP_CHECKSEQUENCEVERIFY OP_CHECKSIGADD"),
+            ("bbfeff", "OP_UNKNOWN OP_UNKNOWN OP_INVALIDOPCODE"),
+            ("4c0501020304057c", "0102030405 OP_SWAP"),
+            // pushes past the end of the script
+            ("76050102", "OP_DUP [error]"),
+            ("4d01", "[error]"),
+        ];
+        for (script, expected) in cases {
+            assert_eq!(
+                asm(&hex::decode(script).unwrap(), false),
+                expected,
+                "{}",
+                script
+            );
+        }
+    }
+}
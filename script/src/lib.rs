@@ -4,27 +4,43 @@
 extern crate alloc;
 
 mod builder;
+mod coin_selection;
 mod error;
 mod flags;
+pub mod json;
 mod num;
 mod opcode;
+mod satisfaction;
 mod script;
 mod sign;
+mod transaction_builder;
 mod verify;
 
 pub use light_bitcoin_primitives::*;
 
 pub use self::builder::Builder;
+pub use self::coin_selection::{
+    branch_and_bound, knapsack, largest_first, select_coins, single_random_draw, Candidate,
+    CoinSelectionParams, Selection, MIN_CHANGE,
+};
 pub use self::error::Error;
 pub use self::flags::VerificationFlags;
 pub use self::num::Num;
 pub use self::opcode::Opcode;
+pub use self::satisfaction::{
+    max_satisfaction_size, max_signature_size, max_tap_leaf_satisfaction_size, SatisfactionSize,
+};
 pub use self::script::{
     is_witness_commitment_script, Script, ScriptAddress, ScriptType, ScriptWitness,
     MAX_OPS_PER_SCRIPT, MAX_PUBKEYS_PER_MULTISIG, MAX_SCRIPT_ELEMENT_SIZE, MAX_SCRIPT_SIZE,
+    OP_CHECKSIGADD,
 };
 pub use self::sign::{
-    check_taproot_tx, ScriptExecutionData, SignatureVersion, TransactionInputSigner,
-    UnsignedTransactionInput,
+    check_taproot_tx, compute_leaf_hash, ScriptExecutionData, SignatureVersion,
+    TransactionInputSigner, UnsignedTransactionInput,
+};
+pub use self::transaction_builder::{
+    dust_threshold, InputType, TransactionBuilder, DUST_RELAY_FEE_RATE, SEQUENCE_LOCKTIME_ENABLED,
+    SEQUENCE_RBF,
 };
 pub use self::verify::{NoopSignatureChecker, SignatureChecker, TransactionSignatureChecker};
@@ -0,0 +1,288 @@
+//! Maximum size of the script_sig and witness satisfying a script
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use light_bitcoin_chain::WITNESS_SCALE_FACTOR;
+
+use crate::script::Script;
+use crate::sign::SignatureVersion;
+use crate::transaction_builder::{compact_size, INPUT_BASE_SIZE};
+
+/// Maximum size of a DER encoded ECDSA signature with its sighash byte
+pub(crate) const MAX_ECDSA_SIGNATURE_SIZE: usize = 73;
+/// Size of a schnorr signature with `SIGHASH_DEFAULT`
+pub(crate) const SCHNORR_SIGNATURE_SIZE: usize = 64;
+/// Size of a compressed public key
+pub(crate) const COMPRESSED_PUBLIC_KEY_SIZE: usize = 33;
+/// Size of the control block of a leaf at depth 0
+pub(crate) const CONTROL_BLOCK_BASE_SIZE: usize = 33;
+/// Size of a hash in a taproot merkle path
+pub(crate) const TAPROOT_NODE_SIZE: usize = 32;
+/// Maximum depth of a taproot script tree
+const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+
+/// Maximum size of the data spending an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SatisfactionSize {
+    /// Size of the script_sig, without its length prefix
+    pub script_sig: usize,
+    /// Size of the serialized witness, 0 for inputs without witness
+    pub witness: usize,
+}
+
+impl SatisfactionSize {
+    /// Weight of an input spending with this satisfaction, including its witness
+    pub fn input_weight(&self) -> usize {
+        WITNESS_SCALE_FACTOR * (INPUT_BASE_SIZE + compact_size(self.script_sig) + self.script_sig)
+            + self.witness
+    }
+}
+
+/// Maximum size of a signature with its sighash byte
+pub fn max_signature_size(version: SignatureVersion) -> usize {
+    match version {
+        SignatureVersion::Base | SignatureVersion::WitnessV0 | SignatureVersion::ForkId => {
+            MAX_ECDSA_SIGNATURE_SIZE
+        }
+        SignatureVersion::Taproot | SignatureVersion::TapScript => SCHNORR_SIGNATURE_SIZE + 1,
+    }
+}
+
+/// Maximum size of the data spending `script_pubkey`.
+///
+/// Supports P2PK, P2PKH and multisig scripts, either bare, in P2SH or in P2WSH,
+/// P2WPKH, P2SH-P2WPKH and taproot key path spends. P2SH and P2WSH outputs require
+/// their redeem and witness scripts. Public keys of P2PKH and P2WPKH outputs are
+/// assumed to be compressed.
+pub fn max_satisfaction_size(
+    script_pubkey: &Script,
+    redeem_script: Option<&Script>,
+    witness_script: Option<&Script>,
+) -> Option<SatisfactionSize> {
+    if script_pubkey.is_pay_to_script_hash() {
+        let redeem_script = redeem_script?;
+        let redeem_push = push_size(redeem_script.len());
+        if redeem_script.parse_witness_program().is_some() {
+            let witness = max_witness_program_size(redeem_script, witness_script)?;
+            return Some(SatisfactionSize {
+                script_sig: redeem_push,
+                witness,
+            });
+        }
+        let items = satisfaction_items(redeem_script, SignatureVersion::Base)?;
+        return Some(SatisfactionSize {
+            script_sig: script_sig_size(&items) + redeem_push,
+            witness: 0,
+        });
+    }
+
+    if script_pubkey.parse_witness_program().is_some() {
+        return Some(SatisfactionSize {
+            script_sig: 0,
+            witness: max_witness_program_size(script_pubkey, witness_script)?,
+        });
+    }
+
+    let items = satisfaction_items(script_pubkey, SignatureVersion::Base)?;
+    Some(SatisfactionSize {
+        script_sig: script_sig_size(&items),
+        witness: 0,
+    })
+}
+
+/// Maximum size of the data spending a taproot output through the leaf `leaf_script`
+/// with `control_block`.
+///
+/// Supports `<key> OP_CHECKSIG` and `multi_a` leaves.
+pub fn max_tap_leaf_satisfaction_size(
+    leaf_script: &Script,
+    control_block: &[u8],
+) -> Option<SatisfactionSize> {
+    let nodes = control_block.len().checked_sub(CONTROL_BLOCK_BASE_SIZE)?;
+    if nodes % TAPROOT_NODE_SIZE != 0 || nodes / TAPROOT_NODE_SIZE > TAPROOT_CONTROL_MAX_NODE_COUNT
+    {
+        return None;
+    }
+
+    let mut items = satisfaction_items(leaf_script, SignatureVersion::TapScript)?;
+    items.push(leaf_script.len());
+    items.push(control_block.len());
+    Some(SatisfactionSize {
+        script_sig: 0,
+        witness: witness_size(&items),
+    })
+}
+
+/// Witness size of a P2WPKH, P2WSH or taproot key path spend
+fn max_witness_program_size(program: &Script, witness_script: Option<&Script>) -> Option<usize> {
+    let items = if program.is_pay_to_witness_key_hash() {
+        vec![
+            max_signature_size(SignatureVersion::WitnessV0),
+            COMPRESSED_PUBLIC_KEY_SIZE,
+        ]
+    } else if program.is_pay_to_witness_script_hash() {
+        let witness_script = witness_script?;
+        let mut items = satisfaction_items(witness_script, SignatureVersion::WitnessV0)?;
+        items.push(witness_script.len());
+        items
+    } else if program.is_pay_to_witness_taproot() {
+        vec![max_signature_size(SignatureVersion::Taproot)]
+    } else {
+        return None;
+    };
+    Some(witness_size(&items))
+}
+
+/// Sizes of the stack items satisfying a P2PK, P2PKH or multisig script, or a
+/// `<key> OP_CHECKSIG` or `multi_a` tapscript leaf
+fn satisfaction_items(script: &Script, version: SignatureVersion) -> Option<Vec<usize>> {
+    let signature = max_signature_size(version);
+    if version == SignatureVersion::TapScript {
+        // keys without a signature take an empty item
+        let (keys, required) = script.parse_tap_leaf_keys()?;
+        let mut items = vec![signature; required];
+        items.resize(keys.len(), 0);
+        return Some(items);
+    }
+
+    if script.is_pay_to_public_key_hash() {
+        return Some(vec![signature, COMPRESSED_PUBLIC_KEY_SIZE]);
+    }
+    if script.is_pay_to_public_key() {
+        return Some(vec![signature]);
+    }
+    if let Some((_, required, _)) = script.parse_redeem_script() {
+        // CHECKMULTISIG pops one element more than it uses
+        let mut items = vec![0];
+        items.extend(vec![signature; required as usize]);
+        return Some(items);
+    }
+    None
+}
+
+/// Size of a push of `len` bytes in a script_sig
+fn push_size(len: usize) -> usize {
+    match len {
+        0 => 1,
+        1..=75 => 1 + len,
+        76..=0xff => 2 + len,
+        0x100..=0xffff => 3 + len,
+        _ => 5 + len,
+    }
+}
+
+fn script_sig_size(items: &[usize]) -> usize {
+    items.iter().map(|&len| push_size(len)).sum()
+}
+
+fn witness_size(items: &[usize]) -> usize {
+    compact_size(items.len())
+        + items
+            .iter()
+            .map(|&len| compact_size(len) + len)
+            .sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::Builder;
+    use crate::opcode::Opcode;
+    use crate::script::OP_CHECKSIGADD;
+    use crate::transaction_builder::InputType;
+    use light_bitcoin_crypto::sha256;
+    use light_bitcoin_keys::{KeyPair, Network, Private};
+    use light_bitcoin_primitives::H256;
+
+    fn keypair(byte: u8) -> KeyPair {
+        KeyPair::from_private(Private {
+            network: Network::Mainnet,
+            secret: H256::repeat_byte(byte).into(),
+            compressed: true,
+        })
+        .unwrap()
+    }
+
+    fn multisig() -> Script {
+        (1..=3)
+            .fold(
+                Builder::default().push_opcode(Opcode::OP_2),
+                |builder, byte| builder.push_data(keypair(byte).public()),
+            )
+            .push_opcode(Opcode::OP_3)
+            .push_opcode(Opcode::OP_CHECKMULTISIG)
+            .into_script()
+    }
+
+    #[test]
+    fn test_key_hash_satisfaction() {
+        let hash = keypair(1).public().address_hash();
+        let p2pkh = max_satisfaction_size(&Builder::build_p2pkh(&hash), None, None).unwrap();
+        assert_eq!(p2pkh.script_sig, InputType::P2PKH.script_sig_size());
+        assert_eq!(p2pkh.input_weight(), InputType::P2PKH.weight());
+
+        let p2wpkh = Builder::build_p2wpkh(&hash);
+        let size = max_satisfaction_size(&p2wpkh, None, None).unwrap();
+        assert_eq!(size.witness, InputType::P2WPKH.witness_size());
+        assert_eq!(size.input_weight(), InputType::P2WPKH.weight());
+
+        // P2SH-P2WPKH pushes the witness program
+        let p2sh = Builder::build_p2sh(&light_bitcoin_crypto::dhash160(&p2wpkh));
+        let size = max_satisfaction_size(&p2sh, Some(&p2wpkh), None).unwrap();
+        assert_eq!(size.script_sig, 23);
+        assert_eq!(size.witness, InputType::P2WPKH.witness_size());
+    }
+
+    #[test]
+    fn test_multisig_satisfaction() {
+        let redeem_script = multisig();
+        assert_eq!(redeem_script.len(), 105);
+
+        // dummy, two signatures and the redeem script
+        let p2sh = Builder::build_p2sh(&light_bitcoin_crypto::dhash160(&redeem_script));
+        let size = max_satisfaction_size(&p2sh, Some(&redeem_script), None).unwrap();
+        assert_eq!(size.script_sig, 1 + 2 * 74 + 2 + 105);
+        assert_eq!(size.witness, 0);
+        assert_eq!(max_satisfaction_size(&p2sh, None, None), None);
+
+        let p2wsh = Builder::build_p2wsh(&sha256(&redeem_script));
+        let size = max_satisfaction_size(&p2wsh, None, Some(&redeem_script)).unwrap();
+        assert_eq!(size.script_sig, 0);
+        assert_eq!(size.witness, 1 + 1 + 2 * 74 + 1 + 105);
+    }
+
+    #[test]
+    fn test_taproot_satisfaction() {
+        let xonly = keypair(1).x_only().unwrap();
+        let p2tr = Builder::build_p2tr(&xonly);
+        let size = max_satisfaction_size(&p2tr, None, None).unwrap();
+        assert_eq!(size.witness, 1 + 1 + 65);
+
+        let leaf = Builder::default()
+            .push_bytes(&xonly.0)
+            .push_opcode(Opcode::OP_CHECKSIG)
+            .into_script();
+        let size = max_tap_leaf_satisfaction_size(&leaf, &[0xc0; 33 + 2 * 32]).unwrap();
+        assert_eq!(size.witness, 1 + 66 + 35 + 1 + 97);
+
+        // 2-of-3 multi_a
+        let mut multi_a = Vec::new();
+        for byte in 1..=3 {
+            multi_a.push(Opcode::OP_PUSHBYTES_32 as u8);
+            multi_a.extend_from_slice(&keypair(byte).x_only().unwrap().0);
+            multi_a.push(if byte == 1 {
+                Opcode::OP_CHECKSIG as u8
+            } else {
+                OP_CHECKSIGADD
+            });
+        }
+        multi_a.extend([Opcode::OP_2 as u8, Opcode::OP_NUMEQUAL as u8]);
+        let multi_a = Script::from(multi_a);
+        let size = max_tap_leaf_satisfaction_size(&multi_a, &[0xc0; 33]).unwrap();
+        assert_eq!(size.witness, 1 + 2 * 66 + 1 + 1 + 104 + 1 + 33);
+
+        assert_eq!(max_tap_leaf_satisfaction_size(&leaf, &[0xc0; 40]), None);
+        assert_eq!(max_tap_leaf_satisfaction_size(&p2tr, &[0xc0; 33]), None);
+    }
+}
@@ -22,6 +22,9 @@ pub const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
 /// Maximum script length in bytes
 pub const MAX_SCRIPT_SIZE: usize = 10000;
 
+/// `OP_CHECKSIGADD` from BIP342
+pub const OP_CHECKSIGADD: u8 = 0xba;
+
 /// Classified script type
 #[derive(PartialEq, Debug, Decode, Encode, scale_info::TypeInfo)]
 pub enum ScriptType {
@@ -615,6 +618,46 @@ impl Script {
         Some((pubkeys, u32::from(siglen), u32::from(keylen)))
     }
 
+    /// Parses a `<key> OP_CHECKSIG` or `multi_a` tapscript leaf, returning its x-only
+    /// keys and the number of signatures it requires.
+    ///
+    /// Leaf scripts are parsed byte by byte since `OP_CHECKSIGADD` from BIP342 shares
+    /// its value with `OP_CHECKDATASIG`.
+    pub fn parse_tap_leaf_keys(&self) -> Option<(Vec<XOnly>, usize)> {
+        let mut keys = Vec::new();
+        let mut rest = &self.data[..];
+        while rest.len() >= 34 && rest[0] == Opcode::OP_PUSHBYTES_32 as u8 {
+            let checksig = if keys.is_empty() {
+                Opcode::OP_CHECKSIG as u8
+            } else {
+                OP_CHECKSIGADD
+            };
+            if rest[33] != checksig {
+                return None;
+            }
+            let mut key = [0u8; 32];
+            key.copy_from_slice(&rest[1..33]);
+            keys.push(XOnly(key));
+            rest = &rest[34..];
+        }
+
+        let numequal = Opcode::OP_NUMEQUAL as u8;
+        let required = match *rest {
+            [] if keys.len() == 1 => 1,
+            [n, op]
+                if op == numequal && (Opcode::OP_1 as u8..=Opcode::OP_16 as u8).contains(&n) =>
+            {
+                (n - Opcode::OP_1 as u8 + 1) as usize
+            }
+            [1, n, op] if op == numequal && n > 16 && n < 0x80 => n as usize,
+            _ => return None,
+        };
+        if required > keys.len() {
+            return None;
+        }
+        Some((keys, required))
+    }
+
     pub fn extract_rear(&self, key: char) -> Vec<u8> {
         if self.data.len() <= 1 {
             return Vec::new();
@@ -1175,5 +1218,38 @@ OP_ADD
         assert_eq!(keylen, 3);
         assert_eq!(keys.len(), 3);
     }
+
+    #[test]
+    fn parse_tap_leaf_keys() {
+        let key = |byte: u8| {
+            let mut script = vec![Opcode::OP_PUSHBYTES_32 as u8];
+            script.extend_from_slice(&[byte; 32]);
+            script
+        };
+
+        let mut single = key(1);
+        single.push(Opcode::OP_CHECKSIG as u8);
+        assert_eq!(
+            Script::from(single.clone()).parse_tap_leaf_keys(),
+            Some((vec![XOnly([1; 32])], 1))
+        );
+
+        let mut multi = single.clone();
+        for byte in 2..=3 {
+            multi.extend(key(byte));
+            multi.push(OP_CHECKSIGADD);
+        }
+        multi.extend([Opcode::OP_2 as u8, Opcode::OP_NUMEQUAL as u8]);
+        let (keys, required) = Script::from(multi.clone()).parse_tap_leaf_keys().unwrap();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(required, 2);
+
+        // more signatures than keys
+        let len = multi.len();
+        multi[len - 2] = Opcode::OP_4 as u8;
+        assert_eq!(Script::from(multi).parse_tap_leaf_keys(), None);
+        single.push(Opcode::OP_VERIFY as u8);
+        assert_eq!(Script::from(single).parse_tap_leaf_keys(), None);
+    }
     // ============================================================================================
 }
@@ -12,16 +12,9 @@ use light_bitcoin_serialization::Stream;
 use crate::script::Script;
 use crate::{builder::Builder, Error};
 
-use core::{
-    cmp::Ordering,
-    convert::{TryFrom, TryInto},
-};
+use core::{cmp::Ordering, convert::TryFrom};
 
 use crate::Opcode;
-use libsecp256k1::{
-    curve::{Affine, Jacobian, Scalar},
-    PublicKey, ECMULT_CONTEXT,
-};
 
 #[derive(Debug, PartialEq, Decode, Encode, Clone, Copy, scale_info::TypeInfo)]
 pub enum SignatureVersion {
@@ -630,54 +623,18 @@ pub fn verify_taproot_commitment(control: &[u8], program: &XOnly, scirpt: &Scrip
     if control.len() < 33 || (control.len() - 33) % 32 != 0 {
         return false;
     }
-    let pubkey: PublicKey = if let Ok(d) = XOnly::try_from(&control[1..33]) {
-        if let Ok(pk) = d.try_into() {
-            pk
-        } else {
-            return false;
-        }
-    } else {
-        return false;
+    let internal = match XOnly::try_from(&control[1..33]) {
+        Ok(internal) => internal,
+        Err(_) => return false,
     };
     let tapleaf_hash = compute_leaf_hash(0xfe & control[0], scirpt);
     let merkle_root = compute_taproot_merkle_root(Bytes::from(control), tapleaf_hash);
 
-    let mut stream = Stream::default();
-    stream.append_slice(&control[1..33]);
-    stream.append(&merkle_root);
-    let out = stream.out();
-    let hash = sha2::Sha256::default()
-        .tagged(b"TapTweak")
-        .add(&out[..])
-        .finalize();
-    let hash = hash.as_slice();
-    if hash.len() != 32 {
-        return false;
-    }
-    let mut keys = [0u8; 32];
-    keys.copy_from_slice(hash);
-    let mut t = Scalar::default();
-    if bool::from(t.set_b32(&keys)) {
-        return false;
-    };
-
-    let mut p: Affine = pubkey.into();
-
-    p.y.normalize();
-    let p = if p.y.is_odd() { p.neg() } else { p };
-
-    let mut pj = libsecp256k1::curve::Jacobian::default();
-    pj.set_ge(&p);
-
-    let mut rj = Jacobian::default();
-    // Q = P + int(t)G.
-    ECMULT_CONTEXT.ecmult(&mut rj, &pj, &Scalar::from_int(1), &t);
-    let mut r = Affine::from_gej(&rj);
-    let rx: XOnly = (&mut r.x).into();
-    if rx != *program {
-        return false;
+    // Q = P + int(t)G, and the parity of Q must match the control block.
+    match internal.tap_tweak(Some(merkle_root)) {
+        Ok((output, odd)) => output == *program && odd == (control[0] & 1 == 1),
+        Err(_) => false,
     }
-    true
 }
 
 /// Check Taproot tx
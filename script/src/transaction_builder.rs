@@ -0,0 +1,519 @@
+//! Transaction builder
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use light_bitcoin_chain::constants::SEQUENCE_FINAL;
+use light_bitcoin_chain::{
+    OutPoint, Transaction, TransactionInput, TransactionOutput, WITNESS_SCALE_FACTOR,
+};
+use light_bitcoin_keys::Address;
+use light_bitcoin_serialization::{CompactInteger, Serializable};
+
+use crate::builder::Builder;
+use crate::satisfaction::{
+    SatisfactionSize, COMPRESSED_PUBLIC_KEY_SIZE, CONTROL_BLOCK_BASE_SIZE,
+    MAX_ECDSA_SIGNATURE_SIZE, SCHNORR_SIGNATURE_SIZE, TAPROOT_NODE_SIZE,
+};
+use crate::script::Script;
+use crate::Error;
+
+/// Sequence number signalling replaceability, see [BIP125]
+///
+/// [BIP125]: https://github.com/bitcoin/bips/blob/master/bip-0125.mediawiki
+pub const SEQUENCE_RBF: u32 = 0xffff_fffd;
+/// Highest sequence number which still enables the transaction lock time
+pub const SEQUENCE_LOCKTIME_ENABLED: u32 = 0xffff_fffe;
+/// Fee rate in sat/vB used to compute the dust threshold of outputs
+pub const DUST_RELAY_FEE_RATE: u64 = 3;
+
+/// Outpoint and sequence of an input
+pub(crate) const INPUT_BASE_SIZE: usize = 32 + 4 + 4;
+/// Size of the script_sig or witness of a key hash spend assumed by the dust threshold
+const DUST_SPEND_DATA_SIZE: usize = 107;
+
+/// How an input is spent, which determines the size of its script_sig and witness
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputType {
+    /// Pay to public key hash with a compressed public key
+    P2PKH,
+    /// Pay to witness public key hash
+    P2WPKH,
+    /// Taproot key path with a `SIGHASH_DEFAULT` signature
+    P2TRKeyPath,
+    /// Taproot script path of a `script_len` bytes leaf at `depth` in the script tree,
+    /// satisfied by `signatures` signatures with `SIGHASH_DEFAULT`
+    P2TRScriptPath {
+        script_len: usize,
+        depth: usize,
+        signatures: usize,
+    },
+    /// Any other spend, see [`max_satisfaction_size`] and
+    /// [`max_tap_leaf_satisfaction_size`]
+    ///
+    /// [`max_satisfaction_size`]: crate::max_satisfaction_size
+    /// [`max_tap_leaf_satisfaction_size`]: crate::max_tap_leaf_satisfaction_size
+    Satisfaction(SatisfactionSize),
+}
+
+impl InputType {
+    /// Maximum size of the script_sig
+    pub fn script_sig_size(&self) -> usize {
+        match *self {
+            InputType::P2PKH => 1 + MAX_ECDSA_SIGNATURE_SIZE + 1 + COMPRESSED_PUBLIC_KEY_SIZE,
+            InputType::Satisfaction(size) => size.script_sig,
+            _ => 0,
+        }
+    }
+
+    /// Maximum size of the serialized witness, 0 for inputs without witness
+    pub fn witness_size(&self) -> usize {
+        match *self {
+            InputType::P2PKH => 0,
+            InputType::P2WPKH => 1 + 1 + MAX_ECDSA_SIGNATURE_SIZE + 1 + COMPRESSED_PUBLIC_KEY_SIZE,
+            InputType::P2TRKeyPath => 1 + 1 + SCHNORR_SIGNATURE_SIZE,
+            InputType::P2TRScriptPath {
+                script_len,
+                depth,
+                signatures,
+            } => {
+                let control_block = CONTROL_BLOCK_BASE_SIZE + TAPROOT_NODE_SIZE * depth;
+                compact_size(signatures + 2)
+                    + signatures * (1 + SCHNORR_SIGNATURE_SIZE)
+                    + compact_size(script_len)
+                    + script_len
+                    + compact_size(control_block)
+                    + control_block
+            }
+            InputType::Satisfaction(size) => size.witness,
+        }
+    }
+
+    /// Maximum weight of an input of this type, including its witness
+    pub fn weight(&self) -> usize {
+        SatisfactionSize {
+            script_sig: self.script_sig_size(),
+            witness: self.witness_size(),
+        }
+        .input_weight()
+    }
+}
+
+/// Minimum value of an output paying to `script_pubkey` that is not dust, which is
+/// the cost of creating and spending it at `DUST_RELAY_FEE_RATE`.
+///
+/// Unspendable outputs have no dust threshold.
+pub fn dust_threshold(script_pubkey: &Script) -> u64 {
+    if script_pubkey.is_null_data_script() {
+        return 0;
+    }
+    let spend_size = if script_pubkey.parse_witness_program().is_some() {
+        // witness data is discounted
+        INPUT_BASE_SIZE + 1 + DUST_SPEND_DATA_SIZE / WITNESS_SCALE_FACTOR
+    } else {
+        INPUT_BASE_SIZE + 1 + DUST_SPEND_DATA_SIZE
+    };
+    (output_size(script_pubkey.len()) + spend_size) as u64 * DUST_RELAY_FEE_RATE
+}
+
+pub(crate) fn compact_size(len: usize) -> usize {
+    CompactInteger::from(len).serialized_size()
+}
+
+pub(crate) fn output_size(script_len: usize) -> usize {
+    8 + compact_size(script_len) + script_len
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct BuilderInput {
+    previous_output: OutPoint,
+    spent: TransactionOutput,
+    input_type: InputType,
+}
+
+/// Builds an unsigned transaction, paying the fee for its estimated signed weight and
+/// sending the remainder to a change output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionBuilder {
+    version: i32,
+    inputs: Vec<BuilderInput>,
+    outputs: Vec<TransactionOutput>,
+    fee_rate: u64,
+    change: Option<Script>,
+    rbf: bool,
+    lock_time: u32,
+}
+
+impl Default for TransactionBuilder {
+    fn default() -> Self {
+        TransactionBuilder {
+            version: 2,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            fee_rate: 1,
+            change: None,
+            rbf: false,
+            lock_time: 0,
+        }
+    }
+}
+
+impl TransactionBuilder {
+    /// Creates a builder of a version 2 transaction paying 1 sat/vB
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the transaction version
+    pub fn version(mut self, version: i32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Spends `previous_output`, which is `spent`, as `input_type`
+    pub fn add_input(
+        mut self,
+        previous_output: OutPoint,
+        spent: TransactionOutput,
+        input_type: InputType,
+    ) -> Self {
+        self.inputs.push(BuilderInput {
+            previous_output,
+            spent,
+            input_type,
+        });
+        self
+    }
+
+    /// Pays `value` to `script_pubkey`
+    pub fn add_output(mut self, script_pubkey: &Script, value: u64) -> Self {
+        self.outputs.push(TransactionOutput {
+            value,
+            script_pubkey: script_pubkey.to_bytes(),
+        });
+        self
+    }
+
+    /// Pays `value` to `address`
+    pub fn add_address_output(self, address: &Address, value: u64) -> Self {
+        self.add_output(&Builder::build_address_types(address), value)
+    }
+
+    /// Sets the fee rate in sat/vB
+    pub fn fee_rate(mut self, fee_rate: u64) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    /// Sends the change to `script_pubkey`. Without a change script the remainder
+    /// is left to the fee.
+    pub fn change_script(mut self, script_pubkey: &Script) -> Self {
+        self.change = Some(script_pubkey.clone());
+        self
+    }
+
+    /// Sends the change to `address`
+    pub fn change_address(self, address: &Address) -> Self {
+        self.change_script(&Builder::build_address_types(address))
+    }
+
+    /// Signals replaceability of the transaction
+    pub fn enable_rbf(mut self) -> Self {
+        self.rbf = true;
+        self
+    }
+
+    /// Sets the transaction lock time
+    pub fn lock_time(mut self, lock_time: u32) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    /// Estimated weight of the signed transaction, without change
+    pub fn estimate_weight(&self) -> usize {
+        self.weight(&self.outputs)
+    }
+
+    /// Estimated virtual size of the signed transaction, without change
+    pub fn estimate_vsize(&self) -> usize {
+        vsize(self.estimate_weight())
+    }
+
+    /// Builds the unsigned transaction.
+    ///
+    /// A change output is appended if the remainder after the fee, including the
+    /// change output itself, is not dust.
+    pub fn build(self) -> Result<Transaction, Error> {
+        if self.inputs.is_empty() {
+            return Err(Error::NoInputs);
+        }
+
+        let total_in = self
+            .inputs
+            .iter()
+            .try_fold(0u64, |total, input| total.checked_add(input.spent.value))
+            .ok_or(Error::InsufficientFunds)?;
+        let total_out = self
+            .outputs
+            .iter()
+            .try_fold(0u64, |total, output| total.checked_add(output.value))
+            .ok_or(Error::InsufficientFunds)?;
+        total_out
+            .checked_add(self.fee(&self.outputs)?)
+            .filter(|&spent| spent <= total_in)
+            .ok_or(Error::InsufficientFunds)?;
+
+        let mut outputs = self.outputs.clone();
+        if let Some(ref change) = self.change {
+            outputs.push(TransactionOutput {
+                value: 0,
+                script_pubkey: change.to_bytes(),
+            });
+            let fee = self.fee(&outputs)?;
+            let remainder = total_in
+                .checked_sub(total_out)
+                .and_then(|remainder| remainder.checked_sub(fee));
+            match remainder {
+                Some(value) if value >= dust_threshold(change) => {
+                    outputs.last_mut().expect("change was pushed; qed").value = value;
+                }
+                _ => {
+                    outputs.pop();
+                }
+            }
+        }
+
+        let sequence = if self.rbf {
+            SEQUENCE_RBF
+        } else if self.lock_time != 0 {
+            SEQUENCE_LOCKTIME_ENABLED
+        } else {
+            SEQUENCE_FINAL
+        };
+        Ok(Transaction {
+            version: self.version,
+            inputs: self
+                .inputs
+                .iter()
+                .map(|input| TransactionInput {
+                    previous_output: input.previous_output,
+                    script_sig: Default::default(),
+                    sequence,
+                    script_witness: Vec::new(),
+                })
+                .collect(),
+            outputs,
+            lock_time: self.lock_time,
+        })
+    }
+
+    fn weight(&self, outputs: &[TransactionOutput]) -> usize {
+        let outputs_size = outputs
+            .iter()
+            .map(|output| output_size(output.script_pubkey.len()))
+            .sum::<usize>();
+        let non_input_size =
+            4 + compact_size(self.inputs.len()) + compact_size(outputs.len()) + outputs_size + 4;
+        let inputs_weight = self
+            .inputs
+            .iter()
+            .map(|input| input.input_type.weight())
+            .sum::<usize>();
+
+        let inputs_without_witness = self
+            .inputs
+            .iter()
+            .filter(|input| input.input_type.witness_size() == 0)
+            .count();
+        let witness_overhead = if inputs_without_witness == self.inputs.len() {
+            0
+        } else {
+            // marker, flag and the empty witness of every other input
+            2 + inputs_without_witness
+        };
+        non_input_size * WITNESS_SCALE_FACTOR + inputs_weight + witness_overhead
+    }
+
+    fn fee(&self, outputs: &[TransactionOutput]) -> Result<u64, Error> {
+        (vsize(self.weight(outputs)) as u64)
+            .checked_mul(self.fee_rate)
+            .ok_or(Error::FeeOverflow)
+    }
+}
+
+pub(crate) fn vsize(weight: usize) -> usize {
+    weight.div_ceil(WITNESS_SCALE_FACTOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sign::{SignatureVersion, TransactionInputSigner};
+    use light_bitcoin_keys::{KeyPair, Network, Private};
+    use light_bitcoin_primitives::{Bytes, H256};
+
+    fn keypair() -> KeyPair {
+        KeyPair::from_private(Private {
+            network: Network::Mainnet,
+            secret: H256::repeat_byte(1).into(),
+            compressed: true,
+        })
+        .unwrap()
+    }
+
+    fn outpoint(index: u32) -> OutPoint {
+        OutPoint {
+            txid: H256::repeat_byte(2),
+            index,
+        }
+    }
+
+    #[test]
+    fn test_input_type_weight() {
+        assert_eq!(InputType::P2PKH.weight(), 4 * 149);
+        assert_eq!(InputType::P2WPKH.weight(), 4 * 41 + 109);
+        assert_eq!(InputType::P2TRKeyPath.weight(), 230);
+        // <key> OP_CHECKSIG leaf at depth 1
+        let script_path = InputType::P2TRScriptPath {
+            script_len: 34,
+            depth: 1,
+            signatures: 1,
+        };
+        assert_eq!(script_path.witness_size(), 1 + 65 + 35 + 66);
+    }
+
+    #[test]
+    fn test_dust_threshold() {
+        let hash = keypair().public().address_hash();
+        assert_eq!(dust_threshold(&Builder::build_p2pkh(&hash)), 546);
+        assert_eq!(dust_threshold(&Builder::build_p2wpkh(&hash)), 294);
+        assert_eq!(dust_threshold(&Builder::build_nulldata(b"data")), 0);
+    }
+
+    #[test]
+    fn test_estimate_matches_signed_weight() {
+        let keypair = keypair();
+        let public = keypair.public();
+        let p2wpkh = Builder::build_p2wpkh(&public.address_hash());
+        let p2pkh = Builder::build_p2pkh(&public.address_hash());
+        let spent = |script: &Script| TransactionOutput {
+            value: 50_000,
+            script_pubkey: script.to_bytes(),
+        };
+
+        let builder = TransactionBuilder::new()
+            .add_input(outpoint(0), spent(&p2wpkh), InputType::P2WPKH)
+            .add_input(outpoint(1), spent(&p2pkh), InputType::P2PKH)
+            .add_output(&p2wpkh, 60_000);
+        let estimate = builder.estimate_weight();
+        let mut tx = builder.build().unwrap();
+
+        let signer = TransactionInputSigner::from(tx.clone());
+        let script_code = p2pkh.clone();
+        for (index, version) in [SignatureVersion::WitnessV0, SignatureVersion::Base]
+            .into_iter()
+            .enumerate()
+        {
+            let hash = signer.signature_hash(index, 50_000, &script_code, version, 1);
+            let mut signature: Vec<u8> = keypair.private().sign(&hash).unwrap().into();
+            signature.push(1);
+            if version == SignatureVersion::Base {
+                tx.inputs[index].script_sig = Builder::default()
+                    .push_data(&signature)
+                    .push_data(public)
+                    .into_bytes();
+            } else {
+                tx.inputs[index].script_witness =
+                    vec![Bytes::from(signature), Bytes::from(public.to_vec())];
+            }
+        }
+
+        // signatures are at most 73 bytes
+        assert!(tx.weight() <= estimate, "{} {}", tx.weight(), estimate);
+        assert!(
+            estimate - tx.weight() <= 4 * 2 + 2,
+            "{} {}",
+            tx.weight(),
+            estimate
+        );
+    }
+
+    #[test]
+    fn test_build_change() {
+        let hash = keypair().public().address_hash();
+        let p2wpkh = Builder::build_p2wpkh(&hash);
+        let spent = TransactionOutput {
+            value: 100_000,
+            script_pubkey: p2wpkh.to_bytes(),
+        };
+        let builder = TransactionBuilder::new()
+            .add_input(outpoint(0), spent.clone(), InputType::P2WPKH)
+            .add_output(&p2wpkh, 50_000)
+            .fee_rate(10)
+            .change_script(&p2wpkh);
+
+        let tx = builder.clone().build().unwrap();
+        assert_eq!(tx.outputs.len(), 2);
+        let with_change = builder.clone().add_output(&p2wpkh, 0).estimate_vsize() as u64;
+        assert_eq!(tx.outputs[1].value, 50_000 - with_change * 10);
+        assert_eq!(tx.outputs[1].script_pubkey, p2wpkh.to_bytes());
+
+        // the remainder is dust
+        let tx = TransactionBuilder::new()
+            .add_input(outpoint(0), spent.clone(), InputType::P2WPKH)
+            .add_output(&p2wpkh, 98_800)
+            .fee_rate(10)
+            .change_script(&p2wpkh)
+            .build()
+            .unwrap();
+        assert_eq!(tx.outputs.len(), 1);
+
+        assert_eq!(
+            TransactionBuilder::new()
+                .add_input(outpoint(0), spent, InputType::P2WPKH)
+                .add_output(&p2wpkh, 99_950)
+                .fee_rate(10)
+                .build(),
+            Err(Error::InsufficientFunds)
+        );
+        assert_eq!(TransactionBuilder::new().build(), Err(Error::NoInputs));
+    }
+
+    #[test]
+    fn test_build_fee_overflow() {
+        let p2wpkh = Builder::build_p2wpkh(&keypair().public().address_hash());
+        let spent = TransactionOutput {
+            value: 100_000,
+            script_pubkey: p2wpkh.to_bytes(),
+        };
+        assert_eq!(
+            TransactionBuilder::new()
+                .add_input(outpoint(0), spent, InputType::P2WPKH)
+                .add_output(&p2wpkh, 50_000)
+                .fee_rate(u64::MAX)
+                .build(),
+            Err(Error::FeeOverflow)
+        );
+    }
+
+    #[test]
+    fn test_build_sequence() {
+        let hash = keypair().public().address_hash();
+        let spent = TransactionOutput {
+            value: 10_000,
+            script_pubkey: Builder::build_p2wpkh(&hash).to_bytes(),
+        };
+        let builder = TransactionBuilder::new()
+            .add_input(outpoint(0), spent, InputType::P2TRKeyPath)
+            .add_output(&Builder::build_p2pkh(&hash), 5_000);
+
+        let tx = builder.clone().build().unwrap();
+        assert_eq!(tx.inputs[0].sequence, SEQUENCE_FINAL);
+        let tx = builder.clone().lock_time(800_000).build().unwrap();
+        assert_eq!(tx.inputs[0].sequence, SEQUENCE_LOCKTIME_ENABLED);
+        assert_eq!(tx.lock_time, 800_000);
+        let tx = builder.version(1).enable_rbf().build().unwrap();
+        assert_eq!(tx.inputs[0].sequence, SEQUENCE_RBF);
+        assert_eq!(tx.version, 1);
+    }
+}
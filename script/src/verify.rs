@@ -1,7 +1,7 @@
 use light_bitcoin_chain::constants::{
-    LOCKTIME_THRESHOLD, SEQUENCE_FINAL, SEQUENCE_LOCKTIME_DISABLE_FLAG, SEQUENCE_LOCKTIME_MASK,
-    SEQUENCE_LOCKTIME_TYPE_FLAG,
+    SEQUENCE_FINAL, SEQUENCE_LOCKTIME_MASK, SEQUENCE_LOCKTIME_TYPE_FLAG,
 };
+use light_bitcoin_chain::{AbsoluteLockTime, RelativeLockTime};
 use light_bitcoin_keys::{Message, Public, Signature};
 
 use crate::num::Num;
@@ -23,6 +23,10 @@ pub trait SignatureChecker {
 
     fn check_lock_time(&self, lock_time: Num) -> bool;
 
+    /// Checks an `OP_CHECKSEQUENCEVERIFY` operand against the sequence of the input.
+    ///
+    /// The operand must not have the disable flag set: callers must treat such an
+    /// operand as a NOP, as BIP112 does, rather than call this.
     fn check_sequence(&self, sequence: Num) -> bool;
 }
 
@@ -83,25 +87,22 @@ impl SignatureChecker for TransactionSignatureChecker {
         self.verify_signature(signature, public, &hash)
     }
 
-    #[cfg_attr(feature = "cargo-clippy", allow(clippy::nonminimal_bool))]
     fn check_lock_time(&self, lock_time: Num) -> bool {
+        // Operands which do not fit in an nLockTime are later than any of them.
+        let lock_time = match u32::try_from(i64::from(lock_time)) {
+            Ok(lock_time) => AbsoluteLockTime::from_consensus(lock_time),
+            Err(_) => return false,
+        };
+
         // There are two kinds of nLockTime: lock-by-blockheight
         // and lock-by-blocktime, distinguished by whether
         // nLockTime < LOCKTIME_THRESHOLD.
         //
         // We want to compare apples to apples, so fail the script
         // unless the type of nLockTime being tested is the same as
-        // the nLockTime in the transaction.
-        let lock_time_u32: u32 = lock_time.into();
-        if self.signer.lock_time < LOCKTIME_THRESHOLD && lock_time_u32 >= LOCKTIME_THRESHOLD
-            || self.signer.lock_time >= LOCKTIME_THRESHOLD && lock_time_u32 < LOCKTIME_THRESHOLD
-        {
-            return false;
-        }
-
-        // Now that we know we're comparing apples-to-apples, the
-        // comparison is a simple numeric one.
-        if i64::from(lock_time) > i64::from(self.signer.lock_time) {
+        // the nLockTime in the transaction, then the comparison is
+        // a simple numeric one.
+        if !lock_time.is_implied_by(AbsoluteLockTime::from_consensus(self.signer.lock_time)) {
             return false;
         }
 
@@ -119,10 +120,6 @@ impl SignatureChecker for TransactionSignatureChecker {
     }
 
     fn check_sequence(&self, sequence: Num) -> bool {
-        // Relative lock times are supported by comparing the passed
-        // in operand to the sequence number of the input.
-        let to_sequence: i64 = i64::from(self.signer.inputs[self.input_index].sequence);
-
         // Fail if the transaction's version number is not set high
         // enough to trigger BIP 68 rules.
         if (self.signer.version as u32) < 2 {
@@ -133,33 +130,91 @@ impl SignatureChecker for TransactionSignatureChecker {
         // consensus constrained. Testing that the transaction's sequence
         // number do not have this bit set prevents using this property
         // to get around a CHECKSEQUENCEVERIFY check.
-        if to_sequence & i64::from(SEQUENCE_LOCKTIME_DISABLE_FLAG) != 0 {
-            return false;
-        }
+        let to_sequence = self.signer.inputs[self.input_index].sequence;
+        let to_lock_time = match RelativeLockTime::from_sequence(to_sequence) {
+            Some(lock_time) => lock_time,
+            None => return false,
+        };
 
         // Mask off any bits that do not have consensus-enforced meaning
-        // before doing the integer comparisons
-        let locktime_mask: u32 = SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK;
-        let to_sequence_masked: i64 = to_sequence & i64::from(locktime_mask);
-        let sequence_masked: i64 = i64::from(sequence) & i64::from(locktime_mask);
+        // before doing the comparisons.
+        let locktime_mask = i64::from(SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK);
+        let sequence_masked = (i64::from(sequence) & locktime_mask) as u32;
+        let lock_time = match RelativeLockTime::from_sequence(sequence_masked) {
+            Some(lock_time) => lock_time,
+            None => return false,
+        };
 
         // There are two kinds of nSequence: lock-by-blockheight
         // and lock-by-blocktime, distinguished by whether
         // nSequenceMasked < CTxIn::SEQUENCE_LOCKTIME_TYPE_FLAG.
-
+        //
         // We want to compare apples to apples, so fail the script
         // unless the type of nSequenceMasked being tested is the same as
-        // the nSequenceMasked in the transaction.
-        if !((to_sequence_masked < i64::from(SEQUENCE_LOCKTIME_TYPE_FLAG)
-            && sequence_masked < i64::from(SEQUENCE_LOCKTIME_TYPE_FLAG))
-            || (to_sequence_masked >= i64::from(SEQUENCE_LOCKTIME_TYPE_FLAG)
-                && sequence_masked >= i64::from(SEQUENCE_LOCKTIME_TYPE_FLAG)))
-        {
-            return false;
+        // the nSequenceMasked in the transaction, then the comparison is
+        // a simple numeric one.
+        lock_time.is_implied_by(to_lock_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use light_bitcoin_chain::constants::{LOCKTIME_THRESHOLD, SEQUENCE_LOCKTIME_DISABLE_FLAG};
+    use light_bitcoin_chain::OutPoint;
+
+    use super::*;
+    use crate::sign::UnsignedTransactionInput;
+
+    fn checker(version: i32, sequence: u32, lock_time: u32) -> TransactionSignatureChecker {
+        TransactionSignatureChecker {
+            signer: TransactionInputSigner {
+                version,
+                inputs: vec![UnsignedTransactionInput {
+                    previous_output: OutPoint::default(),
+                    sequence,
+                }],
+                outputs: vec![],
+                lock_time,
+            },
+            input_index: 0,
+            input_amount: 0,
         }
+    }
+
+    #[test]
+    fn test_check_lock_time() {
+        let checker = checker(1, 0, 100);
+        assert!(checker.check_lock_time(100u32.into()));
+        assert!(!checker.check_lock_time(101u32.into()));
+        assert!(!checker.check_lock_time(LOCKTIME_THRESHOLD.into()));
+        assert!(!checker.check_lock_time((1i64 << 32).into()));
+
+        let checker = self::checker(1, 0, LOCKTIME_THRESHOLD + 10);
+        assert!(checker.check_lock_time(LOCKTIME_THRESHOLD.into()));
+        assert!(!checker.check_lock_time(10u32.into()));
+
+        // disabled by a final input
+        let checker = self::checker(1, SEQUENCE_FINAL, 100);
+        assert!(!checker.check_lock_time(100u32.into()));
+    }
 
-        // Now that we know we're comparing apples-to-apples, the
-        // comparison is a simple numeric one.
-        sequence_masked <= to_sequence_masked
+    #[test]
+    fn test_check_sequence() {
+        let checker = checker(2, 10, 0);
+        assert!(checker.check_sequence(10u32.into()));
+        assert!(!checker.check_sequence(11u32.into()));
+        assert!(!checker.check_sequence((SEQUENCE_LOCKTIME_TYPE_FLAG | 1).into()));
+        // bits without consensus meaning are ignored
+        assert!(checker.check_sequence((0x0001_0000u32 | 10).into()));
+
+        let checker = self::checker(2, SEQUENCE_LOCKTIME_TYPE_FLAG | 4, 0);
+        assert!(checker.check_sequence((SEQUENCE_LOCKTIME_TYPE_FLAG | 4).into()));
+        assert!(!checker.check_sequence(4u32.into()));
+
+        // not enforced before version 2, or when disabled
+        assert!(!self::checker(1, 10, 0).check_sequence(10u32.into()));
+        assert!(
+            !self::checker(2, SEQUENCE_LOCKTIME_DISABLE_FLAG | 10, 0).check_sequence(10u32.into())
+        );
     }
 }
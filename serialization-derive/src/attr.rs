@@ -0,0 +1,202 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+/// How a field is serialized, set with `#[serialize(...)]`
+pub enum Codec {
+    /// `Vec` fields as lists, other fields with their own impls
+    Default,
+    /// Not serialized, deserialized as `Default::default()`
+    Skip,
+    /// With the `serialize` and `deserialize` functions of a module
+    With(syn::Path),
+    /// An unsigned integer as a `CompactInteger`
+    Compact,
+    /// A list prefixed with its length
+    List,
+}
+
+pub fn field_codec(field: &syn::Field) -> Codec {
+    let mut codec = Codec::Default;
+    for meta in serialize_attrs(&field.attrs) {
+        let next = match meta {
+            syn::NestedMeta::Meta(syn::Meta::Path(ref path)) if path.is_ident("skip") => {
+                Codec::Skip
+            }
+            syn::NestedMeta::Meta(syn::Meta::Path(ref path)) if path.is_ident("compact") => {
+                Codec::Compact
+            }
+            syn::NestedMeta::Meta(syn::Meta::Path(ref path)) if path.is_ident("list") => {
+                Codec::List
+            }
+            syn::NestedMeta::Meta(syn::Meta::NameValue(ref nv)) if nv.path.is_ident("with") => {
+                Codec::With(parse_lit_str(&nv.lit, "with"))
+            }
+            _ => panic!("unknown #[serialize] field attribute."),
+        };
+        if !matches!(codec, Codec::Default) {
+            panic!("a field can only have one #[serialize] attribute.");
+        }
+        codec = next;
+    }
+    codec
+}
+
+/// The type of the tag of an enum, from `#[serialize(tag = "...")]` or
+/// `#[repr(...)]`, `u8` by default.
+pub fn enum_tag(ast: &syn::DeriveInput) -> syn::Type {
+    match serialize_attrs(&ast.attrs).as_slice() {
+        [] => (),
+        [syn::NestedMeta::Meta(syn::Meta::NameValue(nv))] if nv.path.is_ident("tag") => {
+            return parse_lit_str(&nv.lit, "tag");
+        }
+        _ => panic!("unknown #[serialize] enum attribute."),
+    }
+
+    for attr in ast.attrs.iter().filter(|attr| attr.path.is_ident("repr")) {
+        if let Ok(syn::Meta::List(list)) = attr.parse_meta() {
+            for nested in list.nested {
+                if let syn::NestedMeta::Meta(syn::Meta::Path(path)) = nested {
+                    if let Some(ident) = path.get_ident() {
+                        if ident.to_string().starts_with(['u', 'i']) {
+                            return syn::parse_quote!(#ident);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    syn::parse_quote!(u8)
+}
+
+/// The discriminants of the variants, explicit or following the previous one
+/// like in Rust.
+pub fn discriminants(data: &syn::DataEnum) -> Vec<proc_macro2::Literal> {
+    let mut next = 0u64;
+    data.variants
+        .iter()
+        .map(|variant| {
+            if let Some((_, ref expr)) = variant.discriminant {
+                next = match expr {
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Int(int),
+                        ..
+                    }) => int
+                        .base10_parse()
+                        .expect("discriminants must be unsigned integers"),
+                    _ => panic!("discriminants must be integer literals."),
+                };
+            }
+            let discriminant = next;
+            next += 1;
+            proc_macro2::Literal::u64_unsuffixed(discriminant)
+        })
+        .collect()
+}
+
+/// The member of the field, in the struct or in the variant.
+pub fn member(index: usize, field: &syn::Field) -> syn::Member {
+    match field.ident {
+        Some(ref ident) => syn::Member::Named(ident.clone()),
+        None => syn::Member::Unnamed(index.into()),
+    }
+}
+
+/// The name of the field bound when matching a variant.
+pub fn binding(index: usize) -> syn::Ident {
+    format_ident!("field_{}", index)
+}
+
+/// Serializes the field, `value` being a reference to it.
+pub fn serialize_field(field: &syn::Field, value: &TokenStream) -> TokenStream {
+    match field_codec(field) {
+        Codec::Default if is_vec(field) => quote! { stream.append_list(#value); },
+        Codec::Default => quote! { stream.append(#value); },
+        Codec::Skip => quote! {},
+        Codec::With(path) => quote! { #path::serialize(#value, stream); },
+        Codec::Compact => {
+            quote! { stream.append(&serialization::CompactInteger::from(*#value)); }
+        }
+        Codec::List => quote! { stream.append_list(#value); },
+    }
+}
+
+/// Serialized size of the field, `value` being a reference to it.
+pub fn serialized_field_size(field: &syn::Field, value: &TokenStream) -> Option<TokenStream> {
+    let size = match field_codec(field) {
+        Codec::Default if is_vec(field) => quote! { serialization::serialized_list_size(#value) },
+        Codec::Default => quote! { serialization::Serializable::serialized_size(#value) },
+        Codec::Skip => return None,
+        Codec::With(path) => quote! {{
+            let mut stream = serialization::Stream::default();
+            #path::serialize(#value, &mut stream);
+            stream.out().len()
+        }},
+        Codec::Compact => quote! {
+            serialization::Serializable::serialized_size(
+                &serialization::CompactInteger::from(*#value)
+            )
+        },
+        Codec::List => quote! { serialization::serialized_list_size(#value) },
+    };
+    Some(size)
+}
+
+/// Expression deserializing the field.
+pub fn deserialize_field(field: &syn::Field) -> TokenStream {
+    match field_codec(field) {
+        Codec::Default if is_vec(field) => quote! { reader.read_list()? },
+        Codec::Default => quote! { reader.read()? },
+        Codec::Skip => quote! { Default::default() },
+        Codec::With(path) => quote! { #path::deserialize(reader)? },
+        Codec::Compact => quote! {{
+            let value: u64 = reader.read::<serialization::CompactInteger>()?.into();
+            core::convert::TryFrom::try_from(value)
+                .map_err(|_| serialization::Error::ReadMalformedData)?
+        }},
+        Codec::List => quote! { reader.read_list()? },
+    }
+}
+
+/// Sum of the sizes, `0` when nothing is serialized.
+pub fn sum(sizes: Vec<TokenStream>) -> TokenStream {
+    if sizes.is_empty() {
+        quote! { 0 }
+    } else {
+        quote! { #(#sizes)+* }
+    }
+}
+
+fn is_vec(field: &syn::Field) -> bool {
+    match field.ty {
+        syn::Type::Path(ref path) => {
+            path.path
+                .segments
+                .first()
+                .expect("there must be at least 1 segment")
+                .ident
+                == "Vec"
+        }
+        _ => panic!("serialization not supported"),
+    }
+}
+
+fn serialize_attrs(attrs: &[syn::Attribute]) -> Vec<syn::NestedMeta> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("serialize"))
+        .flat_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => list.nested,
+            _ => panic!("expected #[serialize(...)]."),
+        })
+        .collect()
+}
+
+fn parse_lit_str<T: syn::parse::Parse>(lit: &syn::Lit, name: &str) -> T {
+    match lit {
+        syn::Lit::Str(s) => s
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid #[serialize({} = \"...\")].", name)),
+        _ => panic!("expected #[serialize({} = \"...\")].", name),
+    }
+}
@@ -1,21 +1,27 @@
 use quote::{format_ident, quote};
 
+use crate::attr::{deserialize_field, discriminants, enum_tag, member};
+
 pub fn impl_deserializable(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
-    let fields = match ast.data {
-        syn::Data::Struct(ref data) => &data.fields,
-        _ => panic!("#[derive(Deserializable)] is only defined for structs."),
-    };
+    let name = &ast.ident;
 
-    let stmts = match fields {
-        syn::Fields::Named(_) | syn::Fields::Unnamed(_) => fields
-            .iter()
-            .enumerate()
-            .map(deserialize_field_map)
-            .collect::<Vec<_>>(),
-        syn::Fields::Unit => panic!("#[derive(Deserializable)] is not defined for Unit structs."),
-    };
+    let body = match ast.data {
+        syn::Data::Struct(ref data) => {
+            if let syn::Fields::Unit = data.fields {
+                panic!("#[derive(Deserializable)] is not defined for Unit structs.");
+            }
+            let fields = deserialize_fields(&data.fields);
+            quote! {
+                let result = #name {
+                    #(#fields)*
+                };
 
-    let name = &ast.ident;
+                Ok(result)
+            }
+        }
+        syn::Data::Enum(ref data) => deserialize_enum(ast, data),
+        syn::Data::Union(_) => panic!("#[derive(Deserializable)] is not defined for unions."),
+    };
 
     let dummy_const = format_ident!("_IMPL_DESERIALIZABLE_FOR_{}", name);
     let impl_block = quote! {
@@ -24,11 +30,7 @@ pub fn impl_deserializable(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
             where
                 T: io::Read,
             {
-                let result = #name {
-                    #(#stmts)*
-                };
-
-                Ok(result)
+                #body
             }
         }
     };
@@ -43,30 +45,43 @@ pub fn impl_deserializable(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
     }
 }
 
-fn deserialize_field_map(tuple: (usize, &syn::Field)) -> proc_macro2::TokenStream {
-    deserialize_field(tuple.0, tuple.1)
+/// The fields, in the order they are serialized.
+fn deserialize_fields(fields: &syn::Fields) -> Vec<proc_macro2::TokenStream> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let member = member(index, field);
+            let value = deserialize_field(field);
+            quote! { #member: #value, }
+        })
+        .collect()
 }
 
-fn deserialize_field(index: usize, field: &syn::Field) -> proc_macro2::TokenStream {
-    let id = match field.ident {
-        Some(ref ident) => format_ident!("{}", ident),
-        None => format_ident!("{}", index),
-    };
+/// Reads the tag, then the fields of its variant.
+fn deserialize_enum(ast: &syn::DeriveInput, data: &syn::DataEnum) -> proc_macro2::TokenStream {
+    let name = &ast.ident;
+    let tag = enum_tag(ast);
 
-    match field.ty {
-        syn::Type::Path(ref path) => {
-            let ident = &path
-                .path
-                .segments
-                .first()
-                .expect("there must be at least 1 segment")
-                .ident;
-            if ident == "Vec" {
-                quote! { #id: reader.read_list()?, }
-            } else {
-                quote! { #id: reader.read()?, }
+    let arms = data
+        .variants
+        .iter()
+        .zip(discriminants(data))
+        .map(|(variant, discriminant)| {
+            let ident = &variant.ident;
+            let fields = deserialize_fields(&variant.fields);
+            quote! {
+                #discriminant => Ok(#name::#ident {
+                    #(#fields)*
+                }),
             }
+        });
+
+    quote! {
+        let tag: #tag = reader.read()?;
+        match tag {
+            #(#arms)*
+            _ => Err(serialization::Error::ReadMalformedData),
         }
-        _ => panic!("serialization not supported"),
     }
 }
@@ -1,19 +1,33 @@
+//! Derives `Serializable` and `Deserializable`.
+//!
+//! Structs are serialized field by field, `Vec` fields as lists. Enums are
+//! serialized as the discriminant of the variant, then its fields. The tag is
+//! a `u8`, or the type of `#[repr(...)]` or of `#[serialize(tag = "u32")]`.
+//!
+//! The serialization of a field changes with:
+//! - `#[serialize(skip)]`: not serialized, deserialized as `Default::default()`
+//! - `#[serialize(with = "module")]`: with `module::serialize(&value, stream)`
+//!   and `module::deserialize(reader)`
+//! - `#[serialize(compact)]`: an unsigned integer as a `CompactInteger`
+//! - `#[serialize(list)]`: a list prefixed with its length
+
 extern crate proc_macro;
 
+mod attr;
 mod de;
 mod ser;
 
 use self::de::impl_deserializable;
 use self::ser::impl_serializable;
 
-#[proc_macro_derive(Serializable)]
+#[proc_macro_derive(Serializable, attributes(serialize))]
 pub fn serializable(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse_macro_input!(input as syn::DeriveInput);
     let gen = impl_serializable(&ast);
     proc_macro::TokenStream::from(gen)
 }
 
-#[proc_macro_derive(Deserializable)]
+#[proc_macro_derive(Deserializable, attributes(serialize))]
 pub fn deserializable(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse_macro_input!(input as syn::DeriveInput);
     let gen = impl_deserializable(&ast);
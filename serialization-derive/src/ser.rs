@@ -1,27 +1,15 @@
 use quote::{format_ident, quote};
 
-pub fn impl_serializable(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
-    let fields = match ast.data {
-        syn::Data::Struct(ref data) => &data.fields,
-        _ => panic!("#[derive(Serializable)] is only defined for structs."),
-    };
+use crate::attr::{
+    binding, discriminants, enum_tag, field_codec, member, serialize_field, serialized_field_size,
+    sum, Codec,
+};
 
-    let stmts = match fields {
-        syn::Fields::Named(_) | syn::Fields::Unnamed(_) => fields
-            .iter()
-            .enumerate()
-            .map(serialize_field_map)
-            .collect::<Vec<_>>(),
-        syn::Fields::Unit => panic!("#[derive(Serializable)] is not defined for Unit structs."),
-    };
-
-    let size_stmts = match fields {
-        syn::Fields::Named(_) | syn::Fields::Unnamed(_) => fields
-            .iter()
-            .enumerate()
-            .map(serialize_field_size_map)
-            .collect::<Vec<_>>(),
-        syn::Fields::Unit => panic!("#[derive(Serializable)] is not defined for Unit structs."),
+pub fn impl_serializable(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+    let (stmts, size) = match ast.data {
+        syn::Data::Struct(ref data) => serialize_struct(&data.fields),
+        syn::Data::Enum(ref data) => serialize_enum(ast, data),
+        syn::Data::Union(_) => panic!("#[derive(Serializable)] is not defined for unions."),
     };
 
     let name = &ast.ident;
@@ -30,11 +18,11 @@ pub fn impl_serializable(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
     let impl_block = quote! {
         impl serialization::Serializable for #name {
             fn serialize(&self, stream: &mut serialization::Stream) {
-                #(#stmts)*
+                #stmts
             }
 
             fn serialized_size(&self) -> usize {
-                #(#size_stmts)+*
+                #size
             }
         }
     };
@@ -48,58 +36,104 @@ pub fn impl_serializable(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
     }
 }
 
-fn serialize_field_size_map(tuple: (usize, &syn::Field)) -> proc_macro2::TokenStream {
-    serialize_field_size(tuple.0, tuple.1)
-}
+fn serialize_struct(fields: &syn::Fields) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    if let syn::Fields::Unit = fields {
+        panic!("#[derive(Serializable)] is not defined for Unit structs.");
+    }
 
-fn serialize_field_size(index: usize, field: &syn::Field) -> proc_macro2::TokenStream {
-    let id = match field.ident {
-        Some(ref ident) => format_ident!("{}", ident),
-        None => format_ident!("{}", index),
-    };
+    let values = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let member = member(index, field);
+            quote! { &self.#member }
+        })
+        .collect::<Vec<_>>();
 
-    match field.ty {
-        syn::Type::Path(ref path) => {
-            let ident = &path
-                .path
-                .segments
-                .first()
-                .expect("there must be at least 1 segment")
-                .ident;
-            if ident == "Vec" {
-                quote! { serialization::serialized_list_size(&self.#id) }
-            } else {
-                quote! { self.#id.serialized_size() }
-            }
-        }
-        _ => panic!("serialization not supported"),
-    }
-}
+    let stmts = fields
+        .iter()
+        .zip(&values)
+        .map(|(field, value)| serialize_field(field, value));
 
-fn serialize_field_map(tuple: (usize, &syn::Field)) -> proc_macro2::TokenStream {
-    serialize_field(tuple.0, tuple.1)
+    let sizes = fields
+        .iter()
+        .zip(&values)
+        .filter_map(|(field, value)| serialized_field_size(field, value))
+        .collect();
+
+    (quote! { #(#stmts)* }, sum(sizes))
 }
 
-fn serialize_field(index: usize, field: &syn::Field) -> proc_macro2::TokenStream {
-    let id = match field.ident {
-        Some(ref ident) => format_ident!("{}", ident),
-        None => format_ident!("{}", index),
-    };
+/// The tag of the variant, then its fields.
+fn serialize_enum(
+    ast: &syn::DeriveInput,
+    data: &syn::DataEnum,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let name = &ast.ident;
+    let tag = enum_tag(ast);
+
+    let mut arms = vec![];
+    let mut size_arms = vec![];
+    for (variant, discriminant) in data.variants.iter().zip(discriminants(data)) {
+        let ident = &variant.ident;
+        let members = variant
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| member(index, field))
+            .collect::<Vec<_>>();
+        let values = (0..members.len())
+            .map(|index| {
+                let binding = binding(index);
+                quote! { #binding }
+            })
+            .collect::<Vec<_>>();
+        let patterns = variant
+            .fields
+            .iter()
+            .zip(&values)
+            .map(|(field, value)| match field_codec(field) {
+                Codec::Skip => quote! { _ },
+                _ => value.clone(),
+            })
+            .collect::<Vec<_>>();
 
-    match field.ty {
-        syn::Type::Path(ref path) => {
-            let ident = &path
-                .path
-                .segments
-                .first()
-                .expect("there must be at least 1 segment")
-                .ident;
-            if ident == "Vec" {
-                quote! { stream.append_list(&self.#id); }
-            } else {
-                quote! { stream.append(&self.#id); }
+        let stmts = variant
+            .fields
+            .iter()
+            .zip(&values)
+            .map(|(field, value)| serialize_field(field, value));
+        arms.push(quote! {
+            #name::#ident { #(#members: #patterns),* } => {
+                let tag: #tag = #discriminant;
+                stream.append(&tag);
+                #(#stmts)*
             }
-        }
-        _ => panic!("serialization not supported"),
+        });
+
+        let mut sizes = vec![quote! { core::mem::size_of::<#tag>() }];
+        sizes.extend(
+            variant
+                .fields
+                .iter()
+                .zip(&values)
+                .filter_map(|(field, value)| serialized_field_size(field, value)),
+        );
+        let size = sum(sizes);
+        size_arms.push(quote! {
+            #name::#ident { #(#members: #patterns),* } => #size,
+        });
     }
+
+    let stmts = quote! {
+        match self {
+            #(#arms)*
+        }
+    };
+    let size = quote! {
+        match self {
+            #(#size_arms)*
+        }
+    };
+    (stmts, size)
 }
@@ -1,4 +1,5 @@
-use light_bitcoin_serialization::{deserialize, serialize};
+use light_bitcoin_serialization::Serializable as _;
+use light_bitcoin_serialization::{deserialize, serialize, Bytes, Error};
 use light_bitcoin_serialization_derive::{Deserializable, Serializable};
 
 #[derive(Debug, PartialEq, Serializable, Deserializable)]
@@ -66,3 +67,136 @@ fn test_bar_serialize() {
     let de = deserialize(expected.as_ref()).unwrap();
     assert_eq!(test_bar, de);
 }
+
+mod reversed {
+    use light_bitcoin_serialization::{primitives::io, Error, Reader, Stream};
+
+    pub fn serialize(value: &u32, stream: &mut Stream) {
+        stream.append(&value.swap_bytes());
+    }
+
+    pub fn deserialize<T: io::Read>(reader: &mut Reader<T>) -> Result<u32, Error> {
+        reader.read::<u32>().map(u32::swap_bytes)
+    }
+}
+
+#[derive(Debug, PartialEq, Serializable, Deserializable)]
+struct Baz {
+    #[serialize(compact)]
+    a: u64,
+    #[serialize(skip)]
+    b: u8,
+    #[serialize(with = "reversed")]
+    c: u32,
+    #[serialize(list)]
+    d: List,
+}
+
+type List = Vec<u16>;
+
+#[derive(Debug, PartialEq, Serializable, Deserializable)]
+struct Tuple(u8, #[serialize(compact)] u32);
+
+#[derive(Debug, PartialEq, Serializable, Deserializable)]
+#[serialize(tag = "u32")]
+enum Kind {
+    A,
+    B,
+    C = 5,
+    D,
+}
+
+#[derive(Debug, PartialEq, Serializable, Deserializable)]
+#[repr(u8)]
+enum Message {
+    Ping(u64),
+    Data {
+        #[serialize(compact)]
+        id: u32,
+        items: Vec<Foo>,
+        #[serialize(skip)]
+        cached: bool,
+    },
+    Kind(Kind),
+    Empty = 0x10,
+}
+
+#[test]
+fn test_field_attributes() {
+    let baz = Baz {
+        a: 0x1234,
+        b: 7,
+        c: 0x0102_0304,
+        d: vec![1, 2],
+    };
+    let expected: Bytes = vec![0xfd, 0x34, 0x12, 1, 2, 3, 4, 2, 1, 0, 2, 0].into();
+    assert_eq!(serialize(&baz), expected);
+    assert_eq!(baz.serialized_size(), expected.len());
+    let de: Baz = deserialize(expected.as_ref()).unwrap();
+    assert_eq!(de, Baz { b: 0, ..baz });
+
+    let tuple = Tuple(1, 300);
+    let expected: Bytes = vec![1, 0xfd, 0x2c, 0x01].into();
+    assert_eq!(serialize(&tuple), expected);
+    assert_eq!(deserialize::<_, Tuple>(expected.as_ref()).unwrap(), tuple);
+
+    let data = [1u8, 0xfe, 0, 0, 1, 0];
+    assert_eq!(
+        deserialize::<_, Tuple>(&data[..]).unwrap(),
+        Tuple(1, 0x10000)
+    );
+    // the compact integer is not canonical
+    let data = [1u8, 0xfe, 0, 0, 0, 0];
+    assert_eq!(
+        deserialize::<_, Tuple>(&data[..]),
+        Err(Error::ReadMalformedData)
+    );
+    // the compact integer does not fit in the field
+    let data = [1u8, 0xff, 0, 0, 0, 0, 1, 0, 0, 0];
+    assert_eq!(
+        deserialize::<_, Tuple>(&data[..]),
+        Err(Error::ReadMalformedData)
+    );
+}
+
+#[test]
+fn test_enum_serialize() {
+    assert_eq!(serialize(&Kind::A), vec![0, 0, 0, 0].into());
+    assert_eq!(serialize(&Kind::B), vec![1, 0, 0, 0].into());
+    assert_eq!(serialize(&Kind::D), vec![6, 0, 0, 0].into());
+    assert_eq!(Kind::C.serialized_size(), 4);
+    for kind in [Kind::A, Kind::B, Kind::C, Kind::D] {
+        assert_eq!(deserialize::<_, Kind>(serialize(&kind).as_ref()), Ok(kind));
+    }
+    assert_eq!(
+        deserialize::<_, Kind>(&[2u8, 0, 0, 0][..]),
+        Err(Error::ReadMalformedData)
+    );
+
+    let messages = vec![
+        Message::Ping(9),
+        Message::Data {
+            id: 3,
+            items: vec![Foo {
+                a: 1,
+                b: 2,
+                c: 3,
+                d: 4,
+            }],
+            cached: false,
+        },
+        Message::Kind(Kind::C),
+        Message::Empty,
+    ];
+    let expected: Vec<Bytes> = vec![
+        vec![0, 9, 0, 0, 0, 0, 0, 0, 0].into(),
+        vec![1, 3, 1, 1, 2, 0, 3, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0].into(),
+        vec![2, 5, 0, 0, 0].into(),
+        vec![0x10].into(),
+    ];
+    for (message, expected) in messages.into_iter().zip(expected) {
+        assert_eq!(serialize(&message), expected);
+        assert_eq!(message.serialized_size(), expected.len());
+        assert_eq!(deserialize::<_, Message>(expected.as_ref()), Ok(message));
+    }
+}
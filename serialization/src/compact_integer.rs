@@ -92,13 +92,19 @@ impl Deserializable for CompactInteger {
     where
         T: io::Read,
     {
-        let result = match reader.read::<u8>()? {
-            i @ 0..=0xfc => i.into(),
-            0xfd => reader.read::<u16>()?.into(),
-            0xfe => reader.read::<u32>()?.into(),
-            _ => reader.read::<u64>()?.into(),
+        let (result, size): (u64, usize) = match reader.read::<u8>()? {
+            i @ 0..=0xfc => (i.into(), 1),
+            0xfd => (reader.read::<u16>()?.into(), 3),
+            0xfe => (reader.read::<u32>()?.into(), 5),
+            _ => (reader.read::<u64>()?, 9),
         };
 
+        // like Core, only the shortest encoding is accepted, so that a value
+        // has a single serialization
+        let result = CompactInteger(result);
+        if result.serialized_size() != size {
+            return Err(io::Error::ReadMalformedData);
+        }
         Ok(result)
     }
 }
@@ -166,4 +172,20 @@ mod tests {
             io::Error::UnexpectedEof
         );
     }
+
+    #[test]
+    fn test_compact_integer_non_canonical() {
+        for buffer in [
+            &[0xfd, 0x00, 0x00][..],
+            &[0xfd, 0xfc, 0x00],
+            &[0xfe, 0xff, 0xff, 0x00, 0x00],
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00],
+        ] {
+            let mut reader = Reader::new(buffer);
+            assert_eq!(
+                reader.read::<CompactInteger>().unwrap_err(),
+                io::Error::ReadMalformedData
+            );
+        }
+    }
 }
@@ -17,7 +17,10 @@ pub use light_bitcoin_primitives::{io::Error, Bytes, Compact, H160, H256, H264,
 
 pub use self::compact_integer::CompactInteger;
 pub use self::list::List;
-pub use self::reader::{deserialize, deserialize_iterator, Deserializable, ReadIterator, Reader};
+pub use self::reader::{
+    deserialize, deserialize_iterator, deserialize_with_limits, DecodeError, Deserializable,
+    Limits, ReadIterator, Reader, DEFAULT_MAX_DEPTH, DEFAULT_MAX_LENGTH,
+};
 pub use self::stream::{
     serialize, serialize_list, serialize_with_flags, serialized_list_size,
     serialized_list_size_with_flags, Serializable, Stream, SERIALIZE_TRANSACTION_WITNESS,
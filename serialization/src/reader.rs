@@ -1,11 +1,20 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
-use core::marker;
+use core::{cmp, fmt, marker, mem};
 
 use light_bitcoin_primitives::io;
 
 use crate::compact_integer::CompactInteger;
 
+/// Default limit on the items of a list and the bytes of a byte string, the
+/// size of a block.
+pub const DEFAULT_MAX_LENGTH: usize = 4 * 1024 * 1024;
+/// Default limit on the nesting of the structures
+pub const DEFAULT_MAX_DEPTH: usize = 32;
+/// Memory allocated at once for a list or a byte string, the rest is only
+/// allocated once the data is actually read.
+const MAX_PREALLOCATION: usize = 64 * 1024;
+
 pub fn deserialize<R, T>(buffer: R) -> Result<T, io::Error>
 where
     R: io::Read,
@@ -21,6 +30,29 @@ where
     }
 }
 
+/// Deserializes untrusted data within the limits, the error tells where the
+/// data stops being valid.
+pub fn deserialize_with_limits<R, T>(buffer: R, limits: Limits) -> Result<T, DecodeError>
+where
+    R: io::Read,
+    T: Deserializable,
+{
+    let mut reader = Reader::from_read(buffer).with_limits(limits);
+    let result = reader.read().map_err(|error| DecodeError {
+        offset: reader.position(),
+        error,
+    })?;
+
+    if reader.is_finished() {
+        Ok(result)
+    } else {
+        Err(DecodeError {
+            offset: reader.position(),
+            error: io::Error::UnreadData,
+        })
+    }
+}
+
 pub fn deserialize_iterator<R, T>(buffer: R) -> ReadIterator<R, T>
 where
     R: io::Read,
@@ -39,20 +71,59 @@ pub trait Deserializable {
         T: io::Read;
 }
 
+/// Limits of a `Reader`, so that a length prefix of crafted data can not make
+/// it allocate more than the data it reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of items of a list
+    pub max_list_length: usize,
+    /// Maximum number of bytes of a byte string
+    pub max_bytes: usize,
+    /// Maximum number of structures read inside each other
+    pub max_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_list_length: DEFAULT_MAX_LENGTH,
+            max_bytes: DEFAULT_MAX_LENGTH,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+/// Deserialization error, at the offset of the data where it happened.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DecodeError {
+    /// Number of bytes read when the error happened
+    pub offset: usize,
+    pub error: io::Error,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.error, self.offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
 /// Bitcoin structures reader.
 #[derive(Debug)]
 pub struct Reader<T> {
     buffer: T,
     peeked: Option<u8>,
+    limits: Limits,
+    depth: usize,
+    position: usize,
 }
 
 impl<'a> Reader<&'a [u8]> {
     /// Convenient way of creating for slice of bytes
     pub fn new(buffer: &'a [u8]) -> Self {
-        Reader {
-            buffer,
-            peeked: None,
-        }
+        Reader::from_read(buffer)
     }
 }
 
@@ -64,17 +135,19 @@ where
         // most of the times, there will be nothing in peeked,
         // so to make it as efficient as possible, check it
         // only once
-        match self.peeked.take() {
-            None => io::Read::read(&mut self.buffer, buf),
+        let read = match self.peeked.take() {
+            None => io::Read::read(&mut self.buffer, buf)?,
             Some(peeked) if buf.is_empty() => {
                 self.peeked = Some(peeked);
-                Ok(0)
+                0
             }
             Some(peeked) => {
                 buf[0] = peeked;
-                io::Read::read(&mut self.buffer, &mut buf[1..]).map(|x| x + 1)
+                io::Read::read(&mut self.buffer, &mut buf[1..])? + 1
             }
-        }
+        };
+        self.position += read;
+        Ok(read)
     }
 }
 
@@ -86,14 +159,37 @@ where
         Reader {
             buffer: read,
             peeked: None,
+            limits: Limits::default(),
+            depth: 0,
+            position: 0,
         }
     }
 
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Number of bytes read so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
     pub fn read<T>(&mut self) -> Result<T, io::Error>
     where
         T: Deserializable,
     {
-        T::deserialize(self)
+        if self.depth >= self.limits.max_depth {
+            return Err(io::Error::TooDeep);
+        }
+        self.depth += 1;
+        let result = T::deserialize(self);
+        self.depth -= 1;
+        result
     }
 
     pub fn read_with_proxy<T, F>(&mut self, proxy: F) -> Result<T, io::Error>
@@ -101,8 +197,11 @@ where
         T: Deserializable,
         F: FnMut(&[u8]),
     {
-        let mut reader = Reader::from_read(Proxy::new(self, proxy));
-        T::deserialize(&mut reader)
+        let limits = self.limits;
+        let depth = self.depth;
+        let mut reader = Reader::from_read(Proxy::new(self, proxy)).with_limits(limits);
+        reader.depth = depth;
+        reader.read()
     }
 
     pub fn skip_while(&mut self, predicate: &dyn Fn(u8) -> bool) -> Result<(), io::Error> {
@@ -120,6 +219,7 @@ where
                 self.peeked = Some(next);
                 return Ok(());
             }
+            self.position += 1;
         }
     }
 
@@ -127,18 +227,29 @@ where
         io::Read::read_exact(self, bytes).map_err(|_| io::Error::UnexpectedEof)
     }
 
+    /// Reads `len` bytes, within the limit of a byte string.
+    pub fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, io::Error> {
+        if len > self.limits.max_bytes {
+            return Err(io::Error::OutOfLength);
+        }
+
+        let mut result = Vec::with_capacity(cmp::min(len, MAX_PREALLOCATION));
+        while result.len() < len {
+            let start = result.len();
+            let end = cmp::min(len, start + MAX_PREALLOCATION);
+            result.resize(end, 0);
+            self.read_slice(&mut result[start..])?;
+        }
+
+        Ok(result)
+    }
+
     pub fn read_list<T>(&mut self) -> Result<Vec<T>, io::Error>
     where
         T: Deserializable,
     {
         let len: usize = self.read::<CompactInteger>()?.into();
-        let mut result = Vec::with_capacity(len);
-
-        for _ in 0..len {
-            result.push(self.read()?);
-        }
-
-        Ok(result)
+        self.read_items(len)
     }
 
     pub fn read_list_max<T>(&mut self, max: usize) -> Result<Vec<T>, io::Error>
@@ -150,7 +261,20 @@ where
             return Err(io::Error::ReadMalformedData);
         }
 
-        let mut result = Vec::with_capacity(len);
+        self.read_items(len)
+    }
+
+    fn read_items<T>(&mut self, len: usize) -> Result<Vec<T>, io::Error>
+    where
+        T: Deserializable,
+    {
+        if len > self.limits.max_list_length {
+            return Err(io::Error::OutOfLength);
+        }
+
+        // the vector grows with the items actually read
+        let preallocated = MAX_PREALLOCATION / cmp::max(mem::size_of::<T>(), 1);
+        let mut result = Vec::with_capacity(cmp::min(len, preallocated));
 
         for _ in 0..len {
             result.push(self.read()?);
@@ -168,6 +292,8 @@ where
         let peek: &mut [u8] = &mut [0u8];
         match self.read_slice(peek) {
             Ok(_) => {
+                // the peeked byte is not read yet
+                self.position -= 1;
                 self.peeked = Some(peek[0]);
                 false
             }
@@ -221,3 +347,112 @@ where
         Ok(len)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bytes, List, Stream};
+
+    #[derive(Debug, PartialEq)]
+    struct Nested(Option<Box<Nested>>);
+
+    impl Deserializable for Nested {
+        fn deserialize<T>(reader: &mut Reader<T>) -> Result<Self, io::Error>
+        where
+            T: io::Read,
+        {
+            match reader.read::<u8>()? {
+                0 => Ok(Nested(None)),
+                _ => Ok(Nested(Some(Box::new(reader.read()?)))),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reader_length_limits() {
+        // a list of u32::MAX items, with only one of them
+        let data = [0xfe, 0xff, 0xff, 0xff, 0xff, 0x01, 0x00, 0x00, 0x00];
+        assert_eq!(
+            deserialize::<_, List<u32>>(&data[..]).map(List::into),
+            Err(io::Error::OutOfLength)
+        );
+        let limits = Limits {
+            max_list_length: usize::MAX,
+            ..Default::default()
+        };
+        assert_eq!(
+            deserialize_with_limits::<_, List<u32>>(&data[..], limits).map(List::into),
+            Err(DecodeError {
+                offset: 9,
+                error: io::Error::UnexpectedEof,
+            })
+        );
+
+        let mut stream = Stream::default();
+        stream.append(&Bytes::from(vec![7u8; 200_000]));
+        let data = stream.out().take();
+        let bytes: Bytes = deserialize(&data[..]).unwrap();
+        assert_eq!(bytes.len(), 200_000);
+        let limits = Limits {
+            max_bytes: 199_999,
+            ..Default::default()
+        };
+        assert_eq!(
+            deserialize_with_limits::<_, Bytes>(&data[..], limits),
+            Err(DecodeError {
+                offset: 5,
+                error: io::Error::OutOfLength,
+            })
+        );
+        assert_eq!(
+            deserialize_with_limits::<_, Bytes>(&data[..1000], Limits::default()),
+            Err(DecodeError {
+                offset: 1000,
+                error: io::Error::UnexpectedEof,
+            })
+        );
+    }
+
+    #[test]
+    fn test_reader_depth_limit() {
+        let mut data = vec![1u8; 10];
+        data.push(0);
+        assert!(deserialize::<_, Nested>(&*data).is_ok());
+
+        // the byte of the innermost structure is read at depth 12
+        let limits = |max_depth| Limits {
+            max_depth,
+            ..Default::default()
+        };
+        assert!(deserialize_with_limits::<_, Nested>(&data[..], limits(12)).is_ok());
+        assert_eq!(
+            deserialize_with_limits::<_, Nested>(&data[..], limits(11)),
+            Err(DecodeError {
+                offset: 10,
+                error: io::Error::TooDeep,
+            })
+        );
+    }
+
+    #[test]
+    fn test_reader_position() {
+        let data = [1u8, 2, 3, 4, 5];
+        let mut reader = Reader::new(&data);
+        reader.skip_while(&|byte| byte < 3).unwrap();
+        assert_eq!(reader.position(), 2);
+        assert!(!reader.is_finished());
+        assert_eq!(reader.position(), 2);
+        assert_eq!(reader.read::<u16>().unwrap(), 0x0403);
+        assert_eq!(reader.position(), 4);
+
+        let mut data = data.to_vec();
+        data.push(6);
+        assert_eq!(
+            deserialize_with_limits::<_, u32>(&data[..], Limits::default()),
+            Err(DecodeError {
+                offset: 4,
+                error: io::Error::UnreadData,
+            })
+        );
+    }
+}
@@ -5,6 +5,9 @@ pub use light_bitcoin_crypto as crypto;
 pub use light_bitcoin_keys as keys;
 pub use light_bitcoin_mast as mast;
 pub use light_bitcoin_merkle as merkle;
+pub use light_bitcoin_p2p as p2p;
 pub use light_bitcoin_primitives as primitives;
+pub use light_bitcoin_psbt as psbt;
 pub use light_bitcoin_script as script;
 pub use light_bitcoin_serialization as serialization;
+pub use light_bitcoin_verification as verification;
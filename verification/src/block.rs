@@ -0,0 +1,341 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use light_bitcoin_chain::{merkle_node_hash, IndexedBlock, WITNESS_SCALE_FACTOR};
+use light_bitcoin_crypto::dhash256;
+use light_bitcoin_primitives::H256;
+use light_bitcoin_script::{is_witness_commitment_script, Script};
+
+use crate::constants::{MAX_BLOCK_SIGOPS_COST, MAX_BLOCK_WEIGHT};
+use crate::error::Error;
+use crate::transaction::check_transaction;
+
+/// Size of the coinbase witness reserved value
+const WITNESS_RESERVED_VALUE_SIZE: usize = 32;
+
+/// Context-free checks of a block, like Bitcoin Core's `CheckBlock` with the witness
+/// commitment and weight checks of `ContextualCheckBlock`.
+///
+/// The proof of work and the checks depending on the chain, such as the coinbase
+/// height or the transaction finality, are not covered.
+pub fn check_block(block: &IndexedBlock) -> Result<(), Error> {
+    let hashes = block
+        .transactions
+        .iter()
+        .map(|tx| tx.hash)
+        .collect::<Vec<_>>();
+    let (merkle_root, mutated) = merkle_root_mutated(&hashes);
+    if merkle_root != block.header.raw.merkle_root_hash {
+        return Err(Error::MerkleRoot);
+    }
+    if mutated {
+        return Err(Error::MutatedMerkleTree);
+    }
+
+    let (coinbase, transactions) = block.transactions.split_first().ok_or(Error::Empty)?;
+    if block.transactions.len() * WITNESS_SCALE_FACTOR > MAX_BLOCK_WEIGHT
+        || block.size() * WITNESS_SCALE_FACTOR > MAX_BLOCK_WEIGHT
+    {
+        return Err(Error::Weight);
+    }
+
+    if !coinbase.raw.is_coinbase() {
+        return Err(Error::MissingCoinbase);
+    }
+    if let Some(index) = transactions.iter().position(|tx| tx.raw.is_coinbase()) {
+        return Err(Error::MultipleCoinbases(index + 1));
+    }
+
+    for (index, tx) in block.transactions.iter().enumerate() {
+        check_transaction(&tx.raw).map_err(|err| Error::Transaction(index, err))?;
+    }
+
+    let mut sigops = 0;
+    for tx in &block.transactions {
+        let inputs = tx.raw.inputs.iter().map(|input| &input.script_sig);
+        let outputs = tx.raw.outputs.iter().map(|output| &output.script_pubkey);
+        sigops += inputs
+            .chain(outputs)
+            .map(|script| Script::from(script.clone()).sigops_count(false, false))
+            .sum::<usize>();
+        if sigops * WITNESS_SCALE_FACTOR > MAX_BLOCK_SIGOPS_COST {
+            return Err(Error::Sigops);
+        }
+    }
+
+    check_witness_commitment(block)?;
+
+    let weight = block.size() * (WITNESS_SCALE_FACTOR - 1) + block.size_with_witness();
+    if weight > MAX_BLOCK_WEIGHT {
+        return Err(Error::Weight);
+    }
+    Ok(())
+}
+
+/// Checks the witness commitment of the coinbase, or that no transaction has witness
+/// data if there is none
+fn check_witness_commitment(block: &IndexedBlock) -> Result<(), Error> {
+    let coinbase = &block.transactions[0].raw;
+    // the last commitment counts
+    let commitment = coinbase
+        .outputs
+        .iter()
+        .rev()
+        .find(|output| is_witness_commitment_script(&output.script_pubkey));
+
+    let commitment = match commitment {
+        Some(commitment) => commitment,
+        None => {
+            return match block
+                .transactions
+                .iter()
+                .position(|tx| tx.raw.has_witness())
+            {
+                Some(index) => Err(Error::UnexpectedWitness(index)),
+                None => Ok(()),
+            };
+        }
+    };
+
+    let reserved_value = match coinbase.inputs[0].script_witness.as_slice() {
+        [reserved_value] if reserved_value.len() == WITNESS_RESERVED_VALUE_SIZE => reserved_value,
+        _ => return Err(Error::WitnessCommitment),
+    };
+    let mut preimage = block.witness_merkle_root().as_bytes().to_vec();
+    preimage.extend_from_slice(reserved_value);
+    if dhash256(&preimage).as_bytes() != &commitment.script_pubkey[6..38] {
+        return Err(Error::WitnessCommitment);
+    }
+    Ok(())
+}
+
+/// Computes the merkle root of `hashes` and whether the tree is mutated, that is some
+/// level has two identical adjacent nodes.
+///
+/// A mutated transaction list has the same merkle root as the list without the
+/// duplicated transactions (CVE-2012-2459).
+pub fn merkle_root_mutated(hashes: &[H256]) -> (H256, bool) {
+    if hashes.is_empty() {
+        return (H256::zero(), false);
+    }
+
+    let mut mutated = false;
+    let mut level = hashes.to_vec();
+    while level.len() > 1 {
+        mutated |= level.chunks_exact(2).any(|pair| pair[0] == pair[1]);
+        if level.len() % 2 == 1 {
+            level.push(level[level.len() - 1]);
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| merkle_node_hash(&pair[0], &pair[1]))
+            .collect();
+    }
+    (level[0], mutated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TransactionError;
+    use light_bitcoin_chain::{
+        merkle_root, Block, BlockHeader, OutPoint, Transaction, TransactionInput, TransactionOutput,
+    };
+    use light_bitcoin_primitives::Bytes;
+    use light_bitcoin_script::{Builder, Opcode};
+
+    // Block 80000
+    const BLOCK_80000: &str = "01000000ba8b9cda965dd8e536670f9ddec10e53aab14b20bacad27b9137190000000000190760b278fe7b8565fda3b968b918d5fd997f993b23674c0af3b6fde300b38f33a5914ce6ed5b1b01e32f570201000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0704e6ed5b1b014effffffff0100f2052a01000000434104b68a50eaa0287eff855189f949c1c6e5f58b37c88231373d8a59809cbae83059cc6469d65c665ccfd1cfeb75c6e8e19413bba7fbff9bc762419a76d87b16086eac000000000100000001a6b97044d03da79c005b20ea9c0e1a6d9dc12d9f7b91a5911c9030a439eed8f5000000004948304502206e21798a42fae0e854281abd38bacd1aeed3ee3738d9e1446618c4571d1090db022100e2ac980643b0b82c0e88ffdfec6b64e3e6ba35e7ba5fdd7d5d6cc8d25c6b241501ffffffff0100f2052a010000001976a914404371705fa9bd789a2fcd52d2c580b65d35549d88ac00000000";
+
+    fn coinbase(outputs: Vec<TransactionOutput>) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput::coinbase(vec![1, 2].into())],
+            outputs,
+            lock_time: 0,
+        }
+    }
+
+    fn spend(byte: u8, witness: Vec<Bytes>) -> Transaction {
+        Transaction {
+            version: 2,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    txid: H256::repeat_byte(byte),
+                    index: 0,
+                },
+                script_sig: Default::default(),
+                sequence: 0xffff_ffff,
+                script_witness: witness,
+            }],
+            outputs: vec![TransactionOutput {
+                value: 1_000,
+                script_pubkey: Default::default(),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    /// Builds a block with a correct merkle root
+    fn block(transactions: Vec<Transaction>) -> IndexedBlock {
+        let hashes = transactions
+            .iter()
+            .map(Transaction::hash)
+            .collect::<Vec<_>>();
+        let header = BlockHeader {
+            merkle_root_hash: merkle_root(&hashes),
+            ..Default::default()
+        };
+        Block::new(header, transactions).into()
+    }
+
+    /// Builds a segwit block committing to its witness merkle root
+    fn segwit_block(mut transactions: Vec<Transaction>) -> IndexedBlock {
+        let reserved_value = Bytes::from(vec![0; 32]);
+        transactions[0].inputs[0].script_witness = vec![reserved_value.clone()];
+        transactions[0].outputs.push(output(0));
+        let witness_root = block(transactions.clone()).witness_merkle_root();
+
+        let mut preimage = witness_root.as_bytes().to_vec();
+        preimage.extend_from_slice(&reserved_value);
+        let mut commitment = vec![0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+        commitment.extend_from_slice(dhash256(&preimage).as_bytes());
+        transactions[0].outputs.last_mut().unwrap().script_pubkey = commitment.into();
+        block(transactions)
+    }
+
+    fn output(value: u64) -> TransactionOutput {
+        TransactionOutput {
+            value,
+            script_pubkey: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_check_block_80000() {
+        let block: IndexedBlock = BLOCK_80000.parse().unwrap();
+        assert_eq!(check_block(&block), Ok(()));
+    }
+
+    #[test]
+    fn test_check_block_structure() {
+        let valid = block(vec![coinbase(vec![output(50)]), spend(1, vec![])]);
+        assert_eq!(check_block(&valid), Ok(()));
+
+        let mut bad_root = valid.clone();
+        bad_root.header.raw.merkle_root_hash = H256::repeat_byte(1);
+        assert_eq!(check_block(&bad_root), Err(Error::MerkleRoot));
+
+        let empty = Block::new(Default::default(), vec![]).into();
+        assert_eq!(check_block(&empty), Err(Error::Empty));
+        assert_eq!(
+            check_block(&block(vec![spend(1, vec![])])),
+            Err(Error::MissingCoinbase)
+        );
+        assert_eq!(
+            check_block(&block(vec![
+                coinbase(vec![output(50)]),
+                coinbase(vec![output(25)])
+            ])),
+            Err(Error::MultipleCoinbases(1))
+        );
+        assert_eq!(
+            check_block(&block(vec![
+                coinbase(vec![output(50)]),
+                spend(1, vec![]),
+                {
+                    let mut tx = spend(2, vec![]);
+                    tx.outputs.clear();
+                    tx
+                }
+            ])),
+            Err(Error::Transaction(2, TransactionError::NoOutputs))
+        );
+    }
+
+    #[test]
+    fn test_check_block_mutated() {
+        // duplicating the last transaction of an odd list keeps the merkle root
+        let transactions = vec![
+            coinbase(vec![output(50)]),
+            spend(1, vec![]),
+            spend(2, vec![]),
+        ];
+        let valid = block(transactions.clone());
+        assert_eq!(check_block(&valid), Ok(()));
+
+        let mut duplicated = transactions;
+        duplicated.push(duplicated[2].clone());
+        let mutated = block(duplicated);
+        assert_eq!(
+            mutated.header.raw.merkle_root_hash,
+            valid.header.raw.merkle_root_hash
+        );
+        assert_eq!(check_block(&mutated), Err(Error::MutatedMerkleTree));
+    }
+
+    #[test]
+    fn test_check_block_sigops() {
+        let checksigs = Builder::default()
+            .push_opcode(Opcode::OP_CHECKMULTISIG)
+            .into_bytes();
+        // each bare CHECKMULTISIG counts as 20 sigops
+        let outputs = (0..1_001)
+            .map(|_| TransactionOutput {
+                value: 0,
+                script_pubkey: checksigs.clone(),
+            })
+            .collect();
+        assert_eq!(
+            check_block(&block(vec![coinbase(outputs)])),
+            Err(Error::Sigops)
+        );
+    }
+
+    #[test]
+    fn test_check_block_witness_commitment() {
+        let witness = vec![Bytes::from(vec![1; 72]), Bytes::from(vec![2; 33])];
+        let transactions = vec![coinbase(vec![output(50)]), spend(1, witness.clone())];
+        assert_eq!(
+            check_block(&block(transactions.clone())),
+            Err(Error::UnexpectedWitness(1))
+        );
+
+        let valid = segwit_block(transactions.clone());
+        assert_eq!(check_block(&valid), Ok(()));
+
+        // the commitment does not cover a different witness
+        let mut raw = valid.clone().raw_block();
+        raw.transactions[1].inputs[0].script_witness[0] = vec![3; 72].into();
+        assert_eq!(
+            check_block(&IndexedBlock::from(raw.clone())),
+            Err(Error::WitnessCommitment)
+        );
+
+        raw.transactions[0].inputs[0].script_witness.clear();
+        let no_reserved_value = block(raw.transactions);
+        assert_eq!(
+            check_block(&no_reserved_value),
+            Err(Error::WitnessCommitment)
+        );
+    }
+
+    #[test]
+    fn test_check_block_weight() {
+        // each output script is 100 000 bytes, which is within the transaction limit
+        let big_output = TransactionOutput {
+            value: 0,
+            script_pubkey: vec![Opcode::OP_NOP as u8; 100_000].into(),
+        };
+        let transactions = (0..10u8)
+            .map(|byte| {
+                let mut tx = spend(byte + 1, vec![]);
+                tx.outputs[0] = big_output.clone();
+                tx
+            })
+            .collect::<Vec<_>>();
+        let mut all = vec![coinbase(vec![output(50)])];
+        all.extend(transactions);
+        assert_eq!(check_block(&block(all)), Err(Error::Weight));
+    }
+}
@@ -0,0 +1,20 @@
+/// Maximum weight of a block
+pub const MAX_BLOCK_WEIGHT: usize = 4_000_000;
+
+/// Maximum signature operations cost of a block
+pub const MAX_BLOCK_SIGOPS_COST: usize = 80_000;
+
+/// Minimum size of the coinbase script_sig
+pub const MIN_COINBASE_SCRIPT_SIZE: usize = 2;
+
+/// Maximum size of the coinbase script_sig
+pub const MAX_COINBASE_SCRIPT_SIZE: usize = 100;
+
+/// Maximum time in seconds a block timestamp may be ahead of the current time
+pub const MAX_FUTURE_BLOCK_TIME: u32 = 2 * 60 * 60;
+
+/// Number of previous blocks used to compute the median time past
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Maximum number of headers kept while waiting for their parent
+pub const MAX_ORPHAN_HEADERS: usize = 2_000;
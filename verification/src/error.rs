@@ -0,0 +1,115 @@
+use core::fmt;
+
+/// Block and header verification errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The block has no transactions
+    Empty,
+    /// The block weight is above `MAX_BLOCK_WEIGHT`
+    Weight,
+    /// The merkle root does not match the transactions
+    MerkleRoot,
+    /// The transaction list has duplicated transactions producing the same merkle
+    /// root as another list (CVE-2012-2459)
+    MutatedMerkleTree,
+    /// The first transaction is not a coinbase
+    MissingCoinbase,
+    /// The transaction at the index is a coinbase but not the first one
+    MultipleCoinbases(usize),
+    /// The legacy signature operations cost is above `MAX_BLOCK_SIGOPS_COST`
+    Sigops,
+    /// The witness commitment does not match the witness merkle root, or the coinbase
+    /// witness is not a single 32 bytes reserved value
+    WitnessCommitment,
+    /// The transaction at the index has witness data but the block has no witness commitment
+    UnexpectedWitness(usize),
+    /// The transaction at the index is invalid
+    Transaction(usize, TransactionError),
+    /// The bits are not a valid target below the pow limit
+    InvalidBits,
+    /// The block hash is above the target
+    ProofOfWork,
+    /// The bits do not match the difficulty retarget rules
+    UnexpectedBits,
+    /// The header at the height is needed to compute the difficulty
+    MissingHeader(u32),
+    /// The header time is not above the median time past
+    TimeTooOld,
+    /// The header time is too far in the future
+    TimeTooNew,
+    /// The header does not match the checkpoint at its height
+    Checkpoint,
+    /// The header forks the chain below the last checkpoint
+    ForkBeforeCheckpoint,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Empty => "Block has no transactions".fmt(f),
+            Error::Weight => "Block weight is above the limit".fmt(f),
+            Error::MerkleRoot => "Merkle root mismatch".fmt(f),
+            Error::MutatedMerkleTree => "Duplicate transactions in merkle tree".fmt(f),
+            Error::MissingCoinbase => "First transaction is not a coinbase".fmt(f),
+            Error::MultipleCoinbases(index) => write!(f, "Transaction {} is a coinbase", index),
+            Error::Sigops => "Signature operations are above the limit".fmt(f),
+            Error::WitnessCommitment => "Witness commitment mismatch".fmt(f),
+            Error::UnexpectedWitness(index) => {
+                write!(f, "Transaction {} has witness without commitment", index)
+            }
+            Error::Transaction(index, err) => write!(f, "Transaction {}: {}", index, err),
+            Error::InvalidBits => "Invalid difficulty bits".fmt(f),
+            Error::ProofOfWork => "Block hash is above the target".fmt(f),
+            Error::UnexpectedBits => "Incorrect proof of work".fmt(f),
+            Error::MissingHeader(height) => write!(f, "Missing header at height {}", height),
+            Error::TimeTooOld => "Block time is too early".fmt(f),
+            Error::TimeTooNew => "Block time is too far in the future".fmt(f),
+            Error::Checkpoint => "Checkpoint mismatch".fmt(f),
+            Error::ForkBeforeCheckpoint => "Fork below the last checkpoint".fmt(f),
+        }
+    }
+}
+
+/// Transaction verification errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionError {
+    /// The transaction has no inputs
+    NoInputs,
+    /// The transaction has no outputs
+    NoOutputs,
+    /// The transaction size without witness is above the block weight limit
+    Oversize,
+    /// The output at the index is above `MAX_MONEY`
+    OutputValue(usize),
+    /// The sum of the outputs is above `MAX_MONEY`
+    TotalOutputValue,
+    /// The transaction spends the same output twice
+    DuplicateInputs,
+    /// The coinbase script_sig size is out of range
+    CoinbaseScriptSize,
+    /// A transaction other than a coinbase spends a null outpoint
+    NullPrevout,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TransactionError {}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TransactionError::NoInputs => "Transaction has no inputs".fmt(f),
+            TransactionError::NoOutputs => "Transaction has no outputs".fmt(f),
+            TransactionError::Oversize => "Transaction size is above the limit".fmt(f),
+            TransactionError::OutputValue(index) => {
+                write!(f, "Output {} value is above the limit", index)
+            }
+            TransactionError::TotalOutputValue => "Total output value is above the limit".fmt(f),
+            TransactionError::DuplicateInputs => "Transaction has duplicate inputs".fmt(f),
+            TransactionError::CoinbaseScriptSize => "Coinbase script size is out of range".fmt(f),
+            TransactionError::NullPrevout => "Transaction spends a null outpoint".fmt(f),
+        }
+    }
+}
@@ -0,0 +1,693 @@
+#[cfg(not(feature = "std"))]
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use light_bitcoin_chain::IndexedBlockHeader;
+use light_bitcoin_primitives::{H256, U256};
+
+use crate::constants::{MAX_FUTURE_BLOCK_TIME, MAX_ORPHAN_HEADERS, MEDIAN_TIME_SPAN};
+use crate::error::Error;
+use crate::pow::{block_proof, check_proof_of_work, next_work_required, ConsensusParams};
+
+/// A header connected to the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderEntry {
+    pub header: IndexedBlockHeader,
+    pub height: u32,
+    /// Cumulative work from the starting header
+    pub chain_work: U256,
+}
+
+/// Storage of a header chain.
+///
+/// Implemented by the in-memory `MemoryHeaderStore`, and meant to be implemented on top of
+/// runtime storage or files.
+pub trait HeaderStore {
+    /// A connected header by hash
+    fn header(&self, hash: &H256) -> Option<HeaderEntry>;
+    fn insert_header(&mut self, entry: HeaderEntry);
+
+    /// The tip of the best chain
+    fn best_hash(&self) -> Option<H256>;
+    fn set_best_hash(&mut self, hash: H256);
+
+    /// The hash of the best chain header at the height
+    fn best_hash_at(&self, height: u32) -> Option<H256>;
+    fn insert_best_hash_at(&mut self, height: u32, hash: H256);
+    fn remove_best_hash_at(&mut self, height: u32);
+
+    /// The connected headers without children
+    fn tips(&self) -> Vec<H256>;
+    fn insert_tip(&mut self, hash: H256);
+    fn remove_tip(&mut self, hash: &H256);
+
+    /// Headers whose parent is not connected yet.
+    ///
+    /// Anyone can send headers on top of unknown parents, so stores must bound the
+    /// number of orphans they keep, and may drop any of them.
+    fn insert_orphan(&mut self, header: IndexedBlockHeader);
+    /// Removes and returns the orphans with the given parent
+    fn take_orphans(&mut self, parent: &H256) -> Vec<IndexedBlockHeader>;
+}
+
+/// In-memory `HeaderStore`
+///
+/// Keeps at most `MAX_ORPHAN_HEADERS` orphans by default, evicting the oldest.
+#[derive(Debug, Clone)]
+pub struct MemoryHeaderStore {
+    headers: BTreeMap<H256, HeaderEntry>,
+    best_hash: Option<H256>,
+    best_chain: BTreeMap<u32, H256>,
+    tips: BTreeSet<H256>,
+    /// Orphans by parent, with their insertion sequence
+    orphans: BTreeMap<H256, Vec<(u64, IndexedBlockHeader)>>,
+    /// Parents of the orphans by insertion sequence, oldest first
+    orphan_order: BTreeMap<u64, H256>,
+    next_orphan: u64,
+    max_orphans: usize,
+}
+
+impl Default for MemoryHeaderStore {
+    fn default() -> Self {
+        MemoryHeaderStore::with_max_orphans(MAX_ORPHAN_HEADERS)
+    }
+}
+
+impl MemoryHeaderStore {
+    pub fn with_max_orphans(max_orphans: usize) -> Self {
+        MemoryHeaderStore {
+            headers: BTreeMap::new(),
+            best_hash: None,
+            best_chain: BTreeMap::new(),
+            tips: BTreeSet::new(),
+            orphans: BTreeMap::new(),
+            orphan_order: BTreeMap::new(),
+            next_orphan: 0,
+            max_orphans,
+        }
+    }
+
+    /// Number of headers waiting for their parent
+    pub fn orphan_count(&self) -> usize {
+        self.orphan_order.len()
+    }
+
+    fn evict_oldest_orphan(&mut self) {
+        let (sequence, parent) = match self.orphan_order.pop_first() {
+            Some(oldest) => oldest,
+            None => return,
+        };
+        if let Some(orphans) = self.orphans.get_mut(&parent) {
+            orphans.retain(|(s, _)| *s != sequence);
+            if orphans.is_empty() {
+                self.orphans.remove(&parent);
+            }
+        }
+    }
+}
+
+impl HeaderStore for MemoryHeaderStore {
+    fn header(&self, hash: &H256) -> Option<HeaderEntry> {
+        self.headers.get(hash).copied()
+    }
+
+    fn insert_header(&mut self, entry: HeaderEntry) {
+        self.headers.insert(entry.header.hash, entry);
+    }
+
+    fn best_hash(&self) -> Option<H256> {
+        self.best_hash
+    }
+
+    fn set_best_hash(&mut self, hash: H256) {
+        self.best_hash = Some(hash);
+    }
+
+    fn best_hash_at(&self, height: u32) -> Option<H256> {
+        self.best_chain.get(&height).copied()
+    }
+
+    fn insert_best_hash_at(&mut self, height: u32, hash: H256) {
+        self.best_chain.insert(height, hash);
+    }
+
+    fn remove_best_hash_at(&mut self, height: u32) {
+        self.best_chain.remove(&height);
+    }
+
+    fn tips(&self) -> Vec<H256> {
+        self.tips.iter().copied().collect()
+    }
+
+    fn insert_tip(&mut self, hash: H256) {
+        self.tips.insert(hash);
+    }
+
+    fn remove_tip(&mut self, hash: &H256) {
+        self.tips.remove(hash);
+    }
+
+    fn insert_orphan(&mut self, header: IndexedBlockHeader) {
+        let parent = header.raw.previous_header_hash;
+        let known = self
+            .orphans
+            .get(&parent)
+            .is_some_and(|orphans| orphans.iter().any(|(_, orphan)| *orphan == header));
+        if known || self.max_orphans == 0 {
+            return;
+        }
+        while self.orphan_order.len() >= self.max_orphans {
+            self.evict_oldest_orphan();
+        }
+        let sequence = self.next_orphan;
+        self.next_orphan += 1;
+        self.orphan_order.insert(sequence, parent);
+        self.orphans
+            .entry(parent)
+            .or_default()
+            .push((sequence, header));
+    }
+
+    fn take_orphans(&mut self, parent: &H256) -> Vec<IndexedBlockHeader> {
+        let orphans = self.orphans.remove(parent).unwrap_or_default();
+        orphans
+            .into_iter()
+            .map(|(sequence, header)| {
+                self.orphan_order.remove(&sequence);
+                header
+            })
+            .collect()
+    }
+}
+
+/// Change of the best chain
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BestChainUpdate {
+    /// Hashes removed from the best chain, from the old tip down to the fork point
+    pub disconnected: Vec<H256>,
+    /// Hashes added to the best chain, from the fork point up to the new tip
+    pub connected: Vec<H256>,
+}
+
+impl BestChainUpdate {
+    pub fn is_reorg(&self) -> bool {
+        !self.disconnected.is_empty()
+    }
+}
+
+/// Result of a header import
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderImport {
+    /// The header was already imported
+    AlreadyKnown,
+    /// The parent is unknown, the header is connected once the parent is imported
+    Orphan,
+    /// The header and the orphans it connects were added to the chain, with the best
+    /// chain update if one of them has more work than the previous best chain
+    Connected(Option<BestChainUpdate>),
+}
+
+/// Header chain of an SPV client, choosing the chain with the most work.
+///
+/// Headers are checked for proof of work, difficulty retarget, median time past,
+/// future time and checkpoints. The chain starts from a trusted header, which should be
+/// at a retarget height so that the difficulty of the next retarget can be computed.
+pub struct HeaderChain<S> {
+    store: S,
+    params: ConsensusParams,
+    checkpoints: BTreeMap<u32, H256>,
+}
+
+impl<S: HeaderStore> HeaderChain<S> {
+    /// Creates the chain, starting from the trusted `start` header at `height` if the
+    /// store is empty.
+    pub fn new(
+        mut store: S,
+        params: ConsensusParams,
+        start: IndexedBlockHeader,
+        height: u32,
+    ) -> Self {
+        if store.best_hash().is_none() {
+            let hash = start.hash;
+            store.insert_header(HeaderEntry {
+                chain_work: block_proof(start.raw.bits),
+                header: start,
+                height,
+            });
+            store.insert_best_hash_at(height, hash);
+            store.insert_tip(hash);
+            store.set_best_hash(hash);
+        }
+        HeaderChain {
+            store,
+            params,
+            checkpoints: BTreeMap::new(),
+        }
+    }
+
+    /// Requires the best chain to go through the given heights and hashes, and rejects
+    /// forks below the last checkpoint reached.
+    pub fn with_checkpoints<I>(mut self, checkpoints: I) -> Self
+    where
+        I: IntoIterator<Item = (u32, H256)>,
+    {
+        self.checkpoints.extend(checkpoints);
+        self
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    pub fn params(&self) -> &ConsensusParams {
+        &self.params
+    }
+
+    /// The tip of the best chain
+    pub fn best(&self) -> HeaderEntry {
+        self.store
+            .best_hash()
+            .and_then(|hash| self.store.header(&hash))
+            .expect("the best header is stored on creation; qed")
+    }
+
+    /// The best chain header at the height
+    pub fn header_at(&self, height: u32) -> Option<HeaderEntry> {
+        self.store
+            .best_hash_at(height)
+            .and_then(|hash| self.store.header(&hash))
+    }
+
+    /// A connected header by hash, in the best chain or not
+    pub fn header(&self, hash: &H256) -> Option<HeaderEntry> {
+        self.store.header(hash)
+    }
+
+    pub fn is_in_best_chain(&self, hash: &H256) -> bool {
+        self.store
+            .header(hash)
+            .is_some_and(|entry| self.store.best_hash_at(entry.height) == Some(*hash))
+    }
+
+    /// The tips of all the known chains
+    pub fn tips(&self) -> Vec<HeaderEntry> {
+        self.store
+            .tips()
+            .iter()
+            .filter_map(|hash| self.store.header(hash))
+            .collect()
+    }
+
+    /// Imports a header, received in any order.
+    ///
+    /// `now` is the current unix time, used to reject headers too far in the future.
+    /// Orphans connected by this header are imported too, and dropped if invalid.
+    pub fn import_header(
+        &mut self,
+        header: IndexedBlockHeader,
+        now: u32,
+    ) -> Result<HeaderImport, Error> {
+        if self.store.header(&header.hash).is_some() {
+            return Ok(HeaderImport::AlreadyKnown);
+        }
+        // checked before storing orphans, so that unconnected headers still cost work
+        check_proof_of_work(&header.hash, header.raw.bits, &self.params)?;
+
+        let parent = match self.store.header(&header.raw.previous_header_hash) {
+            Some(parent) => parent,
+            None => {
+                self.store.insert_orphan(header);
+                return Ok(HeaderImport::Orphan);
+            }
+        };
+
+        let mut best = self.connect(&parent, header, now)?;
+        let mut connected = VecDeque::new();
+        connected.push_back(best);
+        while let Some(entry) = connected.pop_front() {
+            for orphan in self.store.take_orphans(&entry.header.hash) {
+                if let Ok(child) = self.connect(&entry, orphan, now) {
+                    if child.chain_work > best.chain_work {
+                        best = child;
+                    }
+                    connected.push_back(child);
+                }
+            }
+        }
+
+        if best.chain_work > self.best().chain_work {
+            Ok(HeaderImport::Connected(Some(self.set_best(best))))
+        } else {
+            Ok(HeaderImport::Connected(None))
+        }
+    }
+
+    fn connect(
+        &mut self,
+        parent: &HeaderEntry,
+        header: IndexedBlockHeader,
+        now: u32,
+    ) -> Result<HeaderEntry, Error> {
+        let height = parent.height + 1;
+
+        let best_height = self.best().height;
+        if self
+            .checkpoints
+            .range(..=best_height)
+            .next_back()
+            .is_some_and(|(checkpoint_height, _)| height <= *checkpoint_height)
+        {
+            return Err(Error::ForkBeforeCheckpoint);
+        }
+        if let Some(checkpoint) = self.checkpoints.get(&height) {
+            if *checkpoint != header.hash {
+                return Err(Error::Checkpoint);
+            }
+        }
+
+        let headers = |height| self.ancestor(parent, height).map(|entry| entry.header.raw);
+        let bits = next_work_required(&headers, height, header.raw.time, &self.params)?;
+        if header.raw.bits != bits {
+            return Err(Error::UnexpectedBits);
+        }
+        check_proof_of_work(&header.hash, header.raw.bits, &self.params)?;
+
+        if header.raw.time <= self.median_time_past(parent) {
+            return Err(Error::TimeTooOld);
+        }
+        if u64::from(header.raw.time) > u64::from(now) + u64::from(MAX_FUTURE_BLOCK_TIME) {
+            return Err(Error::TimeTooNew);
+        }
+
+        let entry = HeaderEntry {
+            chain_work: parent
+                .chain_work
+                .saturating_add(block_proof(header.raw.bits)),
+            header,
+            height,
+        };
+        self.store.insert_header(entry);
+        self.store.remove_tip(&parent.header.hash);
+        self.store.insert_tip(header.hash);
+        Ok(entry)
+    }
+
+    fn parent(&self, entry: &HeaderEntry) -> Option<HeaderEntry> {
+        self.store.header(&entry.header.raw.previous_header_hash)
+    }
+
+    /// The ancestor of `entry` at the height, looked up in the best chain once the
+    /// walk back reaches it
+    fn ancestor(&self, entry: &HeaderEntry, height: u32) -> Option<HeaderEntry> {
+        let mut current = *entry;
+        while current.height > height {
+            if self.store.best_hash_at(current.height) == Some(current.header.hash) {
+                return self.header_at(height);
+            }
+            current = self.parent(&current)?;
+        }
+        (current.height == height).then_some(current)
+    }
+
+    /// Median time of the last `MEDIAN_TIME_SPAN` headers ending at `entry`
+    fn median_time_past(&self, entry: &HeaderEntry) -> u32 {
+        let mut times = Vec::with_capacity(MEDIAN_TIME_SPAN);
+        let mut current = Some(*entry);
+        while let Some(entry) = current.filter(|_| times.len() < MEDIAN_TIME_SPAN) {
+            times.push(entry.header.raw.time);
+            current = self.parent(&entry);
+        }
+        times.sort_unstable();
+        times[times.len() / 2]
+    }
+
+    fn set_best(&mut self, new_best: HeaderEntry) -> BestChainUpdate {
+        let old_best = self.best();
+        let mut update = BestChainUpdate::default();
+
+        let mut new = Some(new_best);
+        let mut old = Some(old_best);
+        while let (Some(new_entry), Some(old_entry)) = (new, old) {
+            if new_entry.header.hash == old_entry.header.hash {
+                break;
+            }
+            if new_entry.height >= old_entry.height {
+                self.store
+                    .insert_best_hash_at(new_entry.height, new_entry.header.hash);
+                update.connected.push(new_entry.header.hash);
+                new = self.parent(&new_entry);
+            }
+            if old_entry.height >= new_entry.height {
+                update.disconnected.push(old_entry.header.hash);
+                old = self.parent(&old_entry);
+            }
+        }
+        update.connected.reverse();
+
+        for height in new_best.height + 1..=old_best.height {
+            self.store.remove_best_hash_at(height);
+        }
+        self.store.set_best_hash(new_best.header.hash);
+        update
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+
+    fn connected(hashes: Vec<H256>) -> HeaderImport {
+        HeaderImport::Connected(Some(BestChainUpdate {
+            disconnected: vec![],
+            connected: hashes,
+        }))
+    }
+
+    #[test]
+    fn test_import_headers_in_order() {
+        let mut chain = chain();
+        let start = chain.best().header;
+        let headers = mine_chain(&start, 3, 1);
+        for (index, header) in headers.iter().enumerate() {
+            assert_eq!(
+                chain.import_header(*header, START_TIME),
+                Ok(connected(vec![header.hash]))
+            );
+            assert_eq!(chain.best().height, index as u32 + 1);
+        }
+        assert_eq!(
+            chain.import_header(headers[0], START_TIME),
+            Ok(HeaderImport::AlreadyKnown)
+        );
+        assert_eq!(chain.header_at(2).unwrap().header, headers[1]);
+        assert!(chain.is_in_best_chain(&headers[2].hash));
+        assert_eq!(chain.tips().len(), 1);
+        assert_eq!(
+            chain.best().chain_work,
+            block_proof(start.raw.bits) * U256::from(4)
+        );
+    }
+
+    #[test]
+    fn test_import_headers_out_of_order() {
+        let mut chain = chain();
+        let headers = mine_chain(&chain.best().header, 3, 1);
+        assert_eq!(
+            chain.import_header(headers[2], START_TIME),
+            Ok(HeaderImport::Orphan)
+        );
+        assert_eq!(
+            chain.import_header(headers[1], START_TIME),
+            Ok(HeaderImport::Orphan)
+        );
+        assert_eq!(chain.best().height, 0);
+        assert_eq!(
+            chain.import_header(headers[0], START_TIME),
+            Ok(connected(
+                headers.iter().map(|header| header.hash).collect()
+            ))
+        );
+        assert_eq!(chain.best().header, headers[2]);
+    }
+
+    #[test]
+    fn test_orphan_limit() {
+        let params = ConsensusParams::regtest();
+        let start = mine(H256::zero(), START_TIME, 0);
+        let mut chain = HeaderChain::new(MemoryHeaderStore::with_max_orphans(3), params, start, 0);
+        let headers = mine_chain(&start, 2, 1);
+        // orphans on top of unknown parents
+        let unconnected: Vec<_> = (1..=4)
+            .map(|tag| mine(H256::repeat_byte(tag), START_TIME, tag))
+            .collect();
+
+        assert_eq!(
+            chain.import_header(headers[1], START_TIME),
+            Ok(HeaderImport::Orphan)
+        );
+        for header in &unconnected {
+            assert_eq!(
+                chain.import_header(*header, START_TIME),
+                Ok(HeaderImport::Orphan)
+            );
+            assert!(chain.store().orphan_count() <= 3);
+        }
+        assert_eq!(chain.store().orphan_count(), 3);
+
+        // the oldest orphan was evicted, its parent no longer connects it
+        assert_eq!(
+            chain.import_header(headers[0], START_TIME),
+            Ok(connected(vec![headers[0].hash]))
+        );
+        assert_eq!(chain.store().orphan_count(), 3);
+
+        let mut store = chain.into_store();
+        assert!(store.take_orphans(&H256::repeat_byte(1)).is_empty());
+        assert_eq!(
+            store.take_orphans(&H256::repeat_byte(4)),
+            vec![unconnected[3]]
+        );
+        assert_eq!(store.orphan_count(), 2);
+    }
+
+    #[test]
+    fn test_reorg() {
+        let mut chain = chain();
+        let start = chain.best().header;
+        let main = mine_chain(&start, 2, 1);
+        let fork = mine_chain(&start, 3, 2);
+        for header in &main {
+            chain.import_header(*header, START_TIME).unwrap();
+        }
+
+        // the first chain seen wins with the same work
+        assert_eq!(
+            chain.import_header(fork[0], START_TIME),
+            Ok(HeaderImport::Connected(None))
+        );
+        assert_eq!(
+            chain.import_header(fork[1], START_TIME),
+            Ok(HeaderImport::Connected(None))
+        );
+        assert_eq!(chain.best().header, main[1]);
+        assert_eq!(chain.tips().len(), 2);
+
+        assert_eq!(
+            chain.import_header(fork[2], START_TIME),
+            Ok(HeaderImport::Connected(Some(BestChainUpdate {
+                disconnected: vec![main[1].hash, main[0].hash],
+                connected: fork.iter().map(|header| header.hash).collect(),
+            })))
+        );
+        assert_eq!(chain.best().header, fork[2]);
+        assert_eq!(chain.header_at(1).unwrap().header, fork[0]);
+        assert!(!chain.is_in_best_chain(&main[1].hash));
+        assert!(chain.header(&main[1].hash).is_some());
+
+        // back to the first chain, which becomes shorter than the old best chain
+        let longer = mine_chain(&main[1], 2, 1);
+        chain.import_header(longer[0], START_TIME).unwrap();
+        let update = match chain.import_header(longer[1], START_TIME) {
+            Ok(HeaderImport::Connected(Some(update))) => update,
+            result => panic!("unexpected import {:?}", result),
+        };
+        assert!(update.is_reorg());
+        assert_eq!(update.disconnected.len(), 3);
+        assert_eq!(update.connected.len(), 4);
+        assert_eq!(chain.best().height, 4);
+        assert_eq!(chain.header_at(1).unwrap().header, main[0]);
+    }
+
+    #[test]
+    fn test_time_rules() {
+        let mut chain = chain();
+        let start = chain.best().header;
+        assert_eq!(
+            chain.import_header(mine(start.hash, START_TIME, 1), START_TIME),
+            Err(Error::TimeTooOld)
+        );
+        let future = START_TIME + 1 + MAX_FUTURE_BLOCK_TIME;
+        assert_eq!(
+            chain.import_header(mine(start.hash, future, 1), START_TIME),
+            Err(Error::TimeTooNew)
+        );
+        assert_eq!(
+            chain.import_header(mine(start.hash, future, 1), START_TIME + 1),
+            Ok(connected(vec![mine(start.hash, future, 1).hash]))
+        );
+
+        // the median of the last 11 times, not the previous time
+        let mut chain = self::chain();
+        let headers = mine_chain(&start, 10, 1);
+        for header in &headers {
+            chain.import_header(*header, START_TIME).unwrap();
+        }
+        let median = headers[4].raw.time;
+        assert_eq!(
+            chain.import_header(mine(headers[9].hash, median, 1), START_TIME),
+            Err(Error::TimeTooOld)
+        );
+        assert!(chain
+            .import_header(mine(headers[9].hash, median + 1, 1), START_TIME)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let start = chain().best().header;
+        let main = mine_chain(&start, 3, 1);
+        let fork = mine_chain(&start, 3, 2);
+        let mut chain = chain().with_checkpoints(vec![(1, main[0].hash), (3, fork[2].hash)]);
+
+        assert_eq!(
+            chain.import_header(fork[0], START_TIME),
+            Err(Error::Checkpoint)
+        );
+        chain.import_header(main[0], START_TIME).unwrap();
+        chain.import_header(main[1], START_TIME).unwrap();
+        assert_eq!(
+            chain.import_header(main[2], START_TIME),
+            Err(Error::Checkpoint)
+        );
+
+        // the fork can not replace the checkpointed header
+        let other = mine(start.hash, START_TIME + 1, 3);
+        assert_eq!(
+            chain.import_header(other, START_TIME),
+            Err(Error::ForkBeforeCheckpoint)
+        );
+    }
+
+    #[test]
+    fn test_invalid_work() {
+        let mut chain = chain();
+        let start = chain.best().header;
+        let mut header = mine(start.hash, START_TIME + 600, 1).raw;
+        header.bits = 0x1d00ffff.into();
+        assert_eq!(
+            chain.import_header(header.into(), START_TIME),
+            Err(Error::ProofOfWork)
+        );
+        header.bits = 0x2100ffff.into();
+        assert_eq!(
+            chain.import_header(header.into(), START_TIME),
+            Err(Error::InvalidBits)
+        );
+
+        // valid work for other bits than the required ones
+        let header = mine_with_bits(start.hash, START_TIME + 600, 1, 0x2000ffff);
+        assert_eq!(
+            chain.import_header(header, START_TIME),
+            Err(Error::UnexpectedBits)
+        );
+    }
+}
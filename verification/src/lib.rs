@@ -0,0 +1,28 @@
+//! Consensus checks of blocks and transactions.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+pub mod constants;
+
+mod block;
+mod error;
+mod header_chain;
+mod pow;
+mod transaction;
+
+#[cfg(any(test, feature = "test-helpers"))]
+pub mod test_helpers;
+
+pub use self::block::{check_block, merkle_root_mutated};
+pub use self::error::{Error, TransactionError};
+pub use self::header_chain::{
+    BestChainUpdate, HeaderChain, HeaderEntry, HeaderImport, HeaderStore, MemoryHeaderStore,
+};
+pub use self::pow::{
+    block_proof, calculate_next_work_required, chain_work, check_header_work, check_proof_of_work,
+    next_work_required, ConsensusParams, HeaderProvider,
+};
+pub use self::transaction::check_transaction;
@@ -0,0 +1,374 @@
+use light_bitcoin_chain::BlockHeader;
+use light_bitcoin_primitives::{Compact, H256, U256};
+
+use crate::error::Error;
+
+/// Proof of work parameters of a network, like Bitcoin Core's `Consensus::Params`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsensusParams {
+    /// The highest target a block hash may have
+    pub pow_limit: U256,
+    /// Expected duration of a retarget period in seconds
+    pub pow_target_timespan: u32,
+    /// Expected time between two blocks in seconds
+    pub pow_target_spacing: u32,
+    /// Allow a minimum difficulty block when no block was found for twice the target spacing
+    pub allow_min_difficulty_blocks: bool,
+    /// Keep the same difficulty at retarget heights
+    pub no_retargeting: bool,
+}
+
+impl ConsensusParams {
+    pub fn mainnet() -> Self {
+        ConsensusParams {
+            pow_limit: (U256::one() << 224) - 1,
+            pow_target_timespan: 14 * 24 * 60 * 60,
+            pow_target_spacing: 10 * 60,
+            allow_min_difficulty_blocks: false,
+            no_retargeting: false,
+        }
+    }
+
+    pub fn testnet() -> Self {
+        ConsensusParams {
+            allow_min_difficulty_blocks: true,
+            ..Self::mainnet()
+        }
+    }
+
+    pub fn signet() -> Self {
+        ConsensusParams {
+            pow_limit: U256::from(0x0377aeu64) << 216,
+            ..Self::mainnet()
+        }
+    }
+
+    pub fn regtest() -> Self {
+        ConsensusParams {
+            pow_limit: (U256::one() << 255) - 1,
+            allow_min_difficulty_blocks: true,
+            no_retargeting: true,
+            ..Self::mainnet()
+        }
+    }
+
+    /// Number of blocks between two retargets, 2016 on all networks
+    pub fn difficulty_adjustment_interval(&self) -> u32 {
+        self.pow_target_timespan / self.pow_target_spacing
+    }
+
+    /// The compact encoding of `pow_limit`, i.e. the minimum difficulty
+    pub fn pow_limit_bits(&self) -> Compact {
+        Compact::from_u256(self.pow_limit)
+    }
+}
+
+/// Provides the headers of the chain by height
+pub trait HeaderProvider {
+    fn header(&self, height: u32) -> Option<BlockHeader>;
+}
+
+impl<F: Fn(u32) -> Option<BlockHeader>> HeaderProvider for F {
+    fn header(&self, height: u32) -> Option<BlockHeader> {
+        self(height)
+    }
+}
+
+fn header<P: HeaderProvider + ?Sized>(headers: &P, height: u32) -> Result<BlockHeader, Error> {
+    headers.header(height).ok_or(Error::MissingHeader(height))
+}
+
+/// Checks that `bits` is a valid target below the pow limit and that the hash is below
+/// the target, like Bitcoin Core's `CheckProofOfWork`
+pub fn check_proof_of_work(
+    hash: &H256,
+    bits: Compact,
+    params: &ConsensusParams,
+) -> Result<(), Error> {
+    let target = match bits.to_u256() {
+        Ok(target) if !target.is_zero() && target <= params.pow_limit => target,
+        _ => return Err(Error::InvalidBits),
+    };
+    // the hash is compared as a little endian number
+    if U256::from_little_endian(hash.as_bytes()) > target {
+        return Err(Error::ProofOfWork);
+    }
+    Ok(())
+}
+
+/// Computes the target at a retarget height from the target of the last block and the
+/// time the previous retarget period took, clamped to a factor of 4 in both directions.
+///
+/// Like Bitcoin Core, the period only spans 2015 intervals.
+pub fn calculate_next_work_required(
+    last_bits: Compact,
+    first_block_time: u32,
+    last_block_time: u32,
+    params: &ConsensusParams,
+) -> Compact {
+    if params.no_retargeting {
+        return last_bits;
+    }
+
+    let target_timespan = params.pow_target_timespan;
+    let actual_timespan = (i64::from(last_block_time) - i64::from(first_block_time)).clamp(
+        i64::from(target_timespan / 4),
+        i64::from(target_timespan) * 4,
+    );
+
+    let target = U256::from(last_bits)
+        .checked_mul(U256::from(actual_timespan))
+        .map(|target| target / target_timespan)
+        .unwrap_or(params.pow_limit);
+    Compact::from_u256(target.min(params.pow_limit))
+}
+
+/// Computes the bits required for the header at `height` with the given `time`, like
+/// Bitcoin Core's `GetNextWorkRequired`.
+///
+/// `headers` must provide the previous header, the first header of the retarget period,
+/// and, on networks allowing minimum difficulty blocks, the headers back to the last one
+/// not mined at the minimum difficulty.
+pub fn next_work_required<P: HeaderProvider + ?Sized>(
+    headers: &P,
+    height: u32,
+    time: u32,
+    params: &ConsensusParams,
+) -> Result<Compact, Error> {
+    let pow_limit_bits = params.pow_limit_bits();
+    let Some(last_height) = height.checked_sub(1) else {
+        return Ok(pow_limit_bits);
+    };
+    let last = header(headers, last_height)?;
+    let interval = params.difficulty_adjustment_interval();
+
+    if !height.is_multiple_of(interval) {
+        if !params.allow_min_difficulty_blocks {
+            return Ok(last.bits);
+        }
+        // testnet rule: a block more than 20 minutes after the previous one may be
+        // mined at the minimum difficulty
+        if u64::from(time) > u64::from(last.time) + u64::from(params.pow_target_spacing) * 2 {
+            return Ok(pow_limit_bits);
+        }
+        // otherwise the difficulty is the one of the last block not mined with that rule
+        let mut current_height = last_height;
+        let mut current = last;
+        while !current_height.is_multiple_of(interval) && current.bits == pow_limit_bits {
+            current_height -= 1;
+            current = header(headers, current_height)?;
+        }
+        return Ok(current.bits);
+    }
+
+    if params.no_retargeting {
+        return Ok(last.bits);
+    }
+
+    let first = header(headers, height - interval)?;
+    Ok(calculate_next_work_required(
+        last.bits, first.time, last.time, params,
+    ))
+}
+
+/// Checks the bits of the header at `height` against the retarget rules and its proof
+/// of work against them.
+pub fn check_header_work<P: HeaderProvider + ?Sized>(
+    headers: &P,
+    height: u32,
+    header: &BlockHeader,
+    params: &ConsensusParams,
+) -> Result<(), Error> {
+    if header.bits != next_work_required(headers, height, header.time, params)? {
+        return Err(Error::UnexpectedBits);
+    }
+    check_proof_of_work(&header.hash(), header.bits, params)
+}
+
+/// Expected number of hashes to find a block with the given bits, like Bitcoin Core's
+/// `GetBlockProof`. Invalid bits have no work.
+pub fn block_proof(bits: Compact) -> U256 {
+    match bits.to_u256() {
+        // 2**256 / (target + 1) computed as ~target / (target + 1) + 1 to fit in 256 bits
+        Ok(target) if !target.is_zero() => (!target / (target + 1)) + 1,
+        _ => U256::zero(),
+    }
+}
+
+/// Cumulative work of the headers
+pub fn chain_work<'a, I>(headers: I) -> U256
+where
+    I: IntoIterator<Item = &'a BlockHeader>,
+{
+    headers.into_iter().fold(U256::zero(), |work, header| {
+        work.saturating_add(block_proof(header.bits))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAINNET_GENESIS: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+    fn retarget(last_bits: u32, first_block_time: u32, last_block_time: u32) -> Compact {
+        calculate_next_work_required(
+            last_bits.into(),
+            first_block_time,
+            last_block_time,
+            &ConsensusParams::mainnet(),
+        )
+    }
+
+    fn header(time: u32, bits: u32) -> BlockHeader {
+        BlockHeader {
+            time,
+            bits: bits.into(),
+            ..Default::default()
+        }
+    }
+
+    // test vectors from bitcoin core's src/test/pow_tests.cpp
+    #[test]
+    fn test_calculate_next_work_required() {
+        // block 32255 with the time of block 30240
+        assert_eq!(
+            retarget(0x1d00ffff, 1261130161, 1262152739),
+            0x1d00d86a.into()
+        );
+        // block 2015, the target stays at the pow limit
+        assert_eq!(
+            retarget(0x1d00ffff, 1231006505, 1233061996),
+            0x1d00ffff.into()
+        );
+        // block 68543, the timespan is clamped to a quarter
+        assert_eq!(
+            retarget(0x1c05a3f4, 1279008237, 1279297671),
+            0x1c0168fd.into()
+        );
+        // block 46367, the timespan is clamped to 4 times
+        assert_eq!(
+            retarget(0x1c387f6f, 1263163443, 1269211443),
+            0x1d00e1fd.into()
+        );
+    }
+
+    #[test]
+    fn test_consensus_params() {
+        let mainnet = ConsensusParams::mainnet();
+        assert_eq!(mainnet.difficulty_adjustment_interval(), 2016);
+        assert_eq!(mainnet.pow_limit_bits(), 0x1d00ffff.into());
+        assert_eq!(
+            ConsensusParams::signet().pow_limit_bits(),
+            0x1e0377ae.into()
+        );
+        assert_eq!(
+            ConsensusParams::regtest().pow_limit_bits(),
+            0x207fffff.into()
+        );
+    }
+
+    #[test]
+    fn test_check_proof_of_work() {
+        let params = ConsensusParams::mainnet();
+        let genesis: BlockHeader = MAINNET_GENESIS.parse().unwrap();
+        let hash = genesis.hash();
+        assert_eq!(check_proof_of_work(&hash, genesis.bits, &params), Ok(()));
+
+        // a forged header does not match its target
+        let mut forged = genesis;
+        forged.nonce += 1;
+        assert_eq!(
+            check_proof_of_work(&forged.hash(), forged.bits, &params),
+            Err(Error::ProofOfWork)
+        );
+
+        // bits above the pow limit, zero, negative or overflowing are rejected
+        for bits in [0x1d01ffffu32, 0, 0x04923456, 0xff123456] {
+            assert_eq!(
+                check_proof_of_work(&hash, bits.into(), &params),
+                Err(Error::InvalidBits)
+            );
+        }
+        // the regtest limit is higher
+        assert_eq!(
+            check_proof_of_work(&hash, 0x207fffff.into(), &ConsensusParams::regtest()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_next_work_required() {
+        let params = ConsensusParams::mainnet();
+        let headers = |height: u32| match height {
+            30240 => Some(header(1261130161, 0x1d00ffff)),
+            30241..=32255 => Some(header(1262152739, 0x1d00ffff)),
+            _ => None,
+        };
+        assert_eq!(
+            next_work_required(&headers, 32255, 0, &params),
+            Ok(0x1d00ffff.into())
+        );
+        assert_eq!(
+            next_work_required(&headers, 32256, 0, &params),
+            Ok(0x1d00d86a.into())
+        );
+        assert_eq!(
+            next_work_required(&headers, 32257, 0, &params),
+            Err(Error::MissingHeader(32256))
+        );
+        assert_eq!(
+            next_work_required(&headers, 0, 0, &params),
+            Ok(0x1d00ffff.into())
+        );
+
+        let mut header = header(0, 0x1d00d86a);
+        assert_eq!(
+            check_header_work(&headers, 32256, &header, &params),
+            Err(Error::ProofOfWork)
+        );
+        header.bits = 0x1d00ffff.into();
+        assert_eq!(
+            check_header_work(&headers, 32256, &header, &params),
+            Err(Error::UnexpectedBits)
+        );
+    }
+
+    #[test]
+    fn test_next_work_required_min_difficulty() {
+        let params = ConsensusParams::testnet();
+        // block 2017 was mined at the minimum difficulty after a 20 minutes gap
+        let headers = |height: u32| match height {
+            2016 => Some(header(1_000_000, 0x1c0168fd)),
+            2017 => Some(header(1_001_201, 0x1d00ffff)),
+            _ => None,
+        };
+
+        // more than 20 minutes after the previous block
+        assert_eq!(
+            next_work_required(&headers, 2018, 1_002_402, &params),
+            Ok(0x1d00ffff.into())
+        );
+        // otherwise back to the last regular difficulty
+        assert_eq!(
+            next_work_required(&headers, 2018, 1_002_401, &params),
+            Ok(0x1c0168fd.into())
+        );
+        // mainnet ignores the gap
+        assert_eq!(
+            next_work_required(&headers, 2018, 1_002_402, &ConsensusParams::mainnet()),
+            Ok(0x1d00ffff.into())
+        );
+    }
+
+    #[test]
+    fn test_chain_work() {
+        let genesis: BlockHeader = MAINNET_GENESIS.parse().unwrap();
+        assert_eq!(block_proof(genesis.bits), U256::from(0x1_0001_0001u64));
+        assert_eq!(block_proof(0.into()), U256::zero());
+        assert_eq!(
+            chain_work(&[genesis, genesis]),
+            U256::from(0x2_0002_0002u64)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Regtest headers for tests, shared with the crates syncing headers with the
+//! `test-helpers` feature.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use light_bitcoin_chain::{BlockHeader, IndexedBlockHeader};
+use light_bitcoin_primitives::H256;
+
+use crate::header_chain::{HeaderChain, MemoryHeaderStore};
+use crate::pow::{check_proof_of_work, ConsensusParams};
+
+/// Time of the first header of `chain`
+pub const START_TIME: u32 = 1_600_000_000;
+
+/// Mines a regtest header, `tag` is its merkle root to tell apart the forks
+pub fn mine(previous_header_hash: H256, time: u32, tag: u8) -> IndexedBlockHeader {
+    mine_with_bits(previous_header_hash, time, tag, 0x207fffff)
+}
+
+pub fn mine_with_bits(
+    previous_header_hash: H256,
+    time: u32,
+    tag: u8,
+    bits: u32,
+) -> IndexedBlockHeader {
+    let params = ConsensusParams::regtest();
+    let mut header = BlockHeader {
+        version: 4,
+        previous_header_hash,
+        merkle_root_hash: H256::repeat_byte(tag),
+        time,
+        bits: bits.into(),
+        nonce: 0,
+    };
+    while check_proof_of_work(&header.hash(), header.bits, &params).is_err() {
+        header.nonce += 1;
+    }
+    header.into()
+}
+
+/// Mines `count` headers on top of `parent`, 10 minutes apart
+pub fn mine_chain(parent: &IndexedBlockHeader, count: usize, tag: u8) -> Vec<IndexedBlockHeader> {
+    let mut headers = Vec::with_capacity(count);
+    let mut previous = *parent;
+    for _ in 0..count {
+        previous = mine(previous.hash, previous.raw.time + 600, tag);
+        headers.push(previous);
+    }
+    headers
+}
+
+/// The first header of `chain`
+pub fn genesis() -> IndexedBlockHeader {
+    mine(H256::zero(), START_TIME, 0)
+}
+
+/// A regtest chain in memory, starting at `genesis`
+pub fn chain() -> HeaderChain<MemoryHeaderStore> {
+    HeaderChain::new(
+        MemoryHeaderStore::default(),
+        ConsensusParams::regtest(),
+        genesis(),
+        0,
+    )
+}
@@ -0,0 +1,142 @@
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeSet;
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
+
+use light_bitcoin_chain::constants::MAX_MONEY;
+use light_bitcoin_chain::{Transaction, WITNESS_SCALE_FACTOR};
+use light_bitcoin_serialization::Serializable;
+
+use crate::constants::{MAX_BLOCK_WEIGHT, MAX_COINBASE_SCRIPT_SIZE, MIN_COINBASE_SCRIPT_SIZE};
+use crate::error::TransactionError;
+
+/// Context-free checks of a transaction, like Bitcoin Core's `CheckTransaction`
+pub fn check_transaction(tx: &Transaction) -> Result<(), TransactionError> {
+    if tx.inputs.is_empty() {
+        return Err(TransactionError::NoInputs);
+    }
+    if tx.outputs.is_empty() {
+        return Err(TransactionError::NoOutputs);
+    }
+    if tx.serialized_size() * WITNESS_SCALE_FACTOR > MAX_BLOCK_WEIGHT {
+        return Err(TransactionError::Oversize);
+    }
+
+    let mut total = 0u64;
+    for (index, output) in tx.outputs.iter().enumerate() {
+        if output.value > MAX_MONEY {
+            return Err(TransactionError::OutputValue(index));
+        }
+        // both values are at most MAX_MONEY, so the sum cannot overflow
+        total += output.value;
+        if total > MAX_MONEY {
+            return Err(TransactionError::TotalOutputValue);
+        }
+    }
+
+    let mut spent = BTreeSet::new();
+    if !tx
+        .inputs
+        .iter()
+        .all(|input| spent.insert(&input.previous_output))
+    {
+        return Err(TransactionError::DuplicateInputs);
+    }
+
+    if tx.is_coinbase() {
+        let script_size = tx.inputs[0].script_sig.len();
+        if !(MIN_COINBASE_SCRIPT_SIZE..=MAX_COINBASE_SCRIPT_SIZE).contains(&script_size) {
+            return Err(TransactionError::CoinbaseScriptSize);
+        }
+    } else if tx.is_null() {
+        return Err(TransactionError::NullPrevout);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use light_bitcoin_chain::{OutPoint, TransactionInput, TransactionOutput};
+    use light_bitcoin_primitives::H256;
+
+    fn transaction() -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: vec![TransactionInput {
+                previous_output: OutPoint {
+                    txid: H256::repeat_byte(1),
+                    index: 0,
+                },
+                script_sig: Default::default(),
+                sequence: 0xffff_ffff,
+                script_witness: vec![],
+            }],
+            outputs: vec![TransactionOutput {
+                value: 50,
+                script_pubkey: Default::default(),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_check_transaction() {
+        let tx = transaction();
+        assert_eq!(check_transaction(&tx), Ok(()));
+
+        let mut no_inputs = tx.clone();
+        no_inputs.inputs.clear();
+        assert_eq!(
+            check_transaction(&no_inputs),
+            Err(TransactionError::NoInputs)
+        );
+
+        let mut no_outputs = tx.clone();
+        no_outputs.outputs.clear();
+        assert_eq!(
+            check_transaction(&no_outputs),
+            Err(TransactionError::NoOutputs)
+        );
+
+        let mut too_large = tx.clone();
+        too_large.outputs[0].value = MAX_MONEY + 1;
+        assert_eq!(
+            check_transaction(&too_large),
+            Err(TransactionError::OutputValue(0))
+        );
+
+        let mut total_too_large = tx.clone();
+        total_too_large.outputs = vec![tx.outputs[0].clone(); 2];
+        total_too_large.outputs[0].value = MAX_MONEY;
+        assert_eq!(
+            check_transaction(&total_too_large),
+            Err(TransactionError::TotalOutputValue)
+        );
+
+        let mut duplicate = tx.clone();
+        duplicate.inputs.push(tx.inputs[0].clone());
+        assert_eq!(
+            check_transaction(&duplicate),
+            Err(TransactionError::DuplicateInputs)
+        );
+
+        let mut null_prevout = tx.clone();
+        null_prevout
+            .inputs
+            .push(TransactionInput::coinbase(Default::default()));
+        assert_eq!(
+            check_transaction(&null_prevout),
+            Err(TransactionError::NullPrevout)
+        );
+
+        let mut coinbase = tx;
+        coinbase.inputs = vec![TransactionInput::coinbase(vec![0; 1].into())];
+        assert_eq!(
+            check_transaction(&coinbase),
+            Err(TransactionError::CoinbaseScriptSize)
+        );
+        coinbase.inputs[0].script_sig = vec![0; 100].into();
+        assert_eq!(check_transaction(&coinbase), Ok(()));
+    }
+}